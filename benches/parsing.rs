@@ -0,0 +1,227 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Benchmarks for the zone tokenizer, building the root, decoding wire responses and the async
+ * queries. The zone fixture is generated, 1500 delegations with glue, DS and TXT records.
+ * The queries go to a responder on the loopback on a thread of the benchmark.
+ */
+
+use criterion::{criterion_group, criterion_main, Criterion, black_box};
+
+use dns_audit::query::{self, Wire};
+use dns_audit::{root, zone};
+
+const LARGE_ZONE : &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures/large_root.zone");
+
+/**
+ * build a referral like response for name with the nameservers in the authority section and
+ * their A records as glue, names after the question are compressed against it
+ */
+fn referral_response( name : &str, ns_ct : u16 ) -> Vec<u8> {
+
+	let header = query::Header {
+		id: 1,
+		qr: true,
+		qdcount: 1,
+		nscount: ns_ct,
+		arcount: ns_ct,
+		..Default::default()
+	};
+
+	let mut buff = header.write( &mut query::NameOffsets::new(), 0 ).unwrap();
+	buff.append( &mut query::write_qname(name) );
+	buff.extend_from_slice( &query::QueryType::T_NS.as_u16().to_be_bytes() );
+	buff.extend_from_slice( &query::NSClass::C_IN.as_u16().to_be_bytes() );
+
+	let mut ns_offsets : Vec<u16> = Vec::new();
+
+	for x in 0..ns_ct {
+		// pointer to the question name
+		buff.extend_from_slice( &[ 0xc0, 0x0c ] );
+		buff.extend_from_slice( &query::QueryType::T_NS.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &query::NSClass::C_IN.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &172800u32.to_be_bytes() );
+
+		let label = format!("ns{}", x);
+		buff.extend_from_slice( &(label.len() as u16 + 3).to_be_bytes() );
+		ns_offsets.push( buff.len() as u16 );
+		buff.push( label.len() as u8 );
+		buff.extend_from_slice( label.as_bytes() );
+		buff.extend_from_slice( &[ 0xc0, 0x0c ] );
+	}
+
+	for (x, ns_offset) in ns_offsets.iter().enumerate() {
+		buff.extend_from_slice( &(0xc000 | ns_offset).to_be_bytes() );
+		buff.extend_from_slice( &query::QueryType::T_A.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &query::NSClass::C_IN.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &172800u32.to_be_bytes() );
+		buff.extend_from_slice( &4u16.to_be_bytes() );
+		buff.extend_from_slice( &[ 192, 0, 2, x as u8 + 1 ] );
+	}
+
+	buff
+}
+
+/**
+ * a responder on an ephemeral loopback port answering every question with the referral for
+ * example.com., the port is returned
+ */
+fn serve_referrals() -> u16 {
+
+	let udp = std::net::UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+	let port = udp.local_addr().unwrap().port();
+	let response = referral_response( "example.com.", 4 );
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (_, from) ) = udp.recv_from( &mut buff ) {
+			// the id of the request, the question is the same
+			let mut response = response.clone();
+			response[..2].copy_from_slice( &buff[..2] );
+			udp.send_to( &response, from ).unwrap();
+		}
+	});
+
+	port
+}
+
+fn bench_tokenize( c : &mut Criterion ) {
+	let file_name = LARGE_ZONE.to_string();
+	c.bench_function("tokenize large zone", |b| {
+		b.iter(|| zone::tokenizer::ZoneLines::create( black_box(&file_name) ).unwrap() )
+	});
+}
+
+fn bench_root( c : &mut Criterion ) {
+	let file_name = LARGE_ZONE.to_string();
+	let origin = ".".to_string();
+	c.bench_function("root from large zone", |b| {
+		b.iter(|| root::Root::create( black_box(&file_name), &origin ).unwrap() )
+	});
+}
+
+fn bench_decode( c : &mut Criterion ) {
+
+	let responses : Vec<Vec<u8>> = (0..100).map(|x| referral_response( &format!("example{}.com.", x), 4 )).collect();
+	let server = std::net::IpAddr::from( [ 127, 0, 0, 1 ] );
+
+	// make sure the fixture decodes before timing it
+	let mut check = query::Sender::new( &server );
+	check.read_response( &responses[0] ).unwrap();
+	assert_eq!( check.authority.len(), 4 );
+	assert_eq!( check.additional.len(), 4 );
+
+	c.bench_function("decode 100 referrals", |b| {
+		b.iter(|| {
+			for buff in &responses {
+				let mut sender = query::Sender::new( &server );
+				sender.read_response( black_box(buff) ).unwrap();
+				black_box( &sender.authority );
+			}
+		})
+	});
+}
+
+/**
+ * loading the --cache-in of the large zone, json and with the binary-cache feature binary
+ */
+fn bench_cache( c : &mut Criterion ) {
+
+	let large = root::Root::create( &LARGE_ZONE.to_string(), &".".to_string() ).unwrap();
+	for zone in large.root_addr.values() {
+		for server in &zone.read().unwrap().servers {
+			server.write().unwrap().speed = Some( std::time::Duration::from_micros(1500) );
+		}
+	}
+
+	let json = large.to_json();
+	c.bench_function("load json cache", |b| {
+		b.iter(|| {
+			let mut root = root::Root::default();
+			root.cache_from_js( black_box(&json) ).unwrap();
+			root
+		})
+	});
+
+	#[cfg(feature = "binary-cache")]
+	{
+		let binary = large.to_binary().unwrap();
+		println!("cache of {} zones is {} bytes as json, {} bytes binary", large.root_addr.len(), json.len(), binary.len());
+
+		c.bench_function("load binary cache", |b| {
+			b.iter(|| {
+				let mut root = root::Root::default();
+				root.cache_from_binary( black_box(&binary) ).unwrap();
+				root
+			})
+		});
+	}
+}
+
+/**
+ * queries to the loopback responder through query_async, one at a time and 100 at once on the
+ * query runtime
+ */
+fn bench_query( c : &mut Criterion ) {
+
+	let server = std::net::IpAddr::from( [ 127, 0, 0, 1 ] );
+	let port = serve_referrals();
+	let runtime = query::runtime();
+
+	let sender = || {
+		let mut sender = query::Sender::new( &server );
+		sender.port = port;
+		sender
+	};
+
+	// make sure the responder answers before timing it
+	let mut check = sender();
+	runtime.block_on( check.query_async( "example.com.", query::QueryType::T_NS ) ).unwrap();
+	assert_eq!( check.authority.len(), 4 );
+
+	c.bench_function("query loopback", |b| {
+		b.iter(|| {
+			let mut sender = sender();
+			runtime.block_on( sender.query_async( black_box("example.com."), query::QueryType::T_NS ) ).unwrap();
+			sender
+		})
+	});
+
+	// a Sender isn't Send, the queries are interleaved on one thread
+	c.bench_function("100 concurrent queries to loopback", |b| {
+		b.iter(|| {
+			tokio::task::LocalSet::new().block_on( runtime, async {
+				let mut tasks = tokio::task::JoinSet::new();
+				for _ in 0..100 {
+					let mut sender = sender();
+					tasks.spawn_local( async move { sender.query_async( "example.com.", query::QueryType::T_NS ).await } );
+				}
+				while let Some(rval) = tasks.join_next().await {
+					rval.unwrap().unwrap();
+				}
+			})
+		})
+	});
+}
+
+criterion_group!(benches, bench_tokenize, bench_root, bench_decode, bench_cache, bench_query);
+criterion_main!(benches);
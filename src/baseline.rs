@@ -0,0 +1,197 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+use std::collections::HashMap;
+
+use crate::config::println_verbose;
+use crate::monitor;
+
+/**
+ * The answers that were seen for a domain the first time it was audited
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BaselineEntry {
+	pub domain_name : String,
+	pub nameservers : Option<Vec<String>>,
+	pub ips : Option<Vec<std::net::IpAddr>>
+}
+
+/**
+ * the layout of the baseline file
+ */
+#[derive(Serialize)]
+struct BaselineFile<'a> {
+	schema_version : u32,
+	entries : Vec<&'a BaselineEntry>
+}
+
+/**
+ * Known good answers that later runs are diffed against, domains that are not
+ * in the baseline yet are added the first time they are seen
+ */
+pub struct Baseline {
+	pub entries : HashMap<String, BaselineEntry>,
+	pub changed : bool
+}
+
+/**
+ * comma separated list of the addresses for a finding's detail
+ */
+pub fn join_ips( ips : &[std::net::IpAddr] ) -> String {
+	ips.iter().map(|ip| ip.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl Baseline {
+
+	/**
+	 * load the baseline from the file, if the file does not exist an empty baseline is
+	 * returned so the current run becomes the baseline
+	 */
+	pub fn from_file( file_name : &str ) -> Result<Self, String> {
+
+		let mut rval = Self {
+			entries: HashMap::new(),
+			changed: false
+		};
+
+		let contents = match std::fs::read_to_string(file_name) {
+			Ok(m) => { m },
+			Err(e) => {
+				if e.kind() == std::io::ErrorKind::NotFound {
+					println_verbose!(VERBOSE1, "Baseline {} does not exist, it will be created", file_name);
+					return Ok(rval);
+				}
+				return Err(format!("failed to read baseline {} {}", file_name, e));
+			}
+		};
+
+		let mut document = match serde_json::from_str::<serde_json::Value>(&contents) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to parse baseline {} {}", file_name, e)); }
+		};
+
+		// before the schema_version was added the file was only the list of entries
+		let entries = match crate::config::schema_version( &document, &format!("baseline {}", file_name) )? {
+			0 => { document },
+			_ => { document["entries"].take() }
+		};
+
+		let list = match serde_json::from_value::<Vec<BaselineEntry>>(entries) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to parse baseline {} {}", file_name, e)); }
+		};
+
+		for entry in list {
+			rval.entries.insert( entry.domain_name.to_ascii_lowercase(), entry );
+		}
+
+		Ok(rval)
+	}
+
+	/**
+	 * write the baseline out as json
+	 */
+	pub fn to_file( &self, file_name : &str ) -> Result<(), String> {
+
+		let mut list : Vec<&BaselineEntry> = self.entries.values().collect();
+		list.sort_by(|a, b| a.domain_name.cmp(&b.domain_name));
+
+		let document = BaselineFile {
+			schema_version: crate::config::SCHEMA_VERSION,
+			entries: list
+		};
+
+		match std::fs::write( file_name, serde_json::to_string(&document).unwrap() ) {
+			Ok(_) => { Ok(()) },
+			Err(e) => { Err(format!("failed to write baseline {} {}", file_name, e)) }
+		}
+	}
+
+	/**
+	 * compare the result against the baseline, any differences are added to the result
+	 * as a failure. If the domain isn't in the baseline it is recorded.
+	 */
+	pub fn check( &mut self, result : &mut monitor::MonitorResult ) {
+
+		let key = result.domain_name.to_ascii_lowercase();
+
+		let entry = match self.entries.get(&key) {
+			Some(m) => { m },
+			None => {
+				self.entries.insert(key, BaselineEntry {
+					domain_name: result.domain_name.clone(),
+					nameservers: result.nameservers.clone(),
+					ips: result.ips.clone()
+				});
+				self.changed = true;
+				return;
+			}
+		};
+
+		if let (Some(expected), Some(actual)) = (&entry.nameservers, &result.nameservers) {
+			let expected : Vec<String> = expected.iter().map(|n| n.to_ascii_lowercase()).collect();
+			let actual : Vec<String> = actual.iter().map(|n| n.to_ascii_lowercase()).collect();
+			if let Some(diff) = Self::diff(&expected, &actual) {
+				result.findings.push(monitor::Finding::with_detail(
+					monitor::ErrorCode::BaselineChanged,
+					&format!("nameservers changed from baseline, {}", diff),
+					monitor::FindingDetail {
+						expected: Some(expected.join(", ")),
+						actual: Some(actual.join(", ")),
+						..Default::default()
+					}
+				));
+				result.success = false;
+			}
+		}
+
+		if let (Some(expected), Some(actual)) = (&entry.ips, &result.ips) {
+			if let Some(diff) = Self::diff(expected, actual) {
+				result.findings.push(monitor::Finding::with_detail(
+					monitor::ErrorCode::BaselineChanged,
+					&format!("ips changed from baseline, {}", diff),
+					monitor::FindingDetail {
+						expected: Some(join_ips(expected)),
+						actual: Some(join_ips(actual)),
+						..Default::default()
+					}
+				));
+				result.success = false;
+			}
+		}
+
+	}
+
+	/**
+	 * describe what was added and removed between the two lists, order is ignored
+	 */
+	pub fn diff<T : PartialEq + std::fmt::Display>( expected : &[T], actual : &[T] ) -> Option<String> {
+
+		let added : Vec<String> = actual.iter().filter(|a| !expected.contains(a)).map(|a| a.to_string()).collect();
+		let removed : Vec<String> = expected.iter().filter(|e| !actual.contains(e)).map(|e| e.to_string()).collect();
+
+		if added.is_empty() && removed.is_empty() {
+			return None;
+		}
+
+		Some(format!("added [{}] removed [{}]", added.join(", "), removed.join(", ")))
+	}
+
+}
@@ -0,0 +1,1505 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * Checks that are ran against a monitored domain. Each monitor has a list of check names
+ * that are looked up in the registry, custom checks can be added with register()
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::monitor::{ErrorCode, Expect, ExpectedIp, Finding, FindingDetail, Monitor, MonitorResult, ResponseFlags};
+use crate::baseline::join_ips;
+use crate::{query, root, zone, resolver};
+use crate::resolver::query_addresses;
+use crate::config::println_verbose;
+
+/**
+ * creates a sender for the inputted nameserver with the monitor's timeout, retries and port
+ * applied
+ */
+pub type SenderFactory = dyn Fn(&std::net::IpAddr) -> query::Sender;
+
+pub trait Check : Send + Sync {
+
+	/**
+	 * the name the check is registered and referenced as in the monitor config
+	 */
+	fn name( &self ) -> &str;
+
+	/**
+	 * run the check against the monitor and return any failures. The result is shared between
+	 * the checks so information found by an earlier check, like the nameservers, can be used
+	 * by a later one.
+	 */
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding>;
+
+}
+
+lazy_static!(
+	static ref REGISTRY : RwLock<HashMap<String, Arc<dyn Check>>> = {
+		let mut m : HashMap<String, Arc<dyn Check>> = HashMap::new();
+		m.insert( NsCheck{}.name().to_string(), Arc::new(NsCheck{}) );
+		m.insert( IpCheck{}.name().to_string(), Arc::new(IpCheck{}) );
+		m.insert( ResolverCheck{}.name().to_string(), Arc::new(ResolverCheck{}) );
+		m.insert( PtrCheck{}.name().to_string(), Arc::new(PtrCheck{}) );
+		m.insert( NsNetworkCheck{}.name().to_string(), Arc::new(NsNetworkCheck{}) );
+		m.insert( RrsigExpiryCheck{}.name().to_string(), Arc::new(RrsigExpiryCheck{}) );
+		m.insert( DnssecCheck{}.name().to_string(), Arc::new(DnssecCheck{}) );
+		m.insert( NxdomainCheck{}.name().to_string(), Arc::new(NxdomainCheck{}) );
+		m.insert( ZoneFileCheck{}.name().to_string(), Arc::new(ZoneFileCheck{}) );
+		m.insert( GeoCheck{}.name().to_string(), Arc::new(GeoCheck{}) );
+		m.insert( SerialCheck{}.name().to_string(), Arc::new(SerialCheck{}) );
+		m.insert( ConsistencyCheck{}.name().to_string(), Arc::new(ConsistencyCheck{}) );
+		m.insert( TtlCheck{}.name().to_string(), Arc::new(TtlCheck{}) );
+		RwLock::new(m)
+	};
+);
+
+/** the checks that are ran when the monitor doesn't list any */
+pub const DEFAULT_CHECKS : [&str; 10] = [ "ns", "ip", "resolver", "ptr", "ns_network", "dnssec", "zone_file", "geo", "consistency", "ttl" ];
+
+/**
+ * add a check to the registry, a check with the same name is replaced
+ */
+pub fn register( check : Arc<dyn Check> ) {
+	REGISTRY.write().unwrap().insert( check.name().to_string(), check );
+}
+
+/**
+ * look up a check by name
+ */
+pub fn get( name : &str ) -> Option<Arc<dyn Check>> {
+	REGISTRY.read().unwrap().get(name).cloned()
+}
+
+/**
+ * keep the RD and RA flags of the server's response in the result, only the first response
+ * from each server is kept
+ */
+pub fn record_flags( result : &mut MonitorResult, sender : &query::Sender ) {
+
+	if result.response_flags.iter().any(|f| f.server == *sender.server()) {
+		return;
+	}
+
+	result.response_flags.push( ResponseFlags {
+		server: *sender.server(),
+		rd: sender.recv_header.rd,
+		ra: sender.recv_header.ra
+	});
+}
+
+/**
+ * flag the result if the server answered with records in a different class than
+ * we asked for. It is only reported once, found are the findings of the running check
+ * which aren't in the result until it returns
+ */
+pub fn check_class( result : &MonitorResult, found : &[Finding], sender : &query::Sender ) -> Option<Finding> {
+
+	if sender.class_mismatch.is_empty() || result.findings.iter().chain(found).any(|f| f.code == ErrorCode::ClassMismatch) {
+		return None;
+	}
+
+	Some(Finding::with_detail(
+		ErrorCode::ClassMismatch,
+		&format!("{} returned {} records in the wrong class", sender.server(), sender.class_mismatch.len()),
+		FindingDetail {
+			server: Some(sender.server().to_string()),
+			expected: Some(sender.qclass.as_u16().to_string()),
+			actual: Some(sender.class_mismatch.iter().map(|r| r.class.as_u16().to_string()).collect::<Vec<String>>().join(", "))
+		}
+	))
+}
+
+/**
+ * the servers closest to the name from the root, read even when a panic in another domain's
+ * checks poisoned it
+ */
+fn closest_servers( root : &Arc<RwLock<root::Root>>, name : &str ) -> Vec<std::net::IpAddr> {
+	root.read().unwrap_or_else(std::sync::PoisonError::into_inner).closest_servers(name)
+}
+
+/**
+ * find the authoratative nameservers for the domain from the parent zone, the result
+ * nameservers are set so it is only looked up once
+ */
+pub fn delegation( monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+	let mut rval : Vec<Finding> = Vec::new();
+	let mut read_ns: Vec<String> = Vec::new();
+
+	// the root is only locked while the walk reads and adds zones, not while it waits on queries
+	if let Ok(m) = root::Root::get_nameservers_and_resolve(root, &monitor.domain_name) {
+		let servers : Vec<std::net::IpAddr> = m.read().unwrap().servers.iter().map(|s| s.read().unwrap().ip).collect();
+		for ip in servers {
+
+			let mut query = sender_factory( &ip );
+			// the parent zone's servers aren't the monitor's, they listen on the usual port
+			query.port = query::DNS_PORT;
+			if query.query( &monitor.domain_name, query::QueryType::T_NS).is_ok() {
+				rval.extend( check_class(result, &rval, &query) );
+				record_flags( result, &query );
+				read_ns = query.zone_nameservers( &monitor.domain_name );
+				break;
+			}
+		}
+
+	}
+
+	result.nameservers = Some( read_ns );
+
+	rval
+}
+
+/**
+ * query the authoratative nameservers for the A and AAAA records, the first nameserver
+ * that answers is used. result.ips is left as None if none of them answered
+ */
+pub fn authoritative_addresses( monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+	let mut rval = Vec::new();
+
+	if result.nameservers.is_none() {
+		rval = delegation(monitor, sender_factory, root, result);
+	}
+
+	let nameservers = result.nameservers.clone().unwrap_or_default();
+	for auth_ns in &nameservers {
+
+		let mut result_from_ns = false;
+
+		let mut read_addresses : Vec<std::net::IpAddr> = Vec::new();
+		if let Ok( addresses ) = resolver::lookup_host( auth_ns, &|n| closest_servers(root, n) ) {
+			for addr in addresses {
+
+				let mut query = sender_factory( &addr );
+				query.tsig = monitor.tsig.clone();
+
+				if let Some(found) = query_addresses(&monitor.domain_name, &mut query, query::QueryType::T_A) {
+					result_from_ns = true;
+					read_addresses.extend(found.addresses);
+					result.synthesized_from_wildcard |= found.synthesized_from_wildcard;
+				}
+
+				if let Some(found) = query_addresses(&monitor.domain_name, &mut query, query::QueryType::T_AAAA) {
+					result_from_ns = true;
+					read_addresses.extend(found.addresses);
+					result.synthesized_from_wildcard |= found.synthesized_from_wildcard;
+				}
+
+				rval.extend( check_class(result, &rval, &query) );
+
+				if result_from_ns {
+					record_flags( result, &query );
+					break;
+				}
+
+			}
+		}
+
+		if result_from_ns {
+			result.ips = Some(read_addresses);
+			break;
+		}
+
+	}
+
+	rval
+}
+
+/**
+ * Compare the nameservers at the parent zone against the expected ns
+ */
+pub struct NsCheck {}
+
+impl Check for NsCheck {
+
+	fn name( &self ) -> &str {
+		"ns"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval = delegation(monitor, sender_factory, root, result);
+		}
+
+		let read_ns = result.nameservers.as_ref().unwrap();
+
+		if let Some(ns) = &monitor.ns {
+
+			let detail = FindingDetail {
+				expected: Some(ns.join(", ")),
+				actual: Some(read_ns.join(", ")),
+				..Default::default()
+			};
+
+			if read_ns.is_empty() {
+				rval.push( Finding::with_detail(ErrorCode::NoAuthoratative, "no authoratative nameservers at root", detail) );
+			} else if read_ns.len() != ns.len() || ns.iter().any(|nsname| !read_ns.iter().any(|cmpns| cmpns.eq_ignore_ascii_case(nsname))) {
+				rval.push( Finding::with_detail(ErrorCode::AuthoratativeFail, "nameservers at root do not match expected", detail) );
+			}
+
+		}
+
+		rval
+	}
+}
+
+/**
+ * Query the authoratative nameservers for the A and AAAA records and compare them
+ * against the expected ip
+ */
+pub struct IpCheck {}
+
+impl Check for IpCheck {
+
+	fn name( &self ) -> &str {
+		"ip"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let ips = match &monitor.ip {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+
+		let mut rval = authoritative_addresses(monitor, sender_factory, root, result);
+
+		if result.ips.is_none() && !result.nameservers.as_ref().is_none_or(|ns| ns.is_empty()) {
+			rval.push( Finding::new(ErrorCode::NoResolve, "no authoratative nameserver answered") );
+		}
+
+		if let Some(read_addresses) = &result.ips {
+			let detail = FindingDetail {
+				expected: Some(ips.iter().map(|ip| ip.to_string()).collect::<Vec<String>>().join(", ")),
+				actual: Some(join_ips(read_addresses)),
+				..Default::default()
+			};
+
+			// every address has to be expected and every expected address or network has to
+			// be answered, with only exact addresses that means the lists are the same
+			let (unexpected, missing) = ExpectedIp::unmatched( ips, read_addresses );
+			if read_addresses.is_empty() {
+				rval.push( Finding::with_detail(ErrorCode::NoResolve, "domain did not resolve", detail) );
+			} else if !unexpected.is_empty() || !missing.is_empty() {
+				rval.push( Finding::with_detail(ErrorCode::ResolveIpNotMatch, "did not return the correct ips", detail) );
+			}
+		}
+
+		rval
+	}
+}
+
+/**
+ * Ask each of the --resolver recursive resolvers for the A and AAAA records and compare them
+ * against the authoratative answer, a difference means something between the authority and
+ * the resolver is caching, filtering or rewriting the answer
+ */
+pub struct ResolverCheck {}
+
+impl ResolverCheck {
+
+	/**
+	 * a NoResolve when the resolver gave no answer, a ResolverDivergence when the addresses it
+	 * answered with aren't the authoratative ones
+	 */
+	pub fn compare( resolver : &std::net::IpAddr, authoritative : &[std::net::IpAddr], answer : Option<&[std::net::IpAddr]> ) -> Vec<Finding> {
+
+		let read_addresses = match answer {
+			Some(m) => { m },
+			None => {
+				return vec![ Finding::with_detail(
+					ErrorCode::NoResolve,
+					&format!("resolver {} did not answer", resolver),
+					FindingDetail { server: Some(resolver.to_string()), ..Default::default() }
+				) ];
+			}
+		};
+
+		match crate::baseline::Baseline::diff(authoritative, read_addresses) {
+			Some(diff) => {
+				vec![ Finding::with_detail(
+					ErrorCode::ResolverDivergence,
+					&format!("resolver {} differs from authoratative, {}", resolver, diff),
+					FindingDetail {
+						server: Some(resolver.to_string()),
+						expected: Some(join_ips(authoritative)),
+						actual: Some(join_ips(read_addresses))
+					}
+				) ]
+			},
+			None => { Vec::new() }
+		}
+	}
+}
+
+impl Check for ResolverCheck {
+
+	fn name( &self ) -> &str {
+		"resolver"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let resolvers = crate::config::RESOLVERS.read().unwrap().clone();
+		if resolvers.is_empty() {
+			return Vec::new();
+		}
+
+		let mut rval = Vec::new();
+
+		if result.ips.is_none() {
+			rval = authoritative_addresses(monitor, sender_factory, root, result);
+		}
+
+		let authoritative = match &result.ips {
+			Some(m) => { m.clone() },
+			None => { return rval; }
+		};
+
+		for resolver in &resolvers {
+
+			let mut query = sender_factory( resolver );
+			// the resolvers aren't the monitor's nameservers, they listen on the usual port
+			query.port = query::DNS_PORT;
+			query.recursive = true;
+
+			let mut answered = false;
+			let mut read_addresses : Vec<std::net::IpAddr> = Vec::new();
+
+			for qtype in [ query::QueryType::T_A, query::QueryType::T_AAAA ] {
+				if let Some(found) = query_addresses(&monitor.domain_name, &mut query, qtype) {
+					answered = true;
+					read_addresses.extend(found.addresses);
+				}
+			}
+
+			let answer = if answered { Some(read_addresses.as_slice()) } else { None };
+			rval.extend( Self::compare( resolver, &authoritative, answer ) );
+		}
+
+		rval
+	}
+}
+
+/**
+ * the PTR record did not resolve, the expected name is included when the monitor has one
+ */
+fn ptr_not_found( rev : &crate::monitor::ReverseMonitor, message : &str ) -> Finding {
+	match &rev.ptr {
+		Some(ptr) => { Finding::with_detail( ErrorCode::NoResolve, message, FindingDetail { expected: Some(ptr.clone()), ..Default::default() } ) },
+		None => { Finding::new( ErrorCode::NoResolve, message ) }
+	}
+}
+
+/**
+ * Resolve the PTR record of each of the monitor's reverse addresses and compare it against the
+ * expected hostname. With fcrdns set the hostnames must also resolve back to the address
+ */
+pub struct PtrCheck {}
+
+impl PtrCheck {
+
+	/**
+	 * compare the PTR lookup for the address with the expected hostname, with fcrdns the names
+	 * it found are forward resolved with resolve_host and one of them has to give the address back
+	 */
+	pub fn compare( rev : &crate::monitor::ReverseMonitor, lookup : Result<Vec<String>, String>, resolve_host : &dyn Fn(&str) -> Vec<std::net::IpAddr> ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		let names = match lookup {
+			Ok(m) => { m },
+			Err(e) => {
+				return vec![ ptr_not_found( rev, &format!("PTR for {} did not resolve, {}", rev.ip, e) ) ];
+			}
+		};
+
+		if names.is_empty() {
+			return vec![ ptr_not_found( rev, &format!("{} has no PTR record", rev.ip) ) ];
+		}
+
+		if let Some(ptr) = &rev.ptr {
+			if !names.iter().any(|n| zone::record::name_eq(n, ptr)) {
+				rval.push( Finding::with_detail(
+					ErrorCode::PtrMismatch,
+					&format!("PTR for {} does not match expected", rev.ip),
+					FindingDetail { expected: Some(ptr.clone()), actual: Some(names.join(", ")), ..Default::default() }
+				));
+			}
+		}
+
+		if rev.fcrdns {
+
+			let mut forward : Vec<std::net::IpAddr> = Vec::new();
+			for name in &names {
+				forward.extend( resolve_host( name ) );
+			}
+
+			if !forward.contains(&rev.ip) {
+				rval.push( Finding::with_detail(
+					ErrorCode::FcrdnsMismatch,
+					&format!("{} does not resolve back to {}", names.join(", "), rev.ip),
+					FindingDetail { expected: Some(rev.ip.to_string()), actual: Some(join_ips(&forward)), ..Default::default() }
+				));
+			}
+		}
+
+		rval
+	}
+}
+
+impl Check for PtrCheck {
+
+	fn name( &self ) -> &str {
+		"ptr"
+	}
+
+	fn run( &self, monitor : &Monitor, _sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, _result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let reverse = match &monitor.reverse {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+
+		let start_servers = |n : &str| closest_servers(root, n);
+		let mut rval = Vec::new();
+
+		for rev in reverse {
+			let names = resolver::lookup_ptr( &rev.ip, &start_servers );
+			rval.extend( Self::compare( rev, names, &|name| resolver::lookup_host( name, &start_servers ).unwrap_or_default() ) );
+		}
+
+		rval
+	}
+}
+
+/**
+ * Resolve every authoratative nameserver and flag any address outside of the monitor's
+ * allowed_ns_networks, a delegation moved to someone else's infrastructure shows up here
+ */
+pub struct NsNetworkCheck {}
+
+impl NsNetworkCheck {
+
+	/**
+	 * an UnexpectedNsNetwork for each address of the nameserver outside of the networks, a
+	 * nameserver whose addresses couldn't be looked up is a NoResolve
+	 */
+	pub fn compare( networks : &[ipnet::IpNet], ns : &str, lookup : Result<Vec<std::net::IpAddr>, String> ) -> Vec<Finding> {
+
+		let addresses = match lookup {
+			Ok(m) => { m },
+			Err(e) => {
+				return vec![ Finding::with_detail(
+					ErrorCode::NoResolve,
+					&format!("nameserver {} did not resolve, {}", ns, e),
+					FindingDetail { server: Some(ns.to_string()), ..Default::default() }
+				) ];
+			}
+		};
+
+		let allowed = networks.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(", ");
+
+		addresses.iter()
+			.filter(|addr| !networks.iter().any(|n| n.contains(*addr)))
+			.map(|addr| {
+				Finding::with_detail(
+					ErrorCode::UnexpectedNsNetwork,
+					&format!("nameserver {} address {} is outside of the allowed networks", ns, addr),
+					FindingDetail {
+						server: Some(ns.to_string()),
+						expected: Some(allowed.clone()),
+						actual: Some(addr.to_string())
+					}
+				)
+			})
+			.collect()
+	}
+}
+
+impl Check for NsNetworkCheck {
+
+	fn name( &self ) -> &str {
+		"ns_network"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let networks = match &monitor.allowed_ns_networks {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval = delegation(monitor, sender_factory, root, result);
+		}
+
+		for ns in result.nameservers.clone().unwrap_or_default() {
+			let lookup = resolver::lookup_host( &ns, &|n| closest_servers(root, n) );
+			rval.extend( Self::compare( networks, &ns, lookup ) );
+		}
+
+		rval
+	}
+}
+
+/**
+ * a sender for the first authoratative nameserver that answers a SOA query for the domain with
+ * the DNSSEC records asked for, the SOA response is left in the sender
+ */
+fn dnssec_sender( monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult, findings : &mut Vec<Finding> ) -> Option<query::Sender> {
+
+	if result.nameservers.is_none() {
+		findings.extend( delegation(monitor, sender_factory, root, result) );
+	}
+
+	for ns in result.nameservers.clone().unwrap_or_default() {
+
+		let addresses = match resolver::lookup_host( &ns, &|n| closest_servers(root, n) ) {
+			Ok(m) => { m },
+			Err(_) => { continue; }
+		};
+
+		for addr in addresses {
+			let mut query = sender_factory( &addr );
+			query.tsig = monitor.tsig.clone();
+			query.dnssec_ok = true;
+			query.edns_udp_size = Some( query::DNSSEC_EDNS_UDP_SIZE );
+			if query.query( &monitor.domain_name, query::QueryType::T_SOA ).is_ok() {
+				return Some(query);
+			}
+		}
+	}
+
+	None
+}
+
+/** default for Monitor::signature_warning_days */
+pub const SIGNATURE_WARNING_DAYS : u32 = 7;
+
+/**
+ * Ask an authoratative nameserver for the domain's SOA, DNSKEY, A and AAAA records with their
+ * RRSIGs and flag any signature that has expired or expires within the warning window. This
+ * doesn't validate the signatures, it catches the common outage of a zone that stopped being
+ * re-signed
+ */
+pub struct RrsigExpiryCheck {}
+
+impl RrsigExpiryCheck {
+
+	/**
+	 * a SignatureExpired or SignatureExpiringSoon for each RRSIG in the answer from server that
+	 * has expired at now or expires within warning_days of it
+	 */
+	pub fn compare( server : &std::net::IpAddr, now : u32, warning_days : u32, answer : &[zone::record::ZoneRecord] ) -> Vec<Finding> {
+
+		// in u64, a warning window of more than 49710 days overflows u32 seconds
+		let window = warning_days as u64 * 86400;
+		let mut rval = Vec::new();
+
+		for rec in answer {
+
+			let rrsig = match rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATArrsig>()) {
+				Some(m) => { m },
+				None => { continue; }
+			};
+
+			// serial number arithmetic (RFC-4034 3.1.5), the timestamps wrap in 2106
+			let remaining = rrsig.sig_expiration.wrapping_sub(now) as i32;
+			let (code, state) = if remaining <= 0 {
+				(ErrorCode::SignatureExpired, "expired")
+			} else if (remaining as u64) < window {
+				(ErrorCode::SignatureExpiringSoon, "expires")
+			} else {
+				continue;
+			};
+
+			let expiration = zone::rr::format_dnssec_time(rrsig.sig_expiration);
+			rval.push( Finding::with_detail(
+				code,
+				&format!("RRSIG for {} {} by key {} {} {}", rec.name.fqdn, rrsig.covered(), rrsig.key_tag, state, expiration),
+				FindingDetail {
+					server: Some(server.to_string()),
+					actual: Some(expiration),
+					..Default::default()
+				}
+			));
+		}
+
+		rval
+	}
+}
+
+impl Check for RrsigExpiryCheck {
+
+	fn name( &self ) -> &str {
+		"rrsig_expiry"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		let mut query = match dnssec_sender(monitor, sender_factory, root, result, &mut rval) {
+			Some(m) => { m },
+			None => { return rval; }
+		};
+
+		let now = crate::tsig::now() as u32;
+		let warning_days = monitor.signature_warning_days.unwrap_or(SIGNATURE_WARNING_DAYS);
+
+		for qtype in [ query::QueryType::T_SOA, query::QueryType::T_DNSKEY, query::QueryType::T_A, query::QueryType::T_AAAA ] {
+
+			// the SOA answer is already in the sender
+			if qtype != query::QueryType::T_SOA && query.query( &monitor.domain_name, qtype ).is_err() {
+				continue;
+			}
+
+			rval.extend( Self::compare( query.server(), now, warning_days, &query.answer ) );
+		}
+
+		rval
+	}
+}
+
+/**
+ * For a monitor with dnssec set, ask an authoratative nameserver for the domain's SOA, NS, A
+ * and AAAA records and make sure every RRset in the answers has an RRSIG covering it. A
+ * validating resolver SERVFAILs an RRset from a signed zone that is missing its signature
+ */
+pub struct DnssecCheck {}
+
+impl DnssecCheck {
+
+	/**
+	 * a MissingSignature for each RRset in the answer from server without an RRSIG covering it
+	 */
+	pub fn compare( server : &std::net::IpAddr, answer : &[zone::record::ZoneRecord] ) -> Vec<Finding> {
+
+		let mut unsigned : Vec<(&str, zone::record::RecordType)> = Vec::new();
+
+		for rec in answer {
+
+			if rec.record_type == zone::record::RecordType::RRSIG || unsigned.iter().any(|(name, rt)| *rt == rec.record_type && zone::record::name_eq(name, &rec.name.fqdn)) {
+				continue;
+			}
+
+			let signed = answer.iter().any(|sig| {
+				zone::record::name_eq(&sig.name.fqdn, &rec.name.fqdn)
+					&& sig.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATArrsig>()).is_some_and(|r| r.covered() == rec.record_type)
+			});
+
+			if !signed {
+				unsigned.push( (&rec.name.fqdn, rec.record_type) );
+			}
+		}
+
+		unsigned.into_iter().map(|(name, record_type)| {
+			Finding::with_detail(
+				ErrorCode::MissingSignature,
+				&format!("{} {} has no RRSIG", name, record_type),
+				FindingDetail { server: Some(server.to_string()), ..Default::default() }
+			)
+		}).collect()
+	}
+}
+
+impl Check for DnssecCheck {
+
+	fn name( &self ) -> &str {
+		"dnssec"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		if !monitor.dnssec {
+			return Vec::new();
+		}
+
+		let mut rval = Vec::new();
+
+		let mut query = match dnssec_sender(monitor, sender_factory, root, result, &mut rval) {
+			Some(m) => { m },
+			None => { return rval; }
+		};
+
+		for qtype in [ query::QueryType::T_SOA, query::QueryType::T_NS, query::QueryType::T_A, query::QueryType::T_AAAA ] {
+
+			// the SOA answer is already in the sender
+			if qtype != query::QueryType::T_SOA && query.query( &monitor.domain_name, qtype ).is_err() {
+				continue;
+			}
+
+			rval.extend( Self::compare( query.server(), &query.answer ) );
+		}
+
+		rval
+	}
+}
+
+/**
+ * For a monitor that expects nxdomain, look the name up from the root and fail if the
+ * authoratative answer is anything but NXDOMAIN. Used for decommissioned names and names
+ * that must never be delegated
+ */
+pub struct NxdomainCheck {}
+
+impl NxdomainCheck {
+
+	/**
+	 * an UnexpectedlyExists when the lookup of the domain found it, a NoResolve when it failed
+	 */
+	pub fn compare( domain_name : &str, exists : Result<bool, String> ) -> Vec<Finding> {
+
+		match exists {
+			Ok(false) => { Vec::new() },
+			Ok(true) => {
+				vec![ Finding::with_detail(
+					ErrorCode::UnexpectedlyExists,
+					&format!("{} exists but should be NXDOMAIN", domain_name),
+					FindingDetail { expected: Some("NXDOMAIN".to_string()), ..Default::default() }
+				) ]
+			},
+			Err(e) => {
+				vec![ Finding::new( ErrorCode::NoResolve, &format!("could not look up {}, {}", domain_name, e) ) ]
+			}
+		}
+	}
+}
+
+impl Check for NxdomainCheck {
+
+	fn name( &self ) -> &str {
+		"nxdomain"
+	}
+
+	fn run( &self, monitor : &Monitor, _sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, _result : &mut MonitorResult ) -> Vec<Finding> {
+
+		if monitor.expect != Expect::Nxdomain {
+			return Vec::new();
+		}
+
+		Self::compare( &monitor.domain_name, resolver::name_exists( &monitor.domain_name, &|n| closest_servers(root, n) ) )
+	}
+}
+
+/**
+ * the records of a zone file for ZoneFileCheck, read once and kept for the next passes of
+ * --watch until the file is modified
+ */
+pub struct ZoneFile {
+	pub file_name : String,
+	pub apex : String,
+	/** when the file was modified as it was read */
+	pub modified : Option<std::time::SystemTime>,
+	pub records : Vec<zone::record::ZoneRecord>,
+	/** the names of the delegations in the zone, their records are the child zone's */
+	pub delegations : Vec<String>,
+	/** a ZoneFileInvalid for the records --skip-bad-records skipped */
+	pub skipped : Option<Finding>
+}
+
+impl ZoneFile {
+
+	/**
+	 * read the zone file for the apex, the apex ends with a dot
+	 */
+	pub fn read( file_name : &str, apex : &str ) -> Result<Self, String> {
+
+		let modified = std::fs::metadata( file_name ).and_then(|m| m.modified()).ok();
+
+		let zone = zone::Zone::create_with( &file_name.to_string(), &apex.to_string(), *crate::config::SKIP_BAD_RECORDS.read().unwrap() )?;
+
+		// the rest of the zone is still compared
+		let skipped = if zone.skipped.is_empty() {
+			None
+		} else {
+			Some( Finding::new( ErrorCode::ZoneFileInvalid, &format!("skipped {} records of {} that could not be parsed, lines {}", zone.skipped.len(), file_name, zone.skipped_lines()) ) )
+		};
+
+		let records : Vec<zone::record::ZoneRecord> = zone.records.iter()
+			.filter_map(|r| r.as_any_ref().downcast_ref::<zone::record::ZoneRecord>())
+			.cloned()
+			.collect();
+
+		let delegations : Vec<String> = records.iter()
+			.filter(|r| r.record_type == zone::record::RecordType::NS && !zone::record::name_eq( &r.name.fqdn, apex ))
+			.map(|r| r.name.fqdn.clone())
+			.collect();
+
+		Ok(Self {
+			file_name: file_name.to_string(),
+			apex: apex.to_string(),
+			modified,
+			records,
+			delegations,
+			skipped
+		})
+	}
+}
+
+lazy_static!(
+	/** the zone files ZoneFileCheck has read by their name and apex */
+	static ref ZONE_FILES : std::sync::Mutex<HashMap<(String, String), Arc<ZoneFile>>> = std::sync::Mutex::new(HashMap::new());
+);
+
+/**
+ * For a monitor with a zone_file, ask every authoratative nameserver for each RRset in the
+ * zone and make sure it serves the same records, so the live zone can be checked against the
+ * one in version control. The TTLs are not compared. Delegations and the DNSSEC records,
+ * which are added when the zone is signed, are skipped
+ */
+pub struct ZoneFileCheck {}
+
+impl ZoneFileCheck {
+
+	/**
+	 * the zone file read for the apex, the one read on an earlier pass unless the file was
+	 * modified since
+	 */
+	pub fn zone_file( file_name : &str, apex : &str ) -> Result<Arc<ZoneFile>, String> {
+
+		let key = (file_name.to_string(), apex.to_string());
+		let modified = std::fs::metadata( file_name ).and_then(|m| m.modified()).ok();
+
+		if let Some(m) = ZONE_FILES.lock().unwrap().get( &key ) {
+			if m.modified.is_some() && m.modified == modified {
+				return Ok( Arc::clone(m) );
+			}
+		}
+
+		let rval = Arc::new( ZoneFile::read( file_name, apex )? );
+		ZONE_FILES.lock().unwrap().insert( key, Arc::clone(&rval) );
+
+		Ok(rval)
+	}
+
+	/**
+	 * whether the RRset is one the zone's nameservers answer for with the same records as
+	 * the file. Below a delegation the child's servers answer, the parent only has a referral
+	 */
+	fn comparable( rrset : &[&zone::record::ZoneRecord], delegations : &[String], apex : &str ) -> bool {
+
+		let rec = rrset[0];
+
+		if query::QueryType::from_u16( &(rec.record_type as u16) ) == query::QueryType::T_INVALID {
+			return false;
+		}
+
+		if matches!( rec.record_type, zone::record::RecordType::RRSIG | zone::record::RecordType::NSEC | zone::record::RecordType::NSEC3 | zone::record::RecordType::NSEC3PARAM ) {
+			return false;
+		}
+
+		if !zone::record::name_eq( &rec.name.fqdn, apex ) && !zone::record::name_is_under( &rec.name.fqdn, apex ) {
+			return false;
+		}
+
+		// the NS and DS at a delegation are the parent's, anything else there is the child's
+		!delegations.iter().any(|cut| {
+			zone::record::name_is_under( &rec.name.fqdn, cut )
+				|| (zone::record::name_eq( &rec.name.fqdn, cut ) && rec.record_type != zone::record::RecordType::DS)
+		})
+	}
+
+	/**
+	 * ask the query's server, the nameserver ns, for each RRset of the zone and a ZoneMismatch
+	 * for each it serves differently. Only the first unanswered query is reported
+	 */
+	pub fn compare( zone : &ZoneFile, ns : &str, query : &mut query::Sender, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		let records : Vec<&zone::record::ZoneRecord> = zone.records.iter().collect();
+		let index = zone::RRsetIndex::new( &records );
+		let addr = *query.server();
+
+		for rrset in index.iter().filter(|rrset| Self::comparable( rrset, &zone.delegations, &zone.apex )) {
+
+			let owner = &rrset[0].name.fqdn;
+			let record_type = rrset[0].record_type;
+
+			// one unanswered query is reported for the server instead of every RRset
+			if let Err(e) = query.query( owner, query::QueryType::from_u16( &(record_type as u16) ) ) {
+				rval.push( Finding::with_detail(
+					ErrorCode::ZoneMismatch,
+					&format!("{} ({}) did not answer for {} {}, {}", ns, addr, owner, record_type, e),
+					FindingDetail { server: Some(addr.to_string()), ..Default::default() }
+				));
+				break;
+			}
+
+			rval.extend( check_class(result, &rval, query) );
+			record_flags( result, query );
+
+			let served : Vec<&zone::record::ZoneRecord> = query.answer.iter()
+				.filter(|r| r.record_type == record_type && zone::record::name_eq( &r.name.fqdn, owner ))
+				.collect();
+
+			let missing = rrset.iter().any(|r| !served.iter().any(|s| s == r));
+			let extra = served.iter().any(|s| !rrset.iter().any(|r| r == s));
+
+			if missing || extra {
+				let rdata = |list : &[&zone::record::ZoneRecord]| -> String {
+					list.iter().map(|r| r.rdata.as_ref().map_or(String::new(), |d| d.to_string().trim().to_string())).collect::<Vec<String>>().join(", ")
+				};
+				rval.push( Finding::with_detail(
+					ErrorCode::ZoneMismatch,
+					&format!("{} ({}) serves {} {} differently than {}", ns, addr, owner, record_type, zone.file_name),
+					FindingDetail {
+						server: Some(addr.to_string()),
+						expected: Some(rdata(rrset)),
+						actual: Some(rdata(&served))
+					}
+				));
+			}
+		}
+
+		rval
+	}
+
+}
+
+impl Check for ZoneFileCheck {
+
+	fn name( &self ) -> &str {
+		"zone_file"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let zone_file = match &monitor.zone_file {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+
+		let mut rval = Vec::new();
+
+		let apex = if monitor.domain_name.ends_with('.') { monitor.domain_name.clone() } else { format!("{}.", monitor.domain_name) };
+
+		let zone = match Self::zone_file( zone_file, &apex ) {
+			Ok(m) => { m },
+			Err(e) => {
+				rval.push( Finding::new( ErrorCode::ZoneFileInvalid, &format!("could not read {}, {}", zone_file, e) ) );
+				return rval;
+			}
+		};
+
+		rval.extend( zone.skipped.clone() );
+
+		if result.nameservers.is_none() {
+			rval.extend( delegation(monitor, sender_factory, root, result) );
+		}
+
+		for ns in result.nameservers.clone().unwrap_or_default() {
+
+			let addresses = match resolver::lookup_host( &ns, &|n| closest_servers(root, n) ) {
+				Ok(m) => { m },
+				Err(e) => {
+					rval.push( Finding::with_detail(
+						ErrorCode::NoResolve,
+						&format!("could not resolve nameserver {}, {}", ns, e),
+						FindingDetail { server: Some(ns.clone()), ..Default::default() }
+					));
+					continue;
+				}
+			};
+
+			for addr in addresses {
+
+				let mut query = sender_factory( &addr );
+				query.tsig = monitor.tsig.clone();
+
+				// compare only sees its own findings, the class is reported once for the domain
+				for finding in Self::compare( &zone, &ns, &mut query, result ) {
+					if finding.code != ErrorCode::ClassMismatch || !rval.iter().any(|f| f.code == ErrorCode::ClassMismatch) {
+						rval.push( finding );
+					}
+				}
+			}
+		}
+
+		rval
+	}
+}
+
+/**
+ * Ask the authoratative nameservers for the A and AAAA records as a client in each network of
+ * the monitor's geo, with the EDNS Client Subnet option, and compare each answer against the
+ * addresses expected there
+ */
+pub struct GeoCheck {}
+
+impl GeoCheck {
+
+	/**
+	 * a GeoMismatch for each network whose answer from the server isn't what was expected
+	 */
+	pub fn compare( geo : &BTreeMap<ipnet::IpNet, Vec<ExpectedIp>>, server : &std::net::IpAddr, answers : &BTreeMap<ipnet::IpNet, Vec<std::net::IpAddr>> ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		for (subnet, expected) in geo {
+
+			let actual = match answers.get(subnet) {
+				Some(m) => { m },
+				None => { continue; }
+			};
+
+			let (unexpected, missing) = ExpectedIp::unmatched( expected, actual );
+			if unexpected.is_empty() && missing.is_empty() {
+				continue;
+			}
+
+			rval.push( Finding::with_detail(
+				ErrorCode::GeoMismatch,
+				&format!("clients in {} did not get the correct ips, unexpected [{}] missing [{}]",
+					subnet,
+					join_ips(&unexpected),
+					missing.iter().map(|ip| ip.to_string()).collect::<Vec<String>>().join(", ")
+				),
+				FindingDetail {
+					server: Some(server.to_string()),
+					expected: Some(expected.iter().map(|ip| ip.to_string()).collect::<Vec<String>>().join(", ")),
+					actual: Some(join_ips(actual))
+				}
+			));
+		}
+
+		rval
+	}
+
+}
+
+impl Check for GeoCheck {
+
+	fn name( &self ) -> &str {
+		"geo"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let geo = match &monitor.geo {
+			Some(m) if !m.is_empty() => { m },
+			_ => { return Vec::new(); }
+		};
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval.extend( delegation(monitor, sender_factory, root, result) );
+		}
+
+		let nameservers = result.nameservers.clone().unwrap_or_default();
+
+		// the first nameserver that answers for every network is compared, like the ip check
+		for ns in &nameservers {
+
+			let addresses = match resolver::lookup_host( ns, &|n| closest_servers(root, n) ) {
+				Ok(m) => { m },
+				Err(_) => { continue; }
+			};
+
+			for addr in addresses {
+
+				let mut answers : BTreeMap<ipnet::IpNet, Vec<std::net::IpAddr>> = BTreeMap::new();
+
+				for subnet in geo.keys() {
+
+					let mut query = sender_factory( &addr );
+					query.tsig = monitor.tsig.clone();
+					query.client_subnet = Some(*subnet);
+
+					let mut answered : Option<Vec<std::net::IpAddr>> = None;
+					for qtype in [ query::QueryType::T_A, query::QueryType::T_AAAA ] {
+						if let Some(found) = query_addresses( &monitor.domain_name, &mut query, qtype ) {
+							answered.get_or_insert_with(Vec::new).extend( found.addresses );
+						}
+					}
+
+					rval.extend( check_class(result, &rval, &query) );
+
+					match answered {
+						Some(m) => {
+							println_verbose!(VERBOSE2, "{} answered {} for clients in {} with scope {:?}", addr, monitor.domain_name, subnet, query.client_subnet_scope());
+							record_flags( result, &query );
+							answers.insert( *subnet, m );
+						},
+						None => { break; }
+					}
+				}
+
+				if answers.len() == geo.len() {
+					rval.extend( Self::compare( geo, &addr, &answers ) );
+					return rval;
+				}
+			}
+		}
+
+		if !nameservers.is_empty() {
+			rval.push( Finding::new(ErrorCode::NoResolve, "no authoratative nameserver answered the client subnet queries") );
+		}
+
+		rval
+	}
+}
+
+/**
+ * Ask every address of every authoratative nameserver for the SOA and compare the serials, a
+ * secondary that stopped transferring the zone serves an older one. The serials are kept in
+ * the result
+ */
+pub struct SerialCheck {}
+
+impl SerialCheck {
+
+	/**
+	 * a SerialMismatch for each server whose serial isn't the expected one, without an expected
+	 * serial for each server behind the newest serial any of them has
+	 */
+	pub fn compare( expected : Option<u32>, serials : &BTreeMap<std::net::IpAddr, u32> ) -> Vec<Finding> {
+
+		// serial number arithmetic (RFC-1982), a serial that wrapped is still newer
+		let newest = serials.values().copied().reduce(|newest, serial| {
+			if (serial.wrapping_sub(newest) as i32) > 0 { serial } else { newest }
+		});
+
+		let want = match expected.or(newest) {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+
+		serials.iter()
+			.filter(|(_, serial)| **serial != want)
+			.map(|(server, serial)| {
+				let message = match expected {
+					Some(_) => { format!("{} serves serial {}, expected {}", server, serial, want) },
+					None => { format!("{} serves serial {}, other nameservers serve {}", server, serial, want) }
+				};
+				Finding::with_detail(
+					ErrorCode::SerialMismatch,
+					&message,
+					FindingDetail {
+						server: Some(server.to_string()),
+						expected: Some(want.to_string()),
+						actual: Some(serial.to_string())
+					}
+				)
+			})
+			.collect()
+	}
+
+}
+
+impl Check for SerialCheck {
+
+	fn name( &self ) -> &str {
+		"serial"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval.extend( delegation(monitor, sender_factory, root, result) );
+		}
+
+		for ns in result.nameservers.clone().unwrap_or_default() {
+
+			let addresses = match resolver::lookup_host( &ns, &|n| closest_servers(root, n) ) {
+				Ok(m) => { m },
+				Err(_) => { continue; }
+			};
+
+			for addr in addresses {
+
+				let mut query = sender_factory( &addr );
+				query.tsig = monitor.tsig.clone();
+
+				if query.query( &monitor.domain_name, query::QueryType::T_SOA ).is_err() {
+					continue;
+				}
+
+				rval.extend( check_class(result, &rval, &query) );
+				record_flags( result, &query );
+
+				let serial = query.answer.iter()
+					.filter(|r| zone::record::name_eq( &r.name.fqdn, &monitor.domain_name ))
+					.find_map(|r| r.rdata.as_ref().and_then(|d| d.as_any().downcast_ref::<zone::rr::RDATAsoa>()))
+					.map(|soa| soa.serial);
+
+				if let Some(serial) = serial {
+					println_verbose!(VERBOSE2, "{} ({}) serves {} serial {}", ns, addr, monitor.domain_name, serial);
+					result.serials.insert( addr, serial );
+				}
+			}
+		}
+
+		rval.extend( Self::compare( monitor.expected_serial, &result.serials ) );
+
+		rval
+	}
+}
+
+/**
+ * the NS and address records one nameserver address answered with for the domain, the
+ * names are lower case
+ */
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct ServerAnswers {
+	pub ns : BTreeSet<String>,
+	pub addresses : BTreeSet<std::net::IpAddr>
+}
+
+impl std::fmt::Display for ServerAnswers {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "ns [{}] ips [{}]",
+			self.ns.iter().cloned().collect::<Vec<String>>().join(", "),
+			join_ips( &self.addresses.iter().copied().collect::<Vec<std::net::IpAddr>>() )
+		)
+	}
+}
+
+/**
+ * When the monitor has check_consistency set, ask every address of every authoratative
+ * nameserver for the NS, A and AAAA records and compare them. The other checks stop at the
+ * first server that answers so one serving stale records, like a secondary that missed an
+ * update, goes unnoticed
+ */
+pub struct ConsistencyCheck {}
+
+impl ConsistencyCheck {
+
+	/**
+	 * an Inconsistent finding for each server whose answers aren't the ones most of the
+	 * servers gave, the first server's answers win a tie
+	 */
+	pub fn compare( answers : &BTreeMap<std::net::IpAddr, ServerAnswers> ) -> Vec<Finding> {
+
+		let mut counts : Vec<(&ServerAnswers, usize)> = Vec::new();
+		for answer in answers.values() {
+			match counts.iter_mut().find(|(a, _)| *a == answer) {
+				Some((_, count)) => { *count += 1; },
+				None => { counts.push( (answer, 1) ); }
+			}
+		}
+
+		// max_by_key keeps the last of equal counts
+		let (common, agreeing) = match counts.iter().rev().max_by_key(|(_, count)| *count) {
+			Some(m) => { *m },
+			None => { return Vec::new(); }
+		};
+
+		answers.iter()
+			.filter(|(_, answer)| *answer != common)
+			.map(|(server, answer)| {
+				Finding::with_detail(
+					ErrorCode::Inconsistent,
+					&format!("{} answers {} differently than {} of the {} nameserver addresses", server, answer, agreeing, answers.len()),
+					FindingDetail {
+						server: Some(server.to_string()),
+						expected: Some(common.to_string()),
+						actual: Some(answer.to_string())
+					}
+				)
+			})
+			.collect()
+	}
+
+}
+
+impl Check for ConsistencyCheck {
+
+	fn name( &self ) -> &str {
+		"consistency"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		if !monitor.check_consistency {
+			return Vec::new();
+		}
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval.extend( delegation(monitor, sender_factory, root, result) );
+		}
+
+		let mut answers : BTreeMap<std::net::IpAddr, ServerAnswers> = BTreeMap::new();
+
+		for ns in result.nameservers.clone().unwrap_or_default() {
+
+			let addresses = match resolver::lookup_host( &ns, &|n| closest_servers(root, n) ) {
+				Ok(m) => { m },
+				Err(_) => { continue; }
+			};
+
+			for addr in addresses {
+
+				let mut query = sender_factory( &addr );
+				query.tsig = monitor.tsig.clone();
+
+				// a server that doesn't answer is left out rather than counted as disagreeing
+				if query.query( &monitor.domain_name, query::QueryType::T_NS ).is_err() {
+					continue;
+				}
+
+				rval.extend( check_class(result, &rval, &query) );
+				record_flags( result, &query );
+
+				let mut answer = ServerAnswers {
+					ns: query.zone_nameservers( &monitor.domain_name ).iter().map(|n| n.to_ascii_lowercase()).collect(),
+					..Default::default()
+				};
+
+				for qtype in [ query::QueryType::T_A, query::QueryType::T_AAAA ] {
+					if let Some(found) = query_addresses( &monitor.domain_name, &mut query, qtype ) {
+						answer.addresses.extend( found.addresses );
+					}
+				}
+
+				println_verbose!(VERBOSE2, "{} ({}) answers {} with {}", ns, addr, monitor.domain_name, answer);
+				answers.insert( addr, answer );
+			}
+		}
+
+		let findings = Self::compare( &answers );
+		result.inconsistent_servers = findings.iter()
+			.filter_map(|f| f.detail.as_ref().and_then(|d| d.server.as_ref()).and_then(|s| s.parse().ok()))
+			.collect();
+		rval.extend( findings );
+
+		rval
+	}
+}
+
+/**
+ * When the monitor has an expected_ttl, compare the TTLs of the domain's A, AAAA and NS
+ * records from the first authoratative nameserver that answers against it
+ */
+pub struct TtlCheck {}
+
+impl TtlCheck {
+
+	/** the record types whose TTLs are compared */
+	pub const RECORD_TYPES : [zone::record::RecordType; 3] = [ zone::record::RecordType::A, zone::record::RecordType::AAAA, zone::record::RecordType::NS ];
+
+	/**
+	 * a TtlMismatch for each type of the domain's records that has a TTL more than tolerance
+	 * seconds from the expected one, the records are the answers of the server
+	 */
+	pub fn compare( domain_name : &str, expected : i32, tolerance : i32, server : &std::net::IpAddr, records : &[zone::record::ZoneRecord] ) -> Vec<Finding> {
+
+		let mut rval = Vec::new();
+
+		for record_type in Self::RECORD_TYPES {
+
+			let mut outside : Vec<i32> = records.iter()
+				.filter(|r| r.record_type == record_type && zone::record::name_eq( &r.name.fqdn, domain_name ))
+				.map(|r| r.ttl)
+				.filter(|ttl| (*ttl as i64 - expected as i64).abs() > tolerance as i64)
+				.collect();
+			outside.sort_unstable();
+			outside.dedup();
+
+			if outside.is_empty() {
+				continue;
+			}
+
+			let actual = outside.iter().map(|ttl| ttl.to_string()).collect::<Vec<String>>().join(", ");
+			rval.push( Finding::with_detail(
+				ErrorCode::TtlMismatch,
+				&format!("{} {} records from {} have TTL {}, expected {} within {}", domain_name, record_type, server, actual, expected, tolerance),
+				FindingDetail {
+					server: Some(server.to_string()),
+					expected: Some(expected.to_string()),
+					actual: Some(actual)
+				}
+			));
+		}
+
+		rval
+	}
+
+}
+
+impl Check for TtlCheck {
+
+	fn name( &self ) -> &str {
+		"ttl"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, root : &Arc<RwLock<root::Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+
+		let expected = match monitor.expected_ttl {
+			Some(m) => { m },
+			None => { return Vec::new(); }
+		};
+		let tolerance = monitor.ttl_tolerance.unwrap_or(0);
+
+		let mut rval = Vec::new();
+
+		if result.nameservers.is_none() {
+			rval.extend( delegation(monitor, sender_factory, root, result) );
+		}
+
+		for ns in result.nameservers.clone().unwrap_or_default() {
+
+			let addresses = match resolver::lookup_host( &ns, &|n| closest_servers(root, n) ) {
+				Ok(m) => { m },
+				Err(_) => { continue; }
+			};
+
+			for addr in addresses {
+
+				let mut query = sender_factory( &addr );
+				query.tsig = monitor.tsig.clone();
+
+				let mut records : Vec<zone::record::ZoneRecord> = Vec::new();
+				let mut answered = false;
+				for qtype in [ query::QueryType::T_A, query::QueryType::T_AAAA, query::QueryType::T_NS ] {
+					if query.query( &monitor.domain_name, qtype ).is_ok() {
+						answered = true;
+						rval.extend( check_class(result, &rval, &query) );
+						records.extend( query.answer.iter().cloned() );
+					}
+				}
+
+				if answered {
+					record_flags( result, &query );
+					rval.extend( Self::compare( &monitor.domain_name, expected, tolerance, &addr, &records ) );
+					return rval;
+				}
+			}
+		}
+
+		rval
+	}
+}
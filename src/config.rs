@@ -20,6 +20,45 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 lazy_static!(
 	pub static ref VERBOSE : std::sync::RwLock<usize> = std::sync::RwLock::new(0);
+
+	/** when set the IPv4 address family is used even if the egress probe failed */
+	pub static ref FORCE_IPV4 : std::sync::RwLock<bool> = std::sync::RwLock::new(false);
+
+	/** when set the IPv6 address family is used even if the egress probe failed */
+	pub static ref FORCE_IPV6 : std::sync::RwLock<bool> = std::sync::RwLock::new(false);
+
+	/** when set nameserver names are resolved by asking this recursive resolver instead of from the root */
+	pub static ref NS_RESOLVER : std::sync::RwLock<Option<std::net::IpAddr>> = std::sync::RwLock::new(None);
+
+	/** send every query over TCP instead of only asking again over TCP when a UDP response is truncated */
+	pub static ref FORCE_TCP : std::sync::RwLock<bool> = std::sync::RwLock::new(false);
+
+	/** how long a query waits for a response */
+	pub static ref QUERY_TIMEOUT : std::sync::RwLock<std::time::Duration> = std::sync::RwLock::new(std::time::Duration::new(5, 0));
+
+	/** recursive resolvers whose answers are compared against the authoratative answers */
+	pub static ref RESOLVERS : std::sync::RwLock<Vec<std::net::IpAddr>> = std::sync::RwLock::new(Vec::new());
+
+	/** most queries waiting for a response at once across all threads, 0 is unlimited */
+	pub static ref CONCURRENCY : std::sync::RwLock<usize> = std::sync::RwLock::new(0);
+
+	/** leave out the records of the root zone and zone_file that can't be parsed instead of failing */
+	pub static ref SKIP_BAD_RECORDS : std::sync::RwLock<bool> = std::sync::RwLock::new(false);
+
+	/** most names below the closest known zone that are looked up to find a domain's parent zone */
+	pub static ref MAX_DEPTH : std::sync::RwLock<usize> = std::sync::RwLock::new(8);
+
+	/** the longest a nameserver's name is looked up for, including every query it takes */
+	pub static ref LOOKUP_TIMEOUT : std::sync::RwLock<std::time::Duration> = std::sync::RwLock::new(std::time::Duration::from_secs(15));
+
+	/** the most addresses of a nameserver's name that are used */
+	pub static ref MAX_NS_ADDRESSES : std::sync::RwLock<usize> = std::sync::RwLock::new(8);
+
+	/** seed for the query ids so a run sends the same ids each time, random ids when None */
+	pub static ref QUERY_ID_SEED : std::sync::RwLock<Option<u64>> = std::sync::RwLock::new(None);
+
+	/** whether human readable output is colored, see --color */
+	pub static ref COLOR : std::sync::RwLock<ColorMode> = std::sync::RwLock::new(ColorMode::Auto);
 );
 
 pub const VERBOSE_NONE : usize = 0;
@@ -27,31 +66,193 @@ pub const VERBOSE1 : usize = 1;
 pub const VERBOSE2 : usize = 2;
 pub const VERBOSE3 : usize = 3;
 
+pub const COLOR_GREEN : &str = "32";
+pub const COLOR_RED : &str = "31";
+pub const COLOR_YELLOW : &str = "33";
+
+/**
+ * when human readable output is colored, JSON output is never colored
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+	/** color when the output is a terminal and NO_COLOR is not set */
+	Auto,
+	Always,
+	Never,
+}
+
+impl ColorMode {
+
+	pub fn from_string( s : &str ) -> Result<Self, String> {
+		match s.to_lowercase().as_str() {
+			"auto" => { Ok(Self::Auto) }
+			"always" => { Ok(Self::Always) }
+			"never" => { Ok(Self::Never) }
+			_ => { Err(format!("{} is not one of auto, always or never", s)) }
+		}
+	}
+
+	/**
+	 * whether output written to a stream should be colored
+	 * @param is_tty true when the stream is a terminal
+	 */
+	pub fn enabled( &self, is_tty : bool ) -> bool {
+		match self {
+			Self::Auto => { is_tty && std::env::var_os("NO_COLOR").is_none() }
+			Self::Always => { true }
+			Self::Never => { false }
+		}
+	}
+}
+
+/**
+ * wrap text in an ANSI color escape when enabled
+ * @param color one of the COLOR_ constants
+ */
+pub fn colorize( text : &str, color : &str, enabled : bool ) -> String {
+	if enabled {
+		format!("\x1b[{}m{}\x1b[0m", color, text)
+	} else {
+		text.to_string()
+	}
+}
+
+/**
+ * version of the JSON documents dns_audit writes, the results, the cache and the baseline all
+ * carry it as schema_version. It is bumped when a field is removed or changes meaning, a
+ * document without it is from before it was added and is read as version 0
+ */
+pub const SCHEMA_VERSION : u32 = 1;
+
+/**
+ * the schema_version of a document that was read, an error when it is newer than this build
+ * understands so it isn't misparsed
+ * @param what the kind of document for the error, like "cache file"
+ */
+pub fn schema_version( document : &serde_json::Value, what : &str ) -> Result<u32, String> {
+
+	let version = match document.get("schema_version") {
+		Some(v) => {
+			match v.as_u64().and_then(|n| u32::try_from(n).ok()) {
+				Some(m) => { m },
+				None => { return Err(format!("{} has an invalid schema_version {}", what, v)); }
+			}
+		},
+		None => { 0 }
+	};
+
+	check_schema_version( version, what )
+}
+
+/**
+ * the version back when this build understands it, or an error when it's newer
+ */
+pub fn check_schema_version( version : u32, what : &str ) -> Result<u32, String> {
+
+	if version > SCHEMA_VERSION {
+		return Err(format!("{} has schema_version {} but this dns_audit reads up to {}, it was written by a newer version", what, version, SCHEMA_VERSION));
+	}
+
+	Ok(version)
+}
+
+/**
+ * replace each ${NAME} in the text with the environment variable NAME and each ${file:PATH}
+ * with the contents of the file without its trailing newline, so a config can refer to
+ * secrets that aren't committed with it. $${ is a literal ${. An unset variable or a file
+ * that can't be read is an error
+ */
+pub fn expand_env( text : &str ) -> Result<String, String> {
+
+	let mut rval = String::with_capacity( text.len() );
+	let mut rest = text;
+
+	while let Some(pos) = rest.find("${") {
+
+		if rest[..pos].ends_with('$') {
+			rval.push_str( &rest[..pos] );
+			rval.push( '{' );
+			rest = &rest[pos + 2..];
+			continue;
+		}
+
+		rval.push_str( &rest[..pos] );
+
+		let end = match rest[pos + 2..].find('}') {
+			Some(m) => { pos + 2 + m },
+			None => { return Err(format!("unterminated ${{ in \"{}\"", text)); }
+		};
+		let name = &rest[pos + 2..end];
+
+		if let Some(path) = name.strip_prefix("file:") {
+			match std::fs::read_to_string(path) {
+				Ok(m) => { rval.push_str( m.trim_end_matches(['\r', '\n']) ); },
+				Err(e) => { return Err(format!("failed to read ${{{}}} {}", name, e)); }
+			}
+		} else {
+			match std::env::var(name) {
+				Ok(m) => { rval.push_str( &m ); },
+				Err(_) => { return Err(format!("environment variable {} is not set, it is used as ${{{}}}", name, name)); }
+			}
+		}
+
+		rest = &rest[end + 1..];
+	}
+
+	rval.push_str( rest );
+
+	Ok(rval)
+}
+
+/**
+ * expand_env on every string in the document
+ */
+pub fn expand_env_json( document : &mut serde_json::Value ) -> Result<(), String> {
+	match document {
+		serde_json::Value::String(s) if s.contains("${") => {
+			*s = expand_env(s)?;
+		},
+		serde_json::Value::Array(list) => {
+			for v in list {
+				expand_env_json(v)?;
+			}
+		},
+		serde_json::Value::Object(map) => {
+			for v in map.values_mut() {
+				expand_env_json(v)?;
+			}
+		},
+		_ => {}
+	}
+	Ok(())
+}
+
+#[macro_export]
 macro_rules! println_verbose {
 	($level:ident) => {
-		if *crate::config::VERBOSE.read().unwrap() >= crate::config::$level {
+		if *$crate::config::VERBOSE.read().unwrap() >= $crate::config::$level {
 			println!();
 		}
 	};
 	($level:ident, $($args:expr),*) => {
-		if *crate::config::VERBOSE.read().unwrap() >= crate::config::$level {
+		if *$crate::config::VERBOSE.read().unwrap() >= $crate::config::$level {
 			println!($($args),*);
 		}
 	};
 }
 
+#[macro_export]
 macro_rules! print_verbose {
 	($level:ident) => {
-		if *crate::config::VERBOSE.read().unwrap() >= crate::config::$level {
+		if *$crate::config::VERBOSE.read().unwrap() >= $crate::config::$level {
 			print!();
 		}
 	};
 	($level:ident, $($args:expr),*) => {
-		if *crate::config::VERBOSE.read().unwrap() >= crate::config::$level {
+		if *$crate::config::VERBOSE.read().unwrap() >= $crate::config::$level {
 			print!($($args),*);
 		}
 	};
 }
 
-pub(crate) use println_verbose;
-pub(crate) use print_verbose;
+pub use crate::{println_verbose, print_verbose};
@@ -0,0 +1,209 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * An HTTP endpoint for --listen so a --watch run can be scraped instead of read from files.
+ * Each pass replaces what is served:
+ *   /         the results of the last pass as JSON, every domain and not only the failures
+ *   /metrics  the last pass in the Prometheus text format
+ *   /health   200 once a pass has finished, 503 before
+ */
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::config::println_verbose;
+use crate::monitor::MonitorResult;
+use crate::query::QueryType;
+use crate::report::AuditReport;
+use crate::stats::QueryStats;
+
+/** the most of a request that is read, only the request line is used */
+const MAX_REQUEST : usize = 8192;
+
+/** what the last pass found, the responses are built when it is updated */
+#[derive(Default)]
+struct Latest {
+	results : Option<String>,
+	metrics : Option<String>
+}
+
+pub struct Exporter {
+	latest : Arc<Mutex<Latest>>,
+	addr : std::net::SocketAddr
+}
+
+impl Exporter {
+
+	/**
+	 * start serving on addr, like 127.0.0.1:9153. Requests are answered on a thread of their
+	 * own one at a time, nothing is served but the three paths
+	 */
+	pub fn listen( addr : &str ) -> Result<Self, String> {
+
+		let listener = match std::net::TcpListener::bind(addr) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to listen on {} {}", addr, e)); }
+		};
+
+		let local_addr = match listener.local_addr() {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to listen on {} {}", addr, e)); }
+		};
+
+		let latest : Arc<Mutex<Latest>> = Arc::new(Mutex::new(Latest::default()));
+		let served = Arc::clone(&latest);
+
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				match stream {
+					Ok(mut s) => {
+						if let Err(e) = Self::respond( &mut s, &served ) {
+							println_verbose!(VERBOSE1, "failed to answer a request on --listen {}", e);
+						}
+					},
+					Err(e) => {
+						println_verbose!(VERBOSE1, "failed to accept a connection on --listen {}", e);
+					}
+				}
+			}
+		});
+
+		Ok(Self {
+			latest,
+			addr: local_addr
+		})
+	}
+
+	/**
+	 * the address that is listened on, the port is known here when 0 was asked for
+	 */
+	pub fn local_addr( &self ) -> std::net::SocketAddr {
+		self.addr
+	}
+
+	/**
+	 * serve the results of a pass, queries are the run stats of the pass
+	 */
+	pub fn update( &self, results : &[Arc<Mutex<MonitorResult>>], report : &AuditReport, queries : &[(QueryType, QueryStats)] ) {
+
+		let json = serde_json::to_string( results ).unwrap();
+		let text = metrics( results, report, queries );
+
+		let mut latest = self.latest.lock().unwrap();
+		latest.results = Some(json);
+		latest.metrics = Some(text);
+	}
+
+	fn respond( stream : &mut std::net::TcpStream, latest : &Mutex<Latest> ) -> std::io::Result<()> {
+
+		stream.set_read_timeout( Some(std::time::Duration::from_secs(5)) )?;
+
+		// read the head of the request, a GET has no body
+		let mut request : Vec<u8> = Vec::new();
+		let mut buff = [ 0u8; 1024 ];
+		while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+			let read = stream.read( &mut buff )?;
+			if read == 0 {
+				break;
+			}
+			request.extend_from_slice( &buff[..read] );
+		}
+
+		let request = String::from_utf8_lossy( &request );
+		let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+		let method = parts.next().unwrap_or("");
+		let path = parts.next().unwrap_or("");
+		// the query string doesn't change what is served
+		let path = path.split('?').next().unwrap_or("");
+
+		let (status, content_type, body) = if method != "GET" && method != "HEAD" {
+			("405 Method Not Allowed", "text/plain", "only GET is supported\n".to_string())
+		} else {
+			let latest = latest.lock().unwrap();
+			match (path, &latest.results, &latest.metrics) {
+				("/", Some(results), _) => { ("200 OK", "application/json", results.clone()) },
+				("/metrics", _, Some(text)) => { ("200 OK", "text/plain; version=0.0.4", text.clone()) },
+				("/health", Some(_), _) => { ("200 OK", "text/plain", "ok\n".to_string()) },
+				("/" | "/metrics" | "/health", _, _) => { ("503 Service Unavailable", "text/plain", "no pass has finished yet\n".to_string()) },
+				_ => { ("404 Not Found", "text/plain", "not found, try /, /metrics or /health\n".to_string()) }
+			}
+		};
+
+		write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+		if method != "HEAD" {
+			stream.write_all( body.as_bytes() )?;
+		}
+		stream.flush()
+	}
+}
+
+/**
+ * escape a Prometheus label value
+ */
+fn label( value : &str ) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/**
+ * a pass in the Prometheus text format, every value is a gauge for the last pass
+ */
+pub fn metrics( results : &[Arc<Mutex<MonitorResult>>], report : &AuditReport, queries : &[(QueryType, QueryStats)] ) -> String {
+
+	let mut out = String::new();
+
+	let mut gauge = |name : &str, help : &str, values : Vec<(String, String)>| {
+		out += &format!("# HELP dns_audit_{} {}\n# TYPE dns_audit_{} gauge\n", name, help, name);
+		for (labels, value) in values {
+			out += &format!("dns_audit_{}{} {}\n", name, labels, value);
+		}
+	};
+
+	gauge( "domains", "domains tested in the last pass by whether they passed", vec![
+		( "{result=\"passed\"}".to_string(), report.passed.to_string() ),
+		( "{result=\"failed\"}".to_string(), report.failed.to_string() )
+	]);
+
+	gauge( "domain_success", "1 when the domain passed the last pass, 0 when it failed", results.iter().map(|r| {
+		let r = r.lock().unwrap();
+		( format!("{{domain=\"{}\"}}", label(&r.domain_name)), (r.success as u8).to_string() )
+	}).collect());
+
+	gauge( "findings", "findings of the last pass by their code", report.findings.iter().map(|(code, found)| {
+		( format!("{{code=\"{:?}\"}}", code), found.len().to_string() )
+	}).collect());
+
+	gauge( "queries", "queries sent in the last pass by the type asked for, with the ones that failed and timed out", queries.iter().flat_map(|(qtype, stats)| {
+		let qtype = qtype.to_string().trim_start_matches("T_").to_string();
+		[ ("sent", stats.sent), ("failed", stats.failed), ("timed_out", stats.timed_out) ].into_iter().map(move |(outcome, count)| {
+			( format!("{{qtype=\"{}\",outcome=\"{}\"}}", label(&qtype), outcome), count.to_string() )
+		})
+	}).collect());
+
+	gauge( "pass_duration_seconds", "how long the last pass took", vec![
+		( String::new(), format!("{:.3}", report.duration.as_secs_f64()) )
+	]);
+
+	gauge( "last_pass_timestamp_seconds", "when the last pass finished, in seconds since the epoch", vec![
+		( String::new(), std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs()).to_string() )
+	]);
+
+	out
+}
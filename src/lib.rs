@@ -0,0 +1,49 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * The audit library, the dns_audit binary is a thin command line wrapper around it so the
+ * parsers and checks can also be used by benchmarks and other tools
+ */
+
+pub mod zone;
+pub mod root;
+pub mod query;
+pub mod config;
+pub mod monitor;
+pub mod tsig;
+pub mod baseline;
+pub mod check;
+pub mod resolver;
+pub mod stats;
+pub mod lint;
+pub mod pcap;
+pub mod report;
+pub mod exporter;
+
+extern crate ascii;
+#[macro_use]
+extern crate lazy_static;
+extern crate serde_json;
+extern crate serde;
+
+#[macro_use]
+extern crate serde_derive;
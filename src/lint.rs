@@ -0,0 +1,258 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Checks of a zone file's contents, as opposed to the live checks in check.rs that query the
+ * nameservers. Ran with --lint
+ */
+
+use std::collections::HashMap;
+
+use crate::config::println_verbose;
+use crate::resolver;
+use crate::zone::{self, rr, record::{name_eq, name_is_under, RecordType, ZoneDirective, ZoneRecord}};
+
+/**
+ * a problem found in the zone file
+ */
+#[derive(Serialize, Clone)]
+pub enum LintFinding {
+	/** the same record is in the zone more than once, lines are where each copy is */
+	DuplicateRecord { name : String, record_type : String, lines : Vec<u32> },
+	/** there is no SOA record at the apex */
+	MissingSoa { apex : String },
+	/** the apex has more than one SOA record */
+	MultipleSoa { apex : String, lines : Vec<u32> },
+	/** there are no NS records at the apex */
+	MissingApexNs { apex : String },
+	/** the apex only has one NS record, RFC 1034 recommends at least two */
+	SingleApexNs { apex : String, line : u32 },
+	/** an NS, MX or SRV record's target is an alias, RFC 2181 requires it to be the canonical name */
+	TargetIsCname { name : String, record_type : String, target : String, line : u32 },
+}
+
+impl LintFinding {
+
+	/**
+	 * warnings are printed but don't fail the lint
+	 */
+	pub fn is_warning( &self ) -> bool {
+		matches!(self, Self::SingleApexNs { .. })
+	}
+
+}
+
+impl std::fmt::Display for LintFinding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::DuplicateRecord { name, record_type, lines } => {
+				write!(f, "line {}: duplicate {} record for {}, also at line {}", lines[0], record_type, name, join_lines(&lines[1..]))
+			},
+			Self::MissingSoa { apex } => {
+				write!(f, "no SOA record at the apex {}", apex)
+			},
+			Self::MultipleSoa { apex, lines } => {
+				write!(f, "line {}: more than one SOA record at the apex {}, also at line {}", lines[0], apex, join_lines(&lines[1..]))
+			},
+			Self::MissingApexNs { apex } => {
+				write!(f, "no NS records at the apex {}", apex)
+			},
+			Self::SingleApexNs { apex, line } => {
+				write!(f, "line {}: warning: only one NS record at the apex {}, at least two are recommended", line, apex)
+			},
+			Self::TargetIsCname { name, record_type, target, line } => {
+				write!(f, "line {}: {} record for {} points at {} which is a CNAME", line, record_type, name, target)
+			}
+		}
+	}
+}
+
+fn join_lines( lines : &[u32] ) -> String {
+	lines.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+/**
+ * run all of the lint checks against the zone, origin is the apex when the zone doesn't start
+ * with an $ORIGIN. When start_servers is set the checks that need to look at names outside
+ * of the zone query for them, it returns the nameservers to start a lookup at like
+ * Root::closest_servers
+ */
+pub fn lint_zone( zone : &zone::Zone, origin : &str, start_servers : Option<&resolver::StartServers<'_>> ) -> Vec<LintFinding> {
+
+	let records : Vec<&ZoneRecord> = zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.collect();
+
+	let index = zone::RRsetIndex::new(&records);
+	let apex = apex_name(zone, origin);
+
+	let mut rval = Vec::new();
+	rval.extend( duplicates(&index) );
+	rval.extend( apex_records(&index, &apex) );
+	rval.extend( targets_not_cname(&records, &index, &apex, start_servers) );
+	rval
+}
+
+/**
+ * the zone's apex, the first $ORIGIN if it comes before any records otherwise the origin
+ * it was loaded with
+ */
+fn apex_name( zone : &zone::Zone, origin : &str ) -> String {
+
+	for rec in &zone.records {
+		match rec.as_any_ref().downcast_ref::<ZoneDirective>() {
+			Some(d) => {
+				if d.name.eq_ignore_ascii_case("ORIGIN") {
+					return d.value.clone();
+				}
+			},
+			None => { break; }
+		}
+	}
+
+	origin.to_string()
+}
+
+/**
+ * find records that are exactly the same as an earlier one, only records with the same owner
+ * and type are compared
+ */
+fn duplicates( index : &zone::RRsetIndex ) -> Vec<LintFinding> {
+
+	let mut rval = Vec::new();
+
+	for rrset in index.iter() {
+
+		let mut reported = vec![ false; rrset.len() ];
+
+		for i in 0..rrset.len() {
+
+			if reported[i] {
+				continue;
+			}
+
+			let mut lines = vec![ rrset[i].source_line ];
+			for j in (i + 1)..rrset.len() {
+				if !reported[j] && rrset[i] == rrset[j] {
+					reported[j] = true;
+					lines.push( rrset[j].source_line );
+				}
+			}
+
+			if lines.len() > 1 {
+				rval.push( LintFinding::DuplicateRecord {
+					name: rrset[i].name.fqdn.clone(),
+					record_type: rrset[i].type_name(),
+					lines
+				});
+			}
+		}
+	}
+
+	rval
+}
+
+/**
+ * the apex must have exactly one SOA and should have at least two NS records
+ */
+fn apex_records( index : &zone::RRsetIndex, apex : &str ) -> Vec<LintFinding> {
+
+	let mut rval = Vec::new();
+
+	let soa = index.get(apex, RecordType::SOA);
+	if soa.is_empty() {
+		rval.push( LintFinding::MissingSoa { apex: apex.to_string() } );
+	} else if soa.len() > 1 {
+		rval.push( LintFinding::MultipleSoa { apex: apex.to_string(), lines: soa.iter().map(|r| r.source_line).collect() } );
+	}
+
+	let ns = index.get(apex, RecordType::NS);
+	if ns.is_empty() {
+		rval.push( LintFinding::MissingApexNs { apex: apex.to_string() } );
+	} else if ns.len() == 1 {
+		rval.push( LintFinding::SingleApexNs { apex: apex.to_string(), line: ns[0].source_line } );
+	}
+
+	rval
+}
+
+/**
+ * the name an NS, MX or SRV record points at
+ */
+fn target_of( rec : &ZoneRecord ) -> Option<String> {
+
+	let rdata = rec.rdata.as_ref()?;
+
+	match rec.record_type {
+		RecordType::NS => { rdata.as_any().downcast_ref::<rr::RDATANameRR>().map(|r| r.name.fqdn.clone()) },
+		RecordType::MX => { rdata.as_any().downcast_ref::<rr::RDATAmx>().map(|r| r.target.fqdn.clone()) },
+		RecordType::SRV => { rdata.as_any().downcast_ref::<rr::RDATAsrv>().map(|r| r.target.fqdn.clone()) },
+		_ => { None }
+	}
+}
+
+/**
+ * RFC 2181 10.3, the target of an NS or MX record must not be an alias and SRV follows the
+ * same rule by convention. Targets in the zone are checked against its CNAME records, the
+ * ones outside of it are only looked up when start_servers is set
+ */
+fn targets_not_cname( records : &[&ZoneRecord], index : &zone::RRsetIndex, apex : &str, start_servers : Option<&resolver::StartServers<'_>> ) -> Vec<LintFinding> {
+
+	let mut rval = Vec::new();
+	let mut looked_up : HashMap<String, bool> = HashMap::new();
+
+	for rec in records {
+
+		let target = match target_of(rec) {
+			Some(m) => { m },
+			None => { continue; }
+		};
+
+		let in_zone = name_eq(&target, apex) || name_is_under(&target, apex);
+
+		let is_cname = if in_zone {
+			!index.get(&target, RecordType::CNAME).is_empty()
+		} else if let Some(start_servers) = start_servers {
+			*looked_up.entry(target.to_ascii_lowercase()).or_insert_with(|| {
+				match resolver::alias_target(&target, start_servers) {
+					Ok(m) => { m.is_some() },
+					Err(e) => {
+						println_verbose!(VERBOSE1, "lint could not look up {}: {}", target, e);
+						false
+					}
+				}
+			})
+		} else {
+			false
+		};
+
+		if is_cname {
+			rval.push( LintFinding::TargetIsCname {
+				name: rec.name.fqdn.clone(),
+				record_type: rec.record_type.to_string(),
+				target,
+				line: rec.source_line
+			});
+		}
+	}
+
+	rval
+}
@@ -18,44 +18,58 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 */
 
-use std::{io::{Write, stdout, Read, stderr}, process::exit, sync::{Arc, RwLock, Mutex}, fs::File, thread::sleep, time::Duration};
+use std::{io::{Write, stdout, Read, stderr, IsTerminal}, process::exit, sync::{Arc, RwLock, Mutex}, fs::File, thread::sleep, time::Duration};
 
-use monitor::Monitor;
+use dns_audit::{zone, root, config, monitor, baseline, stats, lint, pcap, query, report, exporter};
 
-use crate::config::println_verbose;
-
-pub mod zone;
-pub mod root;
-pub mod query;
-pub mod config;
-pub mod monitor;
+use dns_audit::config::println_verbose;
 
 extern crate getopts;
-extern crate ascii;
-#[macro_use]
-extern crate lazy_static;
-extern crate dns_lookup;
 extern crate serde_json;
-extern crate serde;
-
-#[macro_use]
-extern crate serde_derive;
 
 fn main() {
 
 	let args: Vec<String> = std::env::args().collect();
 
 	let mut opts = getopts::Options::new();
-	opts.optopt("", "root-zone", "Root zone file path", "PATH");
+	opts.optmulti("", "root-zone", "Root zone or root hints file path, the address of a root server to ask for them or builtin for the root hints built in, the default. Repeat it to fall back to the next when one can't be loaded", "PATH");
 	opts.optopt("c", "", "JSON Configuration file, or - for stdin", "file");
 	opts.optopt("", "cache-out", "write cache file", "FILE");
 	opts.optopt("", "cache-in", "read cache file", "FILE");
+	opts.optopt("", "cache-update", "take the root nameserver times from FILE, test the servers it has no time for and write it back", "FILE");
+	opts.optflag("", "cache-refresh", "test every root nameserver again, not only the ones the --cache-in has no time for");
+	opts.optopt("", "probes", "how many queries each root nameserver is timed with for the cache, default 5", "#");
 	opts.optopt("o", "", "Write results as JSON, or - for stdout", "FILE");
+	opts.optopt("", "format", "how the results are written: json, csv or text, default json", "FORMAT");
 	opts.optopt("w", "watch", "Keep running until any change", "# seconds");
+	opts.optopt("", "listen", "with --watch, serve the results of the last pass on / and Prometheus metrics on /metrics", "ADDR");
 	opts.optflag("","all", "When this flag is on, all results are written. when absent only errors are shown");
+	opts.optflag("", "stream", "write each result as a line of JSON as soon as it is ready instead of keeping them for one array");
+	opts.optflag("q", "quiet", "write no results, only a N/M passed line to stderr, the exit code is unchanged");
 	opts.optflagmulti("v", "verbose", "Verbose Mode");
 	opts.optflag("h", "help", "Help");
-	opts.optopt("", "threads", "how many threads are used for testing, default 1", "#");
+	opts.optopt("", "threads", "how many domains are tested at once, default 1 or the --concurrency", "#");
+	opts.optopt("", "max-depth", "most zones below the root zone file that are looked up to find a domain's parent zone, default 8", "#");
+	opts.optopt("", "concurrency", "most queries waiting for a response at once across all threads, default unlimited", "#");
+	opts.optopt("", "result-dir", "write each domain's result to DIR/<domain>.json, replaced on every pass", "DIR");
+	opts.optopt("", "baseline", "record answers on the first run, later runs report changes from it", "FILE");
+	opts.optflag("", "force-ipv4", "Query IPv4 servers even if IPv4 egress was not detected");
+	opts.optflag("", "skip-bad-records", "leave out records of the root zone and zone_file that can't be parsed instead of stopping");
+	opts.optflag("", "force-ipv6", "Query IPv6 servers even if IPv6 egress was not detected");
+	opts.optmulti("", "resolver", "recursive resolver to compare against the authoratative answers, can be given more than once", "IP");
+	opts.optopt("", "ns-resolver", "resolve nameserver names by asking this recursive resolver instead of from the root", "IP");
+	opts.optopt("", "pcap", "write every query and response to FILE in pcap format for Wireshark", "FILE");
+	opts.optflag("", "tcp", "send every query over TCP, by default TCP is only used when a UDP response is truncated");
+	opts.optopt("", "timeout", "how long to wait for each query, default 5000", "MS");
+	opts.optopt("", "lookup-timeout", "how long to spend resolving a nameserver's name, default 15000", "MS");
+	opts.optopt("", "max-ns-addresses", "most addresses of a nameserver's name that are queried, default 8", "#");
+	opts.optopt("", "query-id-seed", "seed the query ids so each run sends the same ones, for comparing captures, default random", "#");
+	opts.optopt("", "lint", "check a zone file for problems, print them then exit", "FILE");
+	opts.optflag("", "lint-live", "with --lint, query for names outside of the zone starting at the --root-zone");
+	opts.optopt("", "origin", "origin of the --lint zone file when it doesn't set $ORIGIN, default .", "NAME");
+	opts.optopt("", "color", "color human readable output: auto, always or never, default auto", "WHEN");
+	opts.optopt("", "decode", "decode a saved DNS response, raw bytes or a hex dump, print its sections then exit, or - for stdin", "FILE");
+	opts.optopt("", "tokenize", "print the tokens of a zone file with their type and line then exit, or - for stdin", "FILE");
 
 	let matches = match opts.parse(&args[1..]) {
 		Ok(m) => { m }
@@ -70,7 +84,145 @@ fn main() {
 		exit(1);
 	}
 
-	*crate::config::VERBOSE.write().unwrap() = matches.opt_count("v");
+	*config::VERBOSE.write().unwrap() = matches.opt_count("v");
+	*config::FORCE_IPV4.write().unwrap() = matches.opt_present("force-ipv4");
+	*config::SKIP_BAD_RECORDS.write().unwrap() = matches.opt_present("skip-bad-records");
+	*config::FORCE_IPV6.write().unwrap() = matches.opt_present("force-ipv6");
+	*config::FORCE_TCP.write().unwrap() = matches.opt_present("tcp");
+
+	if let Some(color) = matches.opt_str("color") {
+		match config::ColorMode::from_string(&color) {
+			Ok(m) => { *config::COLOR.write().unwrap() = m; },
+			Err(e) => {
+				writeln!(stderr().lock(), "{}", opts.usage( &format!("--color is invalid: {}", e) )).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	if let Some(tokenize_file) = matches.opt_str("tokenize") {
+
+		let lines = if tokenize_file == "-" {
+			zone::tokenizer::ZoneLines::from_reader(&mut std::io::stdin().lock())
+		} else {
+			zone::tokenizer::ZoneLines::create(&tokenize_file)
+		};
+
+		match lines {
+			Ok(m) => {
+				write!(stdout().lock(), "{}", m).unwrap();
+				exit(0);
+			},
+			Err(e) => {
+				writeln!(stderr().lock(), "failed to tokenize {} {}", tokenize_file, e).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	if let Some(decode_file) = matches.opt_str("decode") {
+
+		let mut raw = Vec::<u8>::new();
+		let read = if decode_file == "-" {
+			std::io::stdin().lock().read_to_end(&mut raw).map(|_| ())
+		} else {
+			std::fs::read(&decode_file).map(|m| raw = m)
+		};
+		if let Err(e) = read {
+			writeln!(stderr().lock(), "failed to read {} {}", decode_file, e).unwrap();
+			exit(1);
+		}
+
+		// a hex dump is taken as is, anything else is the response itself
+		let buff = match std::str::from_utf8(&raw).ok().and_then(query::decode_hex) {
+			Some(m) => { m },
+			None => { raw }
+		};
+
+		match query::Sender::decode(&buff) {
+			Ok(m) => {
+				let mut out = stdout().lock();
+				write!(out, "{}", m).unwrap();
+				for (section, list) in [ ("ANSWER", &m.answer), ("AUTHORITY", &m.authority), ("ADDITIONAL", &m.additional) ] {
+					writeln!(out, ";; {} {}", section, list.len()).unwrap();
+					for rec in list {
+						writeln!(out, "{}", rec).unwrap();
+					}
+				}
+				exit(0);
+			},
+			Err(e) => {
+				writeln!(stderr().lock(), "failed to decode {} {}", decode_file, e).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	for resolver in matches.opt_strs("resolver") {
+		match resolver.parse::<std::net::IpAddr>() {
+			Ok(m) => { config::RESOLVERS.write().unwrap().push(m); },
+			Err(e) => {
+				writeln!(stderr().lock(), "{}", opts.usage( &format!("--resolver {} is invalid: {}", resolver, e) )).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	if let Some(resolver) = matches.opt_str("ns-resolver") {
+		match resolver.parse::<std::net::IpAddr>() {
+			Ok(m) => { *config::NS_RESOLVER.write().unwrap() = Some(m); },
+			Err(e) => {
+				writeln!(stderr().lock(), "{}", opts.usage( &format!("--ns-resolver {} is invalid: {}", resolver, e) )).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	if let Some(pcap_file) = matches.opt_str("pcap") {
+		if let Err(e) = pcap::open(&pcap_file) {
+			writeln!(stderr().lock(), "{}", e).unwrap();
+			exit(1);
+		}
+	}
+
+	match matches.opt_get::<u64>("timeout") {
+		Ok(Some(ms)) => { *config::QUERY_TIMEOUT.write().unwrap() = Duration::from_millis(ms); },
+		Ok(None) => {},
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--timeout is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	}
+
+	match matches.opt_get::<u64>("lookup-timeout") {
+		Ok(Some(ms)) => { *config::LOOKUP_TIMEOUT.write().unwrap() = Duration::from_millis(ms); },
+		Ok(None) => {},
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--lookup-timeout is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	}
+
+	match matches.opt_get::<usize>("max-ns-addresses") {
+		Ok(Some(v)) if v > 0 => { *config::MAX_NS_ADDRESSES.write().unwrap() = v; },
+		Ok(None) => {},
+		Ok(Some(_)) => {
+			writeln!(stderr().lock(), "{}", opts.usage( "--max-ns-addresses must be at least 1" )).unwrap();
+			exit(1);
+		},
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--max-ns-addresses is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	}
+
+	match matches.opt_get::<u64>("query-id-seed") {
+		Ok(seed) => { *config::QUERY_ID_SEED.write().unwrap() = seed; },
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--query-id-seed is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	}
 
 	let watch : Option<Duration> = match matches.opt_get::<u64>("w") {
 		Ok(w) =>  { 
@@ -85,14 +237,62 @@ fn main() {
 		}
 	};
 	let all = matches.opt_present("all");
+	if let Some(lint_file) = matches.opt_str("lint") {
 
-	let file_name: String = match matches.opt_str("root-zone") {
-		Some(m) => { m },
-		None => { 
-			writeln!(stderr().lock(), "{}", opts.usage("root-zone is required")).unwrap();
+		let mut origin = matches.opt_str("origin").unwrap_or(".".to_string());
+		if !origin.ends_with('.') {
+			origin.push('.');
+		}
+
+		match zone::Zone::create_with_warnings(&lint_file, &origin, false) {
+			Ok((z, warnings)) => {
+				let findings = if matches.opt_present("lint-live") {
+					let root_sources = matches.opt_strs("root-zone");
+					let root = match root::Root::create_from_sources(&root_sources, &".".to_string()) {
+						Ok(m) => { m },
+						Err(e) => { panic!("{}", e); }
+					};
+					lint::lint_zone(&z, &origin, Some(&|n : &str| root.closest_servers(n)))
+				} else {
+					lint::lint_zone(&z, &origin, None)
+				};
+				let color = config::COLOR.read().unwrap().enabled(stdout().is_terminal());
+				let mut out = stdout().lock();
+				// parse warnings come first, like lint warnings they leave the exit code alone
+				for warning in &warnings {
+					writeln!(out, "{}: {}", lint_file, config::colorize(&warning.to_string(), config::COLOR_YELLOW, color)).unwrap();
+				}
+				for finding in &findings {
+					let text = finding.to_string();
+					let text = if finding.is_warning() {
+						config::colorize(&text, config::COLOR_YELLOW, color)
+					} else {
+						config::colorize(&text, config::COLOR_RED, color)
+					};
+					writeln!(out, "{}: {}", lint_file, text).unwrap();
+				}
+				exit( if findings.iter().all(|f| f.is_warning()) { 0 } else { 2 } );
+			},
+			Err(e) => {
+				writeln!(stderr().lock(), "failed to parse {} {}", lint_file, e).unwrap();
+				exit(1);
+			}
+		}
+	}
+
+	let stream = matches.opt_present("stream");
+	let quiet = matches.opt_present("quiet");
+
+	let format = match matches.opt_str("format").map(|m| report::OutputFormat::from_string(&m)) {
+		None => { report::OutputFormat::Json },
+		Some(Ok(m)) => { m },
+		Some(Err(e)) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--format is invalid: {}", e) )).unwrap();
 			exit(1);
 		}
 	};
+
+	let root_sources = matches.opt_strs("root-zone");
 	
 	let mut local_config : Vec<monitor::Monitor>;
 	match matches.opt_str("c") {
@@ -112,7 +312,7 @@ fn main() {
 				json_contents = std::fs::read_to_string(&json_file).expect("failed to read JSON");
 			}
 		
-			local_config = match serde_json::from_str::<Vec<monitor::Monitor>>(json_contents.as_str() ) {
+			local_config = match monitor::Monitor::list_from_json( json_contents.as_str() ) {
 				Ok( m ) =>  { m },
 				Err(e ) => { panic!("{}", e); }
 			};
@@ -123,46 +323,75 @@ fn main() {
 		}
 	};
 
-	let root = match root::Root::create(&file_name, &".".to_string()) {
+	let root = match root::Root::create_from_sources(&root_sources, &".".to_string()) {
 		Ok(m) => { Arc::new(RwLock::new(m)) },
 		Err(e) => { panic!("{}", e); }
 	};
 
-	let out_fp : Arc<RwLock<Box<dyn std::io::Write>>> = Arc::new(RwLock::new(Box::new(stdout())));
+	let out_fp : Arc<RwLock<Box<dyn std::io::Write + Send + Sync>>> = Arc::new(RwLock::new(Box::new(stdout())));
+	let mut out_tty = stdout().is_terminal();
 	if let Some(out_fn ) = &matches.opt_str("o") {
 
 		if out_fn != "-" {
-			*(out_fp.write().unwrap()) = Box::new(match File::create(out_fn) {
+			let fp = match File::create(out_fn) {
 				Ok(fp) => { fp }
 				Err(e) => { panic!("failed to open {} for writing {}", out_fn, e) }
-			});
+			};
+			out_tty = fp.is_terminal();
+			*(out_fp.write().unwrap()) = Box::new(fp);
 		}
 	}
 
 	if let Some(cachefn) = matches.opt_str("cache-in" ) {
 
-		match std::fs::read_to_string(cachefn) {
-			Ok( str ) => {
-				root.write().unwrap().cache_from_js(&str);
-			},
-			Err( e ) =>  {
-				panic!("{}", e.to_string())
-			}
+		if let Err(e) = root.write().unwrap().read_cache(&cachefn) {
+			panic!("{}", e);
 		}
 		
-	} else if let Some(cachefn) = matches.opt_str("cache-out") {
+	}
+
+	// --cache-in alone uses the cache as it is. With --cache-out the servers it doesn't have a
+	// time for are tested and the cache is written back, --cache-refresh tests all of them
+	let cache_refresh = matches.opt_present("cache-refresh");
+	let cache_out = matches.opt_str("cache-out");
+
+	// --cache-update keeps the zones of the root and only takes the times from the cache, so
+	// a zone that is new since it was written is tested without testing the rest again
+	let cache_update = matches.opt_str("cache-update");
+	if let Some(cachefn) = &cache_update {
+
+		if std::path::Path::new(cachefn).exists() {
+			if let Err(e) = root.write().unwrap().merge_cache_file(cachefn) {
+				panic!("{}", e);
+			}
+		}
+	}
+
+	let probe_count = match matches.opt_get::<usize>("probes") {
+		Ok(Some(v)) if v > 0 => { v },
+		Ok(None) => { root::DEFAULT_PROBE_COUNT },
+		Ok(Some(_)) => {
+			writeln!(stderr().lock(), "{}", opts.usage( "--probes must be at least 1" )).unwrap();
+			exit(1);
+		},
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--probes is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	};
+
+	if cache_out.is_some() || cache_update.is_some() || cache_refresh {
 
 		write!(stderr().lock(), "Testing Root Nameservers... ").unwrap();
-		root.write().unwrap().performance_test(20);
+		root.write().unwrap().performance_test(20, cache_refresh, probe_count);
 		writeln!(stderr().lock(), "Complete!").unwrap();
+	}
 
-		match &mut std::fs::File::create(cachefn) {
-			Ok(fp) => { 
-				fp.write_all( root.write().unwrap().to_json().as_bytes() ).expect("Failed to write");
-				fp.flush().expect("failed to flush");
-			},
-			Err(e) => { panic!("{}", e.to_string()) }
-		};
+	for cachefn in cache_out.iter().chain(cache_update.iter()) {
+
+		if let Err(e) = root.read().unwrap().write_cache(cachefn) {
+			panic!("{}", e);
+		}
 		
 	}
 
@@ -173,11 +402,30 @@ fn main() {
 
 	let mut config : Vec<Arc<RwLock<monitor::Monitor>>> = Vec::new();
 
+	let concurrency = match matches.opt_get::<usize>("concurrency") {
+		Ok(v) => { v },
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--concurrency is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	};
+	*config::CONCURRENCY.write().unwrap() = concurrency.unwrap_or(0);
+
+	match matches.opt_get::<usize>("max-depth") {
+		Ok(Some(v)) => { *config::MAX_DEPTH.write().unwrap() = v; },
+		Ok(None) => {},
+		Err(e) => {
+			writeln!(stderr().lock(), "{}", opts.usage( &format!("--max-depth is invalid: {}", e) )).unwrap();
+			exit(1);
+		}
+	}
+
+	// without --threads there is a thread for each query allowed in flight
 	let thread_ct = match matches.opt_get::<i32>("threads") {
 		Ok(v) => {
 			match v {
 				Some(z) => { z }
-				None => { 1 }
+				None => { concurrency.map_or(1, |c| c.max(1) as i32) }
 			}
 		}
 		Err(_) => { 1 }
@@ -191,70 +439,171 @@ fn main() {
 		m.write().unwrap().normalize();
 	}
 
-	let code : Arc<Mutex<i32>> = Arc::new(Mutex::new(0));
+	let baseline_fn = matches.opt_str("baseline");
+	let baseline : Option<Arc<Mutex<baseline::Baseline>>> = match &baseline_fn {
+		Some(bfn) => {
+			match baseline::Baseline::from_file(bfn) {
+				Ok(m) => { Some(Arc::new(Mutex::new(m))) },
+				Err(e) => { panic!("{}", e); }
+			}
+		},
+		None => { None }
+	};
 
-	let mut requests : Vec< std::sync::Arc< RwLock< Vec< std::sync::Arc< RwLock< Monitor >> >> >> = Vec::new();
+	let result_dir = matches.opt_str("result-dir");
+	if let Some(dir) = &result_dir {
+		if let Err(e) = std::fs::create_dir_all(dir) {
+			panic!("failed to create result directory {} {}", dir, e);
+		}
+	}
 
-	let mut monitor_iter = config.iter();
-	for _ in 0..thread_ct {
-		let mut list = Vec::< std::sync::Arc< RwLock< Monitor >>>::new();
-		for _ in 0..((config.len() as f32 / thread_ct as f32).ceil() as i32){
-			if let Some(m) = monitor_iter.next() {
-				list.push(Arc::clone(m));
+	let listen = match matches.opt_str("listen") {
+		Some(_) if watch.is_none() => {
+			writeln!(stderr().lock(), "{}", opts.usage("--listen requires --watch")).unwrap();
+			exit(1);
+		},
+		Some(addr) => {
+			match exporter::Exporter::listen(&addr) {
+				Ok(m) => { Some(m) },
+				Err(e) => { panic!("{}", e); }
 			}
-		}
-		requests.push(Arc::new(RwLock::new(list)));
+		},
+		None => { None }
+	};
+
+	let mut writer = report::ResultWriter::new( format, stream, all );
+	writer.color = config::COLOR.read().unwrap().enabled(out_tty);
+	let writer = Arc::new(Mutex::new(writer));
+	if !quiet {
+		writer.lock().unwrap().start( &mut **out_fp.write().unwrap() ).unwrap();
 	}
 
+	let code : Arc<Mutex<i32>> = Arc::new(Mutex::new(0));
 
 	loop {
 
 		let lcode = code.clone();
 
-		let results : Arc<Mutex<Vec<Arc<Mutex<monitor::MonitorResult>>>>> = Arc::new(Mutex::new(Vec::new()));
+		// with --listen every result is served, not only the ones written out
+		let served : Arc<Mutex<Vec<Arc<Mutex<monitor::MonitorResult>>>>> = Arc::new(Mutex::new(Vec::new()));
+
+		// in stream mode the results are written and dropped, only the report is kept
+		let audit_report = Arc::new(Mutex::new(report::AuditReport::default()));
+		let started = std::time::Instant::now();
+
+		println_verbose!(VERBOSE2, "testing {} domains, {} at a time", config.len(), thread_ct);
 
-		let mut thread_iter = requests.iter();
+		let joined = | rval : Result<(), tokio::task::JoinError> | {
+			if rval.is_err() {
+				// test_guarded catches the checks, this is a panic outside of them
+				writeln!(stderr().lock(), "a test failed outside of its checks, its result is missing").unwrap();
+				*lcode.lock().unwrap_or_else(|e| e.into_inner()) = 2;
+			}
+		};
 
-		let mut threads : Vec<std::thread::JoinHandle<()>> =  Vec::new();
+		// each domain is tested as a task on the query runtime, its checks hand the worker thread
+		// over while their queries wait. A domain is only started when one of the thread_ct
+		// slots is free, so a large config isn't queued up all at once
+		query::runtime().block_on(async {
 
-		println_verbose!(VERBOSE2, "starting with {} threads", requests.len());
+			let slots = Arc::new(tokio::sync::Semaphore::new(thread_ct.max(1) as usize));
+			let mut tasks = tokio::task::JoinSet::new();
 
-		while let Some(_thread_list) = thread_iter.next() {
-			let thread_list = Arc::clone(_thread_list);
-			let result_loc = Arc::clone(&results);
-			let root = Arc::clone(&root);
-			let code = Arc::clone(&code);
-			threads.push(std::thread::spawn(move || {
+			for m in &config {
 
-				let list = & thread_list.read().unwrap();
+				let permit = Arc::clone(&slots).acquire_owned().await.unwrap();
 
-				for m in list.iter(){
-					let res = monitor::Monitor::test( m.clone(), root.clone() );
-					
-					if all || !res.lock().unwrap().success {
-						
-						let res_lock = res.lock().unwrap();
-						
-						if !res_lock.success {
-							*(code.lock().unwrap()) = 2;
+				let m = Arc::clone(m);
+				let writer = Arc::clone(&writer);
+				let root = Arc::clone(&root);
+				let code = Arc::clone(&code);
+				let baseline = baseline.clone();
+				let result_dir = result_dir.clone();
+				let out_fp = Arc::clone(&out_fp);
+				let audit_report = Arc::clone(&audit_report);
+				let served = if listen.is_some() { Some(Arc::clone(&served)) } else { None };
+				tasks.spawn(async move {
+
+					let _permit = permit;
+
+					let res = monitor::Monitor::test_guarded_async( m, root ).await;
+
+					if let Some(bl) = &baseline {
+						bl.lock().unwrap().check( &mut res.lock().unwrap() );
+					}
+
+					if let Some(dir) = &result_dir {
+						if let Err(e) = res.lock().unwrap().write_to_dir(dir) {
+							writeln!(stderr().lock(), "{}", e).unwrap();
 						}
-						result_loc.lock().unwrap().push(res.clone());
-		
 					}
 					
+					audit_report.lock().unwrap().add( &res.lock().unwrap() );
+
+					if let Some(served) = &served {
+						served.lock().unwrap().push( res.clone() );
+					}
+
+					if !res.lock().unwrap().success {
+						*(code.lock().unwrap()) = 2;
+					}
+
+					if !quiet {
+						writer.lock().unwrap().add( &mut **out_fp.write().unwrap(), &res ).unwrap();
+					}
+				});
+
+				// the finished tests are collected as we go so the set stays small
+				while let Some(rval) = tasks.try_join_next() {
+					joined(rval);
 				}
+			}
 
-				println_verbose!(VERBOSE2, "Thread complete");
-			}));
+			while let Some(rval) = tasks.join_next().await {
+				joined(rval);
+			}
+
+			println_verbose!(VERBOSE2, "All domains tested");
+		});
+
+		let mut audit_report = std::mem::take( &mut *audit_report.lock().unwrap() );
+		audit_report.finish( started.elapsed() );
+		let failed = audit_report.failed;
+
+		if !quiet {
+			writer.lock().unwrap().finish( &mut **out_fp.write().unwrap(), &audit_report ).unwrap();
 		}
 
-		for thr in threads { 
-			if let Err(_) = thr.join() { }
+		// the summary goes to stderr so it doesn't mix with the results
+		let color = config::COLOR.read().unwrap().enabled(stderr().is_terminal());
+		if quiet {
+			let passed_text = format!("{}/{} passed", audit_report.passed, audit_report.tested);
+			writeln!(stderr().lock(), "{}", config::colorize(&passed_text, if failed == 0 { config::COLOR_GREEN } else { config::COLOR_RED }, color)).unwrap();
+		} else if stream || *config::VERBOSE.read().unwrap() >= config::VERBOSE1 {
+			let failed_text = format!("{} failed", failed);
+			let failed_text = config::colorize(&failed_text, if failed == 0 { config::COLOR_GREEN } else { config::COLOR_RED }, color);
+			let mut err = stderr().lock();
+			writeln!(err, "{} domains tested, {} in {:.2}s", audit_report.tested, failed_text, audit_report.duration.as_secs_f64()).unwrap();
+			stats::write_summary(&mut err).unwrap();
+			audit_report.write_summary(&mut err, color).unwrap();
+		}
+		if let Some(exporter) = &listen {
+			exporter.update( &served.lock().unwrap(), &audit_report, &stats::snapshot() );
 		}
+		stats::reset();
 
-		out_fp.write().unwrap().write_all(serde_json::to_string( &*results.lock().unwrap() ).unwrap().as_bytes()).unwrap();
+		if let (Some(bl), Some(bfn)) = (&baseline, &baseline_fn) {
+			let mut bl_lock = bl.lock().unwrap();
+			if bl_lock.changed {
+				if let Err(e) = bl_lock.to_file(bfn) {
+					panic!("{}", e);
+				}
+				bl_lock.changed = false;
+			}
+		}
 
-		if watch.is_none()  || *lcode.lock().unwrap() != 0 {
+		// a failure ends the watch, unless the results are being served
+		if watch.is_none()  || (*lcode.lock().unwrap() != 0 && listen.is_none()) {
 			break;
 		}
 
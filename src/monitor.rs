@@ -18,25 +18,210 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 */
 
-use crate::{root, query::{self}, zone};
+use crate::{root, query::{self}, tsig, check};
 use std::{sync::{Arc, RwLock, Mutex}};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum ErrorCode {
 	NoAuthoratative,
 	AuthoratativeFail,
 	NoResolve,
-	ResolveIpNotMatch
+	ResolveIpNotMatch,
+	BaselineChanged,
+	ClassMismatch,
+	UnknownCheck,
+	ResolverDivergence,
+	PtrMismatch,
+	FcrdnsMismatch,
+	UnexpectedNsNetwork,
+	SignatureExpired,
+	SignatureExpiringSoon,
+	MissingSignature,
+	UnexpectedlyExists,
+	InternalError,
+	ZoneFileInvalid,
+	ZoneMismatch,
+	GeoMismatch,
+	SerialMismatch,
+	Inconsistent,
+	TtlMismatch
+}
+
+/**
+ * what a finding was about, each part is only set when it applies to the finding
+ */
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct FindingDetail {
+	/** the nameserver or resolver that gave the answer */
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub server : Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub expected : Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub actual : Option<String>
+}
+
+/**
+ * a single failure found while testing a domain
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Finding {
+	pub code : ErrorCode,
+	pub message : String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub detail : Option<FindingDetail>
+}
+
+impl Finding {
+
+	pub fn new( code : ErrorCode, message : &str ) -> Self {
+		Self {
+			code,
+			message: message.to_string(),
+			detail: None
+		}
+	}
+
+	pub fn with_detail( code : ErrorCode, message : &str, detail : FindingDetail ) -> Self {
+		Self {
+			code,
+			message: message.to_string(),
+			detail: Some(detail)
+		}
+	}
+
+}
+
+impl std::fmt::Display for Finding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+
+/**
+ * the RD and RA flags of a nameserver's response. The authoratative nameservers are asked
+ * without RD so RA being set points at an open resolver, and RD copied back when it wasn't
+ * sent at a server that is not following the protocol
+ */
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ResponseFlags {
+	pub server : std::net::IpAddr,
+	pub rd : bool,
+	pub ra : bool
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct MonitorResult {
+	/** see config::SCHEMA_VERSION */
+	#[serde(default)]
+	pub schema_version : u32,
 	pub domain_name : String,
 	pub success : bool,
-	pub reason : Vec<String>,
-	pub flags : Vec<ErrorCode>,
+	pub findings : Vec<Finding>,
 	pub nameservers : Option<Vec<String>>,
-	pub ips : Option<Vec<std::net::IpAddr>>
+	pub ips : Option<Vec<std::net::IpAddr>>,
+	/** the answer was expanded from a wildcard, only known when the server sent RRSIGs */
+	#[serde(default)]
+	pub synthesized_from_wildcard : bool,
+	/** the flags of the first response from each nameserver queried */
+	#[serde(default)]
+	pub response_flags : Vec<ResponseFlags>,
+	/** the SOA serial each nameserver address answered with, filled in by the serial check */
+	#[serde(default)]
+	pub serials : std::collections::BTreeMap<std::net::IpAddr, u32>,
+	/** the nameserver addresses whose answers differed from the rest, see Monitor.check_consistency */
+	#[serde(default)]
+	pub inconsistent_servers : Vec<std::net::IpAddr>
+}
+
+impl MonitorResult {
+
+	pub fn new( domain_name : &str ) -> Self {
+		Self {
+			schema_version : crate::config::SCHEMA_VERSION,
+			domain_name : domain_name.to_string(),
+			success: true,
+			findings : Vec::new(),
+			nameservers: None,
+			ips: None,
+			synthesized_from_wildcard: false,
+			response_flags: Vec::new(),
+			serials: std::collections::BTreeMap::new(),
+			inconsistent_servers: Vec::new()
+		}
+	}
+
+	/**
+	 * read a result written by dns_audit, one written by a newer version with a different
+	 * schema_version is an error
+	 */
+	pub fn from_json( json : &str ) -> Result<Self, String> {
+
+		let document = match serde_json::from_str::<serde_json::Value>(json) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to parse result {}", e)); }
+		};
+
+		crate::config::schema_version( &document, "result" )?;
+
+		match serde_json::from_value::<Self>(document) {
+			Ok(m) => { Ok(m) },
+			Err(e) => { Err(format!("failed to parse result {}", e)) }
+		}
+	}
+
+	/**
+	 * the file the result is written to in the --result-dir. The trailing dot is dropped and
+	 * anything other than letters, digits, '-' and '_' is escaped as %XX so a name can't
+	 * leave the directory or collide with another name. The dots between labels are kept
+	 * except at the start, which would make it a hidden file
+	 */
+	pub fn file_name( &self ) -> String {
+
+		let name = self.domain_name.to_ascii_lowercase();
+		let name = match name.strip_suffix('.') {
+			Some(m) if !m.is_empty() => { m.to_string() },
+			_ => { name }
+		};
+
+		let mut rval = String::new();
+		for (i, c) in name.chars().enumerate() {
+			if c.is_ascii_alphanumeric() || c == '-' || c == '_' || (c == '.' && i > 0) {
+				rval.push(c);
+			} else {
+				let mut buf = [0u8; 4];
+				for b in c.encode_utf8(&mut buf).bytes() {
+					rval.push_str(&format!("%{:02X}", b));
+				}
+			}
+		}
+
+		rval + ".json"
+	}
+
+	/**
+	 * write the result to its own file in the directory. It is written to a temporary file
+	 * first then renamed over the old one, so a reader never sees a partial result
+	 */
+	pub fn write_to_dir( &self, dir : &str ) -> Result<(), String> {
+
+		let file_name = self.file_name();
+		let path = std::path::Path::new(dir).join(&file_name);
+		let tmp_path = std::path::Path::new(dir).join(format!(".{}.tmp", file_name));
+
+		if let Err(e) = std::fs::write( &tmp_path, serde_json::to_string(self).unwrap() ) {
+			return Err(format!("failed to write result {} {}", tmp_path.display(), e));
+		}
+
+		match std::fs::rename( &tmp_path, &path ) {
+			Ok(_) => { Ok(()) },
+			Err(e) => {
+				let _ = std::fs::remove_file(&tmp_path);
+				Err(format!("failed to replace result {} {}", path.display(), e))
+			}
+		}
+	}
+
 }
 
 impl std::fmt::Display for MonitorResult {
@@ -46,181 +231,288 @@ impl std::fmt::Display for MonitorResult {
 			writeln!(f, "{} OK", self.domain_name)?;
 		} else {
 			writeln!(f, "{} FAIL", self.domain_name)?;
-			writeln!(f, "\t{}", self.reason.join("\n\t"))?;
+			for finding in &self.findings {
+				writeln!(f, "\t{}", finding)?;
+			}
 		}
 		Ok(())
 	}
 
 }
 
+/**
+ * an address the domain is expected to resolve to, either exactly or anywhere in a network
+ * written as a CIDR prefix. In the config it is a string, "192.0.2.1" or "192.0.2.0/24"
+ */
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum ExpectedIp {
+	Addr(std::net::IpAddr),
+	Net(ipnet::IpNet)
+}
+
+impl ExpectedIp {
+
+	pub fn matches( &self, addr : &std::net::IpAddr ) -> bool {
+		match self {
+			Self::Addr(a) => { a == addr },
+			Self::Net(n) => { n.contains(addr) }
+		}
+	}
+
+	/**
+	 * the addresses that no expected entry matches and the expected entries that no address
+	 * matches, both are empty when the answer is right
+	 */
+	pub fn unmatched<'a>( expected : &'a [ExpectedIp], actual : &[std::net::IpAddr] ) -> (Vec<std::net::IpAddr>, Vec<&'a ExpectedIp>) {
+		(
+			actual.iter().filter(|addr| !expected.iter().any(|ip| ip.matches(addr))).cloned().collect(),
+			expected.iter().filter(|ip| !actual.iter().any(|addr| ip.matches(addr))).collect()
+		)
+	}
+
+}
+
+impl TryFrom<String> for ExpectedIp {
+	type Error = String;
+
+	fn try_from( value : String ) -> Result<Self, Self::Error> {
+		if value.contains('/') {
+			match value.parse::<ipnet::IpNet>() {
+				Ok(m) => { Ok(Self::Net(m)) },
+				Err(e) => { Err(format!("invalid network '{}' {}", value, e)) }
+			}
+		} else {
+			match value.parse::<std::net::IpAddr>() {
+				Ok(m) => { Ok(Self::Addr(m)) },
+				Err(e) => { Err(format!("invalid address '{}' {}", value, e)) }
+			}
+		}
+	}
+}
+
+impl From<ExpectedIp> for String {
+	fn from( value : ExpectedIp ) -> Self {
+		value.to_string()
+	}
+}
+
+impl std::fmt::Display for ExpectedIp {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Addr(a) => { write!(f, "{}", a) },
+			Self::Net(n) => { write!(f, "{}", n) }
+		}
+	}
+}
+
+/**
+ * what the monitored name is expected to do
+ */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Expect {
+	/** the name resolves, the normal checks are ran against it */
+	#[default]
+	Exists,
+	/** the name must not exist, only the nxdomain check is ran by default */
+	Nxdomain
+}
+
+/**
+ * an address whose reverse DNS is audited by the ptr check
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReverseMonitor {
+	pub ip : std::net::IpAddr,
+	/** the hostname the PTR record should point at, any name is accepted when not set */
+	pub ptr : Option<String>,
+	/** also check that the PTR hostname resolves back to the ip, forward-confirmed reverse DNS */
+	#[serde(default)]
+	pub fcrdns : bool
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Monitor {
 	pub domain_name : String,
 	pub ns : Option<Vec<String>>,
-	pub ip : Option<Vec<std::net::IpAddr>>
+	pub ip : Option<Vec<ExpectedIp>>,
+	pub tsig : Option<tsig::TsigKey>,
+	/** the port the authoratative nameservers are queried on, 53 when it isn't given */
+	pub port : Option<u16>,
+	/** how long to wait for each query to the authoratative nameservers, --timeout when it isn't given */
+	pub timeout_ms : Option<u64>,
+	/** how many more times a query that failed is sent before the check gives up, default 0 */
+	pub retries : Option<u32>,
+	pub checks : Option<Vec<String>>,
+	pub reverse : Option<Vec<ReverseMonitor>>,
+	/** networks the authoratative nameservers have to be in, any address outside of them is flagged */
+	pub allowed_ns_networks : Option<Vec<ipnet::IpNet>>,
+	/** how many days before an RRSIG expires the rrsig_expiry check warns, default 7 */
+	pub signature_warning_days : Option<u32>,
+	/** the SOA serial every authoratative nameserver has to serve, without it they only have to agree */
+	pub expected_serial : Option<u32>,
+	/**
+	 * ask every address of every authoratative nameserver for the NS, A and AAAA records
+	 * instead of only the first one that answers, and report the ones that disagree
+	 */
+	#[serde(default)]
+	pub check_consistency : bool,
+	/** the TTL the domain's A, AAAA and NS records have to have, give or take ttl_tolerance seconds */
+	pub expected_ttl : Option<i32>,
+	/** how many seconds a TTL can be off of expected_ttl, default 0 */
+	pub ttl_tolerance : Option<i32>,
+	/** the domain is signed, every RRset the dnssec check gets back has to have an RRSIG */
+	#[serde(default)]
+	pub dnssec : bool,
+	#[serde(default)]
+	pub expect : Expect,
+	/** a zone file with the records the authoratative nameservers have to serve, the domain is its origin */
+	pub zone_file : Option<String>,
+	/** the addresses expected by clients in each network, asked for with EDNS Client Subnet */
+	pub geo : Option<std::collections::BTreeMap<ipnet::IpNet, Vec<ExpectedIp>>>
 }
 
 impl Monitor {
 
 	/**
-	 * this function will test to make sure the ns and ip address
-	 * it will return true if everything matches, or false if not
+	 * read the monitors from the JSON config, the ${NAME} environment variables and
+	 * ${file:PATH} secrets in its strings are filled in first
 	 */
-	pub fn test( inme : Arc<RwLock<Monitor>>, root : Arc<RwLock<root::Root>> ) -> Arc<Mutex<MonitorResult>> {
-
-		let me = inme.read().unwrap();
+	pub fn list_from_json( json : &str ) -> Result<Vec<Self>, String> {
 
-		let mut rval = MonitorResult {
-			domain_name : me.domain_name.clone(),
-			success: true,
-			reason : Vec::new(),
-			flags: Vec::new(),
-			nameservers: None,
-			ips: None,
+		let mut document = match serde_json::from_str::<serde_json::Value>(json) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to parse config {}", e)); }
 		};
-		
-		let mut read_ns: Vec<String> = Vec::new();
-
-		if let Ok(mut root_write ) = root.write() {
-
-			if let Ok(m) =  root_write.get_nameservers_and_resolve(&me.domain_name) {
-				let root_ns = m.read().unwrap();
-				for addr in &root_ns.servers {
-
-					let addr_lock = addr.read().unwrap();
-					let mut query = query::Sender::new( &addr_lock.ip );
-					if let Ok(_) = query.query( &me.domain_name, query::QueryType::T_NS) {
-						for rec in &query.authority {
-							if rec.record_type == zone::record::RecordType::NS {
-								if let Some(namerr) = rec.rdata.as_ref().unwrap().as_any().downcast_ref::<zone::rr::RDATANameRR>() {
-									read_ns.push( namerr.name.fqdn.clone());
-								}
-							}
-						}
-						break;
-					}
-				}
 
-			}
+		crate::config::expand_env_json( &mut document )?;
 
-			drop(root_write);
+		match serde_json::from_value::<Vec<Self>>(document) {
+			Ok(m) => { Ok(m) },
+			Err(e) => { Err(format!("failed to parse config {}", e)) }
 		}
+	}
 
-		if let Some(ns) = &me.ns {
-
-			if read_ns.len() == 0 {
-				rval.flags.push(ErrorCode::NoAuthoratative);
-				rval.reason.push( "no authoratative nameservers at root".to_string() );
-				rval.success = false;
-			} else if read_ns.len() != ns.len() {
-				rval.reason.push( "nameservers at root do not match expected".to_string() );
-				rval.flags.push(ErrorCode::AuthoratativeFail);
-				rval.success = false;
-			} else {
-				for nsname in ns {
-					let mut fail = true;
-
-					for cmpns in &read_ns {
-						if cmpns.eq_ignore_ascii_case( &nsname ) {
-							fail = false;
-							break;
-						}
-					}
-
-					if fail {
-						rval.reason.push( "nameservers at root do not match expected".to_string() );
-						rval.flags.push(ErrorCode::AuthoratativeFail);
-						rval.success = false;
-						break;
-					}
+	/**
+	 * the names of the checks test runs, the monitor's checks, which replace the defaults, or
+	 * the DEFAULT_CHECKS. The serial check isn't a default since it asks every nameserver
+	 * address, it is added for a monitor with an expected_serial. The rrsig_expiry check is
+	 * added for a signed one, an unsigned domain has no signatures to expire
+	 */
+	pub fn check_names( &self ) -> Vec<String> {
+		match &self.checks {
+			Some(m) => { m.clone() },
+			None if self.expect == Expect::Nxdomain => { vec![ "nxdomain".to_string() ] },
+			None => {
+				let mut rval : Vec<String> = check::DEFAULT_CHECKS.iter().map(|c| c.to_string()).collect();
+				if self.expected_serial.is_some() {
+					rval.push( "serial".to_string() );
+				}
+				if self.dnssec {
+					rval.push( "rrsig_expiry".to_string() );
 				}
+				rval
 			}
-
 		}
+	}
 
-		rval.nameservers = Some( read_ns );
+	/**
+	 * blocking version of test_async for callers that aren't async
+	 */
+	pub fn test( inme : Arc<RwLock<Monitor>>, root : Arc<RwLock<root::Root>> ) -> Arc<Mutex<MonitorResult>> {
+		query::block_on( Self::test_async( inme, root ) )
+	}
 
+	/**
+	 * this function will run each of the monitor's checks, by default it tests to
+	 * make sure the ns and ip address match. The checks aren't async, a task running this
+	 * gives its worker thread up while each of their queries waits, see query::block_on
+	 */
+	pub async fn test_async( inme : Arc<RwLock<Monitor>>, root : Arc<RwLock<root::Root>> ) -> Arc<Mutex<MonitorResult>> {
 
-		if let Some(ips) = &me.ip {
+		let me = inme.read().unwrap();
 
-			for auth_ns in rval.nameservers.as_ref().unwrap() {
+		let mut rval = MonitorResult::new( &me.domain_name );
 
-				let mut result_from_ns = false;
+		let timeout_ms = me.timeout_ms;
+		let retries = me.retries.unwrap_or(0);
+		let port = me.port.unwrap_or( query::DNS_PORT );
+		let sender_factory = move | addr : &std::net::IpAddr | -> query::Sender {
+			let mut sender = query::Sender::new( addr );
+			sender.port = port;
+			if let Some(timeout_ms) = timeout_ms {
+				sender.timeout = std::time::Duration::from_millis( timeout_ms );
+			}
+			sender.retries = retries;
+			sender
+		};
 
-				let mut read_addresses : Vec<std::net::IpAddr> = Vec::new();
-				if let Ok( addresses ) = dns_lookup::lookup_host( &auth_ns ) {	
-					for addr in addresses {
+		for name in &me.check_names() {
 
-						let mut query = query::Sender::new( &addr );
-						if let Ok(_) = query.query( &me.domain_name, query::QueryType::T_A) {
+			let findings = match check::get(name) {
+				Some(c) => { c.run(&me, &sender_factory, &root, &mut rval) },
+				None => { vec![ Finding::new(ErrorCode::UnknownCheck, &format!("unknown check '{}'", name)) ] }
+			};
 
-							result_from_ns = true;
+			if !findings.is_empty() {
+				rval.success = false;
+				rval.findings.extend(findings);
+			}
+		}
 
-							for res in &query.answer {
-								if res.record_type == zone::record::RecordType::A {
-									if let Some(a) = res.rdata.as_ref().unwrap().as_any().downcast_ref::<zone::rr::RDATAa>() {
-										read_addresses.push( std::net::IpAddr::from(a.ip.clone())) ;
-									}
-								}
-							}
-						}
+		Arc::new(Mutex::new(rval))
+	}
 
-						if let Ok(_) = query.query( &me.domain_name, query::QueryType::T_AAAA) {
+	/**
+	 * blocking version of test_guarded_async for callers that aren't async
+	 */
+	pub fn test_guarded( inme : Arc<RwLock<Monitor>>, root : Arc<RwLock<root::Root>> ) -> Arc<Mutex<MonitorResult>> {
+		query::block_on( Self::test_guarded_async( inme, root ) )
+	}
 
-							result_from_ns = true;
+	/**
+	 * test_async() with a panic in any of the checks caught and reported as an InternalError
+	 * finding, so one bad domain doesn't lose the rest of the run's results
+	 */
+	pub async fn test_guarded_async( inme : Arc<RwLock<Monitor>>, root : Arc<RwLock<root::Root>> ) -> Arc<Mutex<MonitorResult>> {
 
-							for res in &query.answer {
-								if res.record_type == zone::record::RecordType::A {
-									if let Some(a) = res.rdata.as_ref().unwrap().as_any().downcast_ref::<zone::rr::RDATAaaaa>() {
-										read_addresses.push( std::net::IpAddr::from(a.ip.clone())) ;
-									}
-								}
-							}
-						}
+		let domain_name = match inme.read() {
+			Ok(m) => { m.domain_name.clone() },
+			Err(e) => { e.into_inner().domain_name.clone() }
+		};
 
-						if result_from_ns {
-							break;
-						}
+		// the test is its own task so a panic ends only it
+		let e = match tokio::spawn( Self::test_async( inme, Arc::clone(&root) ) ).await {
+			Ok(m) => { return m; },
+			Err(e) => { e }
+		};
 
-					}
-				}
-				
-				if result_from_ns {
-					rval.ips = Some(read_addresses);
-
-					if rval.ips.as_ref().unwrap().len() == 0 { 
-						rval.flags.push(ErrorCode::NoResolve);
-						rval.reason.push( "domain did not resolve".to_string() );
-						rval.success = false;
-					} else if rval.ips.as_ref().unwrap().len() != ips.len() {
-						rval.flags.push(ErrorCode::ResolveIpNotMatch);
-						rval.reason.push( "did not return the correct ips".to_string() );
-						rval.success = false;
-					} else {
-						let ips = rval.ips.as_ref().unwrap();
-						for ip in rval.ips.as_ref().unwrap() {
-							let mut found = false;
-							for ip2 in ips {
-								if ip == ip2 {
-									found = true;
-									break;
-								}
-							}
-							if !found {
-								rval.flags.push(ErrorCode::ResolveIpNotMatch);
-								rval.reason.push( "did not return the correct ips".to_string() );
-								rval.success = false;
-							}
+		// a check that panicked holding the root poisons it for every other domain, the
+		// root only caches lookups so it is taken back as it was left
+		if root.is_poisoned() {
+			drop( root.write().unwrap_or_else(std::sync::PoisonError::into_inner) );
+			root.clear_poison();
+		}
+
+		let reason = match e.try_into_panic() {
+			Ok(e) => {
+				match e.downcast_ref::<&str>() {
+					Some(m) => { m.to_string() },
+					None => {
+						match e.downcast_ref::<String>() {
+							Some(m) => { m.clone() },
+							None => { "unknown panic".to_string() }
 						}
 					}
-
-					break;
 				}
+			},
+			Err(e) => { e.to_string() }
+		};
 
-			}
-
-		}
-
+		let mut rval = MonitorResult::new( &domain_name );
+		rval.success = false;
+		rval.findings.push( Finding::new(ErrorCode::InternalError, &format!("internal error testing {}: {}", domain_name, reason)) );
 		Arc::new(Mutex::new(rval))
 	}
 
@@ -234,6 +526,16 @@ impl Monitor {
 			}
 		}
 
+		if let Some(reverse) = &mut self.reverse {
+			for rev in reverse {
+				if let Some(name) = &mut rev.ptr {
+					if !name.ends_with('.') {
+						name.push('.');
+					}
+				}
+			}
+		}
+
 	}
 
 }
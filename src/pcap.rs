@@ -0,0 +1,253 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Writes every query and response to a pcap file so they can be opened in Wireshark. The
+ * sockets don't see the link or network layers so each message is wrapped in a synthetic
+ * Ethernet, IP and UDP frame built from the socket addresses. A query over TCP is one segment
+ * each way, without the handshake
+ */
+
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+const PCAP_MAGIC : u32 = 0xa1b2c3d4;
+const PCAP_SNAPLEN : u32 = 65535;
+const LINKTYPE_ETHERNET : u32 = 1;
+
+const ETHERTYPE_IPV4 : u16 = 0x0800;
+const ETHERTYPE_IPV6 : u16 = 0x86dd;
+const IPPROTO_UDP : u8 = 17;
+const IPPROTO_TCP : u8 = 6;
+
+lazy_static!(
+	static ref PCAP_FILE : Mutex<Option<std::fs::File>> = Mutex::new(None);
+);
+
+/**
+ * create the capture file and write its header, packets are only captured after this
+ */
+pub fn open( path : &str ) -> Result<(), String> {
+
+	let mut fp = match std::fs::File::create(path) {
+		Ok(m) => { m },
+		Err(e) => { return Err(format!("failed to create {} {}", path, e)); }
+	};
+
+	let mut header = Vec::<u8>::with_capacity(24);
+	header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+	header.extend_from_slice(&2u16.to_le_bytes());
+	header.extend_from_slice(&4u16.to_le_bytes());
+	header.extend_from_slice(&0i32.to_le_bytes());
+	header.extend_from_slice(&0u32.to_le_bytes());
+	header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+	header.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+
+	if let Err(e) = fp.write_all(&header) {
+		return Err(format!("failed to write {} {}", path, e));
+	}
+
+	*PCAP_FILE.lock().unwrap() = Some(fp);
+	Ok(())
+}
+
+/**
+ * whether open() was called, so callers can skip work that is only needed for the capture
+ */
+pub fn enabled() -> bool {
+	PCAP_FILE.lock().unwrap().is_some()
+}
+
+/**
+ * capture one DNS message sent over UDP from src to dst
+ */
+pub fn record( src : SocketAddr, dst : SocketAddr, payload : &[u8] ) {
+
+	let mut udp = Vec::<u8>::with_capacity(8 + payload.len());
+	udp.extend_from_slice(&src.port().to_be_bytes());
+	udp.extend_from_slice(&dst.port().to_be_bytes());
+	udp.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+	udp.extend_from_slice(&0u16.to_be_bytes());
+	udp.extend_from_slice(payload);
+
+	write_frame( src, dst, IPPROTO_UDP, udp );
+}
+
+/**
+ * capture a DNS message sent over TCP with its two byte length as a PSH/ACK segment, seq and
+ * ack are the relative sequence numbers Wireshark uses to put the stream back together
+ */
+pub fn record_tcp( src : SocketAddr, dst : SocketAddr, seq : u32, ack : u32, payload : &[u8] ) {
+
+	let mut tcp = Vec::<u8>::with_capacity(20 + payload.len());
+	tcp.extend_from_slice(&src.port().to_be_bytes());
+	tcp.extend_from_slice(&dst.port().to_be_bytes());
+	tcp.extend_from_slice(&seq.to_be_bytes());
+	tcp.extend_from_slice(&ack.to_be_bytes());
+	tcp.extend_from_slice(&[ 0x50, 0x18 ]);
+	tcp.extend_from_slice(&u16::MAX.to_be_bytes());
+	tcp.extend_from_slice(&[ 0, 0, 0, 0 ]);
+	tcp.extend_from_slice(payload);
+
+	write_frame( src, dst, IPPROTO_TCP, tcp );
+}
+
+/**
+ * write the UDP or TCP segment in a frame, does nothing when no file is open. A write error
+ * is reported once and the capture is stopped so the audit itself carries on
+ */
+fn write_frame( src : SocketAddr, dst : SocketAddr, protocol : u8, segment : Vec<u8> ) {
+
+	let mut lock = PCAP_FILE.lock().unwrap();
+	let fp = match lock.as_mut() {
+		Some(m) => { m },
+		None => { return; }
+	};
+
+	let frame = frame( src, dst, protocol, segment );
+
+	let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+
+	let mut packet = Vec::<u8>::with_capacity(16 + frame.len());
+	packet.extend_from_slice(&(now.as_secs() as u32).to_le_bytes());
+	packet.extend_from_slice(&now.subsec_micros().to_le_bytes());
+	packet.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+	packet.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+	packet.extend_from_slice(&frame);
+
+	if let Err(e) = fp.write_all(&packet) {
+		writeln!(std::io::stderr().lock(), "failed to write packet capture, capture stopped {}", e).unwrap();
+		*lock = None;
+	}
+}
+
+/**
+ * build the Ethernet frame, the MAC addresses are zero. When the two addresses are from
+ * different families, the source is replaced by the unspecified address of the destination's
+ */
+fn frame( src : SocketAddr, dst : SocketAddr, protocol : u8, mut segment : Vec<u8> ) -> Vec<u8> {
+
+	let mut frame = vec![ 0u8; 12 ];
+
+	match (src.ip(), dst.ip()) {
+		(IpAddr::V6(s), IpAddr::V6(d)) => {
+			ipv6_frame( &mut frame, &s.octets(), &d.octets(), protocol, &mut segment );
+		},
+		(IpAddr::V4(s), IpAddr::V4(d)) => {
+			ipv4_frame( &mut frame, &s.octets(), &d.octets(), protocol, &mut segment );
+		},
+		(_, IpAddr::V6(d)) => {
+			ipv6_frame( &mut frame, &[0u8; 16], &d.octets(), protocol, &mut segment );
+		},
+		(_, IpAddr::V4(d)) => {
+			ipv4_frame( &mut frame, &[0u8; 4], &d.octets(), protocol, &mut segment );
+		},
+	}
+
+	frame
+}
+
+/**
+ * IPv4 allows a zero UDP checksum so only the IP header's is calculated, TCP always has one
+ */
+fn ipv4_frame( frame : &mut Vec<u8>, src : &[u8], dst : &[u8], protocol : u8, segment : &mut [u8] ) {
+
+	if protocol == IPPROTO_TCP {
+		segment_checksum( src, dst, protocol, segment );
+	}
+
+	frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+	let mut ip = Vec::<u8>::with_capacity(20);
+	ip.push(0x45);
+	ip.push(0);
+	ip.extend_from_slice(&((20 + segment.len()) as u16).to_be_bytes());
+	ip.extend_from_slice(&[ 0, 0, 0x40, 0 ]);
+	ip.push(64);
+	ip.push(protocol);
+	ip.extend_from_slice(&[ 0, 0 ]);
+	ip.extend_from_slice(src);
+	ip.extend_from_slice(dst);
+
+	let sum = checksum( &ip, 0 );
+	ip[10..12].copy_from_slice(&sum.to_be_bytes());
+
+	frame.extend_from_slice(&ip);
+	frame.extend_from_slice(segment);
+}
+
+/**
+ * the UDP checksum is required over IPv6
+ */
+fn ipv6_frame( frame : &mut Vec<u8>, src : &[u8], dst : &[u8], protocol : u8, segment : &mut [u8] ) {
+
+	frame.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+
+	segment_checksum( src, dst, protocol, segment );
+
+	frame.extend_from_slice(&[ 0x60, 0, 0, 0 ]);
+	frame.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+	frame.push(protocol);
+	frame.push(64);
+	frame.extend_from_slice(src);
+	frame.extend_from_slice(dst);
+	frame.extend_from_slice(segment);
+}
+
+/**
+ * set the UDP or TCP checksum, it covers a pseudo header with the addresses. The IPv6 layout
+ * of the pseudo header sums the same as the IPv4 one so it is used for both
+ */
+fn segment_checksum( src : &[u8], dst : &[u8], protocol : u8, segment : &mut [u8] ) {
+
+	let mut pseudo = Vec::<u8>::with_capacity(40);
+	pseudo.extend_from_slice(src);
+	pseudo.extend_from_slice(dst);
+	pseudo.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+	pseudo.extend_from_slice(&[ 0, 0, 0, protocol ]);
+
+	let offset = if protocol == IPPROTO_TCP { 16 } else { 6 };
+	let sum = match checksum( segment, sum_words(&pseudo) ) {
+		// a zero UDP checksum means there is none
+		0 if protocol == IPPROTO_UDP => { 0xffff },
+		m => { m }
+	};
+	segment[offset..offset + 2].copy_from_slice(&sum.to_be_bytes());
+}
+
+fn sum_words( data : &[u8] ) -> u32 {
+	data.chunks(2).map(|c| {
+		u16::from_be_bytes([ c[0], if c.len() > 1 { c[1] } else { 0 } ]) as u32
+	}).sum()
+}
+
+/**
+ * the internet checksum of data, initial carries a partial sum such as a pseudo header's
+ */
+fn checksum( data : &[u8], initial : u32 ) -> u16 {
+	let mut sum = initial + sum_words(data);
+	while sum > 0xffff {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+	!(sum as u16)
+}
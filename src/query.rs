@@ -28,35 +28,53 @@ use crate::config::{println_verbose, print_verbose};
 /**
  * macro function to convert an arbitrary integer from big endian into the int type specified by t
  * 
- * Usage dns_read_int( int_type, buffer, offset ) where int_type is a primiitive type like u16
+ * Usage dns_read_int( int_type, buffer, offset ) where int_type is a primiitive type like u16,
+ * it returns the error from the function it is used in when the buffer is too short
  */
 macro_rules! dns_read_int {
 	($t:ident, $buff:expr, $offset:expr) => {
-		$t::from_be_bytes( crate::query::read_buff(&$buff, $offset, std::mem::size_of::<$t>() ).try_into().unwrap() )
+		$t::from_be_bytes( crate::query::read_buff(&$buff, $offset, std::mem::size_of::<$t>() )?.try_into().unwrap() )
 	};
 }
 pub(crate) use dns_read_int;
 
+/**
+ * the offset in a message each name and the names it ends with were first written at, for
+ * compressing the names written after them (RFC-1035 section 4.1.4)
+ */
+pub type NameOffsets = std::collections::HashMap<String, usize>;
+
+/** the top two bits of a length byte that make it a compression pointer */
+const COMPRESSION_POINTER : u16 = 0xc000;
+
+/** the furthest into a message a compression pointer can point */
+const MAX_POINTER_OFFSET : usize = 0x3fff;
+
 pub trait Wire {
 
 	/**
-	 * Write this object into the DNS wire format
+	 * Write this object into the DNS wire format, an error when it can't be written like a
+	 * name that is too long
+	 * names : the names written before in the message and their offsets, a name can point
+	 *         to one of them instead of being written out again and adds its own
+	 * start : is the offset from the start of the message the object will be written at
 	 */
-	fn write( &self ) -> Vec<u8>;
+	fn write( &self, names : &mut NameOffsets, start : usize ) -> Result<Vec<u8>, String>;
 
 	/**
 	 * Read this object's values from the dns wire format
 	 * buff : is the buffer
 	 * offset : is the offset from the start of the buffer for where we are to read
+	 * an error is returned when the buffer ends before the object does
 	 */
-	fn read ( &mut self, buff : &[u8], offset: &mut usize );
+	fn read ( &mut self, buff : &[u8], offset: &mut usize ) -> Result<(), String>;
 
 }
 
 #[repr(u16)]
 #[allow(dead_code)]
 #[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq,Clone,Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub enum NSClass {
 	C_INVALID = 0,	/*%< Cookie. */
 	C_IN = 1,		/*%< Internet. */
@@ -111,7 +129,7 @@ impl NSClass {
 #[repr(u16)]
 #[allow(dead_code)]
 #[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq,Clone,Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub enum QueryType {
 	T_INVALID = 0,
 	T_A = 1,
@@ -504,6 +522,23 @@ impl std::fmt::Display for OPCODE {
 		})
 	}
 }
+/**
+ * the bytes of a hex dump such as the one printed with -vv, whitespace and 0x or \x
+ * prefixes are skipped. None when anything else is in the text
+ */
+pub fn decode_hex( text : &str ) -> Option<Vec<u8>> {
+
+	let digits : String = text.split_whitespace()
+		.map(|w| w.replace("0x", "").replace("\\x", ""))
+		.collect();
+
+	if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+		return None;
+	}
+
+	(0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok()).collect()
+}
+
 /**
  * helper function to write the inputted byte string into the buffer vector
 */
@@ -519,50 +554,82 @@ pub fn write_buff(  buff : &mut Vec<u8>, src : &[u8], offset : usize) -> usize {
 
 /**
  * helper function to read SIZE bytes from buffer and return it as a vector, this 
- * is useful as the first step before converting to the final data type
+ * is useful as the first step before converting to the final data type. The buffer
+ * comes from the server so reading past its end is an error, the offset is left as it was
  */
-pub fn read_buff( buff : &[u8], offset: &mut usize, size : usize ) -> Vec<u8> {
+pub fn read_buff( buff : &[u8], offset: &mut usize, size : usize ) -> Result<Vec<u8>, String> {
 	
 	let end = *offset + size;	
+	if end > buff.len() {
+		return Err(format!("FORMERR read of {} bytes at {} is past the end of the {} byte message", size, offset, buff.len()));
+	}
 	let rval = buff[*offset..end].to_vec();
 
 	*offset = *offset + size;
 
-	return rval;
+	return Ok(rval);
 }
 
 /**
- * Read a qualified name with compression fun 
+ * Read a qualified name with compression fun, a label past the end of the buffer is an error
  */
-pub fn read_qname(buff : &[u8], offset : &mut usize) -> String {
+pub fn read_qname(buff : &[u8], offset : &mut usize) -> Result<String, String> {
 	let mut dest = String::new();
+	let mut limit = usize::MAX;
+	let mut pointers = 0;
 	while *offset < buff.len()  {
-		if !qname_namepart( &mut dest, buff, offset) {
+		if !qname_namepart( &mut dest, buff, offset, &mut limit, &mut pointers )? {
 			break;
 		}
 	}
-	dest
+	Ok(dest)
+}
+
+/**
+ * Write a qualified name in the uncompressed wire format, the root name
+ * ("." or empty) is written as a single zero length label
+ */
+pub fn write_qname( host : &str ) -> Vec<u8> {
+	let mut buff = Vec::<u8>::with_capacity( host.len() + 2 );
+
+	for label in host.split('.') {
+		if label.is_empty() {
+			continue;
+		}
+		buff.push( label.len() as u8 );
+		buff.extend_from_slice( label.as_bytes() );
+	}
+
+	buff.push(0u8);
+	buff
 }
 
 /**
- * each part of the name, with support for dns compression
+ * each part of the name, with support for dns compression. A pointer has to point before
+ * the part of the message the name is being read from, so following pointers always moves
+ * back and can't loop, and no more than one per two bytes of the message are followed
  */
-fn qname_namepart(  dn : &mut String, buffer : &[u8], offset : &mut usize ) -> bool {
+fn qname_namepart(  dn : &mut String, buffer : &[u8], offset : &mut usize, limit : &mut usize, pointers : &mut usize ) -> Result<bool, String> {
 
 	if *offset >= buffer.len() {
-		return false;
+		return Ok(false);
 	}
 
 	let part_len : u8 = dns_read_int!(u8, buffer, offset);
 
 	if part_len == 0 {
-		return false;
+		return Ok(false);
 	}
 
 	const COMP : u8 = 0b11000000;
 
 	if (part_len & COMP) == COMP  {
 		// compresed part
+		if *offset >= buffer.len() {
+			return Ok(false);
+		}
+
+		let pointer_at = *offset - 1;
 		let mut buff2 = [0u8;2];
 		buff2[0] = part_len & !COMP;
 		buff2[1] = buffer[*offset];
@@ -571,15 +638,23 @@ fn qname_namepart(  dn : &mut String, buffer : &[u8], offset : &mut usize ) -> b
 
 
 		let mut usize_com_offset : usize = u16::from_be_bytes(buff2) as usize;
+
+		*pointers += 1;
+		if usize_com_offset >= pointer_at.min(*limit) || *pointers > buffer.len() / 2 {
+			println_verbose!(VERBOSE2, "Compression pointer at {} to {} is not followed, the name is cut short", pointer_at, usize_com_offset);
+			return Ok(false);
+		}
+		*limit = usize_com_offset;
+
 		while usize_com_offset < buffer.len() {
-			if !qname_namepart(dn, buffer, &mut usize_com_offset) {
+			if !qname_namepart(dn, buffer, &mut usize_com_offset, limit, pointers)? {
 				break;
 			}
 		}
-		return false;
+		return Ok(false);
 	} else {
 
-		let dn_vec = read_buff(buffer, offset, part_len as usize );
+		let dn_vec = read_buff(buffer, offset, part_len as usize )?;
 
 		if dn.len() > 0 {
 			dn.push('.');
@@ -590,80 +665,122 @@ fn qname_namepart(  dn : &mut String, buffer : &[u8], offset : &mut usize ) -> b
 		}
 
 	}
-	return true;
+	return Ok(true);
 }
 
 /**
  * Question section from the dns query
  */
+#[derive(PartialEq, Eq, Clone)]
 pub struct Question {
-	host : String,
-	qtype : QueryType,
-	qclass : NSClass
+	pub host : String,
+	pub qtype : QueryType,
+	pub qclass : NSClass
+}
+
+impl Question {
+	pub fn new( host : &str, qtype : QueryType, qclass : NSClass ) -> Self {
+		Self {
+			host: host.to_string(),
+			qtype,
+			qclass
+		}
+	}
 }
 
 impl Wire for Question {
 
 	/**
-	 * write out the question into the raw format that is needed for the dns server
+	 * write out the question into the raw format that is needed for the dns server, a label
+	 * longer than MAX_LABEL_SZ or a name longer than MAX_NAME_SZ is an error (RFC-1035 section 2.3.4).
+	 * The name ends in a pointer to the longest part of it that was written before
 	 */
 	#[allow(unused_assignments)]
-	fn write( &self ) -> Vec<u8> {		
+	fn write( &self, names : &mut NameOffsets, start : usize ) -> Result<Vec<u8>, String> {
 		let mut buff = Vec::<u8>::new();
 
 		let mut offset : usize = 0;
 
 		let bytes = self.host.as_bytes();
 
-		if  bytes.len() > 255 {
-			panic!("host length {} is > 255 which isn't valid for domain names", bytes.len());
+		if  bytes.len() > MAX_NAME_SZ {
+			return Err(format!("name '{}' is {} octets, longer than the {} a name can be", self.host, bytes.len(), MAX_NAME_SZ));
 		}
 
-		let mut x: u8 = 0;
-		let mut last_l :u8 = 0;
-		let byte_len :u8 = bytes.len() as u8;
+		let mut x: usize = 0;
+		let mut last_l : usize = 0;
+		let byte_len : usize = bytes.len();
+		let mut labels : Vec<&str> = Vec::new();
 
 
 		println_verbose!(VERBOSE3, "bytes len '{}' ", byte_len);
 
-		while x <= byte_len as u8 {
-			if x == byte_len || bytes[x as usize] == '.' as u8 {
-				let part_len : u8 = x - last_l;
-				if part_len == 0 { 
-					last_l = x;
-					x = x + 1;
-					continue;
+		while x <= byte_len {
+			if x == byte_len || bytes[x] == '.' as u8 {
+				let part_len : usize = x - last_l;
+				if part_len > MAX_LABEL_SZ {
+					return Err(format!("label '{}' of '{}' is {} octets, longer than the {} a label can be", &self.host[last_l..x], self.host, part_len, MAX_LABEL_SZ));
 				}
+				// an empty label is the root name "." or a doubled/leading dot, the name is
+				// terminated by the zero length label written below
+				if part_len > 0 {
+					labels.push( &self.host[last_l..x] );
+				}
+				last_l = x + 1;
+			}
 
-				// add part + 1 
-				buff.resize( buff.len() + part_len as usize + 1, 0u8);
+			x = x + 1;
 
-				offset = write_buff(&mut buff, &part_len.to_be_bytes(), offset);
+		}
 
-				let end : u8 = last_l + part_len;
+		// the labels with their length bytes and the root label that ends them, without a
+		// trailing dot this is one more than the text
+		let name_len = labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1;
+		if name_len > MAX_NAME_SZ {
+			return Err(format!("name '{}' is {} octets on the wire, longer than the {} a name can be", self.host, name_len, MAX_NAME_SZ));
+		}
 
-				offset = write_buff(&mut buff, &bytes[last_l as usize..end as usize], offset);
-				last_l = x + 1;
+		let mut compressed = false;
+		for i in 0..labels.len() {
+
+			let suffix = labels[i..].join(".");
+			if let Some(at) = names.get(&suffix) {
+				buff.resize( buff.len() + 2, 0u8);
+				offset = write_buff(&mut buff, &(COMPRESSION_POINTER | *at as u16).to_be_bytes(), offset);
+				compressed = true;
+				break;
 			}
 
-			x = x + 1;
+			// a pointer only has 14 bits for the offset
+			if start + offset <= MAX_POINTER_OFFSET {
+				names.insert( suffix, start + offset );
+			}
 
+			// add part + 1 
+			buff.resize( buff.len() + labels[i].len() + 1, 0u8);
+			offset = write_buff(&mut buff, &(labels[i].len() as u8).to_be_bytes(), offset);
+			offset = write_buff(&mut buff, labels[i].as_bytes(), offset);
 		}
 
 		println_verbose!(VERBOSE3, "host done");
 
-		buff.resize( buff.len() + 5, 0u8);
-		offset = write_buff(&mut buff, &0u8.to_be_bytes(), offset);
+		if !compressed {
+			buff.resize( buff.len() + 1, 0u8);
+			offset = write_buff(&mut buff, &0u8.to_be_bytes(), offset);
+		}
+
+		buff.resize( buff.len() + 4, 0u8);
 		offset = write_buff(&mut buff, &self.qtype.as_u16().to_be_bytes(), offset);
 		offset = write_buff(&mut buff, &self.qclass.as_u16().to_be_bytes(), offset);
 
-		buff
+		Ok(buff)
 	}
 
-	fn read ( &mut self, buff : &[u8], offset: &mut usize ) {
-		self.host = read_qname(buff, offset);
+	fn read ( &mut self, buff : &[u8], offset: &mut usize ) -> Result<(), String> {
+		self.host = read_qname(buff, offset)?;
 		self.qtype = QueryType::from_u16( &dns_read_int!(u16, buff, offset));
 		self.qclass = NSClass::from_u16( &dns_read_int!(u16, buff, offset));
+		Ok(())
 	}
 
 }
@@ -712,43 +829,43 @@ impl Wire for Header {
 	 * Write the header and return the byte vector in the DNS wire format
 	 */
 	#[allow(unused_assignments)] // for the last offset
-	fn write( &self) -> Vec<u8> {
+	fn write( &self, _names : &mut NameOffsets, _start : usize ) -> Result<Vec<u8>, String> {
 		let mut buff = vec![0u8; 12];
 
 		let mut offset = 0;
 		offset = write_buff(&mut buff, &self.id.to_be_bytes(), offset);
 
-		// boo lack of union bit offsets
+		// boo lack of union bit offsets, RFC-1035 4.1.1 the QR bit is the most significant
 		let mut flag1 :u8 = 0;
-		if self.rd {
+		if self.qr {
 			flag1 |= 0b10000000;
 		}
-		if self.tc {
-			flag1 |= 0b01000000;
-		}
+		flag1 |= 0b01111000 & (self.opcode.as_u8() << 3);
 		if self.aa {
-			flag1 |= 0b00100000;
+			flag1 |= 0b00000100;
 		}
-		flag1 |= 0b00011110 & (self.opcode.as_u8() << 1);
-		if self.qr {
+		if self.tc {
+			flag1 |= 0b00000010;
+		}
+		if self.rd {
 			flag1 |= 0b00000001;
 		}
 		offset = write_buff(&mut buff, &flag1.to_be_bytes(), offset);
 
 
 		let mut flag2 :u8 = 0;
-		flag2 |= 0b11110000 & (self.rcode.as_u8() << 4);
-		flag2 |= 0b00001110 & (self.z << 1);
 		if self.ra {
-			flag2 |= 0b00000001
+			flag2 |= 0b10000000;
 		}
+		flag2 |= 0b01110000 & (self.z << 4);
+		flag2 |= 0b00001111 & self.rcode.as_u8();
 		offset = write_buff(&mut buff, &flag2.to_be_bytes(), offset);
 		offset = write_buff(&mut buff, &self.qdcount.to_be_bytes(), offset);
 		offset = write_buff(&mut buff, &self.ancount.to_be_bytes(), offset);
 		offset = write_buff(&mut buff, &self.nscount.to_be_bytes(), offset);
 		offset = write_buff(&mut buff, &self.arcount.to_be_bytes(), offset);
 
-		return buff;
+		return Ok(buff);
 	}
 
 	/**
@@ -756,12 +873,12 @@ impl Wire for Header {
 	 * offsetted by x bytes, after it is read the offset will point to 12
 	 * bytes past it's starting point
 	 */
-	fn read ( &mut self, buff : &[u8], offset: &mut usize ) {
+	fn read ( &mut self, buff : &[u8], offset: &mut usize ) -> Result<(), String> {
 		self.id = dns_read_int!(u16, buff, offset);
 
 		let flag1 : u8 = dns_read_int!(u8, buff, offset);
 		self.qr 		= if (flag1 & 0b10000000) != 0 { true } else { false };
-		self.opcode 	= OPCODE::from_u8((flag1 & 0b01111000) >> 1);
+		self.opcode 	= OPCODE::from_u8((flag1 & 0b01111000) >> 3);
 		self.aa 		= if (flag1 & 0b00000100) != 0 { true } else { false };
 		self.tc 		= if (flag1 & 0b00000010) != 0 { true } else { false };
 		self.rd 		= if (flag1 & 0b00000001) != 0 { true } else { false };
@@ -775,6 +892,7 @@ impl Wire for Header {
 		self.ancount = dns_read_int!(u16, buff, offset);
 		self.nscount = dns_read_int!(u16, buff, offset);
 		self.arcount = dns_read_int!(u16, buff, offset);
+		Ok(())
 	}
 
 
@@ -823,6 +941,244 @@ impl std::fmt::Display for Header {
 	}
 }
 
+/**
+ * returns true when queries to the address can be sent, see set_family_probe
+ */
+pub type FamilyProbe = fn(&std::net::IpAddr) -> bool;
+
+lazy_static!(
+	/** result of the IPv4 egress probe, this is only ran once per process */
+	static ref IPV4_AVAILABLE : bool = probe_family( &std::net::IpAddr::V4(std::net::Ipv4Addr::new(198, 41, 0, 4)) );
+
+	/** result of the IPv6 egress probe, this is only ran once per process */
+	static ref IPV6_AVAILABLE : bool = probe_family( &std::net::IpAddr::V6(std::net::Ipv6Addr::new(0x2001, 0x503, 0xba3e, 0, 0, 0, 0x2, 0x30)) );
+
+	/** runs the queries, query() blocks on it so its callers don't have to be async */
+	static ref RUNTIME : tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
+		.enable_all()
+		.build()
+		.expect("failed to start the query runtime");
+
+	/**
+	 * a permit for each query allowed to wait for a response at once, sized from
+	 * config::CONCURRENCY when the first query is sent
+	 */
+	static ref IN_FLIGHT : tokio::sync::Semaphore = tokio::sync::Semaphore::new(
+		match *crate::config::CONCURRENCY.read().unwrap() {
+			0 => { tokio::sync::Semaphore::MAX_PERMITS },
+			n => { n }
+		}
+	);
+
+	/**
+	 * replaces the egress probes when set, so the family of an address can be made unavailable
+	 * without changing the routes, see set_family_probe
+	 */
+	static ref FAMILY_PROBE : std::sync::RwLock<Option<FamilyProbe>> = std::sync::RwLock::new(None);
+
+	/** NXDOMAIN responses seen during the run, see NegativeCache */
+	static ref NEGATIVE_CACHE : std::sync::Mutex<NegativeCache> = std::sync::Mutex::new(NegativeCache::new());
+);
+
+/**
+ * the runtime the queries are sent from, shared with the run loop in main
+ */
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+	&RUNTIME
+}
+
+/**
+ * wait for the future from code that isn't async. On a task of the runtime, like a domain's
+ * checks in the run loop, the worker thread is handed over with block_in_place while it waits
+ * so the other tasks carry on. The runtime must be multi threaded like the query runtime
+ */
+pub fn block_on<F : std::future::Future>( future : F ) -> F::Output {
+	match tokio::runtime::Handle::try_current() {
+		Ok(handle) => { tokio::task::block_in_place( || handle.block_on( future ) ) },
+		Err(_) => { runtime().block_on( future ) }
+	}
+}
+
+/**
+ * Check if we have a route for the address family of the probe address, connecting a udp
+ * socket does not send any data, it only asks the kernel for a route so this is cheap.
+ * The probe addresses are a.root-servers.net
+ */
+fn probe_family( probe : &std::net::IpAddr ) -> bool {
+
+	let socket = match UdpSocket::bind( bind_addr(probe) ) {
+		Ok(m) => { m },
+		Err(e) => {
+			println_verbose!(VERBOSE1, "Address family of {} unavailable, bind failed {}", probe, e);
+			return false;
+		}
+	};
+
+	if let Err(e) = socket.connect( SocketAddr::new(*probe, DNS_PORT) ) {
+		println_verbose!(VERBOSE1, "Address family of {} unavailable, no route {}", probe, e);
+		return false;
+	}
+
+	true
+}
+
+/**
+ * the local address a socket for talking to the server is bound to, any address of the
+ * server's family and any port
+ */
+pub fn bind_addr( server : &std::net::IpAddr ) -> SocketAddr {
+	match server {
+		std::net::IpAddr::V4(_) => { SocketAddr::new( std::net::Ipv4Addr::UNSPECIFIED.into(), 0 ) },
+		std::net::IpAddr::V6(_) => { SocketAddr::new( std::net::Ipv6Addr::UNSPECIFIED.into(), 0 ) }
+	}
+}
+
+/**
+ * write all of the buffer to the stream, the runtime doesn't have the io-util helpers
+ */
+async fn tcp_write_all( stream : &tokio::net::TcpStream, buff : &[u8] ) -> std::io::Result<()> {
+	let mut written = 0;
+	while written < buff.len() {
+		stream.writable().await?;
+		match stream.try_write( &buff[written..] ) {
+			Ok(n) => { written += n; },
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => { },
+			Err(e) => { return Err(e); }
+		}
+	}
+	Ok(())
+}
+
+/**
+ * fill the buffer from the stream, the connection closing first is an error
+ */
+async fn tcp_read_exact( stream : &tokio::net::TcpStream, buff : &mut [u8] ) -> std::io::Result<()> {
+	let mut read = 0;
+	while read < buff.len() {
+		stream.readable().await?;
+		match stream.try_read( &mut buff[read..] ) {
+			Ok(0) => { return Err(std::io::ErrorKind::UnexpectedEof.into()); },
+			Ok(n) => { read += n; },
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => { },
+			Err(e) => { return Err(e); }
+		}
+	}
+	Ok(())
+}
+
+/**
+ * Returns true if queries to the address can be sent, either because the address family
+ * passed the egress probe or because it was forced on with --force-ipv4 / --force-ipv6
+ */
+pub fn family_available( addr : &std::net::IpAddr ) -> bool {
+
+	let probe = *FAMILY_PROBE.read().unwrap();
+
+	match addr {
+		std::net::IpAddr::V4(_) => { *crate::config::FORCE_IPV4.read().unwrap() || probe.map_or_else(|| *IPV4_AVAILABLE, |p| p(addr)) },
+		std::net::IpAddr::V6(_) => { *crate::config::FORCE_IPV6.read().unwrap() || probe.map_or_else(|| *IPV6_AVAILABLE, |p| p(addr)) }
+	}
+}
+
+/**
+ * answer whether the family of an address is available with probe instead of the egress
+ * probes, None goes back to the egress probes. --force-ipv4 and --force-ipv6 still win
+ */
+pub fn set_family_probe( probe : Option<FamilyProbe> ) {
+	*FAMILY_PROBE.write().unwrap() = probe;
+}
+
+/** the port nameservers listen on */
+pub const DNS_PORT : u16 = 53;
+
+/** longest label of a name (RFC-1035 section 2.3.4) */
+pub const MAX_LABEL_SZ : usize = 63;
+
+/** longest name on the wire, its labels with their length bytes and the root label */
+pub const MAX_NAME_SZ : usize = 255;
+
+/** size of the message header */
+pub const HEADER_SZ : usize = 12;
+
+/** smallest question, a root name followed by the type and class */
+const MIN_QUESTION_SZ : usize = 5;
+
+/** smallest resource record, a root name, type, class, ttl and an empty rdata length */
+const MIN_RECORD_SZ : usize = 11;
+
+/** UDP payload size advertised in the OPT record, the DNS flag day 2020 recommendation */
+pub const EDNS_UDP_SIZE : u16 = 1232;
+
+/** UDP payload size advertised when auditing a DNSSEC zone, its signed answers are large */
+pub const DNSSEC_EDNS_UDP_SIZE : u16 = 4096;
+
+/** EDNS option code of the Padding option (RFC-7830) */
+pub const EDNS_OPTION_PADDING : u16 = 12;
+
+/** EDNS option code of the Client Subnet option (RFC-7871) */
+pub const EDNS_OPTION_CLIENT_SUBNET : u16 = 8;
+
+/** block size queries are padded to, the recommendation of RFC-8467 section 4.1 */
+pub const PADDING_BLOCK_SIZE : usize = 128;
+
+/**
+ * where the ids of the queries a Sender sends come from. They are random by default so a
+ * response can't be guessed, tests and captures that are compared byte for byte use a seed
+ * or a fixed id
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QueryIds {
+	/** ids from a splitmix64 generator in this state, the same seed gives the same ids */
+	Seeded(u64),
+	/** every query has this id */
+	Fixed(u16)
+}
+
+impl QueryIds {
+
+	/**
+	 * ids seeded from the random keys std gives each HashMap, no two senders share a sequence
+	 */
+	pub fn random() -> Self {
+		use std::hash::{BuildHasher, Hasher};
+		let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+		hasher.write_u128( std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos()) );
+		Self::Seeded( hasher.finish() )
+	}
+
+	/**
+	 * seeded with --query-id-seed when it was given, otherwise random
+	 */
+	pub fn from_config() -> Self {
+		match *crate::config::QUERY_ID_SEED.read().unwrap() {
+			Some(seed) => { Self::Seeded(seed) },
+			None => { Self::random() }
+		}
+	}
+
+	/**
+	 * the id for the next query
+	 */
+	pub fn next_id( &mut self ) -> u16 {
+		match self {
+			Self::Seeded(state) => {
+				*state = state.wrapping_add(0x9e3779b97f4a7c15);
+				let mut z = *state;
+				z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+				z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+				(z ^ (z >> 31)) as u16
+			},
+			Self::Fixed(id) => { *id }
+		}
+	}
+}
+
+impl Default for QueryIds {
+	fn default() -> Self {
+		Self::random()
+	}
+}
+
 /**
  * This structure is our sender to the DNS server, this does not recurse to the final server and just
  * queries this specific server. 
@@ -832,13 +1188,178 @@ impl std::fmt::Display for Header {
  */
 pub struct Sender {
 	server: std::net::IpAddr,
+	/** the port the server listens on, DNS_PORT unless it was set */
+	pub port : u16,
 	pub timeout : std::time::Duration,
+	/** how many more times a query is sent when it fails, like when there is no response before the timeout */
+	pub retries : u32,
 	pub recursive : bool,
+	pub qclass : NSClass,
+	pub tsig : Option<crate::tsig::TsigKey>,
 	pub recv_header : Header,
 	pub recv_questions : Vec<Question>,
 	pub answer : Vec<zone::record::ZoneRecord>,
 	pub authority : Vec<zone::record::ZoneRecord>,
 	pub additional : Vec<zone::record::ZoneRecord>,
+	/** records from the response whose class did not match the query, these are not added to the sections above */
+	pub class_mismatch : Vec<zone::record::ZoneRecord>,
+	/** where the id of each query sent comes from, random unless --query-id-seed was given */
+	pub query_ids : QueryIds,
+	/** the id of the last request sent, a response with another id is rejected */
+	pub request_id : u16,
+	/** the last request built, reused when the same question is asked again */
+	request_cache : Option<CachedRequest>,
+	/** set when the last query got no response before the timeout */
+	pub timed_out : bool,
+	/**
+	 * send every query over TCP, otherwise TCP is only used to ask again when the UDP
+	 * response is truncated (RFC-7766)
+	 */
+	pub use_tcp : bool,
+	/** ask for DNSSEC records, adds an OPT record with the DO bit (RFC-3225) to the query */
+	pub dnssec_ok : bool,
+	/**
+	 * pad queries to a multiple of this many bytes with the EDNS Padding option (RFC-7830),
+	 * PADDING_BLOCK_SIZE is the recommended size
+	 */
+	pub padding : Option<usize>,
+	/**
+	 * ask as a client in this network with the EDNS Client Subnet option (RFC-7871), a geo
+	 * DNS server answers for that location. The scope it answered for is client_subnet_scope
+	 */
+	pub client_subnet : Option<ipnet::IpNet>,
+	/**
+	 * advertise this UDP payload size in an OPT record (RFC-6891) so the server doesn't
+	 * truncate answers over 512 bytes, no OPT record is added for it when None
+	 */
+	pub edns_udp_size : Option<u16>,
+}
+
+/**
+ * the header flags and EDNS options of a request that are up to the caller
+ */
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub struct QueryFlags {
+	/** set RD to ask the server to recurse */
+	pub recursive : bool,
+	/** add an OPT record with the DO bit (RFC-3225) */
+	pub dnssec_ok : bool,
+	/** pad the request to a multiple of this many bytes (RFC-7830) */
+	pub padding : Option<usize>,
+	/** add the Client Subnet option for the network (RFC-7871) */
+	pub client_subnet : Option<ipnet::IpNet>,
+	/** add an OPT record advertising this UDP payload size, EDNS_UDP_SIZE when another flag adds it */
+	pub edns_udp_size : Option<u16>
+}
+
+/**
+ * serialized header and question of a request, only the id changes when it is re-sent
+ */
+struct CachedRequest {
+	questions : Vec<Question>,
+	flags : QueryFlags,
+	bytes : Vec<u8>
+}
+
+/**
+ * the question an NXDOMAIN response was for, the DO bit is part of it because the response
+ * to a query without it has no NSEC records, and the RD bit because a resolver answers a
+ * recursive query for a name an authority would only refer
+ */
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct NegativeKey {
+	pub server : std::net::IpAddr,
+	pub port : u16,
+	pub host : String,
+	pub qtype : QueryType,
+	pub qclass : NSClass,
+	pub dnssec_ok : bool,
+	pub recursive : bool
+}
+
+impl NegativeKey {
+	pub fn new( server : &std::net::IpAddr, port : u16, host : &str, qtype : QueryType, qclass : NSClass, dnssec_ok : bool, recursive : bool ) -> Self {
+		Self {
+			server : *server,
+			port,
+			host : host.trim_end_matches('.').to_ascii_lowercase(),
+			qtype,
+			qclass,
+			dnssec_ok,
+			recursive
+		}
+	}
+}
+
+/**
+ * NXDOMAIN responses kept for their negative TTL (RFC-2308 section 5), asking the same server
+ * about a name that doesn't exist again reads the saved response instead of sending a query
+ */
+pub struct NegativeCache {
+	entries : std::collections::HashMap<NegativeKey, (std::time::Instant, Vec<u8>)>
+}
+
+impl Default for NegativeCache {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl NegativeCache {
+
+	pub fn new() -> Self {
+		Self {
+			entries : std::collections::HashMap::new()
+		}
+	}
+
+	/**
+	 * how long an NXDOMAIN response can be cached, the lower of the TTL of the SOA record in
+	 * the authority section and its minimum field (RFC-2308 section 5). Without an SOA the
+	 * response is not cached
+	 */
+	pub fn negative_ttl( authority : &[zone::record::ZoneRecord] ) -> Option<std::time::Duration> {
+
+		let soa = authority.iter().find(|r| r.record_type == zone::record::RecordType::SOA)?;
+		let rdata = soa.rdata.as_ref()?.as_any().downcast_ref::<zone::rr::RDATAsoa>()?;
+
+		Some( std::time::Duration::from_secs( (soa.ttl.max(0) as u64).min( rdata.min as u64 ) ) )
+	}
+
+	/**
+	 * save the response until now + ttl, the entries that have expired by now are dropped so
+	 * names that are only asked once don't stay for the whole run
+	 */
+	pub fn insert( &mut self, key : NegativeKey, ttl : std::time::Duration, response : &[u8], now : std::time::Instant ) {
+		self.entries.retain(|_, (expires, _)| *expires > now);
+		self.entries.insert( key, (now + ttl, response.to_vec()) );
+	}
+
+	/**
+	 * the number of responses saved, including ones that expired since the last insert
+	 */
+	pub fn len( &self ) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty( &self ) -> bool {
+		self.entries.is_empty()
+	}
+
+	/**
+	 * the saved response for the question if it has not expired by now
+	 */
+	pub fn get( &mut self, key : &NegativeKey, now : std::time::Instant ) -> Option<Vec<u8>> {
+
+		match self.entries.get(key) {
+			Some((expires, response)) if *expires > now => { Some(response.clone()) },
+			Some(_) => {
+				self.entries.remove(key);
+				None
+			},
+			None => { None }
+		}
+	}
 }
 
 impl Sender {
@@ -846,99 +1367,440 @@ impl Sender {
 	pub fn new( server : &std::net::IpAddr) -> Self {
 		Self {
 			server :server.clone(),
-			timeout : std::time::Duration::new(5,0),
+			port : DNS_PORT,
+			timeout : *crate::config::QUERY_TIMEOUT.read().unwrap(),
+			retries : 0,
 			recv_header: Default::default(),
 			recursive : false ,
+			qclass : NSClass::C_IN,
+			tsig : None,
 			recv_questions : Vec::new(),
 			answer: Vec::new(),
 			authority: Vec::new(),
-			additional: Vec::new()
+			additional: Vec::new(),
+			class_mismatch: Vec::new(),
+			query_ids: QueryIds::from_config(),
+			request_id: 0,
+			request_cache: None,
+			timed_out: false,
+			use_tcp: *crate::config::FORCE_TCP.read().unwrap(),
+			dnssec_ok: false,
+			padding: None,
+			client_subnet: None,
+			edns_udp_size: None
 		}
 	}
 
-	pub fn query( &mut self, host : & String , query_type : QueryType ) -> Result<(),String>{
-
-		let socket = match UdpSocket::bind("0.0.0.0:0") {
-			Ok(m) => { m },
-			Err(e) => { return Err(format!("bind failed {}", e).to_string()); }
-		};
+	/**
+	 * decode a saved response without a server so parser problems can be reproduced, the
+	 * query class is ANY so records of every class are kept
+	 */
+	pub fn decode( buff : &[u8] ) -> Result<Self, String> {
+		let mut sender = Self::new( &std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED) );
+		sender.qclass = NSClass::C_ANY;
+		sender.read_response( buff )?;
+		Ok(sender)
+	}
 
-		let sockaddr = SocketAddr::new(self.server.clone(), 53);
+	/**
+	 * the request the next query for the questions is sent as, with the next id from
+	 * query_ids. Each call takes an id like sending does
+	 */
+	pub fn next_request( &mut self, questions : &[Question], flags : QueryFlags ) -> Result<Vec<u8>, String> {
+		let id = self.query_ids.next_id();
+		self.build_request( questions, flags, id )
+	}
 
-		println_verbose!(VERBOSE2, "Querying {} for rec {} at '{:?}'", host, query_type, sockaddr);
+	/**
+	 * build the header and question bytes for the request with the id set. The bytes are
+	 * cached so asking the same question again only rewrites the id, which is the first
+	 * two bytes of the header
+	 */
+	fn build_request( &mut self, questions : &[Question], flags : QueryFlags, id : u16 ) -> Result<Vec<u8>, String> {
 
-		if let Err(e) =  socket.connect(sockaddr) {
-			return Err(format!("connect failed {}", e).to_string()); 
+		if let Some(cached) = &self.request_cache {
+			if cached.questions == questions && cached.flags == flags {
+				let mut request = cached.bytes.clone();
+				request[0..2].copy_from_slice( &id.to_be_bytes() );
+				return Ok(request);
+			}
 		}
 
-		let questions = vec![ 
-			Question { 
-				host: host.clone(), 
-				qtype: query_type.clone(), 
-				qclass: NSClass::C_IN
-			}
-		];
+		let request = Self::encode_request( questions, flags, id )?;
+
+		self.request_cache = Some( CachedRequest {
+			questions: questions.to_vec(),
+			flags,
+			bytes: request.clone()
+		});
+
+		Ok(request)
+	}
+
+	/**
+	 * the wire format of a request with the questions in its question section, an error when a
+	 * question's name can't be written
+	 */
+	pub fn encode_request( questions : &[Question], flags : QueryFlags, id : u16 ) -> Result<Vec<u8>, String> {
 
 		let mut request : Vec<u8> = Vec::new();
+		let mut names = NameOffsets::new();
+
+		let padding = flags.padding.filter(|block| *block > 0);
+		let edns = flags.dnssec_ok || padding.is_some() || flags.client_subnet.is_some() || flags.edns_udp_size.is_some();
 
 		let send_header = Header  {
-			id: 1,
+			id,
 			qdcount: questions.len() as u16,
 			ancount: 0,
 			nscount: 0,
-			arcount: 0,
+			arcount: if edns { 1 } else { 0 },
 			qr: false,
 			opcode: OPCODE::O_QUERY,
 			aa: false,
 			tc: false,
-			rd: self.recursive,
+			rd: flags.recursive,
 			ra: false,
 			z: 0,
 			rcode: RCODE::NOERROR,
 		};
 
 		{
-			let mut header_bytes = send_header.write();
+			let mut header_bytes = send_header.write( &mut names, 0 )?;
 			request.append( &mut header_bytes );
 		}
 
 		println_verbose!(VERBOSE3, "header complete");
 
 		for question in questions {
-			let mut q_bytes = question.write();
+			let mut q_bytes = question.write( &mut names, request.len() )?;
 			request.append(&mut q_bytes);
 
 		}
 
 		println_verbose!(VERBOSE3, "question complete");
 
+		if edns {
+			// root owner, OPT, our UDP size in the class, then extended rcode 0, version 0
+			// and the DO bit in the ttl
+			request.push(0);
+			request.extend_from_slice( &QueryType::T_OPT.as_u16().to_be_bytes() );
+			request.extend_from_slice( &flags.edns_udp_size.unwrap_or(EDNS_UDP_SIZE).to_be_bytes() );
+			request.extend_from_slice( &(if flags.dnssec_ok { 0x8000u32 } else { 0 }).to_be_bytes() );
+
+			let mut options : Vec<u8> = Vec::new();
+
+			if let Some(subnet) = flags.client_subnet {
+				let data = Self::client_subnet_option( &subnet );
+				options.extend_from_slice( &EDNS_OPTION_CLIENT_SUBNET.to_be_bytes() );
+				options.extend_from_slice( &(data.len() as u16).to_be_bytes() );
+				options.extend( data );
+			}
 
-		println_verbose!(VERBOSE2, "Sending request of {} bytes\nSEND: {}", (request.len()), send_header);
+			// padding is the last option. The rdata length, the other options and its code and
+			// length come before it, a TSIG signature is added after so a signed query is longer
+			if let Some(block) = padding {
+				let unpadded = request.len() + 2 + options.len() + 4;
+				let pad = (block - unpadded % block) % block;
+				options.extend_from_slice( &EDNS_OPTION_PADDING.to_be_bytes() );
+				options.extend_from_slice( &(pad as u16).to_be_bytes() );
+				options.resize( options.len() + pad, 0 );
+			}
 
-		if let Err(e) = socket.send( &request ) {
-			return Err(format!("send failed {}", e).to_string());
+			request.extend_from_slice( &(options.len() as u16).to_be_bytes() );
+			request.extend( options );
 		}
 
-		println_verbose!(VERBOSE2, "send complete");
+		Ok(request)
+	}
+
+	/**
+	 * the data of the Client Subnet option for the network, the family, the source prefix
+	 * length, a scope of 0 and only the address bytes the prefix covers (RFC-7871 section 6)
+	 */
+	pub fn client_subnet_option( subnet : &ipnet::IpNet ) -> Vec<u8> {
+
+		let (family, address) : (u16, Vec<u8>) = match subnet.trunc() {
+			ipnet::IpNet::V4(v4) => { (1, v4.addr().octets().to_vec()) },
+			ipnet::IpNet::V6(v6) => { (2, v6.addr().octets().to_vec()) }
+		};
+
+		let mut rval = family.to_be_bytes().to_vec();
+		rval.push( subnet.prefix_len() );
+		rval.push( 0 );
+		rval.extend_from_slice( &address[..subnet.prefix_len().div_ceil(8) as usize] );
+
+		rval
+	}
+
+	/**
+	 * the scope prefix length of the Client Subnet option in the response, the size of the
+	 * network the answer is good for. 0 means the answer is the same for every client and None
+	 * that the server didn't return the option
+	 */
+	pub fn client_subnet_scope( &self ) -> Option<u8> {
 
-		if let Err(e) = socket.set_read_timeout(Some( self.timeout.clone() )) {
-			return Err( format!("set_read_timeout failed {}", e).to_string() );
+		let opt = self.additional.iter().find(|rec| rec.record_type == zone::record::RecordType::OPT)?;
+		let value = opt.rdata.as_ref()?.as_any().downcast_ref::<zone::rr::RDATAopt>()?.option( EDNS_OPTION_CLIENT_SUBNET )?;
+
+		value.get(3).copied()
+	}
+
+	/**
+	 * the OPT record of the response, None when the server doesn't do EDNS
+	 */
+	pub fn edns( &self ) -> Option<&zone::rr::RDATAopt> {
+		self.additional.iter()
+			.find(|rec| rec.record_type == zone::record::RecordType::OPT)?
+			.rdata.as_ref()?.as_any().downcast_ref::<zone::rr::RDATAopt>()
+	}
+
+	/**
+	 * the nameserver this sender queries
+	 */
+	pub fn server( &self ) -> &std::net::IpAddr {
+		&self.server
+	}
+
+	/**
+	 * the address and port queries are sent to
+	 */
+	pub fn server_addr( &self ) -> SocketAddr {
+		SocketAddr::new( self.server, self.port )
+	}
+
+	/**
+	 * the nameserver names for zone in an NS response. A referral, with AA clear, has them in
+	 * the authority section while a server that is authoratative for the zone answers with
+	 * them in the answer section
+	 */
+	pub fn zone_nameservers( &self, zone : &str ) -> Vec<String> {
+
+		let section = if self.recv_header.aa { &self.answer } else { &self.authority };
+		let mut rval : Vec<String> = Vec::new();
+
+		for rec in section {
+
+			if rec.record_type != zone::record::RecordType::NS || !zone::record::name_eq( &rec.name.fqdn, zone ) {
+				continue;
+			}
+
+			if let Some(val) = rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()) {
+				if !rval.contains(&val.name.fqdn) {
+					rval.push( val.name.fqdn.clone() );
+				}
+			}
 		}
 
-		const BUFF_SZ: usize = 512;
-		let mut buff = [ 0u8; BUFF_SZ ];
-		let read_sz : usize;
+		rval
+	}
 
-		match socket.recv_from(&mut buff) {
-			Ok( (size, _addr) ) => {
+	/**
+	 * Follow the CNAME and DNAME records in the answer section starting at name and return the
+	 * final name. A DNAME is synthesized into the CNAME it stands for (RFC-6672) so names below
+	 * it are rewritten even when the server didn't include the synthesized CNAME.
+	 */
+	pub fn follow_aliases( &self, name : &str ) -> String {
 
-				println_verbose!(VERBOSE3, "read {} bytes from {}", size, _addr);
-				read_sz = size;
-			},
-			Err(e) => {
-				return Err(e.to_string());
-			}		
+		let mut rval = name.to_string();
+
+		// every hop has to use a different record, this stops alias loops
+		for _ in 0..self.answer.len() {
+
+			let mut next : Option<String> = None;
+
+			for rec in &self.answer {
+				let target = match rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()) {
+					Some(m) => { &m.name.fqdn },
+					None => { continue; }
+				};
+
+				if rec.record_type == zone::record::RecordType::CNAME && zone::record::name_eq(&rec.name.fqdn, &rval) {
+					next = Some(target.clone());
+					break;
+				}
+
+				if rec.record_type == zone::record::RecordType::DNAME && zone::record::name_is_under(&rval, &rec.name.fqdn) {
+					let owner = rec.name.fqdn.trim_end_matches('.');
+					let prefix = &rval.trim_end_matches('.')[..rval.trim_end_matches('.').len() - owner.len()];
+					next = Some(format!("{}{}", prefix, target));
+					break;
+				}
+			}
+
+			match next {
+				Some(n) => {
+					println_verbose!(VERBOSE2, "Following alias {} -> {}", rval, n);
+					rval = n;
+				},
+				None => { break; }
+			}
+		}
+
+		rval
+	}
+
+	/**
+	 * returns true if the answer for name was synthesized from a wildcard. This can only be
+	 * seen when the server sent DNSSEC data, the RRSIG labels field (RFC-4034 3.1.3) is lower
+	 * than the number of labels in the owner name when the wildcard was expanded.
+	 */
+	pub fn wildcard_synthesized( &self, name : &str ) -> bool {
+
+		let owner_labels = zone::record::label_count(name);
+
+		self.answer.iter().any(|rec| {
+			if rec.record_type != zone::record::RecordType::RRSIG || !zone::record::name_eq(&rec.name.fqdn, name) {
+				return false;
+			}
+			match rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATArrsig>()) {
+				Some(m) => { m.labels < owner_labels },
+				None => { false }
+			}
+		})
+	}
+
+	/**
+	 * blocking version of query_async for callers that aren't async, like the checks, see
+	 * block_on
+	 */
+	pub fn query( &mut self, host : &str, query_type : QueryType ) -> Result<(),String>{
+		block_on( self.query_async( host, query_type ) )
+	}
+
+	/**
+	 * send the query and wait for the response without holding up a thread. NXDOMAIN
+	 * responses are cached, see NegativeCache, except for a client_subnet whose answer can
+	 * depend on the network
+	 */
+	pub async fn query_async( &mut self, host : &str, query_type : QueryType ) -> Result<(),String>{
+
+		let negative_key = NegativeKey::new( &self.server, self.port, host, query_type, self.qclass, self.dnssec_ok, self.recursive );
+		let cached = match self.client_subnet {
+			Some(_) => { None },
+			None => { NEGATIVE_CACHE.lock().unwrap().get( &negative_key, std::time::Instant::now() ) }
+		};
+		if let Some(response) = cached {
+			println_verbose!(VERBOSE2, "{} {} from {} is NXDOMAIN in the negative cache", host, query_type, self.server);
+			self.timed_out = false;
+			self.clear_sections();
+			self.read_response( &response )?;
+			return Ok(());
+		}
+
+		let flags = QueryFlags {
+			recursive: self.recursive,
+			dnssec_ok: self.dnssec_ok,
+			padding: self.padding,
+			client_subnet: self.client_subnet,
+			edns_udp_size: self.edns_udp_size
+		};
+		let response = self.exchange( vec![ Question::new( host, query_type, self.qclass ) ], flags, true ).await?;
+
+		if self.recv_header.rcode == RCODE::NXDOMAIN && self.client_subnet.is_none() {
+			if let Some(ttl) = NegativeCache::negative_ttl( &self.authority ) {
+				NEGATIVE_CACHE.lock().unwrap().insert( negative_key, ttl, &response, std::time::Instant::now() );
+			}
+		}
+
+		Ok(())
+	}
+
+	/**
+	 * blocking version of query_raw_async
+	 */
+	pub fn query_raw( &mut self, questions : Vec<Question>, flags : QueryFlags ) -> Result<Vec<u8>,String>{
+		block_on( self.query_raw_async( questions, flags ) )
+	}
+
+	/**
+	 * send a request with any question section, like more than one question or a class
+	 * other than the sender's, for testing how a server behaves. The response is returned
+	 * as it was read off the wire and the sections are filled in from it, nothing checks
+	 * it is for the questions that were asked
+	 */
+	pub async fn query_raw_async( &mut self, questions : Vec<Question>, flags : QueryFlags ) -> Result<Vec<u8>,String>{
+		self.exchange( questions, flags, false ).await
+	}
+
+	/**
+	 * send the questions, trying again up to retries times when there is no answer or, with
+	 * check_question, the answer is for another question than the first one asked
+	 */
+	async fn exchange( &mut self, questions : Vec<Question>, flags : QueryFlags, check_question : bool ) -> Result<Vec<u8>,String>{
+
+		let query_type = questions.first().map_or(QueryType::T_INVALID, |q| q.qtype);
+
+		if !family_available(&self.server) {
+			println_verbose!(VERBOSE2, "Skipping {} for rec {}, address family unavailable", self.server, query_type);
+			return Err(format!("address family of {} is unavailable", self.server));
+		}
+
+		self.timed_out = false;
+		let permit = match IN_FLIGHT.acquire().await {
+			Ok(m) => { m },
+			Err(e) => { return Err(e.to_string()); }
+		};
+		let start = std::time::Instant::now();
+		let mut rval = self.send_query( &questions, flags, check_question ).await;
+		let mut attempt : u32 = 0;
+		while rval.is_err() && attempt < self.retries {
+			attempt += 1;
+			println_verbose!(VERBOSE1, "Query {} to {} failed, retry {} of {}", query_type, self.server, attempt, self.retries);
+			self.timed_out = false;
+			rval = self.send_query( &questions, flags, check_question ).await;
 		}
+		drop(permit);
+		crate::stats::record( query_type, rval.is_err(), self.timed_out );
+		if rval.is_ok() {
+			crate::stats::record_response( &self.server, start.elapsed() );
+		}
+
+		rval
+	}
+
+	/**
+	 * send the request and read the response, exchange counts the outcome in the run stats. A
+	 * response for another question is an error with check_question, like one with another id
+	 */
+	async fn send_query( &mut self, questions : &[Question], flags : QueryFlags, check_question : bool ) -> Result<Vec<u8>,String>{
+
+		self.clear_sections();
+
+		let sockaddr = self.server_addr();
+
+		for q in questions {
+			println_verbose!(VERBOSE2, "Querying {} for rec {} at '{:?}'", q.host, q.qtype, sockaddr);
+		}
+
+		let mut request = self.next_request( questions, flags )?;
+		let id = u16::from_be_bytes([ request[0], request[1] ]);
+		self.request_id = id;
+
+		let request_mac = match &self.tsig {
+			Some(key) => { Some( key.sign(&mut request, crate::tsig::now())? ) },
+			None => { None }
+		};
+
+
+		println_verbose!(VERBOSE2, "Sending request of {} bytes id {}", (request.len()), id);
+
+		let mut buff = if self.use_tcp {
+			self.exchange_tcp( &request, sockaddr ).await?
+		} else {
+			self.exchange_udp( &request, sockaddr, flags.edns_udp_size.unwrap_or(EDNS_UDP_SIZE) ).await?
+		};
+		self.check_id( &buff )?;
+
+		// the TC bit, the server had more to say than fits in a datagram so the same
+		// request is sent again over TCP
+		if !self.use_tcp && buff.len() >= HEADER_SZ && (buff[2] & 0b00000010) != 0 {
+			println_verbose!(VERBOSE1, "Response from {} is truncated, asking again over TCP", self.server);
+			buff = self.exchange_tcp( &request, sockaddr ).await?;
+			self.check_id( &buff )?;
+		}
+
+		let read_sz = buff.len();
 
 		let mut x : usize = 0;
 		let mut y : usize = 0;
@@ -954,42 +1816,260 @@ impl Sender {
 		}
 		println_verbose!(VERBOSE2);
 
+		if let (Some(key), Some(mac)) = (&self.tsig, &request_mac) {
+			key.verify(mac, &buff[..read_sz], crate::tsig::now())?;
+		}
+
+		self.read_response( &buff )?;
+
+		if let (true, Some(q)) = (check_question, questions.first()) {
+			self.check_question( &q.host, q.qtype )?;
+		}
+
+		Ok(buff)
+	}
+
+	/**
+	 * send the request in a datagram and read the response from the same socket
+	 */
+	async fn exchange_udp( &mut self, request : &[u8], sockaddr : SocketAddr, udp_size : u16 ) -> Result<Vec<u8>,String>{
+
+		let socket = match tokio::net::UdpSocket::bind( bind_addr(&self.server) ).await {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("bind failed {}", e).to_string()); }
+		};
+
+		if let Err(e) =  socket.connect(sockaddr).await {
+			return Err(format!("connect failed {}", e).to_string()); 
+		}
+
+		if let Err(e) = socket.send( request ).await {
+			return Err(format!("send failed {}", e).to_string());
+		}
+
+		println_verbose!(VERBOSE2, "send complete");
+
+		// only asked for when capturing, it is the address the kernel picked for the route
+		let local_addr = if crate::pcap::enabled() { socket.local_addr().ok() } else { None };
+		if let Some(local) = local_addr {
+			crate::pcap::record( local, sockaddr, request );
+		}
+
+		// big enough for the UDP payload size we advertise
+		const BUFF_SZ: usize = 4096;
+		let mut buff = vec![ 0u8; BUFF_SZ.max( udp_size as usize ) ];
+
+		match tokio::time::timeout( self.timeout, socket.recv_from(&mut buff) ).await {
+			Ok( Ok( (size, _addr) ) ) => {
+
+				println_verbose!(VERBOSE3, "read {} bytes from {}", size, _addr);
+				buff.truncate(size);
+				if let Some(local) = local_addr {
+					crate::pcap::record( _addr, local, &buff );
+				}
+			},
+			Ok( Err(e) ) => {
+				return Err(e.to_string());
+			},
+			Err(_) => {
+				self.timed_out = true;
+				return Err(format!("no response from {} after {}ms", self.server, self.timeout.as_millis()));
+			}
+		}
+
+		Ok(buff)
+	}
+
+	/**
+	 * send the request over a TCP connection with the two byte length in front of it and read
+	 * the response the same way (RFC-1035 section 4.2.2). The timeout covers connecting, sending
+	 * and reading. The request and response are captured with --pcap as one TCP segment each
+	 */
+	async fn exchange_tcp( &mut self, request : &[u8], sockaddr : SocketAddr ) -> Result<Vec<u8>,String>{
+
+		let exchange = async {
+
+			let stream = match tokio::net::TcpStream::connect(sockaddr).await {
+				Ok(m) => { m },
+				Err(e) => { return Err(format!("tcp connect failed {}", e)); }
+			};
+
+			let mut message = Vec::<u8>::with_capacity( request.len() + 2 );
+			message.extend_from_slice( &(request.len() as u16).to_be_bytes() );
+			message.extend_from_slice( request );
+			if let Err(e) = tcp_write_all( &stream, &message ).await {
+				return Err(format!("tcp send failed {}", e));
+			}
+
+			let mut len = [ 0u8; 2 ];
+			if let Err(e) = tcp_read_exact( &stream, &mut len ).await {
+				return Err(format!("tcp read failed {}", e));
+			}
+
+			let mut buff = vec![ 0u8; u16::from_be_bytes(len) as usize ];
+			if let Err(e) = tcp_read_exact( &stream, &mut buff ).await {
+				return Err(format!("tcp read failed {}", e));
+			}
+
+			if crate::pcap::enabled() {
+				if let Ok(local) = stream.local_addr() {
+					let mut response = len.to_vec();
+					response.extend_from_slice( &buff );
+					crate::pcap::record_tcp( local, sockaddr, 1, 1, &message );
+					crate::pcap::record_tcp( sockaddr, local, 1, 1 + message.len() as u32, &response );
+				}
+			}
+
+			println_verbose!(VERBOSE3, "read {} bytes over tcp from {}", buff.len(), sockaddr);
+			Ok(buff)
+		};
+
+		match tokio::time::timeout( self.timeout, exchange ).await {
+			Ok(m) => { m },
+			Err(_) => {
+				self.timed_out = true;
+				Err(format!("no response over tcp from {} after {}ms", self.server, self.timeout.as_millis()))
+			}
+		}
+	}
+
+	/**
+	 * the sections only describe the last response, otherwise aliases from an earlier
+	 * question would be followed for this one
+	 */
+	fn clear_sections( &mut self ) {
+		self.recv_questions.clear();
+		self.answer.clear();
+		self.authority.clear();
+		self.additional.clear();
+		self.class_mismatch.clear();
+	}
+
+	/**
+	 * make sure the response has the id the request was sent with, a stray or spoofed
+	 * datagram that doesn't is not read
+	 */
+	fn check_id( &self, response : &[u8] ) -> Result<(), String> {
+
+		if response.len() < 2 {
+			return Err(format!("FORMERR response of {} bytes from {} has no id", response.len(), self.server));
+		}
+
+		let id = u16::from_be_bytes([ response[0], response[1] ]);
+		if id != self.request_id {
+			println_verbose!(VERBOSE1, "Response from {} has id {} but the request was sent with {}", self.server, id, self.request_id);
+			return Err(format!("id mismatch from {}, sent {} got {}", self.server, self.request_id, id));
+		}
+
+		Ok(())
+	}
+
+	/**
+	 * make sure the response is for the question that was sent, a misrouted or spoofed
+	 * datagram can carry a different one. The name is compared case-insensitively
+	 */
+	fn check_question( &self, host : &str, query_type : QueryType ) -> Result<(), String> {
+
+		let q = match self.recv_questions.first() {
+			Some(m) => { m },
+			None => { return Err(format!("response from {} has no question", self.server)); }
+		};
+
+		if !zone::record::name_eq(&q.host, host) || q.qtype != query_type || q.qclass != self.qclass {
+			println_verbose!(VERBOSE1, "Response from {} is for {} but asked {} {}", self.server, q, host, query_type);
+			return Err(format!("question mismatch from {}, asked {} {} got {}", self.server, host, query_type, q));
+		}
+
+		Ok(())
+	}
+
+	/**
+	 * decode a response read off the wire into the header, questions and the record sections
+	 */
+	pub fn read_response( &mut self, buff : &[u8] ) -> Result<(), String> {
+
 		let mut offset : usize = 0;
 
-		self.recv_header.read(&buff, &mut offset);
+		if buff.len() < HEADER_SZ {
+			return Err(format!("FORMERR response of {} bytes is shorter than the header", buff.len()));
+		}
+
+		self.recv_header.read(buff, &mut offset)?;
 
-		println_verbose!(VERBOSE3, "READ {} bytes", read_sz);
+		println_verbose!(VERBOSE3, "READ {} bytes", buff.len());
 		println_verbose!(VERBOSE2, "READ: {}", (self.recv_header) );
 
+		// a question is at least a root name, type and class
+		if (self.recv_header.qdcount as usize) * MIN_QUESTION_SZ > buff.len() - offset {
+			return Err(format!("FORMERR response claims {} questions but only has {} bytes left", self.recv_header.qdcount, buff.len() - offset));
+		}
+
 		// read the question section
 		let mut x = 0;
 		while x < self.recv_header.qdcount {
 			let mut q: Question = Default::default();
-			q.read(&buff, &mut offset);
+			q.read(buff, &mut offset)?;
 			println_verbose!(VERBOSE2, "READ QUESTION: {}", q);
 			self.recv_questions.push( q );
 			x = x + 1;
 		}
 
-		Self::read_record(&buff, &mut offset, &mut self.answer, self.recv_header.ancount);
-		Self::read_record(&buff, &mut offset, &mut self.authority, self.recv_header.nscount);
-		Self::read_record(&buff, &mut offset, &mut self.additional, self.recv_header.arcount);
+		Self::read_record(buff, &mut offset, &mut self.answer, self.recv_header.ancount, self.qclass, &mut self.class_mismatch)?;
+		Self::read_record(buff, &mut offset, &mut self.authority, self.recv_header.nscount, self.qclass, &mut self.class_mismatch)?;
+		Self::read_record(buff, &mut offset, &mut self.additional, self.recv_header.arcount, self.qclass, &mut self.class_mismatch)?;
 			
 		Ok(())
 	}
 
-	fn read_record( buff : &[u8], offset : &mut usize, list : & mut Vec<zone::record::ZoneRecord>, rec_count : u16 ) {
+	/**
+	 * read rec_count records into list, records whose class doesn't match the query class are put
+	 * into mismatch instead. OPT and TSIG records reuse the class field so they are not checked.
+	 * The count comes from the server, so it is checked against what is left in the buffer before
+	 * anything is read, a response claiming more records than it could hold is a format error.
+	 * A record whose RDATA can't be read is skipped, one that runs past the end of the message
+	 * is a format error.
+	 */
+	fn read_record( buff : &[u8], offset : &mut usize, list : & mut Vec<zone::record::ZoneRecord>, rec_count : u16, qclass : NSClass, mismatch : &mut Vec<zone::record::ZoneRecord> ) -> Result<(), String> {
+
+		let remaining = buff.len().saturating_sub(*offset);
+		if (rec_count as usize) * MIN_RECORD_SZ > remaining {
+			return Err(format!("FORMERR response claims {} records but only has {} bytes left", rec_count, remaining));
+		}
 
-		println_verbose!(VERBOSE2, "Reading {} records, cur pos {:b} ", rec_count, buff[*offset]);
+		println_verbose!(VERBOSE2, "Reading {} records, cur pos {} ", rec_count, offset);
 
 		let mut x :u16 = 0;
 		while x < rec_count {
 
-			if let Ok(rec) = zone::record::ZoneRecord::create_from_wire( buff, offset ) {
-				list.push(rec);
+			let start = *offset;
+			match zone::record::ZoneRecord::create_from_wire( buff, offset ) {
+				Ok(rec) => {
+					if qclass == NSClass::C_ANY
+						|| rec.class == qclass
+						|| rec.record_type == zone::record::RecordType::OPT
+						|| rec.record_type == zone::record::RecordType::TSIG
+					{
+						list.push(rec);
+					} else {
+						println_verbose!(VERBOSE1, "Record class {} does not match query class {}: {}", rec.class.as_u16(), qclass.as_u16(), rec);
+						mismatch.push(rec);
+					}
+				},
+				Err(e) if *offset == start => {
+					return Err(format!("FORMERR record {} of {} can't be read, {}", x + 1, rec_count, e));
+				},
+				Err(e) => {
+					println_verbose!(VERBOSE1, "Skipping record {} of {}, {}", x + 1, rec_count, e);
+				}
 			}
 			x = x + 1;
+
+			if *offset >= buff.len() && x < rec_count {
+				return Err(format!("FORMERR response ended after {} of {} records", x, rec_count));
+			}
 		}
+
+		Ok(())
 	}
 
 }
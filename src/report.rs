@@ -0,0 +1,357 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * A summary of a run for library users and the command line summary, so the counts don't
+ * have to be worked out again from the results by everything that reports on them
+ */
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::config;
+use crate::monitor::{ErrorCode, Finding, MonitorResult};
+use crate::stats::{self, ServerStats};
+
+/** how many of the slowest servers are kept in the report */
+pub const SLOWEST_SERVERS : usize = 5;
+
+/**
+ * a finding with the domain it was found on
+ */
+#[derive(Serialize, Clone)]
+pub struct DomainFinding {
+	pub domain_name : String,
+	pub finding : Finding
+}
+
+/**
+ * a server and how long it took to answer
+ */
+#[derive(Serialize, Clone)]
+pub struct ServerTime {
+	pub server : std::net::IpAddr,
+	#[serde(flatten)]
+	pub stats : ServerStats
+}
+
+#[derive(Serialize)]
+pub struct AuditReport {
+	/** see config::SCHEMA_VERSION */
+	pub schema_version : u32,
+	pub tested : usize,
+	pub passed : usize,
+	pub failed : usize,
+	/** every finding of the run grouped by its code */
+	pub findings : BTreeMap<ErrorCode, Vec<DomainFinding>>,
+	/** the servers with the highest average response time, slowest first */
+	pub slowest_servers : Vec<ServerTime>,
+	/** how long the run took */
+	pub duration : std::time::Duration
+}
+
+impl Default for AuditReport {
+	fn default() -> Self {
+		Self {
+			schema_version: config::SCHEMA_VERSION,
+			tested: 0,
+			passed: 0,
+			failed: 0,
+			findings: BTreeMap::new(),
+			slowest_servers: Vec::new(),
+			duration: std::time::Duration::ZERO
+		}
+	}
+}
+
+impl AuditReport {
+
+	/**
+	 * the report for the results of a run that took duration, the slowest servers are
+	 * taken from the run stats
+	 */
+	pub fn new( results : &[MonitorResult], duration : std::time::Duration ) -> Self {
+
+		let mut rval = Self::default();
+
+		for result in results {
+			rval.add( result );
+		}
+
+		rval.finish( duration );
+
+		rval
+	}
+
+	/**
+	 * count a result, this is for when the results are not kept like --stream
+	 */
+	pub fn add( &mut self, result : &MonitorResult ) {
+
+		self.tested += 1;
+
+		if result.success {
+			self.passed += 1;
+		} else {
+			self.failed += 1;
+		}
+
+		for finding in &result.findings {
+			self.findings.entry(finding.code).or_default().push( DomainFinding {
+				domain_name: result.domain_name.clone(),
+				finding: finding.clone()
+			});
+		}
+	}
+
+	/**
+	 * set the duration and the slowest servers from the run stats once every result is added
+	 */
+	pub fn finish( &mut self, duration : std::time::Duration ) {
+		self.duration = duration;
+		self.set_servers( stats::server_snapshot() );
+	}
+
+	/**
+	 * keep the SLOWEST_SERVERS servers with the highest average response time
+	 */
+	pub fn set_servers( &mut self, mut servers : Vec<(std::net::IpAddr, ServerStats)> ) {
+
+		servers.sort_by(|a, b| b.1.average().cmp(&a.1.average()).then(a.0.cmp(&b.0)));
+		servers.truncate( SLOWEST_SERVERS );
+
+		self.slowest_servers = servers.into_iter().map(|(server, stats)| ServerTime { server, stats }).collect();
+	}
+
+	/**
+	 * write the count of each finding code and the slowest servers for the summary, the
+	 * lines are indented under the tested and failed line
+	 */
+	pub fn write_summary( &self, out : &mut dyn std::io::Write, color : bool ) -> std::io::Result<()> {
+
+		for (code, findings) in &self.findings {
+			writeln!(out, "\t{}", config::colorize(&format!("{:?}: {} findings", code, findings.len()), config::COLOR_RED, color))?;
+		}
+
+		for server in &self.slowest_servers {
+			writeln!(out, "\tserver {}: {} responses, {}ms average, {}ms slowest", server.server, server.stats.responses, server.stats.average().as_millis(), server.stats.slowest.as_millis())?;
+		}
+
+		Ok(())
+	}
+}
+
+/**
+ * how the results are written, see --format
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+	/** an array of the results, or a line of JSON for each with --stream */
+	Json,
+	/** a header then one row for each result, see csv_row */
+	Csv,
+	/** the Display of each result then a text_summary line */
+	Text,
+}
+
+impl OutputFormat {
+
+	pub fn from_string( s : &str ) -> Result<Self, String> {
+		match s.to_lowercase().as_str() {
+			"json" => { Ok(Self::Json) }
+			"csv" => { Ok(Self::Csv) }
+			"text" => { Ok(Self::Text) }
+			_ => { Err(format!("{} is not one of json, csv or text", s)) }
+		}
+	}
+}
+
+pub const CSV_HEADER : &str = "domain_name,success,flags,reasons,nameservers,ips";
+
+/**
+ * quote a CSV field when it has a comma, quote or line break in it, quotes are doubled
+ */
+fn csv_field( text : &str ) -> String {
+	if text.contains([ ',', '"', '\n', '\r' ]) {
+		format!("\"{}\"", text.replace('"', "\"\""))
+	} else {
+		text.to_string()
+	}
+}
+
+/**
+ * a CSV row for the result in the CSV_HEADER columns. The finding codes, nameservers and ips
+ * are joined with ; and the finding messages with "; ", a column that wasn't looked up is empty
+ */
+pub fn csv_row( result : &MonitorResult ) -> String {
+
+	let flags : Vec<String> = result.findings.iter().map(|f| format!("{:?}", f.code)).collect();
+	let reasons : Vec<&str> = result.findings.iter().map(|f| f.message.as_str()).collect();
+	let nameservers = result.nameservers.as_ref().map(|m| m.join(";")).unwrap_or_default();
+	let ips = result.ips.as_ref().map(|m| m.iter().map(|ip| ip.to_string()).collect::<Vec<String>>().join(";")).unwrap_or_default();
+
+	[
+		csv_field( &result.domain_name ),
+		result.success.to_string(),
+		csv_field( &flags.join(";") ),
+		csv_field( &reasons.join("; ") ),
+		csv_field( &nameservers ),
+		csv_field( &ips )
+	].join(",")
+}
+
+/**
+ * the line that ends the text output, with color the failed count is green when there are
+ * none and red otherwise
+ */
+pub fn text_summary( passed : usize, failed : usize, color : bool ) -> String {
+	let failed_text = format!("{} failed", failed);
+	format!("{} passed, {}", passed, config::colorize(&failed_text, if failed == 0 { config::COLOR_GREEN } else { config::COLOR_RED }, color))
+}
+
+/**
+ * the Display of the result with OK in green or FAIL in red when color is enabled
+ */
+pub fn text_result( result : &MonitorResult, color : bool ) -> String {
+
+	let text = result.to_string();
+	if !color {
+		return text;
+	}
+
+	// the status ends the first line, the findings under it are left alone
+	let (status, code) = if result.success { ("OK", config::COLOR_GREEN) } else { ("FAIL", config::COLOR_RED) };
+	let end = text.find('\n').unwrap_or(text.len());
+	let start = end - status.len();
+
+	format!("{}{}{}", &text[..start], config::colorize(status, code, true), &text[end..])
+}
+
+/**
+ * writes the results of a pass in the output format. With stream each result is written as it
+ * is added and dropped, otherwise the results are kept until finish writes them together
+ */
+pub struct ResultWriter {
+	pub format : OutputFormat,
+	pub stream : bool,
+	/** write the results that passed too, not only the failures */
+	pub all : bool,
+	/** color the status of text results, see config::COLOR */
+	pub color : bool,
+	/** the results finish will write, always empty with stream */
+	pub results : Vec<Arc<Mutex<MonitorResult>>>
+}
+
+impl ResultWriter {
+
+	pub fn new( format : OutputFormat, stream : bool, all : bool ) -> Self {
+		Self {
+			format,
+			stream,
+			all,
+			color: false,
+			results: Vec::new()
+		}
+	}
+
+	/**
+	 * write the CSV header streamed rows go under, once before the first pass so every pass
+	 * of --watch shares it
+	 */
+	pub fn start( &self, out : &mut dyn std::io::Write ) -> std::io::Result<()> {
+		if self.stream && self.format == OutputFormat::Csv {
+			writeln!(out, "{}", CSV_HEADER)?;
+		}
+		Ok(())
+	}
+
+	/**
+	 * write the result now when streaming or keep it for finish, a result that passed is
+	 * skipped unless all is set
+	 */
+	pub fn add( &mut self, out : &mut dyn std::io::Write, result : &Arc<Mutex<MonitorResult>> ) -> std::io::Result<()> {
+
+		let res_lock = result.lock().unwrap();
+
+		if !self.all && res_lock.success {
+			return Ok(());
+		}
+
+		if !self.stream {
+			drop(res_lock);
+			self.results.push( Arc::clone(result) );
+			return Ok(());
+		}
+
+		match self.format {
+			OutputFormat::Json => { writeln!(out, "{}", serde_json::to_string( &*res_lock ).unwrap())?; },
+			OutputFormat::Csv => { writeln!(out, "{}", csv_row( &res_lock ))?; },
+			OutputFormat::Text => { write!(out, "{}", text_result( &res_lock, self.color ))?; }
+		}
+
+		out.flush()
+	}
+
+	/**
+	 * write the results kept since the last pass and let them go, nothing is kept with stream.
+	 * The text output then ends with the text_summary of the report
+	 */
+	pub fn finish( &mut self, out : &mut dyn std::io::Write, report : &AuditReport ) -> std::io::Result<()> {
+
+		let results = std::mem::take( &mut self.results );
+
+		if !self.stream {
+			self.write_kept( out, &results )?;
+		}
+
+		// the counts are of every domain tested, not only the ones written
+		if self.format == OutputFormat::Text {
+			writeln!(out, "{}", text_summary( report.passed, report.failed, self.color ))?;
+		}
+
+		out.flush()
+	}
+
+	/**
+	 * write the results kept for finish in the output format
+	 */
+	fn write_kept( &self, out : &mut dyn std::io::Write, results : &[Arc<Mutex<MonitorResult>>] ) -> std::io::Result<()> {
+
+		match self.format {
+			OutputFormat::Json => {
+				out.write_all( serde_json::to_string( &results ).unwrap().as_bytes() )?;
+			},
+			OutputFormat::Csv => {
+				writeln!(out, "{}", CSV_HEADER)?;
+				for res in results {
+					writeln!(out, "{}", csv_row( &res.lock().unwrap() ))?;
+				}
+			},
+			OutputFormat::Text => {
+				for res in results {
+					write!(out, "{}", text_result( &res.lock().unwrap(), self.color ))?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,490 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Resolves names to addresses with our own queries instead of the system resolver, so the
+ * timeout and address family settings apply and the answer comes from the real hierarchy.
+ * Names are resolved iteratively starting at the closest zone in the root, or by asking the
+ * --ns-resolver when one is configured.
+ */
+
+use std::net::IpAddr;
+
+use crate::config::println_verbose;
+use crate::{query, zone};
+
+/**
+ * returns the nameservers of the closest zone we already know for a name, normally
+ * Root::closest_servers
+ */
+pub type StartServers<'a> = dyn Fn(&str) -> Vec<IpAddr> + 'a;
+
+/**
+ * asks the server for the records of a type for a name and returns the response, given the
+ * time left for the lookup. Normally ask_server
+ */
+pub type Ask<'a> = dyn Fn(&IpAddr, &str, query::QueryType, std::time::Duration) -> Result<query::Sender, String> + 'a;
+
+/** the most CNAME and DNAME aliases that are followed for one lookup */
+pub const MAX_ALIAS_CHAIN : usize = 8;
+
+/**
+ * the addresses a server answered with for a name
+ */
+pub struct AddressAnswer {
+	pub addresses : Vec<std::net::IpAddr>,
+	/** true when any answer in the alias chain was expanded from a wildcard */
+	pub synthesized_from_wildcard : bool
+}
+
+/**
+ * query the server for the A or AAAA records of the name, following CNAME and DNAME
+ * aliases. When the answer stops at an alias the same server is asked for the target.
+ * Returns None if the server did not answer
+ */
+pub fn query_addresses( domain_name : &str, query : &mut query::Sender, qtype : query::QueryType ) -> Option<AddressAnswer> {
+
+	let mut name = domain_name.to_string();
+	let mut answered = false;
+	let mut rval = AddressAnswer {
+		addresses: Vec::new(),
+		synthesized_from_wildcard: false
+	};
+
+	for _ in 0..MAX_ALIAS_CHAIN {
+
+		if query.query( &name, qtype ).is_err() {
+			break;
+		}
+		answered = true;
+
+		let target = query.follow_aliases(&name);
+
+		if query.wildcard_synthesized(&name) || query.wildcard_synthesized(&target) {
+			rval.synthesized_from_wildcard = true;
+		}
+
+		for res in &query.answer {
+			if !zone::record::name_eq(&res.name.fqdn, &target) {
+				continue;
+			}
+			let addr = match res.record_type {
+				zone::record::RecordType::A => {
+					res.rdata.as_ref().unwrap().as_any().downcast_ref::<zone::rr::RDATAa>().map(|a| std::net::IpAddr::from(a.ip))
+				},
+				zone::record::RecordType::AAAA => {
+					res.rdata.as_ref().unwrap().as_any().downcast_ref::<zone::rr::RDATAaaaa>().map(|a| std::net::IpAddr::from(a.ip))
+				},
+				_ => { None }
+			};
+			if let Some(addr) = addr {
+				if !rval.addresses.contains(&addr) {
+					rval.addresses.push(addr);
+				}
+			}
+		}
+
+		if !rval.addresses.is_empty() || zone::record::name_eq(&target, &name) {
+			break;
+		}
+
+		name = target;
+	}
+
+	if answered {
+		Some(rval)
+	} else {
+		None
+	}
+}
+
+/** the most referrals followed for one name before giving up */
+pub const MAX_REFERRALS : usize = 16;
+
+/**
+ * the A and AAAA records owned by name in the section
+ */
+fn addresses_for( name : &str, records : &[zone::record::ZoneRecord] ) -> Vec<IpAddr> {
+
+	let mut rval = Vec::new();
+
+	for rec in records {
+		if !zone::record::name_eq(&rec.name.fqdn, name) {
+			continue;
+		}
+		if let Some(addr) = zone::record::ZoneRecord::record_to_address(rec) {
+			if !rval.contains(&addr) {
+				rval.push(addr);
+			}
+		}
+	}
+
+	rval
+}
+
+/**
+ * the targets of the NS records in the section
+ */
+fn nameserver_names( records : &[zone::record::ZoneRecord] ) -> Vec<String> {
+	records.iter()
+		.filter(|rec| rec.record_type == zone::record::RecordType::NS)
+		.filter_map(|rec| rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()))
+		.map(|ns| ns.name.fqdn.clone())
+		.collect()
+}
+
+/**
+ * the servers, the time limit and how queries are sent for one lookup. The deadline is
+ * shared by every query the lookup makes, including the ones for glueless nameservers
+ * and aliases, so one name can't hold up the audit for longer than the timeout
+ */
+struct Lookup<'a> {
+	start_servers : &'a StartServers<'a>,
+	ask : &'a Ask<'a>,
+	timeout : std::time::Duration,
+	deadline : std::time::Instant
+}
+
+impl<'a> Lookup<'a> {
+
+	fn new( start_servers : &'a StartServers<'a>, ask : &'a Ask<'a>, timeout : std::time::Duration ) -> Self {
+		Self {
+			start_servers,
+			ask,
+			timeout,
+			deadline: std::time::Instant::now() + timeout
+		}
+	}
+
+	/**
+	 * the time left for the lookup, an error once it is used up
+	 */
+	fn remaining( &self, host : &str ) -> Result<std::time::Duration, String> {
+		match self.deadline.checked_duration_since( std::time::Instant::now() ) {
+			Some(m) if !m.is_zero() => { Ok(m) },
+			_ => { Err(format!("gave up resolving {} after {}ms", host, self.timeout.as_millis())) }
+		}
+	}
+
+	/**
+	 * ask the server, an error without sending anything once the time for the lookup is used up
+	 */
+	fn query( &self, server : &IpAddr, host : &str, qtype : query::QueryType ) -> Result<query::Sender, String> {
+		let remaining = self.remaining( host )?;
+		(self.ask)( server, host, qtype, remaining )
+	}
+}
+
+/**
+ * the default Ask, a query to the server that waits no longer than the time left
+ */
+fn ask_server( server : &IpAddr, host : &str, qtype : query::QueryType, remaining : std::time::Duration ) -> Result<query::Sender, String> {
+
+	let mut sender = query::Sender::new( server );
+	sender.timeout = sender.timeout.min( remaining );
+	sender.query( host, qtype )?;
+
+	Ok(sender)
+}
+
+/**
+ * resolve the A and AAAA records for the host. start_servers returns the nameservers of the
+ * closest zone we already know for a name, normally Root::closest_servers. The lookup gives up
+ * after the --lookup-timeout and at most --max-ns-addresses addresses are returned
+ */
+pub fn lookup_host( host : &str, start_servers : &dyn Fn(&str) -> Vec<IpAddr> ) -> Result<Vec<IpAddr>, String> {
+
+	let timeout = *crate::config::LOOKUP_TIMEOUT.read().unwrap();
+	let max_addresses = *crate::config::MAX_NS_ADDRESSES.read().unwrap();
+	let ns_resolver = *crate::config::NS_RESOLVER.read().unwrap();
+
+	if let Some(resolver) = ns_resolver {
+
+		let deadline = std::time::Instant::now() + timeout;
+
+		let mut sender = query::Sender::new( &resolver );
+		sender.recursive = true;
+		sender.timeout = sender.timeout.min( timeout );
+
+		let mut answered = false;
+		let mut rval : Vec<IpAddr> = Vec::new();
+
+		for qtype in [ query::QueryType::T_A, query::QueryType::T_AAAA ] {
+			if std::time::Instant::now() >= deadline {
+				break;
+			}
+			if let Some(found) = query_addresses(host, &mut sender, qtype) {
+				answered = true;
+				rval.extend(found.addresses);
+			}
+		}
+
+		if !answered {
+			return Err(format!("resolver {} did not answer for {}", resolver, host));
+		}
+
+		rval.truncate( max_addresses );
+		return Ok(rval);
+	}
+
+	lookup_host_asking( host, start_servers, &ask_server )
+}
+
+/**
+ * resolve the A and AAAA records for the host iteratively with ask sending the queries, within
+ * the --lookup-timeout and --max-ns-addresses like lookup_host. The --ns-resolver isn't used
+ */
+pub fn lookup_host_asking( host : &str, start_servers : &StartServers, ask : &Ask ) -> Result<Vec<IpAddr>, String> {
+
+	let timeout = *crate::config::LOOKUP_TIMEOUT.read().unwrap();
+	let max_addresses = *crate::config::MAX_NS_ADDRESSES.read().unwrap();
+
+	lookup_host_with( host, start_servers, timeout, max_addresses, ask )
+}
+
+/**
+ * resolve the A and AAAA records for the host iteratively with ask sending the queries,
+ * lookup_host with the query and the limits supplied
+ */
+pub fn lookup_host_with( host : &str, start_servers : &StartServers, timeout : std::time::Duration, max_addresses : usize, ask : &Ask ) -> Result<Vec<IpAddr>, String> {
+
+	let lookup = Lookup::new( start_servers, ask, timeout );
+
+	let mut rval = addresses_iterative( &lookup, host, 0 )?;
+	if rval.len() > max_addresses {
+		println_verbose!(VERBOSE1, "{} has {} addresses, only the first {} are used", host, rval.len(), max_addresses);
+		rval.truncate( max_addresses );
+	}
+
+	Ok(rval)
+}
+
+/**
+ * resolve the A records for the host iteratively then ask the server that answered for the AAAA
+ */
+fn addresses_iterative( lookup : &Lookup, host : &str, depth : usize ) -> Result<Vec<IpAddr>, String> {
+
+	let (sender, target) = lookup_iterative( lookup, host, query::QueryType::T_A, depth )?;
+	if sender.recv_header.rcode == query::RCODE::NXDOMAIN {
+		return Err(format!("{} does not exist", target));
+	}
+
+	let mut rval = addresses_for(&target, &sender.answer);
+
+	if let Ok(sender) = lookup.query( sender.server(), &target, query::QueryType::T_AAAA ) {
+		for addr in addresses_for(&target, &sender.answer) {
+			if !rval.contains(&addr) {
+				rval.push(addr);
+			}
+		}
+	}
+
+	Ok(rval)
+}
+
+/**
+ * the name the PTR record for the address is found under, in-addr.arpa for IPv4 and a
+ * nibble per label under ip6.arpa for IPv6
+ */
+pub fn reverse_name( ip : &IpAddr ) -> String {
+	match ip {
+		IpAddr::V4(v4) => {
+			let o = v4.octets();
+			format!("{}.{}.{}.{}.in-addr.arpa.", o[3], o[2], o[1], o[0])
+		},
+		IpAddr::V6(v6) => {
+			let mut rval = String::new();
+			for byte in v6.octets().iter().rev() {
+				rval.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+			}
+			rval.push_str("ip6.arpa.");
+			rval
+		}
+	}
+}
+
+/**
+ * resolve the PTR records for the address, the names are returned as they are in the
+ * records. The --ns-resolver is asked when one is configured
+ */
+pub fn lookup_ptr( ip : &IpAddr, start_servers : &dyn Fn(&str) -> Vec<IpAddr> ) -> Result<Vec<String>, String> {
+
+	let name = reverse_name(ip);
+	let (sender, target) = resolve( &name, query::QueryType::T_PTR, start_servers )?;
+	if sender.recv_header.rcode == query::RCODE::NXDOMAIN {
+		return Err(format!("{} does not exist", target));
+	}
+
+	Ok(sender.answer.iter()
+		.filter(|rec| rec.record_type == zone::record::RecordType::PTR && zone::record::name_eq(&rec.name.fqdn, &target))
+		.filter_map(|rec| rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()))
+		.map(|ptr| ptr.name.fqdn.clone())
+		.collect())
+}
+
+/**
+ * the name the host is an alias of, None when it isn't a CNAME or under a DNAME.
+ * The --ns-resolver is asked when one is configured
+ */
+pub fn alias_target( host : &str, start_servers : &dyn Fn(&str) -> Vec<IpAddr> ) -> Result<Option<String>, String> {
+
+	let (_, target) = resolve( host, query::QueryType::T_A, start_servers )?;
+
+	if zone::record::name_eq(&target, host) {
+		Ok(None)
+	} else {
+		Ok(Some(target))
+	}
+}
+
+/**
+ * true unless the authoratative answer for the host is NXDOMAIN, a name that exists without
+ * any records of the type asked for is NODATA and still exists. An alias to a name that
+ * doesn't exist is NXDOMAIN (RFC-6604)
+ */
+pub fn name_exists( host : &str, start_servers : &dyn Fn(&str) -> Vec<IpAddr> ) -> Result<bool, String> {
+
+	let (sender, _) = resolve( host, query::QueryType::T_A, start_servers )?;
+
+	Ok(sender.recv_header.rcode != query::RCODE::NXDOMAIN)
+}
+
+/**
+ * name_exists looked up iteratively with ask sending the queries, within the timeout. The
+ * --ns-resolver isn't used
+ */
+pub fn name_exists_with( host : &str, start_servers : &StartServers, timeout : std::time::Duration, ask : &Ask ) -> Result<bool, String> {
+
+	let (sender, _) = lookup_iterative( &Lookup::new( start_servers, ask, timeout ), host, query::QueryType::T_A, 0 )?;
+
+	Ok(sender.recv_header.rcode != query::RCODE::NXDOMAIN)
+}
+
+/**
+ * ask the --ns-resolver for the host or look it up iteratively when there isn't one. Returns
+ * the response with the name the aliases led to like lookup_iterative
+ */
+fn resolve( host : &str, qtype : query::QueryType, start_servers : &dyn Fn(&str) -> Vec<IpAddr> ) -> Result<(query::Sender, String), String> {
+
+	let timeout = *crate::config::LOOKUP_TIMEOUT.read().unwrap();
+	let ns_resolver = *crate::config::NS_RESOLVER.read().unwrap();
+
+	match ns_resolver {
+		Some(resolver) => {
+			let mut sender = query::Sender::new( &resolver );
+			sender.recursive = true;
+			sender.timeout = sender.timeout.min( timeout );
+			sender.query( host, qtype )?;
+			let target = sender.follow_aliases(host);
+			Ok((sender, target))
+		},
+		None => { lookup_iterative( &Lookup::new( start_servers, &ask_server, timeout ), host, qtype, 0 ) }
+	}
+}
+
+/**
+ * follow the referrals down from the closest known zone until a server answers for the host.
+ * The server's response is returned with the name the aliases led to, the records of the
+ * qtype for it are in the answer. An NXDOMAIN response is returned too with the aliases in
+ * it followed, callers check the rcode. depth counts the lookups started for aliases and
+ * glueless nameservers so they can't loop
+ */
+fn lookup_iterative( lookup : &Lookup, host : &str, qtype : query::QueryType, depth : usize ) -> Result<(query::Sender, String), String> {
+
+	if depth > MAX_ALIAS_CHAIN {
+		return Err(format!("too many aliases or glueless nameservers resolving {}", host));
+	}
+
+	let mut servers = (lookup.start_servers)(host);
+
+	for _ in 0..MAX_REFERRALS {
+
+		if servers.is_empty() {
+			return Err(format!("no nameservers to ask for {}", host));
+		}
+
+		let mut next : Option<Vec<IpAddr>> = None;
+
+		for server in &servers {
+
+			let sender = match lookup.query( server, host, qtype ) {
+				Ok(m) => { m },
+				Err(e) => {
+					println_verbose!(VERBOSE2, "Lookup of {} at {} failed: {}", host, server, e);
+					continue;
+				}
+			};
+
+			if sender.recv_header.rcode == query::RCODE::NXDOMAIN {
+				// the rcode is for the end of the chain, the host itself can be an alias
+				let target = sender.follow_aliases(host);
+				return Ok((sender, target));
+			}
+
+			let ns_names = nameserver_names(&sender.authority);
+
+			if sender.recv_header.aa || !sender.answer.is_empty() || ns_names.is_empty() {
+
+				let target = sender.follow_aliases(host);
+				let rtype = zone::record::RecordType::from_u16(&qtype.as_u16());
+				let answered = sender.answer.iter().any(|rec| rec.record_type == rtype && zone::record::name_eq(&rec.name.fqdn, &target));
+
+				if !answered && !zone::record::name_eq(&target, host) {
+					// the alias points outside of this server's data, start over for the target
+					return lookup_iterative( lookup, &target, qtype, depth + 1 );
+				}
+
+				return Ok((sender, target));
+			}
+
+			// a referral, use the glue or resolve the nameservers when there isn't any
+			let mut glue : Vec<IpAddr> = Vec::new();
+			for ns in &ns_names {
+				glue.extend( addresses_for(ns, &sender.additional) );
+			}
+
+			if glue.is_empty() {
+				for ns in &ns_names {
+					if let Ok(addresses) = addresses_iterative( lookup, ns, depth + 1 ) {
+						glue.extend(addresses);
+					}
+					if !glue.is_empty() {
+						break;
+					}
+				}
+			}
+
+			println_verbose!(VERBOSE2, "Lookup of {} referred by {} to {:?}", host, server, ns_names);
+			next = Some(glue);
+			break;
+		}
+
+		match next {
+			Some(m) => { servers = m; },
+			None => {
+				lookup.remaining( host )?;
+				return Err(format!("no nameserver answered for {}", host));
+			}
+		}
+	}
+
+	Err(format!("too many referrals resolving {}", host))
+}
@@ -30,7 +30,7 @@ use super::zone;
 use super::query;
 
 use std::sync::{Arc};
-use std::sync::RwLock;
+use std::sync::{PoisonError, RwLock};
 
 #[derive(Serialize, Deserialize)]
 pub struct NameServer {
@@ -67,14 +67,35 @@ impl Clone for NameServer {
 #[derive(Serialize, Deserialize)]
 pub struct NameServersForZone	 {
 	pub zone_name : String,
-	pub servers : Vec< Arc<RwLock<NameServer>> >
+	pub servers : Vec< Arc<RwLock<NameServer>> >,
+	/**
+	 * how long the delegation is good for, the TTL of the NS records it was resolved from. The
+	 * zones of the root zone file and a loaded cache have none and don't expire
+	 */
+	#[serde(skip)]
+	pub ttl : Option<std::time::Duration>,
+	/** when the delegation was resolved */
+	#[serde(skip)]
+	pub resolved_at : Option<std::time::Instant>
 }
 
 impl NameServersForZone {
 	pub fn new( zone_name : &String ) -> Self {
 		Self {
 			zone_name : zone_name.clone(),
-			servers: Vec::new()
+			servers: Vec::new(),
+			ttl: None,
+			resolved_at: None
+		}
+	}
+
+	/**
+	 * the delegation's TTL has run out by now and it has to be resolved again
+	 */
+	pub fn expired( &self, now : std::time::Instant ) -> bool {
+		match (self.ttl, self.resolved_at) {
+			(Some(ttl), Some(resolved_at)) => { now.duration_since(resolved_at) >= ttl },
+			_ => { false }
 		}
 	}
 
@@ -101,73 +122,274 @@ impl Clone for NameServersForZone {
 	fn clone(&self) -> Self {
 		Self { 
 			zone_name: self.zone_name.clone(), 
-			servers: self.servers.clone() 
+			servers: self.servers.clone(),
+			ttl: self.ttl,
+			resolved_at: self.resolved_at
 		}
 	}
 }
 
 
+/**
+ * what the nameservers of a parent zone said about a name below it
+ */
+enum ZoneCut {
+	/** the name is a zone delegated to these nameservers */
+	Delegated(Arc<RwLock<NameServersForZone>>),
+	/** the name is part of the parent zone */
+	InParent,
+	/** the name does not exist, with the negative TTL of the SOA in the response if it had one */
+	Nxdomain(Option<std::time::Duration>),
+	/** none of the nameservers answered */
+	Missing
+}
+
+
+/**
+ * times a query to the nameserver for the zone, None when it doesn't answer
+ */
+pub type Measure = fn(&std::net::IpAddr, &str) -> Option<std::time::Duration>;
+
+/**
+ * resolves the addresses of a nameserver that came without glue, starting from the servers
+ * closest to its name. resolver::lookup_host is the one normally used
+ */
+pub type ResolveHost<'a> = dyn Fn(&str, &crate::resolver::StartServers) -> Result<Vec<std::net::IpAddr>, String> + 'a;
+
+/** how many times each nameserver is timed by the performance test when --probes isn't given */
+pub const DEFAULT_PROBE_COUNT : usize = 5;
+
+/** the first bytes of a binary cache file, the schema_version follows as a big endian u32 */
+pub const BINARY_CACHE_MAGIC : &[u8; 8] = b"DNSAUDIT";
+
+/** the IANA root hints, used when no --root-zone is given */
+pub const ROOT_HINTS : &str = include_str!("named.root");
+
+/** the --root-zone source that loads ROOT_HINTS */
+pub const BUILTIN_ROOT_HINTS : &str = "builtin";
+
+/** a cache file name with this extension is written and read in the binary format */
+pub const BINARY_CACHE_EXTENSION : &str = "bin";
+
+/** how long a zone the parent said doesn't exist is remembered when the NXDOMAIN has no SOA */
+pub const DEFAULT_NEGATIVE_TTL : std::time::Duration = std::time::Duration::from_secs(300);
+
 pub struct Root {
-	pub root_addr : std::collections::hash_map::HashMap< String, std::sync::Arc<RwLock<NameServersForZone>> >
+	pub root_addr : std::collections::hash_map::HashMap< String, std::sync::Arc<RwLock<NameServersForZone>> >,
+	/**
+	 * the zones the parent answered NXDOMAIN for and when that runs out, they are not asked
+	 * for again until then (RFC-2308). Not kept in the cache file
+	 */
+	pub missing : std::collections::hash_map::HashMap< String, std::time::Instant >,
+	/** how long a missing zone is remembered when the NXDOMAIN has no SOA for its negative TTL */
+	pub negative_ttl : std::time::Duration
 
 }
 
+impl Default for Root {
+	fn default() -> Self {
+		Self {
+			root_addr: std::collections::hash_map::HashMap::new(),
+			missing: std::collections::hash_map::HashMap::new(),
+			negative_ttl: DEFAULT_NEGATIVE_TTL
+		}
+	}
+}
+
 impl Root {
 
 	pub fn create( file_name : &String, origin : &String )  -> Result< Self, String > {
-		let mut rval = Root {			
-			root_addr: std::collections::hash_map::HashMap::new()
-		};
+		let mut rval = Root::default();
 
 		rval.from_file(file_name, origin)?;
 
 		Ok(rval)
 	}
 
+	/**
+	 * load the root from the first of the sources that can be loaded, a source is a zone or
+	 * root hints file, the address of a root server that is asked for the NS records of the
+	 * origin with a priming query (RFC-8109) or builtin for ROOT_HINTS, which is used when
+	 * there are no sources. Each source that fails is printed before the next is tried, when
+	 * none can be loaded the error has all of them
+	 */
+	pub fn create_from_sources( sources : &[String], origin : &String ) -> Result< Self, String > {
+
+		if sources.is_empty() {
+			return Self::create_from_sources( &[ BUILTIN_ROOT_HINTS.to_string() ], origin );
+		}
+
+		let mut errors : Vec<String> = Vec::new();
+
+		for source in sources {
+
+			let mut rval = Root::default();
+
+			let loaded = match source.parse::<std::net::IpAddr>() {
+				Ok(server) => { rval.from_server(&server, origin) },
+				Err(_) if source == BUILTIN_ROOT_HINTS => { rval.from_builtin_hints() },
+				Err(_) => { rval.from_file(source, origin) }
+			};
+
+			match loaded {
+				Ok(()) => {
+					if !errors.is_empty() {
+						eprintln!("using {} for the root", source);
+					}
+					return Ok(rval);
+				},
+				Err(e) => {
+					eprintln!("failed to load the root from {}, {}", source, e);
+					errors.push(e);
+				}
+			}
+		}
+
+		Err(format!("none of the root sources could be loaded, {}", errors.join("; ")))
+	}
+
 	pub fn from_file<'a>( &'a mut self, file_name : &String, origin : &String ) -> Result< (), String > {
 
-		let mut zone = match zone::Zone::create(&file_name, &origin)  {
+		let zone = match zone::Zone::create_with(&file_name, &origin, *crate::config::SKIP_BAD_RECORDS.read().unwrap())  {
 			Ok(m) => { m },
 			Err(e) => { return Err(e) }
 		};
 
-		let mut nameservers : std::collections::hash_map::HashMap::<String, Vec< std::sync::Arc<RwLock<zone::record::ZoneRecord >>>> = std::collections::hash_map::HashMap::new();
-		let mut addresses : std::collections::hash_map::HashMap::<String, Vec< std::sync::Arc<RwLock<zone::record::ZoneRecord >>>> = std::collections::hash_map::HashMap::new();
+		if !zone.skipped.is_empty() {
+			eprintln!("skipped {} records of {} that could not be parsed, lines {}", zone.skipped.len(), file_name, zone.skipped_lines());
+			for skipped in &zone.skipped {
+				println_verbose!(VERBOSE1, "{} line {}: {}", file_name, skipped.line, skipped.error);
+			}
+		}
+
+		Self::check_apex( &zone, file_name, origin )?;
+
+		self.add_zone( &zone );
+
+		Ok(())
+	}
+
+	/**
+	 * load the root servers from the root hints built in, queries start at the root servers
+	 * so the delegations they answer with are current even when the hints are old
+	 */
+	pub fn from_builtin_hints( &mut self ) -> Result< (), String > {
+
+		let lines = zone::tokenizer::ZoneLines::from_reader( &mut ROOT_HINTS.as_bytes() )?;
+		let (zone, _warnings) = zone::Zone::from_lines( lines, &".".to_string(), false )?;
+
+		Self::check_apex( &zone, BUILTIN_ROOT_HINTS, "." )?;
+
+		self.add_zone( &zone );
+
+		Ok(())
+	}
+
+	/**
+	 * ask the server for the NS records of the origin and use the answer with the addresses
+	 * in the additional section as root hints
+	 */
+	pub fn from_server( &mut self, server : &std::net::IpAddr, origin : &str ) -> Result< (), String > {
+
+		let mut sender = query::Sender::new( server );
+		sender.query( origin, query::QueryType::T_NS )?;
+
+		let mut zone : zone::Zone = Default::default();
+		// names read off the wire have no trailing dot, the zone file names that are compared have it
+		let fqdn = |name : &str| -> String { format!("{}.", name.trim_end_matches('.')) };
+		for rec in sender.answer.iter().chain( sender.additional.iter() ) {
+			let mut rec = rec.clone();
+			rec.name.fqdn = fqdn( &rec.name.fqdn );
+			if let Some(ns_rr) = rec.rdata.as_mut().and_then(|r| r.as_any_mut().downcast_mut::<zone::rr::RDATANameRR>()) {
+				ns_rr.name.fqdn = fqdn( &ns_rr.name.fqdn );
+			}
+			zone.records.push( Box::new( rec ) );
+		}
+
+		Self::check_apex( &zone, &server.to_string(), origin )?;
+
+		self.add_zone( &zone );
+
+		Ok(())
+	}
+
+	/**
+	 * add the nameservers with addresses in the zone
+	 */
+	fn add_zone( &mut self, zone : &zone::Zone ) {
 
+		// index the records by reference, the only copies made are the NameServer entries
+		let mut nameservers : std::collections::hash_map::HashMap::<&str, Vec<&zone::rr::RDATANameRR>> = std::collections::hash_map::HashMap::new();
+		let mut addresses : std::collections::hash_map::HashMap::<&str, Vec<&zone::record::ZoneRecord>> = std::collections::hash_map::HashMap::new();
 
-		for record in &mut zone.records {
+		for record in &zone.records {
 
-			let zone_record = record.as_any().downcast_mut::<zone::record::ZoneRecord>();
-			match zone_record {
-				Some(rec) => {
+			if let Some(rec) = record.as_any_ref().downcast_ref::<zone::record::ZoneRecord>() {
 
-					if rec.record_type == zone::record::RecordType::NS {
-						nameservers.entry(rec.name.fqdn.clone()).or_insert_with( || Vec::new() ).push( Arc::new(RwLock::new(rec.clone())) );
-					} else if rec.record_type == zone::record::RecordType::A || rec.record_type == zone::record::RecordType::AAAA {
-						addresses.entry(rec.name.fqdn.clone()).or_insert_with(|| Vec::new()).push( Arc::new(RwLock::new(rec.clone())) );			
+				if rec.record_type == zone::record::RecordType::NS {
+					if let Some(ns_rr) = rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()) {
+						nameservers.entry(rec.name.fqdn.as_str()).or_default().push( ns_rr );
 					}
-				},
-				None => {}
+				} else if rec.record_type == zone::record::RecordType::A || rec.record_type == zone::record::RecordType::AAAA {
+					addresses.entry(rec.name.fqdn.as_str()).or_default().push( rec );
+				}
 			}
 		}
 
-		for (zone_name, zone_record) in &mut nameservers {
+		for (zone_name, servers) in &nameservers {
 
-			for server in zone_record.iter_mut() {
-				let l = server.read().unwrap();
-				if let Some(rdata) = &l.rdata {
-					if let Some(ns_rr) = rdata.as_any().downcast_ref::<zone::rr::RDATANameRR>() {
-						if let Some(i) = addresses.get_mut( &ns_rr.name.fqdn.clone() ) {
-							let e = self.root_addr.entry(zone_name.clone()).or_insert_with(|| std::sync::Arc::new(RwLock::new( NameServersForZone::new(&zone_name))));
-							for zr in i {
-								let zr_lock = zr.read().unwrap();
-								e.write().unwrap().servers.push( Arc::new(RwLock::new(NameServer::new(&*zr_lock))));
-							}
-						}
+			for ns_rr in servers {
+				if let Some(i) = addresses.get( ns_rr.name.fqdn.as_str() ) {
+					let e = self.root_addr.entry(zone_name.to_string()).or_insert_with(|| std::sync::Arc::new(RwLock::new( NameServersForZone::new(&zone_name.to_string()))));
+					let mut e_write = e.write().unwrap();
+					for zr in i {
+						e_write.servers.push( Arc::new(RwLock::new(NameServer::new(zr))));
 					}
 				}
 			}
 		}
+	}
+
+	/**
+	 * make sure the zone is the root, or the apex it's loaded for, and not some other zone
+	 * file given by mistake. An SOA has to be for the apex and the apex has to have NS records
+	 * with addresses. A root hints file has no SOA, only the NS records and their addresses
+	 */
+	fn check_apex( zone : &zone::Zone, file_name : &str, origin : &str ) -> Result<(), String> {
+
+		let mut ns_names : Vec<&str> = Vec::new();
+		let mut addresses : std::collections::HashSet<String> = std::collections::HashSet::new();
+
+		for record in &zone.records {
+
+			let rec = match record.as_any_ref().downcast_ref::<zone::record::ZoneRecord>() {
+				Some(m) => { m },
+				None => { continue; }
+			};
+
+			match rec.record_type {
+				zone::record::RecordType::SOA if !zone::record::name_eq( &rec.name.fqdn, origin ) => {
+					return Err(format!("{} is the zone file for {} not the root zone {}, give --root-zone the root zone or root hints", file_name, rec.name.fqdn, origin));
+				},
+				zone::record::RecordType::NS if zone::record::name_eq( &rec.name.fqdn, origin ) => {
+					if let Some(ns_rr) = rec.rdata.as_ref().and_then(|r| r.as_any().downcast_ref::<zone::rr::RDATANameRR>()) {
+						ns_names.push( ns_rr.name.fqdn.as_str() );
+					}
+				},
+				zone::record::RecordType::A | zone::record::RecordType::AAAA => {
+					addresses.insert( rec.name.fqdn.to_ascii_lowercase() );
+				},
+				_ => {}
+			}
+		}
+
+		if ns_names.is_empty() {
+			return Err(format!("{} has no NS records for {}, it is not a root zone or root hints file", file_name, origin));
+		}
+
+		if !ns_names.iter().any(|ns| addresses.contains(&ns.to_ascii_lowercase())) {
+			return Err(format!("{} has no addresses for the nameservers of {}, {}", file_name, origin, ns_names.join(", ")));
+		}
 
 		Ok(())
 	}
@@ -218,6 +440,35 @@ impl Root {
 		return rval;
 	}
 
+	/**
+	 * the addresses of the nameservers for the closest zone we know of that contains name,
+	 * the root servers are used when nothing closer is known. This is where resolution of
+	 * a name starts
+	 */
+	pub fn closest_servers( &self, name : &str ) -> Vec<std::net::IpAddr> {
+
+		let mut zone_name = String::new();
+		let mut closest = self.root_addr.get(".");
+
+		for zn in Self::split_name(&name.to_string()).iter().rev() {
+			let mut _zone_name = zn.clone();
+			_zone_name.push_str(zone_name.as_str());
+
+			match self.root_addr.get(&_zone_name) {
+				Some(ns) => {
+					closest = Some(ns);
+					zone_name = _zone_name;
+				},
+				None => { break; }
+			}
+		}
+
+		match closest {
+			Some(ns) => { ns.read().unwrap().servers.iter().map(|s| s.read().unwrap().ip).collect() },
+			None => { Vec::new() }
+		}
+	}
+
 	/**
 	 get a list of nameservers for the indicated domain, this will stop looking for 
 	 nameservers once it can't find a new zone, for example bob.jones.com will stop at .com 
@@ -249,114 +500,124 @@ impl Root {
 
 	}
 
-	/*
-	 This function works more or less like get_nameservers except for 2nd level extensions that are not 
-	 in the list will be added. To prevent this going nuts we are only supporting tld and second level domains like
-	 bob.it.com
+	/**
+	 * the nameservers of the zone that delegates domain_name. This works more or less like
+	 * get_nameservers except zones that are not in the root zone file, like a 2nd level
+	 * extension such as it.com or a subdomain delegated inside a customer's zone, are found
+	 * by following the referrals from the closest zone we know of. At most --max-depth names
+	 * are looked up, the zones found are added so they are only looked up again once the TTL
+	 * of their NS records runs out
+	 */
+	pub fn get_nameservers_and_resolve(root : &RwLock<Root>, domain_name : &String) -> Result<std::sync::Arc<RwLock<NameServersForZone>>, String> {
+
+		let max_depth = *crate::config::MAX_DEPTH.read().unwrap();
+
+		Self::resolve_delegation_shared( root, domain_name, max_depth, &|ip, name| {
+			let mut sender = query::Sender::new( ip );
+			sender.query( name, query::QueryType::T_NS )?;
+			Ok(sender)
+		}, &|name, start_servers| crate::resolver::lookup_host( name, start_servers ))
+	}
+
+	/**
+	 * walk from the root down to the parent zone of domain_name, ask sends the NS query for
+	 * each name that is not a known zone to one of its parent's nameservers. The domain's own
+	 * nameservers are not looked up, the caller asks the parent for them
+	 */
+	pub fn resolve_delegation(&mut self, domain_name : &String, max_depth : usize, ask : &dyn Fn(&std::net::IpAddr, &str) -> Result<query::Sender, String>) -> Result<std::sync::Arc<RwLock<NameServersForZone>>, String> {
+		self.resolve_delegation_with( domain_name, max_depth, ask, &|name, start_servers| crate::resolver::lookup_host( name, start_servers ) )
+	}
+
+	/**
+	 * resolve_delegation with resolve_host looking up the addresses of the nameservers that
+	 * were referred to without glue
 	 */
-	pub fn get_nameservers_and_resolve(&mut self, domain_name : &String) -> Result<std::sync::Arc<RwLock<NameServersForZone>>, String> {
+	pub fn resolve_delegation_with(&mut self, domain_name : &String, max_depth : usize, ask : &dyn Fn(&std::net::IpAddr, &str) -> Result<query::Sender, String>, resolve_host : &ResolveHost) -> Result<std::sync::Arc<RwLock<NameServersForZone>>, String> {
+
+		let shared = RwLock::new( std::mem::take(self) );
+		let rval = Self::resolve_delegation_shared( &shared, domain_name, max_depth, ask, resolve_host );
+		*self = shared.into_inner().unwrap_or_else(PoisonError::into_inner);
+		rval
+	}
+
+	/**
+	 * resolve_delegation_with on a root shared by the domains tested at the same time, the
+	 * lock is only held to look a zone up and to add the ones found, never while a query
+	 * waits, so the other domains aren't held up behind the walk
+	 */
+	pub fn resolve_delegation_shared(root : &RwLock<Root>, domain_name : &String, max_depth : usize, ask : &dyn Fn(&std::net::IpAddr, &str) -> Result<query::Sender, String>, resolve_host : &ResolveHost) -> Result<std::sync::Arc<RwLock<NameServersForZone>>, String> {
+
+		// a panic in another domain's checks can leave the root poisoned until it is cleared
+		let read = || root.read().unwrap_or_else(PoisonError::into_inner);
+		let write = || root.write().unwrap_or_else(PoisonError::into_inner);
 
-		let mut last_ns : Option< std::sync::Arc<RwLock< NameServersForZone> > > = None;
+		let (mut last_ns, negative_ttl) = {
+			let root = read();
+			(root.root_addr.get(".").cloned(), root.negative_ttl)
+		};
 		let mut zone_name : String = String::new();
-		let mut zone_ctr = 0;
+		let mut depth = 0;
 
 		let spl = Self::split_name(domain_name);
 
-		for zn in spl.iter().rev() {
+		for zn in spl.iter().skip(1).rev() {
 			let mut _zone_name = zn.clone();
-			_zone_name.push_str(zone_name.as_str());			
+			_zone_name.push_str(zone_name.as_str());
+			zone_name = _zone_name;
 
-			if let Some(ns) = self.root_addr.get(&_zone_name) {
-				last_ns = Some(ns.clone());
-				zone_name = _zone_name.clone();
-				zone_ctr = zone_ctr + 1;
-			} else { 
-				
-				if zone_ctr == 1 && spl.len() > 2 { 
-
-					// this block is for resolving additional "root" nameservers, these are usuually either fake
-					// root nameservers for a fake extension like it.com, or 2nd level domains in country codes
-					// that are real but are on different servers than their normal root.
-					println_verbose!(VERBOSE2, "Did not find '{}', attempting to resolve", _zone_name);
-
-					if let Some(last_ns_s) = &last_ns {
-
-						for rec in &last_ns_s.read().unwrap().servers {
-							let ip = rec.read().unwrap().ip.clone();
-							let mut sender = query::Sender::new( &ip );
-							if let Err(e) = sender.query(&_zone_name, query::QueryType::T_NS) {
-								println_verbose!(VERBOSE2, "Error querying '{}': {}", _zone_name, e);
-								continue;
-							}
-
-							let mut needs_ip : Vec<String> = Vec::new();
-
-							let zone_ns = std::sync::Arc::new(RwLock::new(NameServersForZone {
-								zone_name: _zone_name.clone(),
-								servers: Vec::new()
-							}));
-							let mut zone_ns_w = zone_ns.write().unwrap();
-
-							println_verbose!(VERBOSE3, "'{}' '{}'", _zone_name, sender.recv_header);
-
-							if sender.recv_header.rcode == query::RCODE::NOERROR {
-
-								// we got an address, yay.
-								for rec in &sender.authority {
-									if rec.record_type == zone::record::RecordType::NS {
-
-										let mut found : bool = false;
-
-										if let Some(rdata) = &rec.rdata {
-											if let Some(val) = rdata.as_any().downcast_ref::<zone::rr::RDATANameRR>() {
-
-												for addrrec in &sender.additional {
-													if (addrrec.record_type == zone::record::RecordType::A || addrrec.record_type == zone::record::RecordType::AAAA) && addrrec.name.fqdn.eq_ignore_ascii_case( &val.name.fqdn ) {
-														found = true;
-														println_verbose!(VERBOSE2, "Adding '{}' for '{}'", addrrec, _zone_name);
-														zone_ns_w.servers.push(Arc::new(RwLock::new(NameServer::new(addrrec))));
-													}
-												}
-											}
-										}
-
-										if !found {
-											needs_ip.push( rec.name.fqdn.clone());
-										}
-
-									}
-								}
-
-								// go through the servers that we need ip addresses for 
-								// that were not in the glue and resolve them the old fashioned way.
-								for name in needs_ip {
-									if let Ok( addresses ) = dns_lookup::lookup_host( &name ) {
-										for addr in addresses {
-											zone_ns_w.servers.push( Arc::new(RwLock::new(NameServer {
-												server_name : name.clone(),
-												ip: addr, 
-												speed: None
-											})));
-										}
-									}
-								}
-
-								self.root_addr.insert(_zone_name.clone(), Arc::clone(&zone_ns));
-
-								println_verbose!(VERBOSE1, "Resolved {} ips for {}", zone_ns_w.servers.len(), _zone_name);
-
-								return Ok( Arc::clone(&zone_ns) );
-
-							}
-							
-						}
-
-					} else {
-						println_verbose!(VERBOSE1, "No parent nameserver for {}", _zone_name);
-					}
+			let known = read().root_addr.get(&zone_name).cloned();
+			if let Some(ns) = known {
+				if !ns.read().unwrap().expired( std::time::Instant::now() ) {
+					last_ns = Some(ns);
+					continue;
+				}
+
+				// the delegation may have changed since, like in a --watch that runs for days
+				println_verbose!(VERBOSE2, "The delegation of '{}' expired, resolving it again", zone_name);
+				write().root_addr.remove(&zone_name);
+			}
+
+			let missing = read().missing.get(&zone_name).copied();
+			match missing {
+				Some(expires) if expires > std::time::Instant::now() => {
+					println_verbose!(VERBOSE2, "'{}' does not exist, from the negative cache", zone_name);
+					break;
+				},
+				Some(_) => { write().missing.remove(&zone_name); },
+				None => {}
+			}
+
+			let parent = match &last_ns {
+				Some(m) => { Arc::clone(m) },
+				None => {
+					println_verbose!(VERBOSE1, "No parent nameserver for {}", zone_name);
+					break;
+				}
+			};
+
+			if depth >= max_depth {
+				println_verbose!(VERBOSE1, "Stopped looking for the parent zone of {} at {}, --max-depth is {}", domain_name, zone_name, max_depth);
+				break;
+			}
+			depth += 1;
 
+			println_verbose!(VERBOSE2, "Did not find '{}', attempting to resolve", zone_name);
+
+			match Self::lookup_zone( &zone_name, &parent, ask, resolve_host, &|n| read().closest_servers(n) ) {
+				ZoneCut::Delegated(zone_ns) => {
+					println_verbose!(VERBOSE1, "Resolved {} ips for {}", zone_ns.read().unwrap().servers.len(), zone_name);
+					write().root_addr.insert(zone_name.clone(), Arc::clone(&zone_ns));
+					last_ns = Some(zone_ns);
+				},
+				ZoneCut::InParent => {
+					// not a zone of its own, a name further down can still be delegated
+					println_verbose!(VERBOSE2, "'{}' is not delegated", zone_name);
+				},
+				ZoneCut::Nxdomain(ttl) => {
+					write().missing.insert( zone_name.clone(), std::time::Instant::now() + ttl.unwrap_or(negative_ttl) );
 					break;
-				} else {
+				},
+				ZoneCut::Missing => {
 					break;
 				}
 			}
@@ -369,12 +630,106 @@ impl Root {
 		Err("nameserver not found".to_string())
 	}
 
+	/**
+	 * ask the parent zone's nameservers for the NS records of zone_name, the first one that
+	 * answers is used. Nameservers without glue are resolved with resolve_host starting from
+	 * the closest servers
+	 */
+	fn lookup_zone(zone_name : &str, parent : &Arc<RwLock<NameServersForZone>>, ask : &dyn Fn(&std::net::IpAddr, &str) -> Result<query::Sender, String>, resolve_host : &ResolveHost, closest : &crate::resolver::StartServers) -> ZoneCut {
+
+		let ips : Vec<std::net::IpAddr> = parent.read().unwrap().servers.iter().map(|s| s.read().unwrap().ip).collect();
+
+		for ip in ips {
+
+			let sender = match ask( &ip, zone_name ) {
+				Ok(m) => { m },
+				Err(e) => {
+					println_verbose!(VERBOSE2, "Error querying '{}': {}", zone_name, e);
+					continue;
+				}
+			};
+
+			println_verbose!(VERBOSE3, "'{}' '{}'", zone_name, sender.recv_header);
+
+			if sender.recv_header.rcode == query::RCODE::NXDOMAIN {
+				println_verbose!(VERBOSE1, "'{}' does not exist", zone_name);
+				return ZoneCut::Nxdomain( query::NegativeCache::negative_ttl( &sender.authority ) );
+			} else if sender.recv_header.rcode != query::RCODE::NOERROR {
+				continue;
+			}
+
+			let mut zone_ns = NameServersForZone::new( &zone_name.to_string() );
+			let mut needs_ip : Vec<String> = Vec::new();
+
+			let section = if sender.recv_header.aa { &sender.answer } else { &sender.authority };
+			zone_ns.ttl = section.iter()
+				.filter(|r| r.record_type == zone::record::RecordType::NS && zone::record::name_eq( &r.name.fqdn, zone_name ))
+				.map(|r| std::time::Duration::from_secs( r.ttl.max(0) as u64 ))
+				.min();
+			zone_ns.resolved_at = Some( std::time::Instant::now() );
+
+			let nameservers = sender.zone_nameservers( zone_name );
+			if nameservers.is_empty() {
+				return ZoneCut::InParent;
+			}
+
+			for name in nameservers {
+
+				let mut found : bool = false;
+
+				for addrrec in &sender.additional {
+					if (addrrec.record_type == zone::record::RecordType::A || addrrec.record_type == zone::record::RecordType::AAAA) && zone::record::name_eq( &addrrec.name.fqdn, &name ) {
+						found = true;
+						println_verbose!(VERBOSE2, "Adding '{}' for '{}'", addrrec, zone_name);
+						zone_ns.servers.push(Arc::new(RwLock::new(NameServer::new(addrrec))));
+					}
+				}
+
+				// glueless, the nameserver's own name has to be resolved
+				if !found {
+					needs_ip.push( name );
+				}
+			}
+
+			for name in needs_ip {
+				if let Ok( addresses ) = resolve_host( &name, closest ) {
+					for addr in addresses {
+						zone_ns.servers.push( Arc::new(RwLock::new(NameServer {
+							server_name : name.clone(),
+							ip: addr,
+							speed: None
+						})));
+					}
+				}
+			}
+
+			if zone_ns.servers.is_empty() {
+				println_verbose!(VERBOSE1, "None of the nameservers for '{}' resolved", zone_name);
+				return ZoneCut::Missing;
+			}
+
+			return ZoneCut::Delegated( Arc::new(RwLock::new(zone_ns)) );
+		}
+
+		ZoneCut::Missing
+	}
+
 	/**
 	 * this function will test the dns servers and sort them by
 	 * how fast they are, the thread_ct is how many threads that
-	 * are going to be used
+	 * are going to be used. Without refresh only the servers that
+	 * don't have a time yet are tested, like the ones a loaded
+	 * cache couldn't reach
 	 */
-	pub fn performance_test(&mut self, thread_ct : usize) {
+	pub fn performance_test(&mut self, thread_ct : usize, refresh : bool, probe_count : usize) {
+		self.performance_test_with( thread_ct, refresh, probe_count, Root::measure );
+	}
+
+	/**
+	 * performance_test with measure timing each of the probe_count queries to a server, it
+	 * returns None when the server didn't answer
+	 */
+	pub fn performance_test_with(&mut self, thread_ct : usize, refresh : bool, probe_count : usize, measure : Measure) {
 
 		let mut server_ct = 0;
 
@@ -404,7 +759,7 @@ impl Root {
 
 		while let Some(ip_list ) = ips.pop() {
 			threads.push(std::thread::spawn(move || {
-				Root::test_main( ip_list );
+				Root::test_main( ip_list, refresh, probe_count, measure );
 			}));
 		}
 
@@ -416,10 +771,45 @@ impl Root {
 
 	}
 
+	/**
+	 * the default Measure, the time of an SOA query for the zone
+	 */
+	fn measure( ip : &std::net::IpAddr, zone_str : &str ) -> Option<std::time::Duration> {
+
+		let start = std::time::Instant::now();
+
+		let mut sender = query::Sender::new( ip );
+		match sender.query( zone_str, query::QueryType::T_SOA) {
+			Ok(()) => { Some( start.elapsed() ) },
+			Err(_) => { None }
+		}
+	}
+
+	/**
+	 * the average time of the probes that got an answer, None when none of them did. The
+	 * ones that failed are left out instead of counting as no time at all
+	 */
+	pub fn average( probes : &[Option<std::time::Duration>] ) -> Option<std::time::Duration> {
+
+		let mut durations = std::time::Duration::new(0,0);
+		let mut answered : u32 = 0;
+
+		for probe in probes.iter().flatten() {
+			durations = durations.add( *probe );
+			answered += 1;
+		}
+
+		if answered > 0 {
+			Some( durations / answered )
+		} else {
+			None
+		}
+	}
+
 	/**
 	 * thread main for testing the inputted list of nameservers
 	 */
-	fn test_main( ip_list : std::sync::Arc<RwLock<Vec<std::sync::Arc<RwLock<NameServersForZone>>>>> ) {
+	fn test_main( ip_list : std::sync::Arc<RwLock<Vec<std::sync::Arc<RwLock<NameServersForZone>>>>>, refresh : bool, probe_count : usize, measure : Measure ) {
 
 		for zone_ns in ip_list.write().unwrap().iter() {
 
@@ -431,62 +821,232 @@ impl Root {
 
 			for server in itr {
 
-				let mut durations = std::time::Duration::new(0,0);
+				if !refresh && server.read().unwrap().speed.is_some() {
+					continue;
+				}
 
-				let mut is_ok = false;
+				let ip = server.read().unwrap().ip;
+				let probes : Vec<Option<std::time::Duration>> = (0..probe_count).map(|_| measure( &ip, &zone_str )).collect();
+				let speed = Self::average( &probes );
 
-				for _ in 0..5 {
+				let mut server_locked = server.write().unwrap();
+				server_locked.speed = speed;
 
-					let start = std::time::SystemTime::now();
+				println_verbose!(VERBOSE1, "Server {} Time {:?}", server_locked.server_name, server_locked.speed);
 
-					let mut sender = query::Sender::new( & server.read().unwrap().ip.clone() );
-					match sender.query(& zone_str, query::QueryType::T_SOA) {
-						Ok(()) => { 
-							is_ok = true;
-							durations = durations.add( start.elapsed( ).unwrap() );
-						},
-						Err(_) => {
-							
-						}
-					}
+			}
 
+			root_ns.sort();
 
-				}
+		}
 
-				let mut server_locked = server.write().unwrap();
+	}
+
+	/**
+	 * true when the cache file is in the binary format, by its extension
+	 */
+	pub fn is_binary_cache( file_name : &str ) -> bool {
+		std::path::Path::new(file_name).extension().is_some_and(|e| e.eq_ignore_ascii_case(BINARY_CACHE_EXTENSION))
+	}
+
+	/**
+	 * replace the root_addr from the cache file, binary or json by its extension
+	 */
+	pub fn read_cache( &mut self, file_name : &str ) -> Result<(), String> {
 
-				if is_ok { 
-					server_locked.speed = Some( durations.div_f32( 5f32 ) );
-				} else {
-					server_locked.speed = None;
+		if Self::is_binary_cache(file_name) {
+			return match std::fs::read(file_name) {
+				Ok(m) => { self.cache_from_binary(&m) },
+				Err(e) => { Err(format!("failed to read cache file {} {}", file_name, e)) }
+			};
+		}
+
+		match std::fs::read_to_string(file_name) {
+			Ok(m) => { self.cache_from_js(&m) },
+			Err(e) => { Err(format!("failed to read cache file {} {}", file_name, e)) }
+		}
+	}
+
+	/**
+	 * take the times of the servers in the cache file, binary or json by its extension, see
+	 * merge_from
+	 */
+	pub fn merge_cache_file( &mut self, file_name : &str ) -> Result<(), String> {
+
+		let mut cached = Root::default();
+		cached.read_cache( file_name )?;
+		self.merge_from( &cached );
+
+		Ok(())
+	}
+
+	/**
+	 * take the times of the servers in the serialized json cache, see merge_from
+	 */
+	pub fn merge_cache( &mut self, serialized : &String ) -> Result<(), String> {
+
+		let mut cached = Root::default();
+		cached.cache_from_js( serialized )?;
+		self.merge_from( &cached );
+
+		Ok(())
+	}
+
+	/**
+	 * unlike reading a cache, which replaces the zones, only the times are taken from the
+	 * cached zones. A server in root_addr that has no time gets the one the cache has for it
+	 * in the same zone, the zones and servers the cache doesn't have are left to be tested
+	 */
+	pub fn merge_from( &mut self, cached : &Root ) {
+
+		let mut merged = 0;
+
+		for (zone_name, zone_ns) in &self.root_addr {
+
+			let cached_ns = match cached.root_addr.get(zone_name) {
+				Some(m) => { m.read().unwrap() },
+				None => { continue; }
+			};
+
+			let mut zone_ns = zone_ns.write().unwrap();
+
+			for server in &zone_ns.servers {
+
+				let mut server = server.write().unwrap();
+				if server.speed.is_some() {
+					continue;
 				}
 
-				println_verbose!(VERBOSE1, "Server {} Time {:?}", server_locked.server_name, server_locked.speed);
+				let speed = cached_ns.servers.iter()
+					.map(|s| s.read().unwrap())
+					.find(|s| s.ip == server.ip)
+					.and_then(|s| s.speed);
 
+				if speed.is_some() {
+					server.speed = speed;
+					merged += 1;
+				}
 			}
 
-			root_ns.sort();
+			zone_ns.sort();
+		}
+
+		println_verbose!(VERBOSE1, "Took the times of {} servers from the cache", merged);
+	}
 
+	/**
+	 * write the cache file, binary or json by its extension
+	 */
+	pub fn write_cache( &self, file_name : &str ) -> Result<(), String> {
+
+		let buff = if Self::is_binary_cache(file_name) {
+			self.to_binary()?
+		} else {
+			self.to_json().into_bytes()
+		};
+
+		match std::fs::write(file_name, buff) {
+			Ok(_) => { Ok(()) },
+			Err(e) => { Err(format!("failed to write cache file {} {}", file_name, e)) }
+		}
+	}
+
+	/**
+	 * the cache in the binary format, BINARY_CACHE_MAGIC and the schema_version then the zones
+	 * in bincode. It's smaller and faster to load than json for a large cache
+	 */
+	#[cfg(feature = "binary-cache")]
+	pub fn to_binary( &self ) -> Result<Vec<u8>, String> {
+
+		let mut rval = BINARY_CACHE_MAGIC.to_vec();
+		rval.extend_from_slice( &crate::config::SCHEMA_VERSION.to_be_bytes() );
+
+		match bincode::serialize_into( &mut rval, self ) {
+			Ok(_) => { Ok(rval) },
+			Err(e) => { Err(format!("failed to write binary cache {}", e)) }
+		}
+	}
+
+	#[cfg(not(feature = "binary-cache"))]
+	pub fn to_binary( &self ) -> Result<Vec<u8>, String> {
+		Err(NO_BINARY_CACHE.to_string())
+	}
+
+	/**
+	 * replace the root_addr from a cache in the binary format
+	 */
+	#[cfg(feature = "binary-cache")]
+	pub fn cache_from_binary( &mut self, buff : &[u8] ) -> Result<(), String> {
+
+		let header = BINARY_CACHE_MAGIC.len() + 4;
+		if buff.len() < header || &buff[..BINARY_CACHE_MAGIC.len()] != BINARY_CACHE_MAGIC {
+			return Err("not a binary cache file".to_string());
 		}
 
+		let version = u32::from_be_bytes( buff[BINARY_CACHE_MAGIC.len()..header].try_into().unwrap() );
+		crate::config::check_schema_version( version, "cache file" )?;
+
+		self.root_addr = match bincode::deserialize::<Root>( &buff[header..] ) {
+			Ok(m) => { m.root_addr },
+			Err(e) => { return Err(format!("failed to parse binary cache {}", e)); }
+		};
+
+		Ok(())
+	}
+
+	#[cfg(not(feature = "binary-cache"))]
+	pub fn cache_from_binary( &mut self, _buff : &[u8] ) -> Result<(), String> {
+		Err(NO_BINARY_CACHE.to_string())
 	}
 
 	/**
-	 * write this to a json and return it
+	 * write this to a json cache file and return it, the zones are under the schema_version
 	 */
 	pub fn to_json( &self ) -> String {
-		serde_json::to_string( &self ).unwrap()
+		serde_json::to_string( &CacheFile {
+			schema_version: crate::config::SCHEMA_VERSION,
+			zones: self
+		}).unwrap()
 	}
 
 	/**
-	 * replace the root_addr serialized from the inputted cache file
+	 * replace the root_addr serialized from the inputted cache file. A cache from before the
+	 * schema_version was added is only the map of zones
 	 */
-	pub fn cache_from_js( &mut self, serialized : &String ) {
-		self.root_addr = serde_json::from_str::<Root>(serialized).unwrap().root_addr.clone();
+	pub fn cache_from_js( &mut self, serialized : &String ) -> Result<(), String> {
+
+		let mut document = match serde_json::from_str::<serde_json::Value>(serialized) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to parse cache file {}", e)); }
+		};
+
+		let zones = match crate::config::schema_version( &document, "cache file" )? {
+			0 => { document },
+			_ => { document["zones"].take() }
+		};
+
+		self.root_addr = match serde_json::from_value::<Root>(zones) {
+			Ok(m) => { m.root_addr },
+			Err(e) => { return Err(format!("failed to parse cache file {}", e)); }
+		};
+
+		Ok(())
 	}
 
 }
 
+#[cfg(not(feature = "binary-cache"))]
+const NO_BINARY_CACHE : &str = "a .bin cache file needs dns_audit built with the binary-cache feature";
+
+/**
+ * the layout of the cache file
+ */
+#[derive(Serialize)]
+struct CacheFile<'a> {
+	schema_version : u32,
+	zones : &'a Root
+}
+
 impl serde::Serialize for Root {
 
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -509,9 +1069,7 @@ impl<'de> serde::Deserialize<'de> for Root {
 		D: serde::Deserializer<'de> 
 	{
 		
-		let mut rval = Self {
-			root_addr: Default::default()
-		};
+		let mut rval = Self::default();
 
 		let visitor = RootVisitor {};
 
@@ -0,0 +1,124 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Counters for the queries sent during a run, broken down by the record type asked for so
+ * the summary can show where the failures and timeouts are, and how long each server took
+ * to answer
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::query::QueryType;
+
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct QueryStats {
+	/** queries sent to a server */
+	pub sent : u64,
+	/** queries that did not get a usable answer, this includes the timeouts */
+	pub failed : u64,
+	/** queries the server never answered */
+	pub timed_out : u64,
+}
+
+/**
+ * response times of a server, only queries it answered are counted
+ */
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct ServerStats {
+	pub responses : u64,
+	pub total : std::time::Duration,
+	pub slowest : std::time::Duration,
+}
+
+impl ServerStats {
+	pub fn average( &self ) -> std::time::Duration {
+		match self.responses {
+			0 => { std::time::Duration::ZERO },
+			n => { self.total / n as u32 }
+		}
+	}
+}
+
+lazy_static!(
+	static ref QUERY_STATS : Mutex<BTreeMap<u16, (QueryType, QueryStats)>> = Mutex::new(BTreeMap::new());
+
+	static ref SERVER_STATS : Mutex<BTreeMap<std::net::IpAddr, ServerStats>> = Mutex::new(BTreeMap::new());
+);
+
+/**
+ * count a query that was sent, failed is set when it did not get a usable answer
+ */
+pub fn record( qtype : QueryType, failed : bool, timed_out : bool ) {
+	let mut stats = QUERY_STATS.lock().unwrap();
+	let entry = &mut stats.entry(qtype.as_u16()).or_insert( (qtype, Default::default()) ).1;
+	entry.sent += 1;
+	if failed {
+		entry.failed += 1;
+	}
+	if timed_out {
+		entry.timed_out += 1;
+	}
+}
+
+/**
+ * count a response from server that took elapsed to arrive
+ */
+pub fn record_response( server : &std::net::IpAddr, elapsed : std::time::Duration ) {
+	let mut stats = SERVER_STATS.lock().unwrap();
+	let entry = stats.entry(*server).or_default();
+	entry.responses += 1;
+	entry.total += elapsed;
+	entry.slowest = entry.slowest.max(elapsed);
+}
+
+/**
+ * the response times of each server that answered, ordered by address
+ */
+pub fn server_snapshot() -> Vec<(std::net::IpAddr, ServerStats)> {
+	SERVER_STATS.lock().unwrap().iter().map(|(k, v)| (*k, *v)).collect()
+}
+
+/**
+ * the counters for each record type that was queried, ordered by the type number
+ */
+pub fn snapshot() -> Vec<(QueryType, QueryStats)> {
+	QUERY_STATS.lock().unwrap().values().cloned().collect()
+}
+
+/**
+ * clear the counters, used between runs when monitoring continuously
+ */
+pub fn reset() {
+	QUERY_STATS.lock().unwrap().clear();
+	SERVER_STATS.lock().unwrap().clear();
+}
+
+/**
+ * write the per type breakdown, one line for each record type that was queried
+ */
+pub fn write_summary( out : &mut dyn std::io::Write ) -> std::io::Result<()> {
+	for (qtype, stats) in snapshot() {
+		writeln!(out, "\t{}: {} queries, {} failed, {} timed out", qtype.to_string().trim_start_matches("T_"), stats.sent, stats.failed, stats.timed_out)?;
+	}
+	Ok(())
+}
@@ -0,0 +1,217 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * TSIG transaction signatures
+ *
+ * RFC-8945
+ */
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+
+use crate::query::{self, Wire};
+use crate::zone;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/** allowed clock skew in seconds between us and the server */
+pub const FUDGE : u16 = 300;
+
+/**
+ * A shared secret used to sign queries, the secret is base64 encoded the same way
+ * it is in a bind key file
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TsigKey {
+	pub name : String,
+	#[serde(default = "TsigKey::default_algorithm")]
+	pub algorithm : String,
+	pub secret : String
+}
+
+impl TsigKey {
+
+	fn default_algorithm() -> String {
+		"hmac-sha256".to_string()
+	}
+
+	/**
+	 * create the hmac for this key, only hmac-sha256 is supported
+	 */
+	fn hmac( &self ) -> Result<HmacSha256, String> {
+
+		let alg = self.algorithm.trim_end_matches('.');
+		if !alg.eq_ignore_ascii_case("hmac-sha256") {
+			return Err(format!("TSIG algorithm '{}' is not supported", self.algorithm));
+		}
+
+		let secret = match base64::engine::general_purpose::STANDARD.decode(&self.secret) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("TSIG secret for '{}' is not valid base64 {}", self.name, e)); }
+		};
+
+		match HmacSha256::new_from_slice(&secret) {
+			Ok(m) => { Ok(m) },
+			Err(e) => { Err(format!("TSIG secret for '{}' is invalid {}", self.name, e)) }
+		}
+	}
+
+	/**
+	 * the TSIG variables that are appended to the message when calculating the MAC
+	 */
+	fn variables( &self, time_signed : u64, fudge : u16, error : u16, other : &[u8] ) -> Vec<u8> {
+		let mut buff = query::write_qname( &self.name.to_ascii_lowercase() );
+		buff.extend_from_slice( &query::NSClass::C_ANY.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &0u32.to_be_bytes() );
+		buff.append( &mut query::write_qname( &self.algorithm.to_ascii_lowercase() ) );
+		buff.extend_from_slice( &time_signed.to_be_bytes()[2..] );
+		buff.extend_from_slice( &fudge.to_be_bytes() );
+		buff.extend_from_slice( &error.to_be_bytes() );
+		buff.extend_from_slice( &(other.len() as u16).to_be_bytes() );
+		buff.extend_from_slice( other );
+		buff
+	}
+
+	/**
+	 * Sign the request, this appends the TSIG record to the additional section and increments
+	 * the arcount in the header. The returned MAC is needed to verify the response.
+	 */
+	pub fn sign( &self, request : &mut Vec<u8>, time_signed : u64 ) -> Result<Vec<u8>, String> {
+
+		let mut hmac = self.hmac()?;
+		hmac.update( request );
+		hmac.update( &self.variables(time_signed, FUDGE, 0, &[]) );
+		let mac = hmac.finalize().into_bytes().to_vec();
+
+		let mut rdata = query::write_qname( &self.algorithm.to_ascii_lowercase() );
+		rdata.extend_from_slice( &time_signed.to_be_bytes()[2..] );
+		rdata.extend_from_slice( &FUDGE.to_be_bytes() );
+		rdata.extend_from_slice( &(mac.len() as u16).to_be_bytes() );
+		rdata.extend_from_slice( &mac );
+		rdata.extend_from_slice( &request[0..2] );	// original id
+		rdata.extend_from_slice( &0u16.to_be_bytes() ); // error
+		rdata.extend_from_slice( &0u16.to_be_bytes() ); // other len
+
+		request.append( &mut query::write_qname( &self.name.to_ascii_lowercase() ) );
+		request.extend_from_slice( &query::QueryType::T_TSIG.as_u16().to_be_bytes() );
+		request.extend_from_slice( &query::NSClass::C_ANY.as_u16().to_be_bytes() );
+		request.extend_from_slice( &0u32.to_be_bytes() );
+		request.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+		request.append( &mut rdata );
+
+		let arcount = u16::from_be_bytes( [request[10], request[11]] ) + 1;
+		request[10..12].copy_from_slice( &arcount.to_be_bytes() );
+
+		Ok(mac)
+	}
+
+	/**
+	 * Verify the TSIG record at the end of the response using the MAC of the request we sent
+	 */
+	pub fn verify( &self, request_mac : &[u8], response : &[u8], now : u64 ) -> Result<(), String> {
+
+		let mut offset : usize = 0;
+		let mut header : query::Header = Default::default();
+		header.read(response, &mut offset)?;
+
+		if header.arcount == 0 {
+			return Err(format!("response is not signed with TSIG key '{}'", self.name));
+		}
+
+		for _ in 0..header.qdcount {
+			let mut q : query::Question = Default::default();
+			q.read(response, &mut offset)?;
+		}
+
+		// the tsig record must be the very last record in the message
+		let rec_count = header.ancount as usize + header.nscount as usize + header.arcount as usize;
+		let mut tsig_start : usize = offset;
+		for _ in 0..rec_count {
+			tsig_start = offset;
+			zone::record::ZoneRecord::create_from_wire(response, &mut offset)?;
+		}
+
+		let mut offset = tsig_start;
+		let key_name = query::read_qname(response, &mut offset)?;
+		let rtype = query::dns_read_int!(u16, response, &mut offset);
+		offset += 2 + 4 + 2; // class, ttl, rdlength
+
+		if rtype != query::QueryType::T_TSIG.as_u16() {
+			return Err(format!("response is not signed with TSIG key '{}'", self.name));
+		}
+
+		if !key_name.trim_end_matches('.').eq_ignore_ascii_case( self.name.trim_end_matches('.') ) {
+			return Err(format!("response signed with unexpected TSIG key '{}'", key_name));
+		}
+
+		let _algorithm = query::read_qname(response, &mut offset)?;
+		let mut time_buff = [0u8; 8];
+		time_buff[2..].copy_from_slice( &query::read_buff(response, &mut offset, 6)? );
+		let time_signed = u64::from_be_bytes(time_buff);
+		let fudge = query::dns_read_int!(u16, response, &mut offset);
+		let mac_size = query::dns_read_int!(u16, response, &mut offset);
+		let mac = query::read_buff(response, &mut offset, mac_size as usize)?;
+		let original_id = query::read_buff(response, &mut offset, 2)?;
+		let error = query::dns_read_int!(u16, response, &mut offset);
+		let other_len = query::dns_read_int!(u16, response, &mut offset);
+		let other = query::read_buff(response, &mut offset, other_len as usize)?;
+
+		match error {
+			0 => { },
+			16 => { return Err("TSIG error BADSIG".to_string()); },
+			17 => { return Err("TSIG error BADKEY".to_string()); },
+			18 => { return Err("TSIG error BADTIME".to_string()); },
+			_ => { return Err(format!("TSIG error {}", error)); }
+		}
+
+		// rebuild the message as it was before the server added the tsig record
+		let mut message = response[..tsig_start].to_vec();
+		message[0..2].copy_from_slice(&original_id);
+		message[10..12].copy_from_slice( &(header.arcount - 1).to_be_bytes() );
+
+		let mut hmac = self.hmac()?;
+		hmac.update( &(request_mac.len() as u16).to_be_bytes() );
+		hmac.update( request_mac );
+		hmac.update( &message );
+		hmac.update( &self.variables(time_signed, fudge, error, &other) );
+
+		if hmac.verify_slice(&mac).is_err() {
+			return Err(format!("TSIG signature from '{}' did not verify", key_name));
+		}
+
+		if now.abs_diff(time_signed) > fudge as u64 {
+			return Err(format!("TSIG time signed {} is outside of the fudge {}", time_signed, fudge));
+		}
+
+		Ok(())
+	}
+
+}
+
+/**
+ * current time in seconds as used by the time signed field
+ */
+pub fn now() -> u64 {
+	match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+		Ok(d) => { d.as_secs() },
+		Err(_) => { 0 }
+	}
+}
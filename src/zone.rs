@@ -22,22 +22,168 @@ pub mod record;
 pub mod tokenizer;
 pub mod rr;
 
+use std::collections::HashMap;
+
+use crate::config::println_verbose;
+
 pub struct Zone {
-	pub records: Vec<Box<dyn record::IZoneRecord> >
+	pub records: Vec<Box<dyn record::IZoneRecord> >,
+	/** the lines that could not be parsed, only when they were skipped with create_with */
+	pub skipped: Vec<SkippedRecord>
+}
 
+/**
+ * something in the zone file that parsed but was guessed at, like a record without a class
+ */
+pub struct ParseWarning {
+	pub line : u32,
+	pub message : String
+}
+
+impl std::fmt::Display for ParseWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "line {}: warning: {}", self.line, self.message)
+	}
+}
+
+/**
+ * a line of the zone file that was left out because it couldn't be parsed
+ */
+pub struct SkippedRecord {
+	pub line : u32,
+	pub error : String
 }
 
 impl Zone {
 
 	pub fn create( filename : & String, __origin : &String ) -> Result<Zone, String> { 
+		Self::create_with( filename, __origin, false )
+	}
 
-		let mut zone: Zone = Default::default();
+	/**
+	 * create the zone, with skip_bad_records a record that can't be parsed is left out and
+	 * kept in skipped instead of failing the whole zone
+	 */
+	pub fn create_with( filename : & String, __origin : &String, skip_bad_records : bool ) -> Result<Zone, String> {
+		Self::create_with_warnings( filename, __origin, skip_bad_records ).map(|(zone, _)| zone)
+	}
+
+	/**
+	 * create_with that also returns what parsed but should be looked at: record types that
+	 * aren't known, records that got the default class or no TTL and $TTL values that aren't
+	 * numbers
+	 */
+	pub fn create_with_warnings( filename : & String, __origin : &String, skip_bad_records : bool ) -> Result<(Zone, Vec<ParseWarning>), String> {
+		Self::from_lines( Self::include_lines( filename, __origin, &mut Vec::new() )?, __origin, skip_bad_records )
+	}
+
+	/**
+	 * tokenize the file with the files its $INCLUDE directives name spliced in where they are
+	 * (RFC-1035 section 5.1), a relative path is relative to the directory of the file that
+	 * includes it. The included lines are put between two $ORIGIN lines, the origin given with
+	 * the $INCLUDE or the current one and then the current one again, so an $ORIGIN in the
+	 * included file doesn't carry over to the rest of this one. including is the files that
+	 * are being read, a file that includes one of them is an include cycle
+	 */
+	fn include_lines( filename : &String, origin : &str, including : &mut Vec<std::path::PathBuf> ) -> Result<tokenizer::ZoneLines, String> {
+
+		let path = match std::fs::canonicalize(filename) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("failed to open {} {}", filename, e)); }
+		};
+
+		if including.contains(&path) {
+			let chain = including.iter().chain(std::iter::once(&path)).map(|p| p.display().to_string()).collect::<Vec<String>>().join(" -> ");
+			return Err(format!("$INCLUDE cycle {}", chain));
+		}
 
 		let token_lines = tokenizer::ZoneLines::create(filename)?;
 
+		// only files with an $INCLUDE need the lines gone through
+		let is_include = |line : &tokenizer::ZoneLine| line.tokens.first().is_some_and(|t| t.token.eq_ignore_ascii_case("$INCLUDE"));
+		if !token_lines.lines.iter().any(is_include) {
+			return Ok(token_lines);
+		}
+
+		including.push( path.clone() );
+
+		let mut rval : tokenizer::ZoneLines = Default::default();
+		let mut current = origin.to_string();
+
+		for line in token_lines.lines {
+
+			let first = match line.tokens.first() {
+				Some(m) => { m },
+				None => { continue; }
+			};
+
+			if first.token.eq_ignore_ascii_case("$ORIGIN") {
+				if let Some(value) = line.tokens.iter().skip(1).find(|t| t.token_type != tokenizer::TokenType::TypeWhite) {
+					current = compose_origin( &value.token, &current );
+				}
+			}
+
+			if !is_include(&line) {
+				rval.lines.push( line );
+				continue;
+			}
+
+			let source_line = first.line;
+			let mut args = line.tokens.iter().skip(1).filter(|t| t.token_type != tokenizer::TokenType::TypeWhite);
+
+			let include = match args.next() {
+				Some(m) => { path.parent().unwrap_or(std::path::Path::new("")).join(&m.token) },
+				None => { return Err(format!("$INCLUDE directive at line {} of {} has no file", source_line, filename)); }
+			};
+
+			let include_origin = match args.next() {
+				Some(m) => { compose_origin( &m.token, &current ) },
+				None => { current.clone() }
+			};
+
+			println_verbose!(VERBOSE2, "Including {} with origin {}", include.display(), include_origin);
+
+			let included = match Self::include_lines( &include.to_string_lossy().to_string(), &include_origin, including ) {
+				Ok(m) => { m },
+				Err(e) => { return Err(format!("$INCLUDE at line {} of {}: {}", source_line, filename, e)); }
+			};
+
+			rval.lines.push( directive_line( "ORIGIN", &include_origin, source_line ) );
+			rval.lines.extend( included.lines );
+			rval.lines.push( directive_line( "ORIGIN", &current, source_line ) );
+		}
+
+		including.pop();
+
+		Ok(rval)
+	}
+
+	/**
+	 * create the zone from lines that were already tokenized, like a zone that isn't in a file
+	 */
+	pub fn from_lines( token_lines : tokenizer::ZoneLines, __origin : &String, skip_bad_records : bool ) -> Result<(Zone, Vec<ParseWarning>), String> {
+
+		let mut zone: Zone = Default::default();
+		let mut warnings : Vec<ParseWarning> = Vec::new();
+
+		let mut lines : Vec<tokenizer::ZoneLine> = Vec::with_capacity( token_lines.lines.len() );
 		for line in token_lines.lines {
+			if line.tokens.first().is_some_and(|t| t.token.eq_ignore_ascii_case("$GENERATE")) {
+				lines.extend( generate_lines( &line )? );
+			} else {
+				lines.push( line );
+			}
+		}
+
+		for line in lines {
 
 			match record::ZoneRecord::create(&line) {
+				Err(e) if skip_bad_records => {
+					zone.skipped.push( SkippedRecord {
+						line: line.tokens.first().map_or(0, |t| t.line),
+						error: e
+					});
+				},
 				Err(e) => { return Err(e); },
 				Ok(m) => { 
 					zone.records.push( m ); 
@@ -47,17 +193,33 @@ impl Zone {
 
 		let mut origin = __origin.clone();
 		let mut ttl: i32 = 0;
+		let mut ttl_given = false;
+		let mut last_owner = record::RecordName::default();
+		// like the owner, a record without a class has the last one given (RFC-1035 section 5.1)
+		let mut class = crate::query::NSClass::C_IN;
+		let mut class_given = false;
+		let mut class_warned = false;
 
 		for record in &mut zone.records {
 
-			match record.as_any().downcast_ref::<record::ZoneDirective>() {
+			match record.as_any().downcast_mut::<record::ZoneDirective>() {
 				Some(b) => {
 					if b.name.eq_ignore_ascii_case( "ORIGIN") {
+						// stored absolute so anything reading the directives later sees the composed name
+						b.value = compose_origin( &b.value, &origin );
 						origin = b.value.clone();
 					} else if b.name.eq_ignore_ascii_case("TTL") {
 						match b.value.parse::<i32>() {
-							Ok(t) => { ttl = t }, 
-							Err(_e) => { }
+							Ok(t) => {
+								ttl = t;
+								ttl_given = true;
+							}, 
+							Err(_e) => {
+								warnings.push( ParseWarning {
+									line: b.source_line,
+									message: format!("$TTL {} is not a number, it is ignored", b.value)
+								});
+							}
 						}
 					}
 				},
@@ -67,10 +229,39 @@ impl Zone {
 			match record.as_any().downcast_mut::<record::ZoneRecord>() {
 
 				Some(b) => {
+					if b.name.name.is_empty() {
+						b.name = last_owner.clone();
+					}
 					b.origin(&origin);
+					last_owner = b.name.clone();
+					if b.class_explicit {
+						class = b.class;
+						class_given = true;
+					} else {
+						if !class_given && !class_warned {
+							warnings.push( ParseWarning {
+								line: b.source_line,
+								message: format!("{} has no class and none was given before it, {} is assumed for it and the records after it without one", b.name.fqdn, class.to_string().trim())
+							});
+							class_warned = true;
+						}
+						b.class = class;
+					}
 					if b.ttl == 0 {
+						if !ttl_given {
+							warnings.push( ParseWarning {
+								line: b.source_line,
+								message: format!("{} has no TTL and there is no $TTL before it, the TTL is 0", b.name.fqdn)
+							});
+						}
 						b.ttl = ttl;
 					}
+					if b.record_type == record::RecordType::RecordTypeOther && !b.type_name().starts_with("TYPE") {
+						warnings.push( ParseWarning {
+							line: b.source_line,
+							message: format!("{} is not a known record type, the data of {} is kept as it was written", b.type_name(), b.name.fqdn)
+						});
+					}
 				},
 				None => {}
 			}
@@ -78,15 +269,268 @@ impl Zone {
 
 		}
 
-		return Ok(zone);
+		return Ok((zone, warnings));
+	}
+
+	/**
+	 * the records owned by the name or any name below it, on label boundaries so
+	 * barfoo.example is not under foo.example. The name is absolute, the trailing dot and
+	 * case don't matter
+	 */
+	pub fn records_under( &self, name : &str ) -> Vec<&record::ZoneRecord> {
+		self.records.iter()
+			.filter_map(|r| r.as_any_ref().downcast_ref::<record::ZoneRecord>())
+			.filter(|r| record::name_eq( &r.name.fqdn, name ) || record::name_is_under( &r.name.fqdn, name ))
+			.collect()
+	}
+
+	/**
+	 * the lines of the skipped records, for a message
+	 */
+	pub fn skipped_lines( &self ) -> String {
+		self.skipped.iter().map(|s| s.line.to_string()).collect::<Vec<String>>().join(", ")
 	}
 
 }
 
+/**
+ * the zone's records grouped by owner name and type, the keys are kept in the order they are
+ * first seen so anything reported about them follows the file
+ */
+pub struct RRsetIndex<'a> {
+	rrsets : HashMap<(String, String), Vec<&'a record::ZoneRecord>>,
+	order : Vec<(String, String)>
+}
+
+impl<'a> RRsetIndex<'a> {
+
+	pub fn new( records : &[&'a record::ZoneRecord] ) -> Self {
+
+		let mut rval = Self {
+			rrsets: HashMap::new(),
+			order: Vec::new()
+		};
+
+		for rec in records {
+			let key = (rec.name.fqdn.to_ascii_lowercase(), rec.type_name());
+			let order = &mut rval.order;
+			rval.rrsets.entry(key.clone()).or_insert_with(|| { order.push(key); Vec::new() }).push(rec);
+		}
+
+		rval
+	}
+
+	/**
+	 * the records of the type at the name, empty when there aren't any
+	 */
+	pub fn get( &self, name : &str, record_type : record::RecordType ) -> &[&'a record::ZoneRecord] {
+		self.rrsets.get( &(name.to_ascii_lowercase(), record_type.to_string()) ).map_or(&[], |m| m.as_slice())
+	}
+
+	/**
+	 * each RRset in the order it first appears in the zone
+	 */
+	pub fn iter( &self ) -> impl Iterator<Item = &[&'a record::ZoneRecord]> {
+		self.order.iter().map(|key| self.rrsets[key].as_slice())
+	}
+
+}
+
+/** the most records one $GENERATE can expand to, the same as BIND's limit */
+pub const GENERATE_MAX_STEPS : u32 = 65535;
+
+/**
+ * the record lines of a $GENERATE range lhs [ttl] [class] type rhs directive, one for each
+ * number in the range start-stop[/step] with it put in place of the $ in the lhs and rhs, see
+ * generate_substitute. The lines are tokenized the same as the lines of the zone file, a range
+ * of more than GENERATE_MAX_STEPS numbers is an error
+ */
+pub fn generate_lines( line : &tokenizer::ZoneLine ) -> Result<Vec<tokenizer::ZoneLine>, String> {
+
+	let source_line = line.tokens.first().map_or(0, |t| t.line);
+	let mut args = line.tokens.iter().skip(1).filter(|t| t.token_type != tokenizer::TokenType::TypeWhite);
+
+	let range = match args.next() {
+		Some(m) => { m.token.clone() },
+		None => { return Err(format!("$GENERATE at line {} has no range", source_line)); }
+	};
+
+	let template : Vec<String> = args.map(|t| t.to_string()).collect();
+	if template.len() < 3 {
+		return Err(format!("$GENERATE at line {} needs an owner, a type and rdata", source_line));
+	}
+
+	let (bounds, step) = match range.split_once('/') {
+		Some((bounds, step)) => { (bounds, step) },
+		None => { (range.as_str(), "1") }
+	};
+	let parsed = match (bounds.split_once('-'), step.parse::<u32>()) {
+		(Some((start, stop)), Ok(step)) if step > 0 => {
+			match (start.parse::<u32>(), stop.parse::<u32>()) {
+				(Ok(start), Ok(stop)) if start <= stop => { Some((start, stop, step)) },
+				_ => { None }
+			}
+		},
+		_ => { None }
+	};
+	let (start, stop, step) = match parsed {
+		Some(m) => { m },
+		None => { return Err(format!("$GENERATE at line {} has an invalid range '{}'", source_line, range)); }
+	};
+
+	if (stop - start) / step >= GENERATE_MAX_STEPS {
+		return Err(format!("$GENERATE at line {} range '{}' is more than {} records", source_line, range, GENERATE_MAX_STEPS));
+	}
+
+	let mut rval : Vec<tokenizer::ZoneLine> = Vec::new();
+
+	for value in (start..=stop).step_by(step as usize) {
+
+		let mut text : Vec<String> = Vec::with_capacity( template.len() );
+		for part in &template {
+			match generate_substitute( part, value ) {
+				Ok(m) => { text.push(m); },
+				Err(e) => { return Err(format!("$GENERATE at line {}: {}", source_line, e)); }
+			}
+		}
+
+		let mut generated = tokenizer::ZoneLines::from_reader( &mut text.join(" ").as_bytes() )?;
+		for mut generated_line in generated.lines.drain(..) {
+			for tok in &mut generated_line.tokens {
+				tok.line = source_line;
+			}
+			rval.push( generated_line );
+		}
+	}
+
+	Ok(rval)
+}
+
+/**
+ * put the $GENERATE value in the text, $ is the value and ${offset,width,base} the value plus
+ * offset zero padded to width in base d, o, x or X, the width and base can be left out. \$
+ * is a $ that isn't replaced
+ */
+pub fn generate_substitute( text : &str, value : u32 ) -> Result<String, String> {
+
+	let mut rval = String::new();
+	let mut chars = text.chars().peekable();
+
+	while let Some(c) = chars.next() {
+
+		if c == '\\' && chars.peek() == Some(&'$') {
+			rval.push( chars.next().unwrap() );
+			continue;
+		} else if c != '$' {
+			rval.push(c);
+			continue;
+		}
+
+		if chars.peek() != Some(&'{') {
+			rval.push_str( &value.to_string() );
+			continue;
+		}
+		chars.next();
+
+		let mut modifier = String::new();
+		loop {
+			match chars.next() {
+				Some('}') => { break; },
+				Some(m) => { modifier.push(m); },
+				None => { return Err(format!("'{}' has a ${{ without a }}", text)); }
+			}
+		}
+
+		let mut parts = modifier.split(',');
+		let offset = match parts.next().unwrap_or("").trim().parse::<i64>() {
+			Ok(m) => { m },
+			Err(_) => { return Err(format!("'{}' has an invalid offset in ${{{}}}", text, modifier)); }
+		};
+		let width = match parts.next() {
+			Some(m) => {
+				match m.trim().parse::<usize>() {
+					Ok(w) => { w },
+					Err(_) => { return Err(format!("'{}' has an invalid width in ${{{}}}", text, modifier)); }
+				}
+			},
+			None => { 0 }
+		};
+
+		let number = value as i64 + offset;
+		if number < 0 {
+			return Err(format!("'{}' is negative for {} with ${{{}}}", text, value, modifier));
+		}
+
+		match parts.next().map(|b| b.trim()) {
+			None | Some("d") => { rval.push_str( &format!("{:0width$}", number, width = width) ); },
+			Some("o") => { rval.push_str( &format!("{:0width$o}", number, width = width) ); },
+			Some("x") => { rval.push_str( &format!("{:0width$x}", number, width = width) ); },
+			Some("X") => { rval.push_str( &format!("{:0width$X}", number, width = width) ); },
+			Some(base) => { return Err(format!("'{}' has the unknown base {} in ${{{}}}", text, base, modifier)); }
+		}
+	}
+
+	Ok(rval)
+}
+
+/**
+ * a directive line that wasn't in the zone file, like the $ORIGIN lines around an $INCLUDE
+ */
+fn directive_line( name : &str, value : &str, line : u32 ) -> tokenizer::ZoneLine {
+	tokenizer::ZoneLine {
+		tokens: vec![
+			tokenizer::ZoneToken { token: format!("${}", name), token_type: tokenizer::TokenType::TypeDirective, line },
+			tokenizer::ZoneToken { token: " ".to_string(), token_type: tokenizer::TokenType::TypeWhite, line },
+			tokenizer::ZoneToken { token: value.to_string(), token_type: tokenizer::TokenType::TypeToken, line }
+		]
+	}
+}
+
+/**
+ * the absolute name for an $ORIGIN value or a name in a record, like BIND a name without the
+ * trailing dot is relative to the current origin so $ORIGIN sub inside example.com. is
+ * sub.example.com. and @ is the origin itself
+ */
+pub fn compose_origin( value : &str, current : &str ) -> String {
+	if record::is_absolute( value ) {
+		value.to_string()
+	} else if value == "@" {
+		current.to_string()
+	} else if current == "." {
+		format!("{}.", value)
+	} else {
+		format!("{}.{}", value, current)
+	}
+}
+
+/**
+ * writes the zone back out one record per line. Every name is absolute and the TTLs are
+ * applied, so the directives are left out and the output parses the same with any origin
+ */
+impl std::fmt::Display for Zone {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for record in &self.records {
+			if let Some(r) = record.as_any_ref().downcast_ref::<record::ZoneRecord>() {
+				write!(f, "{}\t", r.name)?;
+				if r.ttl > 0 {
+					write!(f, "{}\t", r.ttl)?;
+				}
+				write!(f, "{}\t{}", r.class.to_string().trim(), r.type_name())?;
+				if let Some(rd) = &r.rdata {
+					write!(f, "\t{}", rd.to_string().trim_end())?;
+				}
+				writeln!(f)?;
+			}
+		}
+		Ok(())
+	}
+}
+
 impl Default for Zone {
 	fn default() -> Self {
 		Zone {
-			records: Vec::new()
+			records: Vec::new(),
+			skipped: Vec::new()
 		}
 	}
 }
\ No newline at end of file
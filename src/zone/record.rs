@@ -64,6 +64,7 @@ pub enum RecordType {
 	NSEC3 = 50,
 	NSEC3PARAM = 51,
 	OPENPGPKEY = 61,
+	OPT = 41,
 	PTR = 12,
 	RRSIG = 46,
 	RP = 17,
@@ -119,6 +120,7 @@ impl RecordType {
 			50 => { Self::NSEC3 },
 			51 => { Self::NSEC3PARAM },
 			61 => { Self::OPENPGPKEY },
+			41 => { Self::OPT },
 			12 => { Self::PTR },
 			46 => { Self::RRSIG },
 			17 => { Self::RP },
@@ -153,7 +155,7 @@ impl RecordName {
 			fqdn: String::new()
 		};
 
-		if dn.ends_with(".") {
+		if is_absolute( dn ) {
 			r.fqdn = dn.clone();
 		}
 
@@ -162,23 +164,65 @@ impl RecordName {
 
 	pub fn origin ( &mut self, origin : &String ) {
 		if self.fqdn.len() == 0{
-			
-			if self.name == "@" {
-				self.fqdn = origin.clone();
-			} else if !self.name.ends_with(".") {
+			self.fqdn = crate::zone::compose_origin( &self.name, origin );
+		}
 
-				self.fqdn = self.name.clone();
-				if !self.fqdn.starts_with(".") {
-					self.fqdn.push('.');
-				}
-				self.fqdn += origin;
-			} else {
-				self.fqdn = self.name.clone();
-			}
+	}
+}
 
-		}
+/**
+ * returns true if the name ends in an unescaped dot, "foo\\." is the relative label "foo."
+ */
+pub fn is_absolute( name : &str ) -> bool {
+	let Some(body) = name.strip_suffix('.') else {
+		return false;
+	};
+	body.chars().rev().take_while(|c| *c == '\\').count() % 2 == 0
+}
+
+/**
+ * compare two domain names ignoring case and the trailing root dot, names read off the
+ * wire don't always have the trailing dot
+ */
+pub fn name_eq( a : &str, b : &str ) -> bool {
+	a.trim_end_matches('.').eq_ignore_ascii_case( b.trim_end_matches('.') )
+}
+
+/**
+ * returns true if name is below parent on a label boundary, so foo.example is under
+ * example but barfoo.example is not under foo.example. A name is not under itself.
+ */
+pub fn name_is_under( name : &str, parent : &str ) -> bool {
+	let name = name.trim_end_matches('.');
+	let parent = parent.trim_end_matches('.');
+
+	if parent.is_empty() {
+		return !name.is_empty();
+	}
+
+	if name.len() <= parent.len() + 1 {
+		return false;
+	}
+
+	let split = name.len() - parent.len();
+	name.is_char_boundary(split)
+		&& name[split..].eq_ignore_ascii_case(parent)
+		&& name.as_bytes()[split - 1] == b'.'
+}
+
+/**
+ * count the labels in the name the way the RRSIG labels field does, the root and a
+ * leading wildcard label are not counted
+ */
+pub fn label_count( name : &str ) -> u8 {
+	let name = name.trim_end_matches('.');
+	let name = name.strip_prefix("*").unwrap_or(name).trim_start_matches('.');
 
+	if name.is_empty() {
+		return 0;
 	}
+
+	name.split('.').count() as u8
 }
 
 impl std::fmt::Display for RecordName { 
@@ -218,6 +262,8 @@ pub trait IZoneRecord : std::fmt::Display {
 
 	fn as_any(&mut self) -> &mut dyn std::any::Any;
 
+	/** read only version of as_any, so records can be indexed without a mutable borrow */
+	fn as_any_ref(&self) -> &dyn std::any::Any;
 
 }
 
@@ -230,7 +276,11 @@ pub struct ZoneRecord {
 	pub class : query::NSClass,
 	pub record_type : RecordType,
 	pub record_type_other : Option<String>,
-	pub rdata : Option<Box<dyn rr::RecordRDATA>>
+	pub rdata : Option<Box<dyn rr::RecordRDATA>>,
+	/** line in the zone file the record started on, 0 when it was read off the wire */
+	pub source_line : u32,
+	/** whether the class was given, when it wasn't the zone fills in the last one that was */
+	pub class_explicit : bool
 }
 
 
@@ -245,7 +295,7 @@ enum RecordPos {
 
 impl RecordType {
 
-	fn from_string ( instr : & str )  -> RecordType {
+	pub fn from_string ( instr : & str )  -> RecordType {
 
 		match instr.to_ascii_uppercase().as_str() {
 			"A" => { RecordType::A },
@@ -279,6 +329,7 @@ impl RecordType {
 			"NSEC3" => { RecordType::NSEC3 },
 			"NSEC3PARAM" => { RecordType::NSEC3PARAM },
 			"OPENPGPKEY" => { RecordType::OPENPGPKEY },
+			"OPT" => { RecordType::OPT },
 			"PTR" => { RecordType::PTR },
 			"RRSIG" => { RecordType::RRSIG },
 			"RP" => { RecordType::RP },
@@ -333,6 +384,7 @@ impl RecordType {
 			RecordType::NSEC3 => { "NSEC3" },
 			RecordType::NSEC3PARAM => { "NSEC3PARAM" },
 			RecordType::OPENPGPKEY => { "OPENPGPKEY" },
+			RecordType::OPT => { "OPT" },
 			RecordType::PTR => { "PTR" },
 			RecordType::RRSIG => { "RRSIG" },
 			RecordType::RP => { "RP" },
@@ -381,11 +433,19 @@ impl ZoneRecord {
 		if tok.token.starts_with("$") {
 			let mut r = Box::<ZoneDirective>::new( Default::default() );
 			r.name = tok.token[1..].to_string().clone();
-			r.from_iter(&mut line_iter)?;
+			r.source_line = tok.line;
+			if let Err(e) = r.from_iter(&mut line_iter) {
+				return Err(format!("${} directive at line {} has no value ({})", r.name, r.source_line, e));
+			}
 			return Ok(r);
 		} else {
 			let mut r = Box::<ZoneRecord>::new( Default::default() );
-			r.name.name = tok.token.clone();
+			// a line starting with whitespace has no owner, it is the previous record's and
+			// is filled in by the zone (RFC-1035 section 5.1)
+			if tok.token_type != tokenizer::TokenType::TypeWhite {
+				r.name.name = tok.token.clone();
+			}
+			r.source_line = tok.line;
 			r.from_iter(&mut line_iter)?;
 			return Ok(r);
 		}
@@ -408,29 +468,86 @@ impl ZoneRecord {
 	}
 
 	/*
-	 * Create a zone record from the wire format that was returned from the dns query 
+	 * Create a zone record from the wire format that was returned from the dns query. When the
+	 * RDATA can't be read the offset is moved past the record so the next one can be, when the
+	 * record runs past the end of the buffer the offset is left where it was
 	 */
 	pub fn create_from_wire(  buff : &[u8], offset : &mut usize ) -> Result<Self, String> {
 
-		let mut record = Self { ..Default::default() };
+		let start = *offset;
 
-		record.name.name = query::read_qname(buff,offset).as_str().to_string();
-		record.name.fqdn = record.name.name.clone();
-
-		record.record_type = RecordType::from_u16(&query::dns_read_int!(u16, buff, offset));
-		record.class = query::NSClass::from_u16(&query::dns_read_int!(u16, buff, offset));
-		record.ttl = query::dns_read_int!(i32, buff, offset);
-		let rdlength = query::dns_read_int!(u16, buff, offset);
+		let (mut record, class, rdlength) = match Self::read_wire_header( buff, offset ) {
+			Ok(m) => { m },
+			Err(e) => {
+				*offset = start;
+				return Err(e);
+			}
+		};
 
 		println_verbose!(VERBOSE3, "offset {} name {} type {} ttl {} rdlength {}", offset, record.name.fqdn, record.record_type, record.ttl, rdlength);
 
+		let rdata_end = *offset + rdlength as usize;
+		if rdata_end > buff.len() {
+			*offset = start;
+			return Err(format!("FORMERR {} RDATA of {} bytes is past the end of the {} byte message", record.type_name(), rdlength, buff.len()));
+		}
+
 		let mut rdata = rr::create_from_type(record.record_type);
-		rdata.from_wire(rdlength, buff, offset)?;
+		let rval = rdata.from_wire(rdlength, buff, offset);
+
+		// the next record starts after rdlength whatever the RDATA parser made of it
+		*offset = rdata_end;
+		rval?;
+
+		if let Some(opt) = rdata.as_any_mut().downcast_mut::<rr::RDATAopt>() {
+			opt.set_header( class, record.ttl as u32 );
+		}
 		record.rdata = Some(rdata);
 		
 		Ok(record)
 	}
 
+	/**
+	 * the owner name, type, class, ttl and rdlength of a record, the class is returned as it
+	 * was on the wire because OPT uses it for its UDP payload size, and the rdlength
+	 */
+	fn read_wire_header(  buff : &[u8], offset : &mut usize ) -> Result<(Self, u16, u16), String> {
+
+		let mut record = Self { ..Default::default() };
+
+		record.name.name = query::read_qname(buff,offset)?.as_str().to_string();
+		record.name.fqdn = record.name.name.clone();
+
+		let type_number = query::dns_read_int!(u16, buff, offset);
+		record.record_type = RecordType::from_u16(&type_number);
+		if record.record_type == RecordType::RecordTypeOther {
+			// RFC-3597 section 5, a type without a mnemonic is written as TYPE and its number
+			record.record_type_other = Some(format!("TYPE{}", type_number));
+		}
+		let class = query::dns_read_int!(u16, buff, offset);
+		record.class = query::NSClass::from_u16(&class);
+		record.class_explicit = true;
+		record.ttl = query::dns_read_int!(i32, buff, offset);
+		let rdlength = query::dns_read_int!(u16, buff, offset);
+
+		Ok( (record, class, rdlength) )
+	}
+
+	/**
+	 * the record type as written in a zone file, types this crate doesn't know keep the
+	 * text they were read with
+	 */
+	pub fn type_name( &self ) -> String {
+		if self.record_type == RecordType::RecordTypeOther { 
+			match &self.record_type_other {
+				Some(rec) => { rec.to_string() },
+				None => { "__invalid_record_type__".to_string() }
+			}
+		} else {
+			self.record_type.to_string()
+		}
+	}
+
 	/**
 	 * If the zone record provided is an Address record of A or AAAA it will return
 	 * the corresponding IP ADdress
@@ -465,7 +582,9 @@ impl Default for ZoneRecord {
 			class: query::NSClass::C_IN,
 			record_type : RecordType::RecordTypeOther,
 			record_type_other: None,
-			rdata:  None
+			rdata:  None,
+			source_line: 0,
+			class_explicit: false
 		}
 	}
 
@@ -479,17 +598,7 @@ impl std::fmt::Display for ZoneRecord {
 			v += &format!("{}\t", self.ttl);
 		}
 
-		let record_type = 
-			if self.record_type == RecordType::RecordTypeOther { 
-				match &self.record_type_other {
-					Some(rec) => { rec.to_string() },
-					None => { "__invalid_record_type__".to_string() }
-				}
-			} else {
-				self.record_type.to_string()
-			};
-
-		v += &format!("{}\t{}\t", self.class.to_string(), record_type);
+		v += &format!("{}\t{}\t", self.class.to_string(), self.type_name());
 
 		match &self.rdata {
 			Some(rd) => { v += &format!("{}\t", rd.to_string() ); },
@@ -518,6 +627,11 @@ impl IZoneRecord for ZoneRecord {
 				continue;
 			}
 
+			// the TTL and class are both optional, a known type where either could be is the type
+			if (rec_pos == RecordPos::TTL || rec_pos == RecordPos::IN) && RecordType::from_string( &tok.token ) != RecordType::RecordTypeOther {
+				rec_pos = RecordPos::RTYPE;
+			}
+
 			if rec_pos == RecordPos::TTL {
 
 				match tok.token.parse::<i32>() {
@@ -533,6 +647,7 @@ impl IZoneRecord for ZoneRecord {
 							tok.token.to_ascii_uppercase() == "HS"
 						{
 							self.class = query::NSClass::from_string( &tok.token );
+							self.class_explicit = true;
 							rec_pos = RecordPos::RTYPE;
 						} else {
 							return Err(format!("invalid TTL, got '{}' at line {} ({e}) ", tok.token, tok.line));
@@ -548,6 +663,7 @@ impl IZoneRecord for ZoneRecord {
 					tok.token.to_ascii_uppercase() == "HS"
 				{
 					self.class = query::NSClass::from_string( &tok.token );
+					self.class_explicit = true;
 					rec_pos = RecordPos::RTYPE;
 				} else {
 					return Err( format!("Record class is of an invalid type, expected IN,CS,CH,HS got '{}' at line {}", tok.token, tok.line ));
@@ -565,8 +681,18 @@ impl IZoneRecord for ZoneRecord {
 			// every command should have whitespace after it maybe, enforce that
 			match line_iter.next() {
 				None => { break; }
-				Some(a) => { 
-					if a.token_type != tokenizer::TokenType::TypeWhite {
+				Some(a) => {
+					// a quoted SvcParam value can follow its key= directly, like alpn="h2,h3"
+					if matches!( self.record_type, RecordType::SVCB | RecordType::HTTPS ) && rec_pos == RecordPos::RDATA && a.token_type == tokenizer::TokenType::TypeString && tok.token.ends_with('=') {
+						rdata_tokens.push( a );
+						match line_iter.next() {
+							None => { break; },
+							Some(b) if b.token_type != tokenizer::TokenType::TypeWhite => {
+								return Err( format!("Expected whitespace, got '{}' at line {}", b.token, b.line ));
+							},
+							Some(_) => { }
+						}
+					} else if a.token_type != tokenizer::TokenType::TypeWhite {
 						return Err( format!(
 							"Expected whitespace, got '{}' at line {}",
 							tok.token,
@@ -580,7 +706,9 @@ impl IZoneRecord for ZoneRecord {
 
 		self.rdata = Some(rr::create_from_type( self.record_type ));
 		if let Some(m) = &mut self.rdata {
-			m.from_tokens( &rdata_tokens )?;
+			if let Err(e) = m.from_tokens( &rdata_tokens ) {
+				return Err(format!("invalid {} record at line {}, {}", self.record_type, self.source_line, e));
+			}
 		}
 
 		Ok(())
@@ -591,6 +719,10 @@ impl IZoneRecord for ZoneRecord {
 		self
 	}
 
+	fn as_any_ref( &self ) -> &dyn std::any::Any {
+		self
+	}
+
 }
 
 impl Clone for ZoneRecord {
@@ -607,19 +739,42 @@ impl Clone for ZoneRecord {
 			class: self.class.clone(),
 			record_type: self.record_type.clone(),
 			record_type_other: self.record_type_other.clone(),
-			rdata: new_rd
+			rdata: new_rd,
+			source_line: self.source_line,
+			class_explicit: self.class_explicit
 		}
 	}
 	
 }
 
+/**
+ * records are equal when the owner, class, type and rdata match, the TTL and where the
+ * record came from are ignored. The names are compared without case, see
+ * RecordRDATA::rdata_eq
+ */
+impl PartialEq for ZoneRecord {
+	fn eq(&self, other: &Self) -> bool {
+		name_eq(&self.name.fqdn, &other.name.fqdn)
+			&& self.class == other.class
+			&& self.record_type == other.record_type
+			&& self.record_type_other == other.record_type_other
+			&& match (&self.rdata, &other.rdata) {
+				(Some(a), Some(b)) => { a.rdata_eq( b.as_ref() ) },
+				(None, None) => { true },
+				_ => { false }
+			}
+	}
+}
+
 
 /**
  * Directive, like $TTL
  */
 pub struct ZoneDirective {
 	pub name : String,
-	pub value : String 
+	pub value : String,
+	/** line in the zone file the directive is on */
+	pub source_line : u32
 }
 
 impl IZoneRecord for ZoneDirective {
@@ -646,13 +801,18 @@ impl IZoneRecord for ZoneDirective {
 	fn as_any( &mut self) -> &mut dyn std::any::Any {
 		self
 	}
+
+	fn as_any_ref( &self ) -> &dyn std::any::Any {
+		self
+	}
 }
 
 impl Default for ZoneDirective {
 	fn default() -> Self {
 		ZoneDirective {
 			name: String::new(),
-			value : String::new()
+			value : String::new(),
+			source_line: 0
 		}
 	}
 }
@@ -667,7 +827,8 @@ impl Clone for ZoneDirective {
 	fn clone(&self) -> Self {
 		Self {
 			name: self.name.clone(),
-			value: self.value.clone()
+			value: self.value.clone(),
+			source_line: self.source_line
 		}
 	}
 }
\ No newline at end of file
@@ -23,7 +23,7 @@ use base64::Engine;
 use super::{tokenizer, record};
 
 
-pub trait RecordRDATA : std::fmt::Display {
+pub trait RecordRDATA : std::fmt::Display + Send + Sync {
 
 	/** process the ORIGIN function for me */
 	fn origin( &mut self, origin : &String );
@@ -38,6 +38,14 @@ pub trait RecordRDATA : std::fmt::Display {
 
 	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String>;
 
+	/**
+	 * whether the rdata is the same as other's. The types with names in them compare the
+	 * names with record::name_eq, the rest compare their presentation format
+	 */
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		self.to_string() == other.to_string()
+	}
+
 }
 
 
@@ -250,7 +258,7 @@ impl RecordRDATA for RDATAgeneric {
 	}
 
 	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		self.wire_data = crate::query::read_buff(buff, offset, size as usize );
+		self.wire_data = crate::query::read_buff(buff, offset, size as usize )?;
 		Ok(())
 	}
 
@@ -270,6 +278,17 @@ impl std::fmt::Display for RDATAgeneric {
 
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 
+		// read off the wire there are no tokens, RFC-3597 section 5 gives the unknown rdata
+		// as \# then its length and the bytes in hex
+		if self.tokens.is_empty() {
+			write!(f, "\\# {}", self.wire_data.len())?;
+			if !self.wire_data.is_empty() {
+				let encoded: String = self.wire_data.iter().map(|b| format!("{:02X}", b)).collect();
+				write!(f, " {}", encoded)?;
+			}
+			return Ok(());
+		}
+
 		for tok in &self.tokens {
 			if tok.token_type == tokenizer::TokenType::TypeString {
 				write!(f, "\"{}\" ", tokenizer::ZoneLines::escape(&tok.token))?;
@@ -301,6 +320,10 @@ impl Default for RDATANameRR {
 
 impl RecordRDATA for RDATANameRR { 
 
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| record::name_eq( &self.name.fqdn, &o.name.fqdn ))
+	}
+
 	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
 		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
@@ -334,7 +357,7 @@ impl RecordRDATA for RDATANameRR {
 
 
 	fn from_wire( &mut self, _size : u16,  buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		self.name.name = crate::query::read_qname(buff, offset).as_str().to_string();
+		self.name.name = crate::query::read_qname(buff, offset)?.as_str().to_string();
 		if !self.name.name.ends_with(&".".to_string()) {
 			self.name.name.push('.');
 		}
@@ -381,6 +404,10 @@ impl Default for RDATAmx {
 
 impl RecordRDATA for RDATAmx { 
 
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| self.weight == o.weight && record::name_eq( &self.target.fqdn, &o.target.fqdn ))
+	}
+
 	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
 		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
@@ -436,7 +463,10 @@ impl RecordRDATA for RDATAmx {
 
 	fn from_wire( &mut self, _size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
 		self.weight = crate::query::dns_read_int!(u16, buff, offset);
-		self.target.name = crate::query::read_qname(buff,offset).to_string();
+		self.target.name = crate::query::read_qname(buff,offset)?.to_string();
+		if !self.target.name.ends_with('.') {
+			self.target.name.push('.');
+		}
 		self.target.fqdn = self.target.name.clone();
 		Ok(())
 	}
@@ -459,57 +489,43 @@ impl std::fmt::Display for RDATAmx {
 }
 
 /**
- * DS Records
- * 
- * RFC-3658
+ * SRV Records
+ *
+ * RFC-2782
  */
-pub struct RDATAds {
-	pub key_tag : u16,
-	pub algorithm : u8,
-	pub digest_type: u8,
-	pub digest : Vec<u8>
+#[derive(Default, Clone)]
+pub struct RDATAsrv {
+	pub priority : u16,
+	pub weight : u16,
+	pub port : u16,
+	pub target : record::RecordName
 }
 
+impl RecordRDATA for RDATAsrv {
 
-impl Default for RDATAds {
-	fn default() -> Self {
-		Self {
-			key_tag: 0,
-			algorithm: 0,
-			digest_type: 0,
-			digest: Vec::new()
-		}
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| {
+			(self.priority, self.weight, self.port) == (o.priority, o.weight, o.port) && record::name_eq( &self.target.fqdn, &o.target.fqdn )
+		})
 	}
-}
-
-impl RecordRDATA for RDATAds { 
 
 	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
 		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
 
-		self.key_tag = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
-		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
-		self.digest_type = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
-
-		let mut digest = String::new();
-		loop {
-			match iter.next() {
-				Some(tok) => {
-					digest.push_str( &tok.token );
-				},
-				None => {
-					break;
-				}
-			}
-		}
+		self.priority = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.weight = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.port = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.target = record::RecordName::new( &tokenizer::ZoneToken::expect_non_white(&mut iter)? );
 
-		self.digest = base64::engine::general_purpose::STANDARD.decode(digest).unwrap();
+		tokenizer::ZoneToken::ignore_white(&mut iter)?;
 
 		Ok(())
 	}
 
-	fn origin( &mut self, _origin : &String ) { }
+	fn origin( &mut self, origin : &String ) {
+		self.target.origin(origin);
+	}
 
 	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
 		self
@@ -518,74 +534,73 @@ impl RecordRDATA for RDATAds {
 		self
 	}
 	fn clone_box(&self) -> Box<dyn RecordRDATA> {
-		Box::new(self.clone())
+		Box::new( self.clone() )
 	}
 
-	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		let start_offset : usize = offset.clone();
-		self.key_tag = crate::query::dns_read_int!(u16, buff, offset);
-		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
-		self.digest_type = crate::query::dns_read_int!(u8, buff, offset);
-		self.digest = crate::query::read_buff(buff, offset, size as usize - (*offset - start_offset));
-
+	fn from_wire( &mut self, _size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		self.priority = crate::query::dns_read_int!(u16, buff, offset);
+		self.weight = crate::query::dns_read_int!(u16, buff, offset);
+		self.port = crate::query::dns_read_int!(u16, buff, offset);
+		self.target.name = crate::query::read_qname(buff,offset)?.to_string();
+		if !self.target.name.ends_with('.') {
+			self.target.name.push('.');
+		}
+		self.target.fqdn = self.target.name.clone();
 		Ok(())
 	}
 
-
-
-}
-
-impl Clone for RDATAds { 
-	fn clone(&self) -> Self {
-		Self {
-			key_tag: self.key_tag,
-			algorithm: self.algorithm,
-			digest_type: self.digest_type,
-			digest: self.digest.clone()
-		}
-	}
 }
 
-
-impl std::fmt::Display for RDATAds { 
+impl std::fmt::Display for RDATAsrv {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-
-		let encoded: String = base64::engine::general_purpose::STANDARD.encode(self.digest.clone());
-
-		write!(f, "{}\t{}\t{}\t{}", self.key_tag, self.algorithm, self.digest_type, encoded)
+		write!(f, "{}\t{}\t{}\t{}", self.priority, self.weight, self.port, self.target)
 	}
 }
 
-
 /**
- * TXT Records
+ * CAA Records
+ *
+ * RFC-8659
  */
-pub struct RDATAtxt {
+#[derive(Default, Clone)]
+pub struct RDATAcaa {
+	pub flags : u8,
+	pub tag : String,
 	pub value : String
 }
 
-impl Default for RDATAtxt {
-	fn default() -> Self {
-		Self {
-			value: String::new()
-		}
+impl RDATAcaa {
+
+	/**
+	 * the issuer critical flag, a CA that doesn't know the tag must not issue
+	 */
+	pub fn critical( &self ) -> bool {
+		self.flags & 0x80 != 0
 	}
+
 }
 
+impl RecordRDATA for RDATAcaa {
 
-impl RecordRDATA for RDATAtxt { 
 	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
-		self.value.clear();
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
 
-		for tok in tokens {
-			self.value.push_str( &tok.token );
+		self.flags = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+
+		// a tag is 1 to 15 letters and digits (RFC-8659 section 4.1)
+		self.tag = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
+		if self.tag.is_empty() || self.tag.len() > 15 || !self.tag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+			return Err(format!("invalid CAA tag '{}', expected 1 to 15 letters and digits", self.tag));
 		}
 
-		Ok(())
+		// the value is usually quoted but doesn't have to be
+		self.value = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
 
-	}
+		tokenizer::ZoneToken::ignore_white(&mut iter)?;
 
+		Ok(())
+	}
 
 	fn origin( &mut self, _origin : &String ) { }
 
@@ -596,107 +611,102 @@ impl RecordRDATA for RDATAtxt {
 		self
 	}
 	fn clone_box(&self) -> Box<dyn RecordRDATA> {
-		Box::new(self.clone())
+		Box::new( self.clone() )
 	}
 
 	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		let value = crate::query::read_buff(buff, offset, size as usize);
-		self.value = String::with_capacity( size as usize );
-		for c in value {
-			self.value.push(c as char);
+
+		if size < 2 {
+			return Err(format!("CAA of {} bytes is shorter than its flags and tag length", size));
 		}
-		Ok(())
-	}
 
-}
+		self.flags = crate::query::dns_read_int!(u8, buff, offset);
+		let tag_len = crate::query::dns_read_int!(u8, buff, offset) as usize;
 
-impl Clone for RDATAtxt { 
-	fn clone(&self) -> Self {
-		Self {
-			value : self.value.clone()
+		if tag_len > size as usize - 2 {
+			return Err(format!("CAA tag of {} bytes is longer than the {} bytes of the record", tag_len, size));
 		}
-	}
-}
 
-impl std::fmt::Display for RDATAtxt { 
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.tag = crate::query::read_buff(buff, offset, tag_len)?.iter().map(|c| *c as char).collect();
+		self.value = crate::query::read_buff(buff, offset, size as usize - 2 - tag_len)?.iter().map(|c| *c as char).collect();
 
-		if self.value.len() > 255 {
+		Ok(())
+	}
 
-			write!(f, "(\n")?;
+}
 
-			let mut x : usize = 0;
-			let max_sz : usize = 255;
-			while x < self.value.len() {
-				let s :&str;
-				if x + max_sz < self.value.len() {
-					s = &self.value[x..(x+max_sz)];
-				} else {
-					s = &self.value[x..];
-				}
-				
-				write!(f, "\t\"{}\"\n", tokenizer::ZoneLines::escape( &s.to_string() ))?;
-				x += max_sz;
-			}
+impl std::fmt::Display for RDATAcaa {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}\t{}\t\"{}\"", self.flags, self.tag, tokenizer::ZoneLines::escape( &self.value ))
+	}
+}
 
-			write!(f, ")")
+/**
+ * read a <character-string> (RFC-1035 section 3.3), a length byte then that many bytes. end
+ * is where the RDATA ends, a string can't run past it
+ */
+fn read_character_string( buff : &[u8], offset : &mut usize, end : usize ) -> Result<String, String> {
 
-		} else {
-			write!(f, "\"{}\"", tokenizer::ZoneLines::escape( &self.value ))
-		}
+	if *offset >= end {
+		return Err("character-string is past the end of the record".to_string());
+	}
 
+	let len = crate::query::dns_read_int!(u8, buff, offset) as usize;
+	if *offset + len > end {
+		return Err(format!("character-string of {} bytes is past the end of the record", len));
 	}
-}
 
+	Ok( crate::query::read_buff(buff, offset, len)?.iter().map(|c| *c as char).collect() )
+}
 
 /**
- * SOA Records
+ * NAPTR Records
+ *
+ * RFC-3403
  */
-pub struct RDATAsoa {
-	pub mname : record::RecordName,
-	pub rname : record::RecordName,
-	pub serial : u32,
-	pub refresh : u32,
-	pub retry : u32,
-	pub expire : u32,
-	pub min : u32
+#[derive(Default, Clone)]
+pub struct RDATAnaptr {
+	pub order : u16,
+	pub preference : u16,
+	pub flags : String,
+	pub services : String,
+	pub regexp : String,
+	pub replacement : record::RecordName
 }
 
-impl Default for RDATAsoa {
-	fn default() -> Self {
-		Self {
-			mname: Default::default(),
-			rname: Default::default(),
-			serial: 0,
-			refresh: 0,
-			retry: 0,
-			expire: 0,
-			min: 0
-		}
+impl RecordRDATA for RDATAnaptr {
+
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| {
+			(self.order, self.preference, &self.flags, &self.services, &self.regexp) == (o.order, o.preference, &o.flags, &o.services, &o.regexp)
+				&& record::name_eq( &self.replacement.fqdn, &o.replacement.fqdn )
+		})
 	}
-}
 
-impl RecordRDATA for RDATAsoa { 
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
-	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result<(), String> {
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
 
-		let mut iter = tokens.iter();
+		self.order = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.preference = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
 
-		self.mname.name = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
-		self.rname.name = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
-		self.serial = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
-		self.refresh = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
-		self.retry = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
-		self.expire = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
-		self.min = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
-		
-		Ok(())
+		// the strings are usually quoted, an empty one has to be
+		for field in [ &mut self.flags, &mut self.services, &mut self.regexp ] {
+			*field = match iter.next() {
+				Some(tok) => { tok.token.clone() },
+				None => { return Err("Expected token, got EOL".to_string()); }
+			};
+		}
+
+		self.replacement = record::RecordName::new( &tokenizer::ZoneToken::expect_non_white(&mut iter)? );
 
+		tokenizer::ZoneToken::ignore_white(&mut iter)?;
+
+		Ok(())
 	}
 
-	fn origin( &mut self, origin : &String ) { 
-		self.mname.origin(origin);
-		self.rname.origin(origin);
+	fn origin( &mut self, origin : &String ) {
+		self.replacement.origin(origin);
 	}
 
 	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
@@ -709,128 +719,1052 @@ impl RecordRDATA for RDATAsoa {
 		Box::new( self.clone() )
 	}
 
-	fn from_wire( &mut self, _size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		
-		self.mname.name = crate::query::read_qname(buff, offset).to_string();
-		self.mname.fqdn = self.mname.name.clone();
-
-		self.rname.name = crate::query::read_qname(buff, offset).to_string();
-		self.rname.fqdn = self.mname.name.clone();
-
-		self.serial = crate::query::dns_read_int!(u32, buff, offset );
-		self.refresh = crate::query::dns_read_int!(u32, buff, offset );
-		self.retry = crate::query::dns_read_int!(u32, buff, offset );
-		self.expire = crate::query::dns_read_int!(u32, buff, offset );
-		self.min = crate::query::dns_read_int!(u32, buff, offset );
-
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		let end = *offset + size as usize;
+		self.order = crate::query::dns_read_int!(u16, buff, offset);
+		self.preference = crate::query::dns_read_int!(u16, buff, offset);
+		self.flags = read_character_string(buff, offset, end)?;
+		self.services = read_character_string(buff, offset, end)?;
+		self.regexp = read_character_string(buff, offset, end)?;
+		self.replacement.name = crate::query::read_qname(buff,offset)?.to_string();
+		if !self.replacement.name.ends_with('.') {
+			self.replacement.name.push('.');
+		}
+		self.replacement.fqdn = self.replacement.name.clone();
 		Ok(())
 	}
 
-
-
-}
-
-impl Clone for RDATAsoa { 
-	fn clone(&self) -> Self {
-		Self {
-			mname: self.mname.clone(),
-			rname: self.rname.clone(),
-			serial: self.serial,
-			refresh: self.refresh,
-			retry: self.retry,
-			expire: self.expire,
-			min: self.min
-		}
-	}
 }
 
-impl std::fmt::Display for RDATAsoa { 
+impl std::fmt::Display for RDATAnaptr {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-
-		write!(f, "{} {} {} {} {} {} {} ", self.mname, self.rname, self.serial, self.refresh, self.retry, self.expire, self.min)
-
+		write!(f, "{} {} \"{}\" \"{}\" \"{}\" {}", self.order, self.preference,
+			tokenizer::ZoneLines::escape( &self.flags ),
+			tokenizer::ZoneLines::escape( &self.services ),
+			tokenizer::ZoneLines::escape( &self.regexp ),
+			self.replacement)
 	}
 }
 
 /**
- * RDATAdnskey Records
+ * TLSA Records
+ *
+ * RFC-6698
  */
-pub struct RDATAdnskey {
-	pub flags : u16,
-	pub protocol : u8,
-	pub algorithm : u8,
-	pub public_key : Vec<u8>
+#[derive(Default, Clone)]
+pub struct RDATAtlsa {
+	pub usage : u8,
+	pub selector : u8,
+	pub matching_type : u8,
+	pub cert_data : Vec<u8>
 }
 
-impl Default for RDATAdnskey {
-	fn default() -> Self {
-		Self {
-			flags: 0,
-			protocol: 0,
-			algorithm: 0,
-			public_key: Vec::new()
-		}
-	}
-}
-
-impl RecordRDATA for RDATAdnskey { 
+impl RecordRDATA for RDATAtlsa {
 
-	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result<(), String> {
-
-		let mut iter = tokens.iter();
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
 
-		self.flags = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
-		self.protocol = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
-		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?; 
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
 
-		let mut public_key =  String::new();
-		
+		self.usage = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.selector = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.matching_type = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
 
-		loop {
-			match iter.next() {
-				Some(m) =>{ 
-					if m.token_type != tokenizer::TokenType::TypeWhite {
-						public_key.push_str(&m.token);
-						
-					}
-				}
-				None => { break ; }
-			}
+		// the data is hex like a DS digest (RFC-6698 section 2.2) and may be split with whitespace
+		let data : String = iter.map(|tok| tok.token.as_str()).collect();
+		self.cert_data = match crate::query::decode_hex(&data) {
+			Some(m) => { m },
+			None => { return Err(format!("invalid TLSA certificate association data '{}', expected hex", data)); }
 		};
 
-		self.public_key = base64::engine::general_purpose::STANDARD.decode(public_key).unwrap();
-
 		Ok(())
-
 	}
 
-
 	fn origin( &mut self, _origin : &String ) { }
 
 	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
 		self
 	}
-
 	fn as_any( &self ) -> &dyn std::any::Any {
 		self
 	}
-
-	fn clone_box (&self) -> Box<dyn RecordRDATA> {
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
 		Box::new( self.clone() )
 	}
 
 	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
-		let offset_start = offset.clone();
-		self.flags = crate::query::dns_read_int!(u16, buff, offset);
-		self.protocol = crate::query::dns_read_int!(u8, buff, offset);
-		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
-		self.public_key = crate::query::read_buff(buff, offset, size as usize  - (*offset - offset_start));
+
+		if size < 3 {
+			return Err(format!("TLSA of {} bytes is shorter than its fixed fields", size));
+		}
+
+		self.usage = crate::query::dns_read_int!(u8, buff, offset);
+		self.selector = crate::query::dns_read_int!(u8, buff, offset);
+		self.matching_type = crate::query::dns_read_int!(u8, buff, offset);
+		self.cert_data = crate::query::read_buff(buff, offset, size as usize - 3)?;
 		Ok(())
 	}
 
 }
 
-impl Clone for RDATAdnskey { 
+impl std::fmt::Display for RDATAtlsa {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		let encoded: String = self.cert_data.iter().map(|b| format!("{:02X}", b)).collect();
+
+		write!(f, "{}\t{}\t{}\t{}", self.usage, self.selector, self.matching_type, encoded)
+	}
+}
+
+/**
+ * SSHFP Records
+ *
+ * RFC-4255
+ */
+#[derive(Default, Clone)]
+pub struct RDATAsshfp {
+	pub algorithm : u8,
+	pub fp_type : u8,
+	pub fingerprint : Vec<u8>
+}
+
+impl RecordRDATA for RDATAsshfp {
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
+
+		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.fp_type = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+
+		// the fingerprint is hex and is split into several tokens when it is wrapped in ( )
+		let fingerprint : String = iter.map(|tok| tok.token.as_str()).collect();
+		self.fingerprint = match crate::query::decode_hex(&fingerprint) {
+			Some(m) => { m },
+			None => { return Err(format!("invalid SSHFP fingerprint '{}', expected hex", fingerprint)); }
+		};
+
+		Ok(())
+	}
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+
+		if size < 2 {
+			return Err(format!("SSHFP of {} bytes is shorter than its fixed fields", size));
+		}
+
+		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
+		self.fp_type = crate::query::dns_read_int!(u8, buff, offset);
+		self.fingerprint = crate::query::read_buff(buff, offset, size as usize - 2)?;
+		Ok(())
+	}
+
+}
+
+impl std::fmt::Display for RDATAsshfp {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		let encoded: String = self.fingerprint.iter().map(|b| format!("{:02X}", b)).collect();
+
+		write!(f, "{}\t{}\t{}", self.algorithm, self.fp_type, encoded)
+	}
+}
+
+/**
+ * the SvcParamKeys with a name (RFC-9460 section 14.3.2), the index is the key
+ */
+const SVC_PARAM_KEYS : [&str; 7] = [ "mandatory", "alpn", "no-default-alpn", "port", "ipv4hint", "ech", "ipv6hint" ];
+
+/**
+ * SVCB and HTTPS Records, HTTPS is SVCB for the https scheme and has the same RDATA
+ *
+ * RFC-9460
+ */
+#[derive(Default, Clone)]
+pub struct RDATAsvcb {
+	pub priority : u16,
+	pub target : record::RecordName,
+	/** the SvcParams in the order of their keys, each is the key and its wire format value */
+	pub params : Vec<(u16, Vec<u8>)>
+}
+
+impl RDATAsvcb {
+
+	/**
+	 * the wire format value of the param with the key
+	 */
+	pub fn param( &self, key : u16 ) -> Option<&[u8]> {
+		self.params.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_slice())
+	}
+
+	/**
+	 * the name of the key, keyNNNN when it has none
+	 */
+	pub fn key_name( key : u16 ) -> String {
+		match SVC_PARAM_KEYS.get(key as usize) {
+			Some(name) => { name.to_string() },
+			None => { format!("key{}", key) }
+		}
+	}
+
+	/**
+	 * the key for a name or keyNNNN
+	 */
+	pub fn key_from_name( name : &str ) -> Option<u16> {
+		match SVC_PARAM_KEYS.iter().position(|k| k.eq_ignore_ascii_case(name)) {
+			Some(m) => { Some(m as u16) },
+			None => { name.strip_prefix("key").and_then(|n| n.parse::<u16>().ok()) }
+		}
+	}
+
+	/**
+	 * the presentation format of a value, an error when a known key's value is malformed
+	 */
+	fn value_to_string( key : u16, value : &[u8] ) -> Result<String, String> {
+
+		let malformed = || format!("malformed {} of {} bytes", Self::key_name(key), value.len());
+
+		match key {
+			0 => {
+				if !value.len().is_multiple_of(2) {
+					return Err(malformed());
+				}
+				Ok( value.chunks(2).map(|c| Self::key_name(u16::from_be_bytes([ c[0], c[1] ]))).collect::<Vec<String>>().join(",") )
+			},
+			1 => {
+				let mut ids : Vec<String> = Vec::new();
+				let mut offset = 0;
+				while offset < value.len() {
+					let len = value[offset] as usize;
+					if len == 0 || offset + 1 + len > value.len() {
+						return Err(malformed());
+					}
+					ids.push( value[offset + 1..offset + 1 + len].iter().map(|c| *c as char).collect() );
+					offset += 1 + len;
+				}
+				Ok( ids.join(",") )
+			},
+			2 => {
+				if !value.is_empty() {
+					return Err(malformed());
+				}
+				Ok( String::new() )
+			},
+			3 => {
+				if value.len() != 2 {
+					return Err(malformed());
+				}
+				Ok( u16::from_be_bytes([ value[0], value[1] ]).to_string() )
+			},
+			4 => {
+				if value.is_empty() || !value.len().is_multiple_of(4) {
+					return Err(malformed());
+				}
+				Ok( value.chunks(4).map(|c| std::net::Ipv4Addr::new(c[0], c[1], c[2], c[3]).to_string()).collect::<Vec<String>>().join(",") )
+			},
+			5 => {
+				Ok( base64::engine::general_purpose::STANDARD.encode(value) )
+			},
+			6 => {
+				if value.is_empty() || !value.len().is_multiple_of(16) {
+					return Err(malformed());
+				}
+				Ok( value.chunks(16).map(|c| std::net::Ipv6Addr::from( <[u8; 16]>::try_from(c).unwrap() ).to_string()).collect::<Vec<String>>().join(",") )
+			},
+			_ => {
+				// kept as one unquoted token, so a space or a quote is escaped like the characters that aren't printable
+				let text : String = value.iter().map(|c| *c as char).collect();
+				Ok( tokenizer::ZoneLines::escape(&text).replace("\\\"", "\\034").replace(' ', "\\032") )
+			}
+		}
+	}
+
+	/**
+	 * the wire format of a value in the presentation format
+	 */
+	fn value_from_string( key : u16, text : &str ) -> Result<Vec<u8>, String> {
+
+		let invalid = |e : String| format!("invalid {} '{}' {}", Self::key_name(key), text, e);
+
+		match key {
+			0 => {
+				let mut rval : Vec<u8> = Vec::new();
+				for name in text.split(',') {
+					match Self::key_from_name(name) {
+						Some(k) => { rval.extend_from_slice( &k.to_be_bytes() ); },
+						None => { return Err(invalid(format!("unknown key {}", name))); }
+					}
+				}
+				Ok(rval)
+			},
+			1 => {
+				let mut rval : Vec<u8> = Vec::new();
+				for id in text.split(',') {
+					if id.is_empty() || id.len() > 255 {
+						return Err(invalid("an id is empty or too long".to_string()));
+					}
+					rval.push( id.len() as u8 );
+					rval.extend_from_slice( id.as_bytes() );
+				}
+				Ok(rval)
+			},
+			2 => {
+				if !text.is_empty() {
+					return Err(invalid("it has no value".to_string()));
+				}
+				Ok( Vec::new() )
+			},
+			3 => {
+				text.parse::<u16>().map(|p| p.to_be_bytes().to_vec()).map_err(|e| invalid(e.to_string()))
+			},
+			4 => {
+				let mut rval : Vec<u8> = Vec::new();
+				for ip in text.split(',') {
+					rval.extend_from_slice( &ip.parse::<std::net::Ipv4Addr>().map_err(|e| invalid(e.to_string()))?.octets() );
+				}
+				Ok(rval)
+			},
+			5 => {
+				base64::engine::general_purpose::STANDARD.decode(text).map_err(|e| invalid(e.to_string()))
+			},
+			6 => {
+				let mut rval : Vec<u8> = Vec::new();
+				for ip in text.split(',') {
+					rval.extend_from_slice( &ip.parse::<std::net::Ipv6Addr>().map_err(|e| invalid(e.to_string()))?.octets() );
+				}
+				Ok(rval)
+			},
+			_ => {
+				Ok( tokenizer::ZoneLines::unescape(&text.to_string()).chars().map(|c| c as u8).collect() )
+			}
+		}
+	}
+
+}
+
+impl RecordRDATA for RDATAsvcb {
+
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| {
+			self.priority == o.priority && self.params == o.params && record::name_eq( &self.target.fqdn, &o.target.fqdn )
+		})
+	}
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
+
+		self.priority = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.target = record::RecordName::new( &tokenizer::ZoneToken::expect_non_white(&mut iter)? );
+		self.params.clear();
+
+		// each param is key=value or a key without a value, a quoted value is a string token
+		// of its own after the key=
+		let mut iter = iter.peekable();
+		while let Some(tok) = iter.next() {
+			let (name, mut value) = tok.token.split_once('=').unwrap_or( (tok.token.as_str(), "") );
+			if tok.token.ends_with('=') {
+				if let Some(quoted) = iter.next_if(|t| t.token_type == tokenizer::TokenType::TypeString) {
+					value = quoted.token.as_str();
+				}
+			}
+			let key = match Self::key_from_name(name) {
+				Some(m) => { m },
+				None => { return Err(format!("unknown SvcParamKey '{}' at line {}", name, tok.line)); }
+			};
+			if self.param(key).is_some() {
+				return Err(format!("SvcParamKey {} is given more than once at line {}", name, tok.line));
+			}
+			self.params.push( (key, Self::value_from_string(key, value)?) );
+		}
+
+		// on the wire the keys are in order (RFC-9460 section 2.2)
+		self.params.sort_by_key(|(k, _)| *k);
+
+		Ok(())
+	}
+
+	fn origin( &mut self, origin : &String ) {
+		self.target.origin(origin);
+	}
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+
+		let end = *offset + size as usize;
+
+		self.priority = crate::query::dns_read_int!(u16, buff, offset);
+		self.target.name = crate::query::read_qname(buff,offset)?.to_string();
+		if !self.target.name.ends_with('.') {
+			self.target.name.push('.');
+		}
+		self.target.fqdn = self.target.name.clone();
+		self.params.clear();
+
+		while *offset < end {
+
+			if *offset + 4 > end {
+				return Err("SvcParam is past the end of the record".to_string());
+			}
+
+			let key = crate::query::dns_read_int!(u16, buff, offset);
+			let len = crate::query::dns_read_int!(u16, buff, offset) as usize;
+
+			if *offset + len > end {
+				return Err(format!("{} of {} bytes is past the end of the record", Self::key_name(key), len));
+			}
+
+			if self.params.last().is_some_and(|(last, _)| *last >= key) {
+				return Err(format!("SvcParamKey {} is out of order", Self::key_name(key)));
+			}
+
+			let value = crate::query::read_buff(buff, offset, len)?;
+			Self::value_to_string(key, &value)?;
+			self.params.push( (key, value) );
+		}
+
+		if *offset > end {
+			return Err(format!("SVCB of {} bytes is shorter than its target", size));
+		}
+
+		Ok(())
+	}
+
+}
+
+impl std::fmt::Display for RDATAsvcb {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		write!(f, "{} {}", self.priority, self.target)?;
+
+		for (key, value) in &self.params {
+			match Self::value_to_string(*key, value) {
+				Ok(text) if text.is_empty() => { write!(f, " {}", Self::key_name(*key))?; },
+				Ok(text) => { write!(f, " {}={}", Self::key_name(*key), text)?; },
+				// a malformed value of a known key is shown as the bytes it had
+				Err(_) => { write!(f, " key{}={}", key, Self::value_to_string(u16::MAX, value).unwrap_or_default())?; }
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/**
+ * URI Records
+ *
+ * RFC-7553
+ */
+#[derive(Default, Clone)]
+pub struct RDATAuri {
+	pub priority : u16,
+	pub weight : u16,
+	pub target : String
+}
+
+impl RecordRDATA for RDATAuri {
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
+
+		self.priority = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.weight = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+
+		// the target is always quoted (RFC-7553 section 4.4)
+		let target = match iter.next() {
+			Some(tok) if tok.token_type == tokenizer::TokenType::TypeString => { tok },
+			Some(tok) => { return Err(format!("expected a quoted URI target, got '{}' at line {}", tok.token, tok.line)); },
+			None => { return Err("expected a quoted URI target".to_string()); }
+		};
+		if target.token.is_empty() {
+			return Err(format!("empty URI target at line {}", target.line));
+		}
+		self.target = target.token.clone();
+
+		tokenizer::ZoneToken::ignore_white(&mut iter)?;
+
+		Ok(())
+	}
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+
+		if size < 5 {
+			return Err(format!("URI of {} bytes is shorter than its priority, weight and a target", size));
+		}
+
+		self.priority = crate::query::dns_read_int!(u16, buff, offset);
+		self.weight = crate::query::dns_read_int!(u16, buff, offset);
+
+		// the target isn't a character-string, it is the rest of the record
+		self.target = crate::query::read_buff(buff, offset, size as usize - 4)?.iter().map(|c| *c as char).collect();
+
+		Ok(())
+	}
+
+}
+
+impl std::fmt::Display for RDATAuri {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}\t{}\t\"{}\"", self.priority, self.weight, tokenizer::ZoneLines::escape( &self.target ))
+	}
+}
+
+/**
+ * OPT pseudo record of EDNS, the UDP payload size and the flags are in the class and the ttl
+ * of the record so they are set from there after the options are read
+ *
+ * RFC-6891
+ */
+#[derive(Default, Clone)]
+pub struct RDATAopt {
+	/** the largest UDP payload the sender can take, from the class */
+	pub udp_size : u16,
+	/** upper 8 bits of the extended rcode, from the ttl */
+	pub extended_rcode : u8,
+	/** EDNS version, from the ttl */
+	pub version : u8,
+	/** the DO bit (RFC-3225), from the ttl */
+	pub dnssec_ok : bool,
+	/** each option code and its data */
+	pub options : Vec<(u16, Vec<u8>)>
+}
+
+impl RDATAopt {
+
+	/**
+	 * set the fields carried in the class and ttl of the record
+	 */
+	pub fn set_header( &mut self, class : u16, ttl : u32 ) {
+		self.udp_size = class;
+		self.extended_rcode = (ttl >> 24) as u8;
+		self.version = (ttl >> 16) as u8;
+		self.dnssec_ok = ttl & 0x8000 != 0;
+	}
+
+	/**
+	 * the data of the option with the code
+	 */
+	pub fn option( &self, code : u16 ) -> Option<&[u8]> {
+		self.options.iter().find(|(c, _)| *c == code).map(|(_, v)| v.as_slice())
+	}
+
+}
+
+impl RecordRDATA for RDATAopt {
+
+	fn from_tokens( &mut self, _tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+		Err("OPT is a pseudo record of a message, it can't be in a zone".to_string())
+	}
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+
+		let end = *offset + size as usize;
+		self.options.clear();
+
+		while *offset < end {
+
+			if *offset + 4 > end {
+				return Err("EDNS option is past the end of the OPT record".to_string());
+			}
+
+			let code = crate::query::dns_read_int!(u16, buff, offset);
+			let len = crate::query::dns_read_int!(u16, buff, offset) as usize;
+
+			if *offset + len > end {
+				return Err(format!("EDNS option {} of {} bytes is past the end of the OPT record", code, len));
+			}
+
+			self.options.push( (code, crate::query::read_buff(buff, offset, len)?) );
+		}
+
+		Ok(())
+	}
+
+}
+
+impl std::fmt::Display for RDATAopt {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		write!(f, "udp {} version {}", self.udp_size, self.version)?;
+		if self.extended_rcode != 0 {
+			write!(f, " rcode {}", self.extended_rcode)?;
+		}
+		if self.dnssec_ok {
+			write!(f, " do")?;
+		}
+		for (code, data) in &self.options {
+			write!(f, " option{}={}", code, data.iter().map(|b| format!("{:02X}", b)).collect::<String>())?;
+		}
+
+		Ok(())
+	}
+}
+
+/**
+ * DS Records
+ * 
+ * RFC-3658
+ */
+pub struct RDATAds {
+	pub key_tag : u16,
+	pub algorithm : u8,
+	pub digest_type: u8,
+	pub digest : Vec<u8>
+}
+
+
+impl Default for RDATAds {
+	fn default() -> Self {
+		Self {
+			key_tag: 0,
+			algorithm: 0,
+			digest_type: 0,
+			digest: Vec::new()
+		}
+	}
+}
+
+impl RecordRDATA for RDATAds { 
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+
+		let mut iter: std::slice::Iter<&tokenizer::ZoneToken> = tokens.iter();
+
+		self.key_tag = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.digest_type = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+
+		let mut digest = String::new();
+		loop {
+			match iter.next() {
+				Some(tok) => {
+					digest.push_str( &tok.token );
+				},
+				None => {
+					break;
+				}
+			}
+		}
+
+		// the digest is hex (RFC-4034 section 5.3) and may be split with whitespace
+		self.digest = match crate::query::decode_hex(&digest) {
+			Some(m) => { m },
+			None => { return Err(format!("invalid digest '{}', expected hex", digest)); }
+		};
+
+		Ok(())
+	}
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new(self.clone())
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		let start_offset : usize = offset.clone();
+		self.key_tag = crate::query::dns_read_int!(u16, buff, offset);
+		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
+		self.digest_type = crate::query::dns_read_int!(u8, buff, offset);
+		if size < 4 {
+			return Err(format!("DS of {} bytes is shorter than its fixed fields", size));
+		}
+		self.digest = crate::query::read_buff(buff, offset, size as usize - (*offset - start_offset))?;
+
+		Ok(())
+	}
+
+
+
+}
+
+impl Clone for RDATAds { 
+	fn clone(&self) -> Self {
+		Self {
+			key_tag: self.key_tag,
+			algorithm: self.algorithm,
+			digest_type: self.digest_type,
+			digest: self.digest.clone()
+		}
+	}
+}
+
+
+impl std::fmt::Display for RDATAds { 
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		let encoded: String = self.digest.iter().map(|b| format!("{:02X}", b)).collect();
+
+		write!(f, "{}\t{}\t{}\t{}", self.key_tag, self.algorithm, self.digest_type, encoded)
+	}
+}
+
+
+/**
+ * TXT Records, the RDATA is one or more <character-string>s which are kept apart, a long
+ * SPF record or DKIM key is split over several of them
+ */
+pub struct RDATAtxt {
+	pub segments : Vec<String>
+}
+
+/** longest <character-string>, its length is one byte */
+const MAX_CHARACTER_STRING : usize = 255;
+
+impl RDATAtxt {
+
+	/**
+	 * the segments joined together, the way a TXT record is read by SPF and DKIM
+	 */
+	pub fn text( &self ) -> String {
+		self.segments.concat()
+	}
+
+}
+
+impl Default for RDATAtxt {
+	fn default() -> Self {
+		Self {
+			segments: Vec::new()
+		}
+	}
+}
+
+
+impl RecordRDATA for RDATAtxt { 
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result< (), String> {
+
+		self.segments.clear();
+
+		// each token is a segment, one too long for a <character-string> is split up
+		for tok in tokens {
+			let chars : Vec<char> = tok.token.chars().collect();
+			if chars.is_empty() {
+				self.segments.push( String::new() );
+			}
+			for chunk in chars.chunks(MAX_CHARACTER_STRING) {
+				self.segments.push( chunk.iter().collect() );
+			}
+		}
+
+		Ok(())
+
+	}
+
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new(self.clone())
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+
+		let end = *offset + size as usize;
+		self.segments.clear();
+
+		while *offset < end {
+			self.segments.push( read_character_string(buff, offset, end)? );
+		}
+
+		Ok(())
+	}
+
+}
+
+impl Clone for RDATAtxt { 
+	fn clone(&self) -> Self {
+		Self {
+			segments : self.segments.clone()
+		}
+	}
+}
+
+impl std::fmt::Display for RDATAtxt { 
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		if self.segments.is_empty() {
+			return write!(f, "\"\"");
+		}
+
+		if self.segments.iter().map(|s| s.chars().count()).sum::<usize>() > MAX_CHARACTER_STRING {
+
+			write!(f, "(\n")?;
+
+			for s in &self.segments {
+				write!(f, "\t\"{}\"\n", tokenizer::ZoneLines::escape( s ))?;
+			}
+
+			write!(f, ")")
+
+		} else {
+			let quoted : Vec<String> = self.segments.iter().map(|s| format!("\"{}\"", tokenizer::ZoneLines::escape( s ))).collect();
+			write!(f, "{}", quoted.join(" "))
+		}
+
+	}
+}
+
+
+/**
+ * SOA Records
+ */
+pub struct RDATAsoa {
+	pub mname : record::RecordName,
+	pub rname : record::RecordName,
+	pub serial : u32,
+	pub refresh : u32,
+	pub retry : u32,
+	pub expire : u32,
+	pub min : u32
+}
+
+impl Default for RDATAsoa {
+	fn default() -> Self {
+		Self {
+			mname: Default::default(),
+			rname: Default::default(),
+			serial: 0,
+			refresh: 0,
+			retry: 0,
+			expire: 0,
+			min: 0
+		}
+	}
+}
+
+impl RecordRDATA for RDATAsoa { 
+
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| {
+			(self.serial, self.refresh, self.retry, self.expire, self.min) == (o.serial, o.refresh, o.retry, o.expire, o.min)
+				&& record::name_eq( &self.mname.fqdn, &o.mname.fqdn )
+				&& record::name_eq( &self.rname.fqdn, &o.rname.fqdn )
+		})
+	}
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result<(), String> {
+
+		let mut iter = tokens.iter();
+
+		self.mname.name = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
+		self.rname.name = tokenizer::ZoneToken::expect_non_white(&mut iter)?;
+		self.serial = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+		self.refresh = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+		self.retry = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+		self.expire = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+		self.min = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+		
+		Ok(())
+
+	}
+
+	fn origin( &mut self, origin : &String ) { 
+		self.mname.origin(origin);
+		self.rname.origin(origin);
+	}
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+	fn clone_box(&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, _size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		
+		self.mname.name = crate::query::read_qname(buff, offset)?.to_string();
+		if !self.mname.name.ends_with('.') {
+			self.mname.name.push('.');
+		}
+		self.mname.fqdn = self.mname.name.clone();
+
+		self.rname.name = crate::query::read_qname(buff, offset)?.to_string();
+		if !self.rname.name.ends_with('.') {
+			self.rname.name.push('.');
+		}
+		self.rname.fqdn = self.rname.name.clone();
+
+		self.serial = crate::query::dns_read_int!(u32, buff, offset );
+		self.refresh = crate::query::dns_read_int!(u32, buff, offset );
+		self.retry = crate::query::dns_read_int!(u32, buff, offset );
+		self.expire = crate::query::dns_read_int!(u32, buff, offset );
+		self.min = crate::query::dns_read_int!(u32, buff, offset );
+
+		Ok(())
+	}
+
+
+
+}
+
+impl Clone for RDATAsoa { 
+	fn clone(&self) -> Self {
+		Self {
+			mname: self.mname.clone(),
+			rname: self.rname.clone(),
+			serial: self.serial,
+			refresh: self.refresh,
+			retry: self.retry,
+			expire: self.expire,
+			min: self.min
+		}
+	}
+}
+
+impl std::fmt::Display for RDATAsoa { 
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		write!(f, "{} {} {} {} {} {} {} ", self.mname, self.rname, self.serial, self.refresh, self.retry, self.expire, self.min)
+
+	}
+}
+
+/**
+ * RDATAdnskey Records
+ */
+pub struct RDATAdnskey {
+	pub flags : u16,
+	pub protocol : u8,
+	pub algorithm : u8,
+	pub public_key : Vec<u8>
+}
+
+impl Default for RDATAdnskey {
+	fn default() -> Self {
+		Self {
+			flags: 0,
+			protocol: 0,
+			algorithm: 0,
+			public_key: Vec::new()
+		}
+	}
+}
+
+impl RecordRDATA for RDATAdnskey { 
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result<(), String> {
+
+		let mut iter = tokens.iter();
+
+		self.flags = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+		self.protocol = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?; 
+
+		let mut public_key =  String::new();
+		
+
+		loop {
+			match iter.next() {
+				Some(m) =>{ 
+					if m.token_type != tokenizer::TokenType::TypeWhite {
+						public_key.push_str(&m.token);
+						
+					}
+				}
+				None => { break ; }
+			}
+		};
+
+		self.public_key = match base64::engine::general_purpose::STANDARD.decode(public_key) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("invalid public key {}", e)); }
+		};
+
+		Ok(())
+
+	}
+
+
+	fn origin( &mut self, _origin : &String ) { }
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+
+	fn clone_box (&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		let offset_start = offset.clone();
+		self.flags = crate::query::dns_read_int!(u16, buff, offset);
+		self.protocol = crate::query::dns_read_int!(u8, buff, offset);
+		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
+		if size < 4 {
+			return Err(format!("DNSKEY of {} bytes is shorter than its fixed fields", size));
+		}
+		self.public_key = crate::query::read_buff(buff, offset, size as usize  - (*offset - offset_start))?;
+		Ok(())
+	}
+
+}
+
+impl Clone for RDATAdnskey { 
 	fn clone(&self) -> Self {
 		Self{
 			flags: self.flags,
@@ -851,6 +1785,214 @@ impl std::fmt::Display for RDATAdnskey {
 	}
 }
 
+/**
+ * RDATArrsig Records, RFC-4034 section 3
+ */
+#[derive(Default)]
+pub struct RDATArrsig {
+	pub type_covered : u16,
+	pub algorithm : u8,
+	pub labels : u8,
+	pub original_ttl : u32,
+	pub sig_expiration : u32,
+	pub sig_inception : u32,
+	pub key_tag : u16,
+	pub signer_name : record::RecordName,
+	pub signature : Vec<u8>
+}
+
+impl RDATArrsig {
+
+	/**
+	 * the type of the RRset this signature covers
+	 */
+	pub fn covered( &self ) -> record::RecordType {
+		record::RecordType::from_u16(&self.type_covered)
+	}
+
+}
+
+impl RecordRDATA for RDATArrsig {
+
+	fn rdata_eq( &self, other : &dyn RecordRDATA ) -> bool {
+		other.as_any().downcast_ref::<Self>().is_some_and(|o| {
+			(self.type_covered, self.algorithm, self.labels, self.original_ttl) == (o.type_covered, o.algorithm, o.labels, o.original_ttl)
+				&& (self.sig_expiration, self.sig_inception, self.key_tag) == (o.sig_expiration, o.sig_inception, o.key_tag)
+				&& self.signature == o.signature
+				&& record::name_eq( &self.signer_name.fqdn, &o.signer_name.fqdn )
+		})
+	}
+
+	fn from_tokens( &mut self, tokens : &Vec<&tokenizer::ZoneToken> ) -> Result<(), String> {
+
+		let mut iter = tokens.iter();
+
+		self.type_covered = match iter.next() {
+			Some(tok) => {
+				let rt = record::RecordType::from_string(&tok.token);
+				if rt == record::RecordType::RecordTypeOther {
+					return Err(format!("unknown type covered '{}' at line {}", tok.token, tok.line));
+				}
+				rt as u16
+			},
+			None => { return Err("Expected token, got EOL".to_string()); }
+		};
+
+		self.algorithm = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.labels = tokenizer::ZoneToken::expect_int::<u8>(&mut iter)?;
+		self.original_ttl = tokenizer::ZoneToken::expect_int::<u32>(&mut iter)?;
+
+		for field in [ &mut self.sig_expiration, &mut self.sig_inception ] {
+			*field = match iter.next() {
+				Some(tok) => { parse_dnssec_time(&tok.token).map_err(|e| format!("{} at line {}", e, tok.line))? },
+				None => { return Err("Expected token, got EOL".to_string()); }
+			};
+		}
+
+		self.key_tag = tokenizer::ZoneToken::expect_int::<u16>(&mut iter)?;
+
+		self.signer_name = match iter.next() {
+			Some(tok) => { record::RecordName::new(&tok.token) },
+			None => { return Err("Expected token, got EOL".to_string()); }
+		};
+
+		let mut signature = String::new();
+		for tok in iter {
+			if tok.token_type != tokenizer::TokenType::TypeWhite {
+				signature.push_str(&tok.token);
+			}
+		}
+
+		self.signature = match base64::engine::general_purpose::STANDARD.decode(signature) {
+			Ok(m) => { m },
+			Err(e) => { return Err(format!("invalid RRSIG signature {}", e)); }
+		};
+
+		Ok(())
+	}
+
+	fn origin( &mut self, origin : &String ) {
+		self.signer_name.origin(origin);
+	}
+
+	fn as_any_mut( &mut self ) -> &mut dyn std::any::Any {
+		self
+	}
+
+	fn as_any( &self ) -> &dyn std::any::Any {
+		self
+	}
+
+	fn clone_box (&self) -> Box<dyn RecordRDATA> {
+		Box::new( self.clone() )
+	}
+
+	fn from_wire( &mut self, size : u16, buff : &[u8], offset : &mut usize ) -> Result<(), String> {
+		let offset_start = *offset;
+		self.type_covered = crate::query::dns_read_int!(u16, buff, offset);
+		self.algorithm = crate::query::dns_read_int!(u8, buff, offset);
+		self.labels = crate::query::dns_read_int!(u8, buff, offset);
+		self.original_ttl = crate::query::dns_read_int!(u32, buff, offset);
+		self.sig_expiration = crate::query::dns_read_int!(u32, buff, offset);
+		self.sig_inception = crate::query::dns_read_int!(u32, buff, offset);
+		self.key_tag = crate::query::dns_read_int!(u16, buff, offset);
+		self.signer_name.name = crate::query::read_qname(buff, offset)?.as_str().to_string();
+		if !self.signer_name.name.ends_with('.') {
+			self.signer_name.name.push('.');
+		}
+		self.signer_name.fqdn = self.signer_name.name.clone();
+
+		let used = *offset - offset_start;
+		if used > size as usize {
+			return Err(format!("RRSIG of {} bytes is shorter than its fixed fields", size));
+		}
+		self.signature = crate::query::read_buff(buff, offset, size as usize - used)?;
+		Ok(())
+	}
+
+}
+
+impl Clone for RDATArrsig {
+	fn clone(&self) -> Self {
+		Self {
+			type_covered: self.type_covered,
+			algorithm: self.algorithm,
+			labels: self.labels,
+			original_ttl: self.original_ttl,
+			sig_expiration: self.sig_expiration,
+			sig_inception: self.sig_inception,
+			key_tag: self.key_tag,
+			signer_name: self.signer_name.clone(),
+			signature: self.signature.clone()
+		}
+	}
+}
+
+impl std::fmt::Display for RDATArrsig {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+		let encoded: String = base64::engine::general_purpose::STANDARD.encode(self.signature.clone());
+
+		write!(f, "{} {} {} {} {} {} {} {} {} ",
+			self.covered(), self.algorithm, self.labels, self.original_ttl,
+			format_dnssec_time(self.sig_expiration), format_dnssec_time(self.sig_inception),
+			self.key_tag, self.signer_name, encoded)
+	}
+}
+
+/**
+ * days since 1970-01-01 for the date, Howard Hinnant's days_from_civil
+ */
+fn days_from_civil( year : i64, month : i64, day : i64 ) -> i64 {
+	let y = if month <= 2 { year - 1 } else { year };
+	let era = if y >= 0 { y } else { y - 399 } / 400;
+	let yoe = y - era * 400;
+	let mp = (month + 9) % 12;
+	let doy = (153 * mp + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146097 + doe - 719468
+}
+
+/**
+ * the date for days since 1970-01-01 as (year, month, day), the inverse of days_from_civil
+ */
+fn civil_from_days( days : i64 ) -> (i64, i64, i64) {
+	let z = days + 719468;
+	let era = if z >= 0 { z } else { z - 146096 } / 146097;
+	let doe = z - era * 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	(if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, month, day)
+}
+
+/**
+ * format a DNSSEC timestamp, seconds since the epoch, as YYYYMMDDHHmmSS (RFC-4034 3.2)
+ */
+pub fn format_dnssec_time( time : u32 ) -> String {
+	let time = time as i64;
+	let (year, month, day) = civil_from_days(time / 86400);
+	let secs = time % 86400;
+	format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+/**
+ * parse a DNSSEC timestamp written as YYYYMMDDHHmmSS or as seconds since the epoch
+ */
+pub fn parse_dnssec_time( value : &str ) -> Result<u32, String> {
+
+	if value.len() != 14 || !value.bytes().all(|b| b.is_ascii_digit()) {
+		return value.parse::<u32>().map_err(|_| format!("invalid DNSSEC timestamp '{}'", value));
+	}
+
+	let field = |start : usize, end : usize| -> i64 { value[start..end].parse::<i64>().unwrap() };
+	let secs = days_from_civil(field(0, 4), field(4, 6), field(6, 8)) * 86400 + field(8, 10) * 3600 + field(10, 12) * 60 + field(12, 14);
+
+	u32::try_from(secs).map_err(|_| format!("DNSSEC timestamp '{}' is out of range", value))
+}
+
 
 pub fn create_from_type( record_type : record::RecordType ) -> Box<dyn RecordRDATA> {
 	
@@ -873,9 +2015,39 @@ pub fn create_from_type( record_type : record::RecordType ) -> Box<dyn RecordRDA
 		record::RecordType::NS => {
 			Box::new(RDATANameRR{ ..Default::default() })
 		},	
+		record::RecordType::PTR => {
+			Box::new(RDATANameRR{ ..Default::default() })
+		},
 		record::RecordType::TXT => {
 			Box::new(RDATAtxt{ ..Default::default() })
 		},
+		record::RecordType::SRV => {
+			Box::new(RDATAsrv{ ..Default::default() })
+		},
+		record::RecordType::CAA => {
+			Box::new(RDATAcaa{ ..Default::default() })
+		},
+		record::RecordType::NAPTR => {
+			Box::new(RDATAnaptr{ ..Default::default() })
+		},
+		record::RecordType::TLSA => {
+			Box::new(RDATAtlsa{ ..Default::default() })
+		},
+		record::RecordType::SSHFP => {
+			Box::new(RDATAsshfp{ ..Default::default() })
+		},
+		record::RecordType::SVCB => {
+			Box::new(RDATAsvcb{ ..Default::default() })
+		},
+		record::RecordType::HTTPS => {
+			Box::new(RDATAsvcb{ ..Default::default() })
+		},
+		record::RecordType::URI => {
+			Box::new(RDATAuri{ ..Default::default() })
+		},
+		record::RecordType::OPT => {
+			Box::new(RDATAopt{ ..Default::default() })
+		},
 		record::RecordType::SOA => {
 			Box::new(RDATAsoa{ ..Default::default() })
 		},
@@ -885,6 +2057,9 @@ pub fn create_from_type( record_type : record::RecordType ) -> Box<dyn RecordRDA
 		record::RecordType::DS => {
 			Box::new(RDATAds{ ..Default::default() })
 		},
+		record::RecordType::RRSIG => {
+			Box::new(RDATArrsig{ ..Default::default() })
+		},
 		_ => {
 			Box::new(RDATAgeneric{ ..Default::default() })
 		}
@@ -18,6 +18,8 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 */
 
+use crate::config::println_verbose;
+
 
 #[derive(PartialEq, Eq,Clone)]
 pub enum TokenType {
@@ -148,16 +150,54 @@ impl ZoneLines {
 
 		let mut rval : ZoneLines =  Default::default();
 
-		let file: std::fs::File = match std::fs::File::open(filename) {
+		let mut file: std::fs::File = match std::fs::File::open(filename) {
 			Ok(m) => { m },
 			Err(e) => { return Err(e.to_string()) }
 		};
 
-		rval.lines = rval.get_record_tokens( &file )?;
+		if filename.ends_with(".gz") || Self::is_gzip(&mut file)? {
+			println_verbose!(VERBOSE1, "Decompressing gzip zone {}", filename);
+			let mut decoder = flate2::read::MultiGzDecoder::new(file);
+			rval.lines = rval.get_record_tokens( &mut decoder )?;
+		} else {
+			rval.lines = rval.get_record_tokens( &mut file )?;
+		}
 
 		return Ok(rval);
 	}
 
+	/**
+	 * check the file for the gzip magic number, the file is rewound afterwards
+	 */
+	fn is_gzip( file : &mut std::fs::File ) -> Result<bool, String> {
+		use std::io::{Read, Seek};
+
+		let mut magic = [0u8; 2];
+		let read = match file.read(&mut magic) {
+			Ok(m) => { m },
+			Err(e) => { return Err(e.to_string()); }
+		};
+
+		if let Err(e) = file.rewind() {
+			return Err(e.to_string());
+		}
+
+		Ok(read == 2 && magic == [0x1f, 0x8b])
+	}
+
+	/**
+	 * tokenize a zone from any reader, for tooling that only wants the tokens and not the
+	 * records
+	 */
+	pub fn from_reader( reader : &mut dyn std::io::Read ) -> Result<ZoneLines, String> {
+
+		let mut rval : ZoneLines =  Default::default();
+
+		rval.lines = rval.get_record_tokens( reader )?;
+
+		Ok(rval)
+	}
+
 	/**
 	 * Take the zone file and split it up into a vector of the individual record lines, this will also
 	 * properly take in the () values as well as split up strings and whitespace
@@ -166,7 +206,7 @@ impl ZoneLines {
 	 *
 	 * returns the tokenized zone file, not the records
 	 */
-	fn get_record_tokens( &self, mut file : &std::fs::File ) -> Result<Vec<ZoneLine>, String> {
+	fn get_record_tokens( &self, file : &mut dyn std::io::Read ) -> Result<Vec<ZoneLine>, String> {
 
 		let mut lines : Vec<ZoneLine> = Vec::new();
 
@@ -193,7 +233,8 @@ impl ZoneLines {
 
 		// lambda function to push the tokens and reset everything that needs it
 		let push_token = | mut tok : ZoneToken, line : &mut ZoneLine, is_white : &mut bool, line_no : &u32 | -> ZoneToken { 
-			if tok.token.len() > 0 {
+			// an empty quoted string is still a token, like the regexp of a NAPTR record
+			if tok.token.len() > 0 || tok.token_type == TokenType::TypeString {
 
 				// no token type, figure it out
 				if tok.token_type == TokenType::TypeNone {
@@ -219,12 +260,14 @@ impl ZoneLines {
 		};
 
 		let mut push_line= | line : ZoneLine | -> ZoneLine { 
-			lines.push( line );
+			// blank and comment only lines have nothing to parse
+			if line.tokens.iter().any(|t| t.token_type != TokenType::TypeWhite) {
+				lines.push( line );
+			}
 			return ZoneLine { tokens: Vec::new() };
 		};
 
 		loop {
-			use std::io::Read;
 			let read_count = match file.read(&mut buffer[..]) {
 				Ok(x) => { x },
 				Err( e ) => { 
@@ -257,14 +300,13 @@ impl ZoneLines {
 					} else {
 						tok.token.push(buffer[idx] as char );
 					}
-				} else if skip_endline {
+				} else if skip_endline && buffer[idx] != '\n' as u8 {
 					// skipping until the end of the line, this is for comments in the file which we are stripping
-					// out
-					if buffer[idx] == '\n' as u8 {
-						skip_endline = false;
-					}
+					// out. The newline itself is handled below so a comment doesn't join the next record on
 				} else {
 
+					skip_endline = false;
+
 					// other characters
 
 					let c: char =  buffer[idx] as char;
@@ -336,7 +378,8 @@ impl ZoneLines {
 				idx+=1;
 			}
 
-			if read_count != BUFF_LEN {
+			// a short read doesn't mean the end of a pipe, only 0 does
+			if read_count == 0 {
 				break;
 			}
 		}
@@ -432,7 +475,7 @@ impl ZoneLines {
 	}
 
 	/**
-	 * escape quotes and non-printable ascii characters
+	 * escape quotes, backslashes and non-printable ascii characters
 	 */
 	pub fn escape( in_str : & String ) -> String {
 		let mut rval = String::new();
@@ -446,7 +489,7 @@ impl ZoneLines {
 				None => { break; }
 			};
 
-			if c == '"' {
+			if c == '"' || c == '\\' {
 				rval.push('\\');
 				rval.push(c);
 			} else if (c as u8) < 0x20 || (c as u8) >= 0x7f {
@@ -469,6 +512,31 @@ impl Default for ZoneLines {
 	}
 }
 
+/**
+ * annotated view of the tokens for debugging the parser, one token per row as
+ * line number, token type and the token quoted so whitespace is visible
+ */
+impl std::fmt::Display for ZoneLine {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for tok in &self.tokens {
+			writeln!(f, "{}\t{}\t{:?}", tok.line, tok.token_type, tok.token)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::fmt::Display for ZoneLines {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (idx, line) in self.lines.iter().enumerate() {
+			if idx > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{}", line)?;
+		}
+		Ok(())
+	}
+}
+
 impl std::fmt::Display for TokenType {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(f, "{}", match &self {
@@ -0,0 +1,85 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for diffing results against the answers recorded in a baseline
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::baseline::Baseline;
+use dns_audit::monitor::{ErrorCode, MonitorResult};
+
+fn result( nameservers : &[&str], ips : &[&str] ) -> MonitorResult {
+	let mut rval = MonitorResult::new( "example.com" );
+	rval.nameservers = Some(nameservers.iter().map(|n| n.to_string()).collect());
+	rval.ips = Some(ips.iter().map(|ip| ip.parse::<IpAddr>().unwrap()).collect());
+	rval
+}
+
+#[test]
+fn diffs_against_the_first_run() {
+
+	let file_name = std::env::temp_dir().join(format!("dns_audit_baseline_{}.json", std::process::id())).to_string_lossy().to_string();
+	let mut baseline = Baseline::from_file( &file_name ).unwrap();
+	assert!( baseline.entries.is_empty() );
+
+	// the first run is recorded, not reported
+	let mut first = result( &[ "ns1.example.com.", "ns2.example.com." ], &[ "192.0.2.1", "192.0.2.2" ] );
+	baseline.check( &mut first );
+	assert!( baseline.changed );
+	assert!( first.success && first.findings.is_empty() );
+
+	// the same answers in another order and case are not a change
+	let mut same = result( &[ "NS2.example.com.", "ns1.example.com." ], &[ "192.0.2.2", "192.0.2.1" ] );
+	baseline.check( &mut same );
+	assert!( same.success && same.findings.is_empty() );
+
+	let mut moved = result( &[ "ns1.example.com.", "ns3.example.net." ], &[ "192.0.2.1", "198.51.100.7" ] );
+	baseline.check( &mut moved );
+	assert!( !moved.success );
+
+	let reported : Vec<(ErrorCode, String)> = moved.findings.iter().map(|f| (f.code, f.message.clone())).collect();
+	assert_eq!( reported, vec![
+		(ErrorCode::BaselineChanged, "nameservers changed from baseline, added [ns3.example.net.] removed [ns2.example.com.]".to_string()),
+		(ErrorCode::BaselineChanged, "ips changed from baseline, added [198.51.100.7] removed [192.0.2.2]".to_string())
+	]);
+
+	let detail = moved.findings[1].detail.as_ref().unwrap();
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.1, 192.0.2.2") );
+	assert_eq!( detail.actual.as_deref(), Some("192.0.2.1, 198.51.100.7") );
+
+	// a later run reads the recorded answers back
+	baseline.to_file( &file_name ).unwrap();
+	let mut reread = Baseline::from_file( &file_name ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+	let mut again = result( &[ "ns1.example.com.", "ns2.example.com." ], &[ "192.0.2.1" ] );
+	reread.check( &mut again );
+	assert_eq!( again.findings.len(), 1 );
+	assert!( !reread.changed );
+}
+
+#[test]
+fn diff_ignores_order() {
+	assert_eq!( Baseline::diff( &[ 1, 2, 3 ], &[ 3, 2, 1 ] ), None );
+	assert_eq!( Baseline::diff( &[ 1, 2 ], &[ 2, 4 ] ).unwrap(), "added [4] removed [1]" );
+}
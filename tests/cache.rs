@@ -0,0 +1,230 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for reading, updating and writing the root cache. The binary format is built with
+ * --features binary-cache
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use dns_audit::root::{NameServer, NameServersForZone, Root};
+
+fn cache() -> Root {
+
+	let mut root_addr = HashMap::new();
+
+	for (zone_name, ns, ip, speed) in [ (".", "a.root-servers.net.", "198.41.0.4", Some(1200)), ("com.", "a.gtld-servers.net.", "2001:503:a83e::2:30", None) ] {
+		let mut zone = NameServersForZone::new( &zone_name.to_string() );
+		zone.servers.push( Arc::new(RwLock::new(NameServer {
+			server_name: ns.to_string(),
+			ip: ip.parse().unwrap(),
+			speed: speed.map(std::time::Duration::from_micros)
+		})));
+		root_addr.insert( zone_name.to_string(), Arc::new(RwLock::new(zone)) );
+	}
+
+	Root { root_addr, ..Default::default() }
+}
+
+fn speeds( root : &Root ) -> Vec<(String, Option<Duration>)> {
+	let mut rval : Vec<(String, Option<Duration>)> = root.root_addr.values()
+		.flat_map(|zone| zone.read().unwrap().servers.iter().map(|s| {
+			let s = s.read().unwrap();
+			(s.server_name.clone(), s.speed)
+		}).collect::<Vec<_>>())
+		.collect();
+	rval.sort();
+	rval
+}
+
+static MEASURED : AtomicUsize = AtomicUsize::new(0);
+
+fn measure( _ip : &IpAddr, _zone : &str ) -> Option<Duration> {
+	MEASURED.fetch_add( 1, Ordering::SeqCst );
+	Some( Duration::from_micros(700) )
+}
+
+#[test]
+fn load_update_save() {
+
+	let cache_in = std::env::temp_dir().join(format!("dns_audit_cache_in_{}.json", std::process::id()));
+	let cache_out = std::env::temp_dir().join(format!("dns_audit_cache_out_{}.json", std::process::id()));
+	std::fs::write( &cache_in, cache().to_json() ).unwrap();
+
+	let mut root = Root::default();
+	root.read_cache( &cache_in.to_string_lossy() ).unwrap();
+
+	// only com. has no time in the cache so it is the only one tested
+	root.performance_test_with( 2, false, 3, measure );
+	assert_eq!( MEASURED.swap( 0, Ordering::SeqCst ), 3 );
+	root.write_cache( &cache_out.to_string_lossy() ).unwrap();
+
+	let mut saved = Root::default();
+	saved.read_cache( &cache_out.to_string_lossy() ).unwrap();
+	assert_eq!( speeds(&saved), vec![
+		("a.gtld-servers.net.".to_string(), Some(Duration::from_micros(700))),
+		("a.root-servers.net.".to_string(), Some(Duration::from_micros(1200)))
+	]);
+
+	// refreshing tests them all again
+	saved.performance_test_with( 2, true, 3, measure );
+	assert_eq!( MEASURED.swap( 0, Ordering::SeqCst ), 6 );
+	assert!( speeds(&saved).iter().all(|(_, speed)| *speed == Some(Duration::from_micros(700))) );
+
+	std::fs::remove_file( &cache_in ).unwrap();
+	std::fs::remove_file( &cache_out ).unwrap();
+}
+
+static MERGE_MEASURED : AtomicUsize = AtomicUsize::new(0);
+
+fn merge_measure( _ip : &IpAddr, _zone : &str ) -> Option<Duration> {
+	MERGE_MEASURED.fetch_add( 1, Ordering::SeqCst );
+	Some( Duration::from_micros(900) )
+}
+
+#[test]
+fn merge_partial_cache() {
+
+	// a root built from the zone file, with a root server and a zone the cache doesn't have
+	let mut root_addr = HashMap::new();
+	for (zone_name, servers) in [
+		(".", vec![ ("a.root-servers.net.", "198.41.0.4"), ("b.root-servers.net.", "170.247.170.2") ]),
+		("com.", vec![ ("a.gtld-servers.net.", "2001:503:a83e::2:30") ]),
+		("org.", vec![ ("a0.org.afilias-nst.info.", "199.19.56.1") ])
+	] {
+		let mut zone = NameServersForZone::new( &zone_name.to_string() );
+		for (ns, ip) in servers {
+			zone.servers.push( Arc::new(RwLock::new(NameServer {
+				server_name: ns.to_string(),
+				ip: ip.parse().unwrap(),
+				speed: None
+			})));
+		}
+		root_addr.insert( zone_name.to_string(), Arc::new(RwLock::new(zone)) );
+	}
+	let mut root = Root { root_addr, ..Default::default() };
+
+	root.merge_cache( &cache().to_json() ).unwrap();
+
+	// only a.root-servers.net. had a time, the zones are still the root's
+	assert_eq!( root.root_addr.len(), 3 );
+	assert_eq!( speeds(&root), vec![
+		("a.gtld-servers.net.".to_string(), None),
+		("a.root-servers.net.".to_string(), Some(Duration::from_micros(1200))),
+		("a0.org.afilias-nst.info.".to_string(), None),
+		("b.root-servers.net.".to_string(), None)
+	]);
+	let root_zone = root.root_addr.get(".").unwrap().read().unwrap();
+	assert_eq!( root_zone.servers[0].read().unwrap().server_name, "a.root-servers.net." );
+	drop(root_zone);
+
+	// the rest are the ones tested
+	root.performance_test_with( 2, false, 1, merge_measure );
+	assert_eq!( MERGE_MEASURED.load(Ordering::SeqCst), 3 );
+	assert_eq!( speeds(&root)[1].1, Some(Duration::from_micros(1200)) );
+
+	assert!( root.merge_cache( &"not json".to_string() ).is_err() );
+}
+
+static PROBED : AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * every other probe gets no answer, the ones that do take 1ms, 3ms, 5ms...
+ */
+fn flaky( _ip : &IpAddr, _zone : &str ) -> Option<Duration> {
+	let probe = PROBED.fetch_add( 1, Ordering::SeqCst ) as u64;
+	if probe % 2 == 1 { None } else { Some( Duration::from_millis(probe + 1) ) }
+}
+
+#[test]
+fn average_of_answered_probes() {
+
+	let ms = |m : u64| Some( Duration::from_millis(m) );
+
+	// the failures don't pull the average down
+	assert_eq!( Root::average( &[ ms(100), None, ms(300), None, None ] ), ms(200) );
+	assert_eq!( Root::average( &[ ms(10), ms(20), ms(30), ms(40) ] ), ms(25) );
+	assert_eq!( Root::average( &[ None, None ] ), None );
+	assert_eq!( Root::average( &[] ), None );
+
+	// the 4 probes of the one server are 1ms, none, 3ms and none
+	let mut root = Root::default();
+	let mut zone = NameServersForZone::new( &".".to_string() );
+	zone.servers.push( Arc::new(RwLock::new(NameServer {
+		server_name: "a.root-servers.net.".to_string(),
+		ip: "198.41.0.4".parse().unwrap(),
+		speed: None
+	})));
+	root.root_addr.insert( ".".to_string(), Arc::new(RwLock::new(zone)) );
+
+	root.performance_test_with( 1, true, 4, flaky );
+	assert_eq!( speeds(&root), vec![ ("a.root-servers.net.".to_string(), ms(2)) ] );
+}
+
+#[test]
+fn format_by_extension() {
+	assert!( Root::is_binary_cache("cache.bin") );
+	assert!( Root::is_binary_cache("/var/lib/dns_audit/cache.BIN") );
+	assert!( !Root::is_binary_cache("cache.json") );
+	assert!( !Root::is_binary_cache("bin") );
+}
+
+#[test]
+#[cfg(feature = "binary-cache")]
+fn binary_round_trip() {
+
+	let binary = cache().to_binary().unwrap();
+	assert_eq!( &binary[..8], b"DNSAUDIT" );
+	assert_eq!( binary[8..12], dns_audit::config::SCHEMA_VERSION.to_be_bytes() );
+
+	let mut root = Root::default();
+	root.cache_from_binary( &binary ).unwrap();
+
+	// the same cache as json either way
+	let json : serde_json::Value = serde_json::from_str( &root.to_json() ).unwrap();
+	let expected : serde_json::Value = serde_json::from_str( &cache().to_json() ).unwrap();
+	assert_eq!( json, expected );
+}
+
+#[test]
+#[cfg(feature = "binary-cache")]
+fn binary_future_version() {
+
+	let mut binary = cache().to_binary().unwrap();
+	binary[8..12].copy_from_slice( &(dns_audit::config::SCHEMA_VERSION + 1).to_be_bytes() );
+
+	let mut root = Root::default();
+	assert!( root.cache_from_binary( &binary ).is_err() );
+	assert!( root.cache_from_binary( b"{}" ).is_err() );
+}
+
+#[test]
+#[cfg(not(feature = "binary-cache"))]
+fn binary_needs_feature() {
+	let e = cache().to_binary().err().unwrap();
+	assert!( e.contains("binary-cache"), "{}", e );
+}
@@ -0,0 +1,218 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the check registry, checks that don't send any queries are ran through
+ * Monitor::test
+ */
+
+use std::net::UdpSocket;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use dns_audit::check::{self, Check, SenderFactory};
+use dns_audit::monitor::{ErrorCode, Finding, Monitor, MonitorResult};
+use dns_audit::query::{self, QueryType};
+use dns_audit::root::Root;
+
+static RAN : AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * a check that counts its runs and reports the domain's nameservers were never looked up
+ */
+struct CountingCheck {}
+
+impl Check for CountingCheck {
+
+	fn name( &self ) -> &str {
+		"counting"
+	}
+
+	fn run( &self, monitor : &Monitor, _sender_factory : &SenderFactory, _root : &Arc<RwLock<Root>>, result : &mut MonitorResult ) -> Vec<Finding> {
+		RAN.fetch_add( 1, Ordering::SeqCst );
+		assert_eq!( result.domain_name, monitor.domain_name );
+		vec![ Finding::new( ErrorCode::NoAuthoratative, &format!("{} was counted", monitor.domain_name) ) ]
+	}
+}
+
+/**
+ * a check that panics holding the root, poisoning it
+ */
+struct PanickingCheck {}
+
+impl Check for PanickingCheck {
+
+	fn name( &self ) -> &str {
+		"panicking"
+	}
+
+	fn run( &self, monitor : &Monitor, _sender_factory : &SenderFactory, root : &Arc<RwLock<Root>>, _result : &mut MonitorResult ) -> Vec<Finding> {
+		let _root = root.write().unwrap();
+		panic!("{} is a bad domain", monitor.domain_name);
+	}
+}
+
+/**
+ * a check that reads the root the way the checks that send queries do and finds nothing
+ */
+struct RootReadingCheck {}
+
+impl Check for RootReadingCheck {
+
+	fn name( &self ) -> &str {
+		"root_reading"
+	}
+
+	fn run( &self, _monitor : &Monitor, _sender_factory : &SenderFactory, root : &Arc<RwLock<Root>>, _result : &mut MonitorResult ) -> Vec<Finding> {
+		root.read().unwrap().closest_servers( "example.com." );
+		Vec::new()
+	}
+}
+
+/**
+ * a check that asks a server on the loopback for the domain's A records and reports a
+ * failed query, the server is on the monitor's port
+ */
+struct QueryingCheck {}
+
+impl Check for QueryingCheck {
+
+	fn name( &self ) -> &str {
+		"querying"
+	}
+
+	fn run( &self, monitor : &Monitor, sender_factory : &SenderFactory, _root : &Arc<RwLock<Root>>, _result : &mut MonitorResult ) -> Vec<Finding> {
+		let mut query = sender_factory( &"127.0.0.1".parse().unwrap() );
+		match query.query( &monitor.domain_name, QueryType::T_A ) {
+			Ok(_) => { Vec::new() },
+			Err(e) => { vec![ Finding::new( ErrorCode::NoResolve, &e ) ] }
+		}
+	}
+}
+
+/**
+ * a server answering every question with an empty response after 200ms, the port it listens
+ * on is returned
+ */
+fn serve_slowly() -> u16 {
+
+	let udp = Arc::new( UdpSocket::bind( "127.0.0.1:0" ).unwrap() );
+	let port = udp.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			let mut response = buff[..size].to_vec();
+			response[2] |= 0x84;
+			let udp = Arc::clone(&udp);
+			std::thread::spawn( move || {
+				std::thread::sleep( Duration::from_millis(200) );
+				udp.send_to( &response, from ).unwrap();
+			});
+		}
+	});
+
+	port
+}
+
+fn monitor( checks : &str ) -> Arc<RwLock<Monitor>> {
+	let json = format!("[{{ \"domain_name\": \"example.com\", \"ns\": null, \"ip\": null, \"checks\": {} }}]", checks);
+	Arc::new(RwLock::new( Monitor::list_from_json( &json ).unwrap().remove(0) ))
+}
+
+#[test]
+fn registered_check_runs() {
+
+	check::register( Arc::new(CountingCheck {}) );
+	assert_eq!( check::get("counting").unwrap().name(), "counting" );
+
+	let root = Arc::new(RwLock::new(Root::default()));
+	let result = Monitor::test( monitor( "[ \"counting\", \"missing\" ]" ), root );
+	let result = result.lock().unwrap();
+
+	assert_eq!( RAN.load(Ordering::SeqCst), 1 );
+	assert!( !result.success );
+
+	let reported : Vec<(ErrorCode, &str)> = result.findings.iter().map(|f| (f.code, f.message.as_str())).collect();
+	assert_eq!( reported, vec![
+		(ErrorCode::NoAuthoratative, "example.com was counted"),
+		(ErrorCode::UnknownCheck, "unknown check 'missing'")
+	]);
+}
+
+#[test]
+fn no_checks_pass() {
+	let result = Monitor::test( monitor( "[]" ), Arc::new(RwLock::new(Root::default())) );
+	assert!( result.lock().unwrap().success );
+}
+
+#[test]
+fn panic_is_contained() {
+
+	check::register( Arc::new(PanickingCheck {}) );
+	check::register( Arc::new(RootReadingCheck {}) );
+
+	let root = Arc::new(RwLock::new(Root::default()));
+
+	let result = Monitor::test_guarded( monitor( "[ \"panicking\" ]" ), Arc::clone(&root) );
+	let result = result.lock().unwrap();
+	assert!( !result.success );
+	assert_eq!( result.findings.len(), 1 );
+	assert_eq!( result.findings[0].code, ErrorCode::InternalError );
+	assert_eq!( result.findings[0].message, "internal error testing example.com: example.com is a bad domain" );
+
+	// the domains tested after it still get their own results
+	assert!( !root.is_poisoned() );
+	for _ in 0..2 {
+		assert!( Monitor::test_guarded( monitor( "[ \"root_reading\" ]" ), Arc::clone(&root) ).lock().unwrap().success );
+	}
+}
+
+#[test]
+fn monitors_as_tasks() {
+
+	check::register( Arc::new(QueryingCheck {}) );
+	let port = serve_slowly();
+
+	let root = Arc::new(RwLock::new(Root::default()));
+	let start = Instant::now();
+
+	// the way the run loop tests the domains, the checks' queries don't hold up the workers
+	let results = query::runtime().block_on( async {
+		let mut tasks = tokio::task::JoinSet::new();
+		for _ in 0..16 {
+			let m = monitor( "[ \"querying\" ]" );
+			m.write().unwrap().port = Some(port);
+			tasks.spawn( Monitor::test_guarded_async( m, Arc::clone(&root) ) );
+		}
+		tasks.join_all().await
+	});
+
+	assert_eq!( results.len(), 16 );
+	for res in results {
+		let res = res.lock().unwrap();
+		assert!( res.success, "{:?}", res.findings.iter().map(|f| f.message.clone()).collect::<Vec<String>>() );
+	}
+	// one after another they would take 3.2s
+	assert!( start.elapsed() < Duration::from_millis(1600), "took {:?}", start.elapsed() );
+}
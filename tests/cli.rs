@@ -0,0 +1,103 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the output and exit code of the dns_audit command. The monitors only run checks
+ * that don't send queries, an empty list passes and an unknown check fails
+ */
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const PASSING : &str = r#"[{ "domain_name": "a.example", "checks": [] }]"#;
+const FAILING : &str = r#"[{ "domain_name": "a.example", "checks": [] }, { "domain_name": "b.example", "checks": [ "not_a_check" ] }]"#;
+
+/**
+ * run dns_audit with the args and the monitors, or anything else given as -, read from stdin
+ */
+fn run( args : &[&str], stdin : &str ) -> Output {
+
+	let mut child = Command::new( env!("CARGO_BIN_EXE_dns_audit") )
+		.args( [ "-c", "-" ] )
+		.args( args )
+		.stdin( Stdio::piped() )
+		.stdout( Stdio::piped() )
+		.stderr( Stdio::piped() )
+		.spawn()
+		.unwrap();
+
+	child.stdin.take().unwrap().write_all( stdin.as_bytes() ).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+#[test]
+fn quiet() {
+
+	let output = run( &[ "--quiet", "--color", "never" ], PASSING );
+	assert!( output.stdout.is_empty() );
+	assert_eq!( String::from_utf8(output.stderr).unwrap(), "1/1 passed\n" );
+	assert_eq!( output.status.code(), Some(0) );
+
+	// --all would write both results without --quiet
+	let output = run( &[ "--quiet", "--all", "--color", "never" ], FAILING );
+	assert!( output.stdout.is_empty() );
+	assert_eq!( String::from_utf8(output.stderr).unwrap(), "1/2 passed\n" );
+	assert_eq!( output.status.code(), Some(2) );
+
+	assert!( !run( &[ "--all" ], FAILING ).stdout.is_empty() );
+}
+
+#[test]
+fn pcap_not_created() {
+
+	let output = run( &[ "--pcap", "/nonexistent/dns_audit.pcap" ], PASSING );
+	assert!( output.stdout.is_empty() );
+	assert!( String::from_utf8(output.stderr).unwrap().starts_with("failed to create /nonexistent/dns_audit.pcap") );
+	assert_eq!( output.status.code(), Some(1) );
+}
+
+#[test]
+fn decode() {
+
+	let expected = "Header id=4660 rd=false tc=false aa=true opcode=QUERY qr=true rcode=NOERROR z=0 ra=false qdcount=1 ancount=2 nscount=1 arcount=1 Question host=example.com qtype=1 class=1
+;; ANSWER 2
+example.com\t3600\tIN \tA\t192.0.2.1\t
+example.com\t3600\tIN \tA\t192.0.2.2\t
+;; AUTHORITY 1
+example.com\t86400\tIN \tNS\tns1.example.com.\t
+;; ADDITIONAL 1
+ns1.example.com\t86400\tIN \tA\t192.0.2.53\t
+";
+
+	// the raw response and a hex dump of it decode the same
+	for file in [ "tests/responses/example.com.bin", "tests/responses/example.com.hex" ] {
+		let output = run( &[ "--decode", file ], "" );
+		assert_eq!( String::from_utf8(output.stdout).unwrap(), expected, "{}", file );
+		assert_eq!( output.status.code(), Some(0) );
+	}
+
+	// the header alone, the question and records it counts aren't there
+	let hex = std::fs::read_to_string( "tests/responses/example.com.hex" ).unwrap();
+	let output = run( &[ "--decode", "-" ], &hex[..36] );
+	assert!( output.stdout.is_empty() );
+	assert!( String::from_utf8(output.stderr).unwrap().starts_with("failed to decode -") );
+	assert_eq!( output.status.code(), Some(1) );
+}
@@ -0,0 +1,96 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the --concurrency limit on the queries in flight across all threads, the queries
+ * go to a slow server on the loopback on threads of the test
+ */
+
+use std::net::{IpAddr, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use dns_audit::config;
+use dns_audit::query::{QueryType, Sender};
+
+/** how many requests the server has received and not answered yet */
+static WAITING : AtomicUsize = AtomicUsize::new(0);
+
+/** the most requests the server has had waiting at once */
+static MOST_WAITING : AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * a server answering every question with an empty response after 100ms, the port it listens
+ * on is returned
+ */
+fn serve() -> u16 {
+
+	let udp = Arc::new( UdpSocket::bind( "127.0.0.1:0" ).unwrap() );
+	let port = udp.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+
+			let waiting = WAITING.fetch_add( 1, Ordering::SeqCst ) + 1;
+			MOST_WAITING.fetch_max( waiting, Ordering::SeqCst );
+
+			let mut response = buff[..size].to_vec();
+			response[2] |= 0x84;
+			let udp = Arc::clone(&udp);
+			std::thread::spawn( move || {
+				std::thread::sleep( Duration::from_millis(100) );
+				WAITING.fetch_sub( 1, Ordering::SeqCst );
+				udp.send_to( &response, from ).unwrap();
+			});
+		}
+	});
+
+	port
+}
+
+#[test]
+fn queries_in_flight() {
+
+	// read when the first query is sent
+	*config::CONCURRENCY.write().unwrap() = 2;
+
+	let port = serve();
+	let server : IpAddr = "127.0.0.1".parse().unwrap();
+
+	let threads : Vec<std::thread::JoinHandle<()>> = (0..8).map(|_| {
+		std::thread::spawn( move || {
+			let mut sender = Sender::new( &server );
+			sender.port = port;
+			sender.timeout = Duration::from_secs(5);
+			sender.query( "example.com.", QueryType::T_A ).unwrap();
+		})
+	}).collect();
+
+	for thr in threads {
+		thr.join().unwrap();
+	}
+
+	// eight threads, but only two of their queries were ever waiting on the server
+	assert_eq!( MOST_WAITING.load(Ordering::SeqCst), 2 );
+	assert_eq!( WAITING.load(Ordering::SeqCst), 0 );
+}
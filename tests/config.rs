@@ -0,0 +1,74 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for filling in environment variables and secret files in the config
+ */
+
+use dns_audit::config::expand_env;
+use dns_audit::monitor::Monitor;
+
+#[test]
+fn config_reads_environment() {
+
+	std::env::set_var( "DNS_AUDIT_TEST_SECRET", "c2VjcmV0" );
+	std::env::set_var( "DNS_AUDIT_TEST_DOMAIN", "example" );
+
+	let monitors = Monitor::list_from_json( r#"[{
+		"domain_name": "${DNS_AUDIT_TEST_DOMAIN}.com",
+		"ns": [ "ns1.${DNS_AUDIT_TEST_DOMAIN}.com." ],
+		"tsig": { "name": "key.", "secret": "${DNS_AUDIT_TEST_SECRET}" }
+	}]"# ).unwrap();
+
+	assert_eq!( monitors[0].domain_name, "example.com" );
+	assert_eq!( monitors[0].ns, Some(vec![ "ns1.example.com.".to_string() ]) );
+	assert_eq!( monitors[0].tsig.as_ref().unwrap().secret, "c2VjcmV0" );
+}
+
+#[test]
+fn unset_variable_is_an_error() {
+
+	std::env::remove_var( "DNS_AUDIT_TEST_UNSET" );
+
+	let e = Monitor::list_from_json( r#"[{ "domain_name": "${DNS_AUDIT_TEST_UNSET}" }]"# ).err().unwrap();
+	assert!( e.contains("DNS_AUDIT_TEST_UNSET is not set"), "{}", e );
+}
+
+#[test]
+fn secret_file() {
+
+	let file_name = std::env::temp_dir().join(format!("dns_audit_secret_{}", std::process::id()));
+	std::fs::write( &file_name, "token-1234\n" ).unwrap();
+
+	let text = format!("v=${{file:{}}}", file_name.to_string_lossy());
+	assert_eq!( expand_env( &text ).unwrap(), "v=token-1234" );
+
+	std::fs::remove_file( &file_name ).unwrap();
+	assert!( expand_env( &text ).is_err() );
+}
+
+#[test]
+fn literal_text() {
+	assert_eq!( expand_env( "no variables $1 {x}" ).unwrap(), "no variables $1 {x}" );
+	assert_eq!( expand_env( "$${HOME}" ).unwrap(), "${HOME}" );
+	assert!( expand_env( "${HOME" ).is_err() );
+}
@@ -0,0 +1,119 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * Tests for comparing the answers of every authoratative nameserver of a domain, the answers
+ * are hand built responses
+ */
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use dns_audit::check::{ConsistencyCheck, ServerAnswers};
+use dns_audit::monitor::{ErrorCode, Monitor};
+use dns_audit::query::Sender;
+use dns_audit::zone::record::ZoneRecord;
+
+/**
+ * the authoratative response for example.com. with the nameservers, the NS records are
+ * under ns.example.net.
+ */
+fn ns_response( nameservers : &[&str] ) -> Vec<u8> {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, nameservers.len() as u8, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 2, 0, 1 ] );
+
+	for ns in nameservers {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 2, 0, 1, 0, 0, 0x0e, 0x10, 0, ns.len() as u8 + 1 + 13 ] );
+		buff.push( ns.len() as u8 );
+		buff.extend_from_slice( ns.as_bytes() );
+		buff.extend_from_slice( b"\x07example\x03net\x00" );
+	}
+	buff
+}
+
+/**
+ * the authoratative response for the A records of example.com.
+ */
+fn a_response( addresses : &[[u8; 4]] ) -> Vec<u8> {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, addresses.len() as u8, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+
+	for address in addresses {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4 ] );
+		buff.extend_from_slice( address );
+	}
+	buff
+}
+
+/**
+ * the answers of a server from its NS and A responses
+ */
+fn server_answers( nameservers : &[&str], addresses : &[[u8; 4]] ) -> ServerAnswers {
+
+	let ns = Sender::decode( &ns_response(nameservers) ).unwrap();
+	let a = Sender::decode( &a_response(addresses) ).unwrap();
+
+	ServerAnswers {
+		ns: ns.zone_nameservers( "example.com." ).iter().map(|n| n.to_ascii_lowercase()).collect(),
+		addresses: a.answer.iter().filter_map(ZoneRecord::record_to_address).collect()
+	}
+}
+
+#[test]
+fn servers_agree() {
+
+	let answers : BTreeMap<IpAddr, ServerAnswers> = [
+		( "192.0.2.1".parse().unwrap(), server_answers( &[ "ns1", "ns2" ], &[ [ 192, 0, 2, 10 ] ] ) ),
+		( "192.0.2.2".parse().unwrap(), server_answers( &[ "ns2", "NS1" ], &[ [ 192, 0, 2, 10 ] ] ) ),
+		( "192.0.2.3".parse().unwrap(), server_answers( &[ "ns1", "ns2" ], &[ [ 192, 0, 2, 10 ] ] ) )
+	].into_iter().collect();
+
+	// the order and case of the records don't matter
+	assert!( ConsistencyCheck::compare( &answers ).is_empty() );
+	assert!( ConsistencyCheck::compare( &BTreeMap::new() ).is_empty() );
+}
+
+#[test]
+fn stale_server() {
+
+	let monitor = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "check_consistency": true }]"# ).unwrap().pop().unwrap();
+	assert!( monitor.check_consistency );
+
+	// the third server still has the address and nameserver from before a change
+	let answers : BTreeMap<IpAddr, ServerAnswers> = [
+		( "192.0.2.1".parse().unwrap(), server_answers( &[ "ns1", "ns2" ], &[ [ 192, 0, 2, 20 ] ] ) ),
+		( "192.0.2.2".parse().unwrap(), server_answers( &[ "ns1", "ns2" ], &[ [ 192, 0, 2, 20 ] ] ) ),
+		( "192.0.2.3".parse().unwrap(), server_answers( &[ "ns1", "ns3" ], &[ [ 192, 0, 2, 10 ] ] ) )
+	].into_iter().collect();
+
+	let findings = ConsistencyCheck::compare( &answers );
+	assert_eq!( findings.len(), 1 );
+	assert!( findings[0].code == ErrorCode::Inconsistent );
+
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.server.as_deref(), Some("192.0.2.3") );
+	assert_eq!( detail.expected.as_deref(), Some("ns [ns1.example.net., ns2.example.net.] ips [192.0.2.20]") );
+	assert_eq!( detail.actual.as_deref(), Some("ns [ns1.example.net., ns3.example.net.] ips [192.0.2.10]") );
+	assert_eq!( findings[0].message, "192.0.2.3 answers ns [ns1.example.net., ns3.example.net.] ips [192.0.2.10] differently than 2 of the 3 nameserver addresses" );
+}
@@ -0,0 +1,380 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for following referrals down from the root zone file to the zone that delegates a
+ * domain. The parent nameservers are stood in for by hand built responses
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+use dns_audit::query::Sender;
+use dns_audit::root::{NameServer, NameServersForZone, Root};
+
+fn wire_name( name : &str ) -> Vec<u8> {
+	let mut buff = Vec::new();
+	for label in name.trim_end_matches('.').split('.') {
+		buff.push( label.len() as u8 );
+		buff.extend_from_slice( label.as_bytes() );
+	}
+	buff.push(0);
+	buff
+}
+
+fn record( buff : &mut Vec<u8>, name : &str, record_type : u16, rdata : &[u8] ) {
+	buff.extend( wire_name(name) );
+	buff.extend_from_slice( &record_type.to_be_bytes() );
+	buff.extend_from_slice( &[ 0, 1, 0, 0, 0x0e, 0x10 ] );
+	buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+	buff.extend_from_slice( rdata );
+}
+
+/**
+ * the response to an NS query for name, nameservers are the NS records with their glue. They
+ * are in the authority section of a referral and the answer section of an authoratative answer
+ */
+fn response( name : &str, rcode : u8, authoratative : bool, nameservers : &[(&str, &str)] ) -> Sender {
+
+	let count = nameservers.len() as u8;
+	let mut buff : Vec<u8> = if authoratative {
+		vec![ 0x12, 0x34, 0x84, rcode, 0, 1, 0, count, 0, 0, 0, count ]
+	} else {
+		vec![ 0x12, 0x34, 0x80, rcode, 0, 1, 0, 0, 0, count, 0, count ]
+	};
+	buff.extend( wire_name(name) );
+	buff.extend_from_slice( &[ 0, 2, 0, 1 ] );
+
+	for (ns, _) in nameservers {
+		record( &mut buff, name, 2, &wire_name(ns) );
+	}
+	for (ns, ip) in nameservers {
+		let ip : std::net::Ipv4Addr = ip.parse().unwrap();
+		record( &mut buff, ns, 1, &ip.octets() );
+	}
+
+	Sender::decode( &buff ).unwrap()
+}
+
+/**
+ * the NXDOMAIN response for name from the example.com nameserver, with the zone's SOA in the
+ * authority section and its minimum as the negative TTL
+ */
+fn nxdomain( name : &str, minimum : u32 ) -> Sender {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 3, 0, 1, 0, 0, 0, 1, 0, 0 ];
+	buff.extend( wire_name(name) );
+	buff.extend_from_slice( &[ 0, 2, 0, 1 ] );
+
+	let mut rdata = wire_name( "ns.example.com." );
+	rdata.extend( wire_name( "hostmaster.example.com." ) );
+	for value in [ 2023100101, 7200, 3600, 1209600, minimum ] {
+		rdata.extend_from_slice( &value.to_be_bytes() );
+	}
+	record( &mut buff, "example.com.", 6, &rdata );
+
+	Sender::decode( &buff ).unwrap()
+}
+
+fn referral( name : &str, nameservers : &[(&str, &str)] ) -> Sender {
+	response( name, 0, false, nameservers )
+}
+
+fn authoratative( name : &str, nameservers : &[(&str, &str)] ) -> Sender {
+	response( name, 0, true, nameservers )
+}
+
+/**
+ * a root zone file that knows the root and com
+ */
+fn root() -> Root {
+
+	let mut root_addr = HashMap::new();
+
+	for (zone_name, ns, ip) in [ (".", "a.root-servers.net.", "198.41.0.4"), ("com.", "a.gtld-servers.net.", "192.5.6.30") ] {
+		let mut zone = NameServersForZone::new( &zone_name.to_string() );
+		zone.servers.push( Arc::new(RwLock::new(NameServer {
+			server_name: ns.to_string(),
+			ip: ip.parse().unwrap(),
+			speed: None
+		})));
+		root_addr.insert( zone_name.to_string(), Arc::new(RwLock::new(zone)) );
+	}
+
+	Root { root_addr, ..Default::default() }
+}
+
+/**
+ * example.com is delegated from com, a.example.com from example.com and b.a.example.com
+ * from a.example.com. sub.example.com is a name in example.com and nx.example.com does not exist.
+ * same.example.com is a zone on the example.com nameserver so it answers authoratatively
+ */
+fn ask( ip : &IpAddr, name : &str ) -> Result<Sender, String> {
+
+	match (ip.to_string().as_str(), name) {
+		("192.5.6.30", "example.com.") => { Ok(referral( name, &[ ("ns.example.com.", "192.0.2.1") ] )) },
+		("192.0.2.1", "a.example.com.") => { Ok(referral( name, &[ ("ns.a.example.com.", "192.0.2.2") ] )) },
+		("192.0.2.1", "sub.example.com.") => { Ok(authoratative( name, &[] )) },
+		("192.0.2.1", "nx.example.com.") => { Ok(response( name, 3, true, &[] )) },
+		("192.0.2.1", "gone.example.com.") => { Ok(nxdomain( name, 60 )) },
+		("192.0.2.1", "same.example.com.") => { Ok(authoratative( name, &[ ("ns.example.com.", "192.0.2.1") ] )) },
+		("192.0.2.2", "b.a.example.com.") => { Ok(referral( name, &[ ("ns.b.a.example.com.", "192.0.2.3") ] )) },
+		_ => { Err(format!("unexpected query for {} to {}", name, ip)) }
+	}
+}
+
+fn parent_servers( root : &mut Root, domain_name : &str, max_depth : usize ) -> (String, Vec<String>) {
+	let zone = root.resolve_delegation( &domain_name.to_string(), max_depth, &ask ).unwrap();
+	let zone = zone.read().unwrap();
+	(zone.zone_name.clone(), zone.servers.iter().map(|s| s.read().unwrap().ip.to_string()).collect())
+}
+
+#[test]
+fn two_levels() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "www.a.example.com.", 8 ), ("a.example.com.".to_string(), vec![ "192.0.2.2".to_string() ]) );
+	assert!( root.root_addr.contains_key("example.com.") );
+}
+
+#[test]
+fn three_levels() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "www.b.a.example.com.", 8 ), ("b.a.example.com.".to_string(), vec![ "192.0.2.3".to_string() ]) );
+}
+
+#[test]
+fn zones_are_looked_up_once() {
+
+	let asked = std::cell::RefCell::new( Vec::<String>::new() );
+	let counting = |ip : &IpAddr, name : &str| -> Result<Sender, String> {
+		asked.borrow_mut().push( name.to_string() );
+		ask( ip, name )
+	};
+
+	let mut root = root();
+	root.resolve_delegation( &"www.b.a.example.com.".to_string(), 8, &counting ).unwrap();
+	assert_eq!( *asked.borrow(), vec![ "example.com.", "a.example.com.", "b.a.example.com." ] );
+	for zone_name in [ "example.com.", "a.example.com.", "b.a.example.com." ] {
+		assert!( root.root_addr.contains_key(zone_name), "{}", zone_name );
+	}
+
+	// the next name under them starts from the deepest zone found
+	asked.borrow_mut().clear();
+	let zone = root.resolve_delegation( &"mail.b.a.example.com.".to_string(), 8, &counting ).unwrap();
+	assert!( asked.borrow().is_empty() );
+	assert_eq!( zone.read().unwrap().zone_name, "b.a.example.com." );
+	assert_eq!( root.closest_servers("mail.b.a.example.com."), vec![ "192.0.2.3".parse::<IpAddr>().unwrap() ] );
+}
+
+#[test]
+fn shared_root_is_not_locked_while_asking() {
+
+	let root = RwLock::new( root() );
+	let unlocked = |ip : &IpAddr, name : &str| -> Result<Sender, String> {
+		assert!( root.try_write().is_ok(), "the root is locked while asking for {}", name );
+		ask( ip, name )
+	};
+
+	let zone = Root::resolve_delegation_shared( &root, &"www.b.a.example.com.".to_string(), 8, &unlocked, &|_, _| Err("no glueless nameservers".to_string()) ).unwrap();
+	assert_eq!( zone.read().unwrap().zone_name, "b.a.example.com." );
+	for zone_name in [ "example.com.", "a.example.com.", "b.a.example.com." ] {
+		assert!( root.read().unwrap().root_addr.contains_key(zone_name), "{}", zone_name );
+	}
+}
+
+#[test]
+fn expired_zone_is_resolved_again() {
+
+	let asked = std::cell::RefCell::new( Vec::<String>::new() );
+	let counting = |ip : &IpAddr, name : &str| -> Result<Sender, String> {
+		asked.borrow_mut().push( name.to_string() );
+		ask( ip, name )
+	};
+
+	// example.com. was resolved to an old nameserver with a TTL of a second
+	let insert = |root : &mut Root, resolved_ago : std::time::Duration| {
+		let mut zone = NameServersForZone::new( &"example.com.".to_string() );
+		zone.servers.push( Arc::new(RwLock::new(NameServer {
+			server_name: "old.example.com.".to_string(),
+			ip: "192.0.2.99".parse().unwrap(),
+			speed: None
+		})));
+		zone.ttl = Some( std::time::Duration::from_secs(1) );
+		zone.resolved_at = Some( std::time::Instant::now() - resolved_ago );
+		root.root_addr.insert( "example.com.".to_string(), Arc::new(RwLock::new(zone)) );
+	};
+
+	// it is still used within its TTL
+	let mut root = root();
+	insert( &mut root, std::time::Duration::ZERO );
+	assert_eq!( parent_servers( &mut root, "www.example.com.", 8 ), ("example.com.".to_string(), vec![ "192.0.2.99".to_string() ]) );
+
+	// and asked for again from com once it runs out, the TTL of the new NS records is kept
+	insert( &mut root, std::time::Duration::from_secs(2) );
+	let zone = root.resolve_delegation( &"www.a.example.com.".to_string(), 8, &counting ).unwrap();
+	assert_eq!( *asked.borrow(), vec![ "example.com.", "a.example.com." ] );
+	assert_eq!( zone.read().unwrap().zone_name, "a.example.com." );
+
+	let example = root.root_addr.get("example.com.").unwrap().read().unwrap();
+	assert_eq!( example.servers[0].read().unwrap().ip.to_string(), "192.0.2.1" );
+	assert_eq!( example.ttl, Some( std::time::Duration::from_secs(3600) ) );
+	assert!( !example.expired( std::time::Instant::now() ) );
+	assert!( example.expired( std::time::Instant::now() + std::time::Duration::from_secs(3600) ) );
+
+	// the zones of the root zone file don't expire
+	assert!( !root.root_addr.get("com.").unwrap().read().unwrap().expired( std::time::Instant::now() + std::time::Duration::from_secs(86400 * 365) ) );
+}
+
+#[test]
+fn glueless_nameserver() {
+
+	use dns_audit::query::QueryType;
+	use dns_audit::resolver::lookup_host_with;
+
+	// com refers example.com to ns.example.net. with no address for it
+	let ask = |ip : &IpAddr, name : &str| -> Result<Sender, String> {
+		match (ip.to_string().as_str(), name) {
+			("192.5.6.30", "example.com.") => {
+				let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x80, 0, 0, 1, 0, 0, 0, 1, 0, 0 ];
+				buff.extend( wire_name(name) );
+				buff.extend_from_slice( &[ 0, 2, 0, 1 ] );
+				record( &mut buff, name, 2, &wire_name("ns.example.net.") );
+				Sender::decode( &buff )
+			},
+			_ => { Err(format!("unexpected query for {} to {}", name, ip)) }
+		}
+	};
+
+	// the root isn't asked for net so it answers for the nameserver itself
+	let asked = std::cell::RefCell::new( Vec::<String>::new() );
+	let host_ask = |ip : &IpAddr, name : &str, qtype : QueryType, _ : std::time::Duration| -> Result<Sender, String> {
+		asked.borrow_mut().push( format!("{} {} {}", ip, name, qtype) );
+		if ip.to_string() != "198.41.0.4" || name != "ns.example.net." {
+			return Err(format!("unexpected query for {} to {}", name, ip));
+		}
+		let count = if qtype == QueryType::T_A { 1 } else { 0 };
+		let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0, 0, 1, 0, count, 0, 0, 0, 0 ];
+		buff.extend( wire_name(name) );
+		buff.extend_from_slice( &qtype.as_u16().to_be_bytes() );
+		buff.extend_from_slice( &[ 0, 1 ] );
+		if count > 0 {
+			record( &mut buff, name, 1, &[ 203, 0, 113, 53 ] );
+		}
+		// the AAAA is asked of the server that answered the A
+		let mut sender = Sender::new( ip );
+		sender.read_response( &buff )?;
+		Ok(sender)
+	};
+	let resolve_host = |name : &str, start_servers : &dns_audit::resolver::StartServers| -> Result<Vec<IpAddr>, String> {
+		lookup_host_with( name, start_servers, std::time::Duration::from_secs(5), 8, &host_ask )
+	};
+
+	let mut root = root();
+	let zone = root.resolve_delegation_with( &"www.example.com.".to_string(), 8, &ask, &resolve_host ).unwrap();
+	let zone = zone.read().unwrap();
+
+	assert_eq!( zone.zone_name, "example.com." );
+	let servers : Vec<String> = zone.servers.iter().map(|s| format!("{} {}", s.read().unwrap().server_name, s.read().unwrap().ip)).collect();
+	assert_eq!( servers, vec![ "ns.example.net. 203.0.113.53" ] );
+	assert_eq!( *asked.borrow(), vec![ "198.41.0.4 ns.example.net. T_A", "198.41.0.4 ns.example.net. T_AAAA" ] );
+}
+
+#[test]
+fn name_in_parent_zone() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "www.sub.example.com.", 8 ).0, "example.com." );
+	assert_eq!( parent_servers( &mut root, "www.nx.example.com.", 8 ).0, "example.com." );
+}
+
+#[test]
+fn missing_zone_is_not_asked_again() {
+
+	let asked = std::cell::RefCell::new( Vec::<String>::new() );
+	let counting = |ip : &IpAddr, name : &str| -> Result<Sender, String> {
+		asked.borrow_mut().push( name.to_string() );
+		ask( ip, name )
+	};
+
+	let mut root = root();
+	root.resolve_delegation( &"www.nx.example.com.".to_string(), 8, &counting ).unwrap();
+	root.resolve_delegation( &"www.gone.example.com.".to_string(), 8, &counting ).unwrap();
+	assert_eq!( *asked.borrow(), vec![ "example.com.", "nx.example.com.", "gone.example.com." ] );
+
+	// the second lookups don't send anything
+	asked.borrow_mut().clear();
+	let zone = root.resolve_delegation( &"mail.nx.example.com.".to_string(), 8, &counting ).unwrap();
+	root.resolve_delegation( &"mail.gone.example.com.".to_string(), 8, &counting ).unwrap();
+	assert!( asked.borrow().is_empty(), "{:?}", asked.borrow() );
+	assert_eq!( zone.read().unwrap().zone_name, "example.com." );
+
+	// without an SOA the default negative TTL is used, with one its minimum
+	let now = std::time::Instant::now();
+	let nx = root.missing.get("nx.example.com.").unwrap().duration_since(now);
+	let gone = root.missing.get("gone.example.com.").unwrap().duration_since(now);
+	assert!( nx > root.negative_ttl - std::time::Duration::from_secs(5) && nx <= root.negative_ttl, "{:?}", nx );
+	assert!( gone > std::time::Duration::from_secs(55) && gone <= std::time::Duration::from_secs(60), "{:?}", gone );
+
+	// once it runs out the parent is asked again
+	root.missing.insert( "gone.example.com.".to_string(), now );
+	root.resolve_delegation( &"www.gone.example.com.".to_string(), 8, &counting ).unwrap();
+	assert_eq!( *asked.borrow(), vec![ "gone.example.com." ] );
+}
+
+#[test]
+fn max_depth() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "www.b.a.example.com.", 1 ).0, "example.com." );
+	assert_eq!( parent_servers( &mut root, "www.b.a.example.com.", 0 ).0, "example.com." );
+	assert_eq!( parent_servers( &mut root, "www.b.a.example.com.", 2 ).0, "b.a.example.com." );
+}
+
+#[test]
+fn domain_itself_is_not_looked_up() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "example.com.", 8 ).0, "com." );
+	assert_eq!( parent_servers( &mut root, "com.", 8 ).0, "." );
+}
+
+#[test]
+fn authoratative_answer() {
+	let mut root = root();
+	assert_eq!( parent_servers( &mut root, "www.same.example.com.", 8 ), ("same.example.com.".to_string(), vec![ "192.0.2.1".to_string() ]) );
+}
+
+#[test]
+fn nameservers_by_response_shape() {
+
+	let nameservers = [ ("ns1.example.com.", "192.0.2.1"), ("ns2.example.com.", "192.0.2.2") ];
+	let expected = vec![ "ns1.example.com.".to_string(), "ns2.example.com.".to_string() ];
+
+	let sender = referral( "example.com.", &nameservers );
+	assert!( sender.answer.is_empty() );
+	assert_eq!( sender.zone_nameservers("example.com."), expected );
+
+	let sender = authoratative( "example.com.", &nameservers );
+	assert!( sender.authority.is_empty() );
+	assert_eq!( sender.zone_nameservers("example.com"), expected );
+
+	// the NS records of another zone are not the ones asked for
+	assert!( sender.zone_nameservers("other.example.com.").is_empty() );
+}
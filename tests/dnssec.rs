@@ -0,0 +1,124 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the DNSSEC checks of a domain's answers, the records are read from zone file
+ * strings
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::check::{DnssecCheck, RrsigExpiryCheck};
+use dns_audit::monitor::{ErrorCode, Monitor};
+use dns_audit::zone::Zone;
+use dns_audit::zone::record::ZoneRecord;
+use dns_audit::zone::rr;
+use dns_audit::zone::tokenizer::ZoneLines;
+
+/**
+ * the records of the zone for example.com. as they would be in an answer
+ */
+fn answer( contents : &str ) -> Vec<ZoneRecord> {
+	let lines = ZoneLines::from_reader( &mut contents.as_bytes() ).unwrap();
+	let (zone, _warnings) = Zone::from_lines( lines, &"example.com.".to_string(), false ).unwrap();
+	zone.records_under( "example.com." ).into_iter().cloned().collect()
+}
+
+/**
+ * an A record for example.com. with an RRSIG for it that expires at expiration
+ */
+fn signed_a( expiration : &str ) -> Vec<ZoneRecord> {
+	answer( &format!("$ORIGIN example.com.
+@	3600	IN	A	192.0.2.1
+@	3600	IN	RRSIG	A 13 2 3600 {} 20261001000000 40915 example.com. AAECAwQFBgc=
+", expiration) )
+}
+
+#[test]
+fn expired_and_expiring_signatures() {
+
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+	let now = rr::parse_dnssec_time( "20261016000000" ).unwrap();
+
+	let findings = RrsigExpiryCheck::compare( &server, now, 7, &signed_a( "20261010000000" ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::SignatureExpired );
+	assert_eq!( findings[0].message, "RRSIG for example.com. A by key 40915 expired 20261010000000" );
+	assert_eq!( findings[0].detail.as_ref().unwrap().server.as_deref(), Some("192.0.2.53") );
+
+	// four days left inside of a seven day window
+	let findings = RrsigExpiryCheck::compare( &server, now, 7, &signed_a( "20261020000000" ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::SignatureExpiringSoon );
+	assert_eq!( findings[0].detail.as_ref().unwrap().actual.as_deref(), Some("20261020000000") );
+
+	// expiring at now is expired, a week and a second out is outside of the window
+	assert_eq!( RrsigExpiryCheck::compare( &server, now, 7, &signed_a( "20261016000000" ) )[0].code, ErrorCode::SignatureExpired );
+	assert!( RrsigExpiryCheck::compare( &server, now, 7, &signed_a( "20261023000001" ) ).is_empty() );
+	assert!( RrsigExpiryCheck::compare( &server, now, 7, &signed_a( "20261201000000" ) ).is_empty() );
+}
+
+#[test]
+fn long_warning_window() {
+
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+	let now = rr::parse_dnssec_time( "20261016000000" ).unwrap();
+
+	// 50000 days of seconds is past u32::MAX, every signature is inside of the window
+	let findings = RrsigExpiryCheck::compare( &server, now, 50000, &signed_a( "20261201000000" ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::SignatureExpiringSoon );
+}
+
+#[test]
+fn missing_signature() {
+
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+
+	// a signed A RRset
+	assert!( DnssecCheck::compare( &server, &signed_a( "20261201000000" ) ).is_empty() );
+
+	// two A records and the RRSIG covers the SOA instead, the RRset is reported once
+	let findings = DnssecCheck::compare( &server, &answer( "$ORIGIN example.com.
+@	3600	IN	A	192.0.2.1
+@	3600	IN	A	192.0.2.2
+@	3600	IN	RRSIG	SOA 13 2 3600 20261201000000 20261001000000 40915 example.com. AAECAwQFBgc=
+" ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::MissingSignature );
+	assert_eq!( findings[0].message, "example.com. A has no RRSIG" );
+	assert_eq!( findings[0].detail.as_ref().unwrap().server.as_deref(), Some("192.0.2.53") );
+}
+
+#[test]
+fn rrsig_expiry_runs_for_signed_domains() {
+
+	let unsigned = Monitor::list_from_json( r#"[{ "domain_name": "example.com" }]"# ).unwrap().pop().unwrap();
+	assert!( !unsigned.check_names().contains( &"rrsig_expiry".to_string() ) );
+
+	let signed = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "dnssec": true }]"# ).unwrap().pop().unwrap();
+	assert!( signed.check_names().contains( &"rrsig_expiry".to_string() ) );
+	assert!( signed.check_names().contains( &"dnssec".to_string() ) );
+
+	// listing the checks replaces the defaults
+	let listed = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "dnssec": true, "checks": [ "ns" ] }]"# ).unwrap().pop().unwrap();
+	assert_eq!( listed.check_names(), vec![ "ns" ] );
+}
@@ -0,0 +1,82 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for matching the addresses a domain resolved to against its expected ip entries, an
+ * address or a network
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::monitor::ExpectedIp;
+
+fn entries( list : &[&str] ) -> Vec<ExpectedIp> {
+	serde_json::from_value( serde_json::json!(list) ).unwrap()
+}
+
+fn ips( list : &[&str] ) -> Vec<IpAddr> {
+	list.iter().map(|a| a.parse().unwrap()).collect()
+}
+
+fn unmatched( expected : &[ExpectedIp], actual : &[IpAddr] ) -> (Vec<String>, Vec<String>) {
+	let (unexpected, missing) = ExpectedIp::unmatched( expected, actual );
+	(unexpected.iter().map(|a| a.to_string()).collect(), missing.iter().map(|e| e.to_string()).collect())
+}
+
+#[test]
+fn inside_and_outside_a_network() {
+
+	let expected = entries(&[ "192.0.2.0/24" ]);
+
+	// any address of the /24 matches, and one is enough for the network
+	assert_eq!( unmatched( &expected, &ips(&[ "192.0.2.1", "192.0.2.254" ]) ), (vec![], vec![]) );
+
+	// next to it is outside
+	assert_eq!( unmatched( &expected, &ips(&[ "192.0.2.7", "192.0.3.1" ]) ), (vec![ "192.0.3.1".to_string() ], vec![]) );
+
+	// nothing inside, the network is missing
+	assert_eq!( unmatched( &expected, &ips(&[ "198.51.100.1" ]) ), (vec![ "198.51.100.1".to_string() ], vec![ "192.0.2.0/24".to_string() ]) );
+}
+
+#[test]
+fn addresses_and_networks() {
+
+	let expected = entries(&[ "198.51.100.7", "2001:db8::/32" ]);
+
+	assert_eq!( unmatched( &expected, &ips(&[ "198.51.100.7", "2001:db8::1" ]) ), (vec![], vec![]) );
+	assert_eq!( unmatched( &expected, &ips(&[ "198.51.100.8", "2001:db8::1" ]) ), (vec![ "198.51.100.8".to_string() ], vec![ "198.51.100.7".to_string() ]) );
+
+	// an IPv4 address is never in an IPv6 network
+	assert_eq!( unmatched( &entries(&[ "::/0" ]), &ips(&[ "192.0.2.1" ]) ), (vec![ "192.0.2.1".to_string() ], vec![ "::/0".to_string() ]) );
+}
+
+#[test]
+fn invalid_entries() {
+
+	let e = serde_json::from_value::<Vec<ExpectedIp>>( serde_json::json!([ "192.0.2.0/33" ]) ).err().unwrap();
+	assert!( e.to_string().contains("invalid network '192.0.2.0/33'"), "{}", e );
+
+	let e = serde_json::from_value::<Vec<ExpectedIp>>( serde_json::json!([ "192.0.2" ]) ).err().unwrap();
+	assert!( e.to_string().contains("invalid address '192.0.2'"), "{}", e );
+
+	// written back the way it was read
+	assert_eq!( serde_json::to_string( &entries(&[ "192.0.2.0/24", "198.51.100.7" ]) ).unwrap(), "[\"192.0.2.0/24\",\"198.51.100.7\"]" );
+}
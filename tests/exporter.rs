@@ -0,0 +1,94 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * Tests for the --listen endpoint, a pass is served and scraped over HTTP
+ */
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use dns_audit::exporter::Exporter;
+use dns_audit::monitor::{ErrorCode, Finding, MonitorResult};
+use dns_audit::query::QueryType;
+use dns_audit::report::AuditReport;
+use dns_audit::stats::QueryStats;
+
+fn get( exporter : &Exporter, path : &str ) -> (String, String) {
+	let mut stream = std::net::TcpStream::connect( exporter.local_addr() ).unwrap();
+	write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+	let mut response = String::new();
+	stream.read_to_string( &mut response ).unwrap();
+
+	let (head, body) = response.split_once("\r\n\r\n").unwrap();
+	(head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[test]
+fn serve_pass() {
+
+	let exporter = Exporter::listen( "127.0.0.1:0" ).unwrap();
+
+	// nothing is served until the first pass
+	assert_eq!( get( &exporter, "/health" ).0, "HTTP/1.1 503 Service Unavailable" );
+	assert_eq!( get( &exporter, "/metrics" ).0, "HTTP/1.1 503 Service Unavailable" );
+
+	let passed = MonitorResult::new( "example.com" );
+	let mut failed = MonitorResult::new( "example.net" );
+	failed.success = false;
+	failed.findings.push( Finding::new( ErrorCode::NoResolve, "example.net did not resolve" ) );
+
+	let results = vec![ Arc::new(Mutex::new(passed)), Arc::new(Mutex::new(failed)) ];
+	let mut report = AuditReport::default();
+	for r in &results {
+		report.add( &r.lock().unwrap() );
+	}
+	report.duration = std::time::Duration::from_millis(1500);
+	let queries = vec![ (QueryType::T_A, QueryStats { sent: 4, failed: 1, timed_out: 1 }) ];
+
+	exporter.update( &results, &report, &queries );
+
+	assert_eq!( get( &exporter, "/health" ), ("HTTP/1.1 200 OK".to_string(), "ok\n".to_string()) );
+
+	let (status, body) = get( &exporter, "/" );
+	assert_eq!( status, "HTTP/1.1 200 OK" );
+	let json : serde_json::Value = serde_json::from_str( &body ).unwrap();
+	assert_eq!( json[0]["domain_name"], "example.com" );
+	assert_eq!( json[1]["findings"][0]["code"], "NoResolve" );
+
+	let (status, body) = get( &exporter, "/metrics" );
+	assert_eq!( status, "HTTP/1.1 200 OK" );
+	for line in [
+		"# TYPE dns_audit_domains gauge",
+		"dns_audit_domains{result=\"passed\"} 1",
+		"dns_audit_domains{result=\"failed\"} 1",
+		"dns_audit_domain_success{domain=\"example.com\"} 1",
+		"dns_audit_domain_success{domain=\"example.net\"} 0",
+		"dns_audit_findings{code=\"NoResolve\"} 1",
+		"dns_audit_queries{qtype=\"A\",outcome=\"sent\"} 4",
+		"dns_audit_queries{qtype=\"A\",outcome=\"timed_out\"} 1",
+		"dns_audit_pass_duration_seconds 1.500"
+	] {
+		assert!( body.lines().any(|l| l == line), "{} is not in\n{}", line, body );
+	}
+
+	assert_eq!( get( &exporter, "/other" ).0, "HTTP/1.1 404 Not Found" );
+}
@@ -0,0 +1,62 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for skipping servers of an address family we have no route for, the egress probes
+ * are replaced so the test doesn't depend on the routes of the machine it runs on
+ */
+
+use std::net::{IpAddr, UdpSocket};
+use std::time::Duration;
+
+use dns_audit::query::{self, QueryType, Sender};
+
+#[test]
+fn unavailable_family_is_not_queried() {
+
+	query::set_family_probe( Some(|addr : &IpAddr| addr.is_ipv4()) );
+
+	// a server on ::1 that would see the request if it were sent
+	let server = UdpSocket::bind( "[::1]:0" ).unwrap();
+	server.set_read_timeout( Some(Duration::from_millis(200)) ).unwrap();
+
+	let mut sender = Sender::new( &"::1".parse().unwrap() );
+	sender.port = server.local_addr().unwrap().port();
+	sender.timeout = Duration::from_millis(200);
+
+	let e = sender.query( "example.com.", QueryType::T_AAAA ).unwrap_err();
+	assert!( e.contains("address family of ::1 is unavailable"), "{}", e );
+	assert!( !sender.timed_out );
+
+	let mut buff = [ 0u8; 512 ];
+	assert!( server.recv_from( &mut buff ).is_err(), "the request was sent" );
+
+	assert!( query::family_available( &"192.0.2.1".parse().unwrap() ) );
+	assert!( !query::family_available( &"2001:db8::1".parse().unwrap() ) );
+
+	// forcing the family on wins over the probe
+	*dns_audit::config::FORCE_IPV6.write().unwrap() = true;
+	assert!( query::family_available( &"2001:db8::1".parse().unwrap() ) );
+	*dns_audit::config::FORCE_IPV6.write().unwrap() = false;
+
+	query::set_family_probe( None );
+}
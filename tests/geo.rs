@@ -0,0 +1,100 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for comparing the answers clients in different networks get against the monitor's geo
+ * map. The answers are hand built responses with the EDNS Client Subnet option
+ */
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use dns_audit::check::GeoCheck;
+use dns_audit::monitor::{ErrorCode, ExpectedIp, Monitor};
+use dns_audit::query::Sender;
+use dns_audit::zone::record::ZoneRecord;
+
+/**
+ * the answer for example.com. a server gives a client in the /24 of subnet, the address and
+ * the scope it is good for
+ */
+fn answer( subnet : [u8; 3], address : [u8; 4] ) -> (ipnet::IpNet, Vec<IpAddr>) {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, 1, 0, 0, 0, 1 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+	buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4 ] );
+	buff.extend_from_slice( &address );
+	buff.extend_from_slice( &[ 0, 0, 41, 0x04, 0xd0, 0, 0, 0, 0, 0, 11, 0, 8, 0, 7, 0, 1, 24, 24 ] );
+	buff.extend_from_slice( &subnet );
+
+	let sender = Sender::decode( &buff ).unwrap();
+	assert_eq!( sender.client_subnet_scope(), Some(24) );
+
+	let network = format!("{}.{}.{}.0/24", subnet[0], subnet[1], subnet[2]).parse().unwrap();
+	(network, sender.answer.iter().filter_map(ZoneRecord::record_to_address).collect())
+}
+
+fn geo() -> BTreeMap<ipnet::IpNet, Vec<ExpectedIp>> {
+	let monitor = Monitor::list_from_json( r#"[{
+		"domain_name": "example.com",
+		"geo": {
+			"198.51.100.0/24": [ "192.0.2.10" ],
+			"203.0.113.0/24": [ "192.0.2.16/28" ]
+		}
+	}]"# ).unwrap().pop().unwrap();
+	monitor.geo.unwrap()
+}
+
+#[test]
+fn each_network_gets_its_addresses() {
+
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+	let answers : BTreeMap<ipnet::IpNet, Vec<IpAddr>> = [
+		answer( [ 198, 51, 100 ], [ 192, 0, 2, 10 ] ),
+		answer( [ 203, 0, 113 ], [ 192, 0, 2, 20 ] )
+	].into_iter().collect();
+
+	assert!( GeoCheck::compare( &geo(), &server, &answers ).is_empty() );
+}
+
+#[test]
+fn network_gets_another_networks_address() {
+
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+
+	// clients in 203.0.113.0/24 are sent to the address meant for 198.51.100.0/24
+	let answers : BTreeMap<ipnet::IpNet, Vec<IpAddr>> = [
+		answer( [ 198, 51, 100 ], [ 192, 0, 2, 10 ] ),
+		answer( [ 203, 0, 113 ], [ 192, 0, 2, 10 ] )
+	].into_iter().collect();
+
+	let findings = GeoCheck::compare( &geo(), &server, &answers );
+	assert_eq!( findings.len(), 1 );
+	assert!( findings[0].code == ErrorCode::GeoMismatch );
+	assert_eq!( findings[0].message, "clients in 203.0.113.0/24 did not get the correct ips, unexpected [192.0.2.10] missing [192.0.2.16/28]" );
+
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.server.as_deref(), Some("192.0.2.53") );
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.16/28") );
+	assert_eq!( detail.actual.as_deref(), Some("192.0.2.10") );
+}
@@ -0,0 +1,101 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Golden tests for the zone parser. Each tests/zones/NAME.zone is parsed with NAME. as the
+ * origin and written back out, the output has to match NAME.golden and parse again to the
+ * same records. Run with UPDATE_GOLDEN=1 to rewrite the golden files after a deliberate change
+ */
+
+use std::path::{Path, PathBuf};
+
+use dns_audit::zone::{Zone, record::ZoneRecord};
+
+fn zones_dir() -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("zones")
+}
+
+fn parse( path : &Path, origin : &str ) -> Zone {
+	match Zone::create( &path.to_string_lossy().to_string(), &origin.to_string() ) {
+		Ok(m) => { m },
+		Err(e) => { panic!("failed to parse {} {}", path.display(), e); }
+	}
+}
+
+fn records( zone : &Zone ) -> Vec<&ZoneRecord> {
+	zone.records.iter().filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>()).collect()
+}
+
+/**
+ * parse the zone, compare it with its golden file then check the written zone parses the same
+ */
+fn check_golden( zone_file : &Path ) {
+
+	let name = zone_file.file_stem().unwrap().to_string_lossy().to_string();
+	let origin = format!("{}.", name);
+	let golden_file = zone_file.with_extension("golden");
+
+	let zone = parse( zone_file, &origin );
+	let written = zone.to_string();
+
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		std::fs::write( &golden_file, &written ).unwrap();
+	}
+
+	let golden = match std::fs::read_to_string( &golden_file ) {
+		Ok(m) => { m },
+		Err(e) => { panic!("failed to read {}, run with UPDATE_GOLDEN=1 to create it {}", golden_file.display(), e); }
+	};
+	assert_eq!( written, golden, "{} does not match {}", zone_file.display(), golden_file.display() );
+
+	// the names are absolute so a different origin must not change anything
+	let reparse_file = std::env::temp_dir().join(format!("dns_audit_golden_{}_{}.zone", std::process::id(), name));
+	std::fs::write( &reparse_file, &written ).unwrap();
+	let reparsed = parse( &reparse_file, "invalid." );
+	std::fs::remove_file( &reparse_file ).unwrap();
+
+	assert_eq!( reparsed.to_string(), written, "{} changed when the written zone was parsed again", zone_file.display() );
+
+	let before = records( &zone );
+	let after = records( &reparsed );
+	assert_eq!( before.len(), after.len() );
+	for (a, b) in before.iter().zip(after.iter()) {
+		assert!( a == b, "{} record {} parsed again as {}", zone_file.display(), a, b );
+		assert_eq!( a.ttl, b.ttl );
+	}
+}
+
+#[test]
+fn golden_zones() {
+
+	let mut zone_files : Vec<PathBuf> = std::fs::read_dir( zones_dir() ).unwrap()
+		.map(|e| e.unwrap().path())
+		.filter(|p| p.extension().is_some_and(|e| e == "zone"))
+		.collect();
+	zone_files.sort();
+
+	assert!( !zone_files.is_empty() );
+
+	for zone_file in &zone_files {
+		check_golden( zone_file );
+	}
+}
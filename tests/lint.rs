@@ -0,0 +1,124 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the --lint checks of a zone file's contents, the zones are read from strings
+ */
+
+use dns_audit::lint::{lint_zone, LintFinding};
+use dns_audit::zone::Zone;
+use dns_audit::zone::tokenizer::ZoneLines;
+
+/**
+ * the lint findings of the zone for example.com., written the way --lint prints them
+ */
+fn lint( contents : &str ) -> Vec<String> {
+	let lines = ZoneLines::from_reader( &mut contents.as_bytes() ).unwrap();
+	let (zone, _warnings) = Zone::from_lines( lines, &"example.com.".to_string(), false ).unwrap();
+	lint_zone( &zone, "example.com.", None ).iter().map(|f| f.to_string()).collect()
+}
+
+#[test]
+fn duplicate_a_record() {
+
+	let findings = lint( "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	NS	ns2
+ns1	IN	A	192.0.2.1
+ns2	IN	A	192.0.2.2
+www	IN	A	192.0.2.80
+www	IN	A	192.0.2.81
+www.example.com.	IN	A	192.0.2.80
+" );
+
+	// written relative and absolute it's still the same record, the other address isn't one
+	assert_eq!( findings, vec![ "line 8: duplicate A record for www.example.com., also at line 10" ] );
+
+	// the lines of a duplicated NS record
+	let lines = ZoneLines::from_reader( &mut "$ORIGIN example.com.\n@ IN SOA ns1 hostmaster 1 7200 3600 1209600 3600\n@ IN NS ns1\n@ IN NS ns1\n".as_bytes() ).unwrap();
+	let (zone, _warnings) = Zone::from_lines( lines, &"example.com.".to_string(), false ).unwrap();
+	let findings = lint_zone( &zone, "example.com.", None );
+	assert!( findings.iter().any(|f| matches!(f, LintFinding::DuplicateRecord { record_type, lines, .. } if record_type == "NS" && *lines == vec![ 3, 4 ])) );
+}
+
+#[test]
+fn apex_records() {
+
+	// no SOA, the apex comes from the $ORIGIN
+	let findings = lint( "$ORIGIN example.com.
+@	IN	NS	ns1
+@	IN	NS	ns2
+ns1	IN	A	192.0.2.1
+ns2	IN	A	192.0.2.2
+" );
+	assert_eq!( findings, vec![ "no SOA record at the apex example.com." ] );
+
+	// a single NS is a warning, the line it's on is given
+	let lines = ZoneLines::from_reader( &mut "$ORIGIN example.com.
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+ns1	IN	A	192.0.2.1
+".as_bytes() ).unwrap();
+	let (zone, _warnings) = Zone::from_lines( lines, &"example.com.".to_string(), false ).unwrap();
+	let findings = lint_zone( &zone, "example.com.", None );
+	assert_eq!( findings.len(), 1 );
+	assert!( findings[0].is_warning() );
+	assert_eq!( findings[0].to_string(), "line 3: warning: only one NS record at the apex example.com., at least two are recommended" );
+
+	// neither, and the NS records of a delegation aren't the apex's
+	assert_eq!( lint( "$ORIGIN example.com.
+sub	IN	NS	ns1.sub
+sub	IN	NS	ns2.sub
+" ), vec![ "no SOA record at the apex example.com.", "no NS records at the apex example.com." ] );
+
+	// two SOA records
+	assert_eq!( lint( "$ORIGIN example.com.
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	SOA	ns1 hostmaster 2 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	NS	ns2
+" ), vec![ "line 2: more than one SOA record at the apex example.com., also at line 3" ] );
+}
+
+#[test]
+fn mx_target_is_cname() {
+
+	let findings = lint( "$ORIGIN example.com.
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	NS	ns2.example.net.
+ns1	IN	A	192.0.2.1
+@	IN	MX	10 mail
+@	IN	MX	20 mx.example.net.
+@	IN	MX	30 smtp
+mail	IN	CNAME	smtp
+smtp	IN	A	192.0.2.25
+_sip._tcp	IN	SRV	10 60 5060 mail
+" );
+
+	// the target outside of the zone is only looked up with --lint-live
+	assert_eq!( findings, vec![
+		"line 6: MX record for example.com. points at mail.example.com. which is a CNAME",
+		"line 11: SRV record for _sip._tcp.example.com. points at mail.example.com. which is a CNAME"
+	] );
+}
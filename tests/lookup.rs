@@ -0,0 +1,129 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the limits on resolving a nameserver's name. The servers are stood in for by an
+ * Ask that answers from hand built responses or never answers
+ */
+
+use std::cell::Cell;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use dns_audit::query::{QueryType, Sender};
+use dns_audit::resolver::{lookup_host_asking, lookup_host_with};
+
+/**
+ * an answer for ns.example.com. with count A records, 192.0.2.1 and up, for an A query and
+ * no records for any other
+ */
+fn answer( qtype : QueryType, count : u8 ) -> Sender {
+
+	let count = if qtype == QueryType::T_A { count } else { 0 };
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, count, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x02ns\x07example\x03com\x00" );
+	buff.extend_from_slice( &qtype.as_u16().to_be_bytes() );
+	buff.extend_from_slice( &[ 0, 1 ] );
+
+	for i in 0..count {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, i + 1 ] );
+	}
+
+	Sender::decode( &buff ).unwrap()
+}
+
+fn servers( count : u8 ) -> Vec<IpAddr> {
+	(1..=count).map(|i| IpAddr::from([ 198, 51, 100, i ])).collect()
+}
+
+#[test]
+fn gives_up_after_timeout() {
+
+	let asked = Cell::new(0);
+
+	// every server takes 100ms to not answer, asking all of them would take 5s
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, remaining : Duration| -> Result<Sender, String> {
+		asked.set( asked.get() + 1 );
+		std::thread::sleep( remaining.min( Duration::from_millis(100) ) );
+		Err("no response".to_string())
+	};
+
+	let start = Instant::now();
+	let e = lookup_host_with( "ns.example.com.", &|_| servers(50), Duration::from_millis(300), 8, &ask ).err().unwrap();
+
+	assert!( e.contains("gave up resolving ns.example.com. after 300ms"), "{}", e );
+	assert!( start.elapsed() < Duration::from_secs(1) );
+	assert!( asked.get() <= 3, "asked {} servers", asked.get() );
+}
+
+#[test]
+fn hung_server_is_cut_off() {
+
+	// the only server holds on to the query for as long as it is allowed to
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, remaining : Duration| -> Result<Sender, String> {
+		std::thread::sleep( remaining );
+		Err("no response".to_string())
+	};
+
+	let start = Instant::now();
+	assert!( lookup_host_with( "ns.example.com.", &|_| servers(1), Duration::from_millis(200), 8, &ask ).is_err() );
+	assert!( start.elapsed() < Duration::from_secs(1) );
+}
+
+#[test]
+fn addresses_are_capped() {
+
+	let ask = |_ : &IpAddr, _ : &str, qtype : QueryType, _ : Duration| -> Result<Sender, String> {
+		Ok(answer( qtype, 20 ))
+	};
+
+	let addresses = lookup_host_with( "ns.example.com.", &|_| servers(1), Duration::from_secs(5), 8, &ask ).unwrap();
+	assert_eq!( addresses, (1..=8).map(|i| IpAddr::from([ 192, 0, 2, i ])).collect::<Vec<IpAddr>>() );
+
+	let addresses = lookup_host_with( "ns.example.com.", &|_| servers(1), Duration::from_secs(5), 30, &ask ).unwrap();
+	assert_eq!( addresses.len(), 20 );
+}
+
+#[test]
+fn configured_limits() {
+
+	*dns_audit::config::LOOKUP_TIMEOUT.write().unwrap() = Duration::from_millis(300);
+	*dns_audit::config::MAX_NS_ADDRESSES.write().unwrap() = 4;
+
+	// the servers never answer, the lookup stops at the --lookup-timeout
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, remaining : Duration| -> Result<Sender, String> {
+		std::thread::sleep( remaining.min( Duration::from_millis(100) ) );
+		Err("no response".to_string())
+	};
+
+	let start = Instant::now();
+	let e = lookup_host_asking( "ns.example.com.", &|_| servers(50), &ask ).err().unwrap();
+	assert!( e.contains("after 300ms"), "{}", e );
+	assert!( start.elapsed() < Duration::from_secs(1) );
+
+	// and only --max-ns-addresses of the addresses are kept
+	let ask = |_ : &IpAddr, _ : &str, qtype : QueryType, _ : Duration| -> Result<Sender, String> {
+		Ok(answer( qtype, 20 ))
+	};
+	assert_eq!( lookup_host_asking( "ns.example.com.", &|_| servers(1), &ask ).unwrap().len(), 4 );
+}
@@ -0,0 +1,132 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the negative cache, NXDOMAIN responses are kept for the TTL the SOA in their
+ * authority section allows
+ */
+
+use std::time::{Duration, Instant};
+
+use dns_audit::query::{NSClass, NegativeCache, NegativeKey, QueryType, Sender};
+
+fn wire_name( name : &str ) -> Vec<u8> {
+	let mut buff = Vec::new();
+	for label in name.trim_end_matches('.').split('.') {
+		buff.push( label.len() as u8 );
+		buff.extend_from_slice( label.as_bytes() );
+	}
+	buff.push(0);
+	buff
+}
+
+/**
+ * an NXDOMAIN response for gone.example.com. A with the zone's SOA in the authority section,
+ * the SOA record has a TTL of soa_ttl and minimum of 60
+ */
+fn nxdomain( soa_ttl : u32 ) -> Vec<u8> {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x03, 0, 1, 0, 0, 0, 1, 0, 0 ];
+	buff.extend( wire_name("gone.example.com.") );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+
+	let mut rdata = wire_name("ns1.example.com.");
+	rdata.extend( wire_name("hostmaster.example.com.") );
+	for field in [ 1u32, 7200, 3600, 1209600, 60 ] {
+		rdata.extend_from_slice( &field.to_be_bytes() );
+	}
+
+	buff.extend( wire_name("example.com.") );
+	buff.extend_from_slice( &[ 0, 6, 0, 1 ] );
+	buff.extend_from_slice( &soa_ttl.to_be_bytes() );
+	buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+	buff.extend( rdata );
+
+	buff
+}
+
+fn key( host : &str ) -> NegativeKey {
+	NegativeKey::new( &"192.0.2.1".parse().unwrap(), 53, host, QueryType::T_A, NSClass::C_IN, false, false )
+}
+
+#[test]
+fn ttl_is_the_lower_of_the_soa_ttl_and_minimum() {
+	let sender = Sender::decode( &nxdomain(300) ).unwrap();
+	assert_eq!( NegativeCache::negative_ttl( &sender.authority ), Some(Duration::from_secs(60)) );
+
+	let sender = Sender::decode( &nxdomain(30) ).unwrap();
+	assert_eq!( NegativeCache::negative_ttl( &sender.authority ), Some(Duration::from_secs(30)) );
+
+	assert_eq!( NegativeCache::negative_ttl( &[] ), None );
+}
+
+#[test]
+fn second_query_within_the_ttl_is_cached() {
+
+	let response = nxdomain(300);
+	let ttl = NegativeCache::negative_ttl( &Sender::decode( &response ).unwrap().authority ).unwrap();
+
+	let mut cache = NegativeCache::new();
+	let now = Instant::now();
+	cache.insert( key("gone.example.com."), ttl, &response, now );
+
+	// the name is compared case-insensitively with or without the trailing dot
+	assert_eq!( cache.get( &key("Gone.Example.com"), now + Duration::from_secs(30) ), Some(response.clone()) );
+	assert_eq!( cache.get( &key("other.example.com."), now + Duration::from_secs(30) ), None );
+
+	let cached = Sender::decode( &cache.get( &key("gone.example.com."), now ).unwrap() ).unwrap();
+	assert!( cached.recv_header.rcode == dns_audit::query::RCODE::NXDOMAIN );
+
+	assert_eq!( cache.get( &key("gone.example.com."), now + Duration::from_secs(61) ), None );
+}
+
+#[test]
+fn recursive_query_or_other_port_is_cached_apart() {
+
+	let response = nxdomain(300);
+	let mut cache = NegativeCache::new();
+	let now = Instant::now();
+	cache.insert( key("gone.example.com."), Duration::from_secs(60), &response, now );
+
+	let recursive = NegativeKey::new( &"192.0.2.1".parse().unwrap(), 53, "gone.example.com.", QueryType::T_A, NSClass::C_IN, false, true );
+	assert_eq!( cache.get( &recursive, now ), None );
+	let other_port = NegativeKey::new( &"192.0.2.1".parse().unwrap(), 5353, "gone.example.com.", QueryType::T_A, NSClass::C_IN, false, false );
+	assert_eq!( cache.get( &other_port, now ), None );
+	assert_eq!( cache.get( &key("gone.example.com."), now ), Some(response) );
+}
+
+#[test]
+fn insert_drops_expired_entries() {
+
+	let response = nxdomain(300);
+	let mut cache = NegativeCache::new();
+	let now = Instant::now();
+	for i in 0..10 {
+		cache.insert( key(&format!("gone{}.example.com.", i)), Duration::from_secs(60), &response, now );
+	}
+	assert_eq!( cache.len(), 10 );
+
+	// the names asked once are never read again, the next insert after they expire drops them
+	cache.insert( key("gone.example.com."), Duration::from_secs(60), &response, now + Duration::from_secs(61) );
+	assert_eq!( cache.len(), 1 );
+	assert!( cache.get( &key("gone.example.com."), now + Duration::from_secs(61) ).is_some() );
+}
@@ -0,0 +1,66 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for checking the addresses of a domain's nameservers are in the allowed_ns_networks
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::check::NsNetworkCheck;
+use dns_audit::monitor::ErrorCode;
+
+fn networks() -> Vec<ipnet::IpNet> {
+	vec![ "192.0.2.0/24".parse().unwrap(), "2001:db8::/32".parse().unwrap() ]
+}
+
+fn addresses( list : &[&str] ) -> Result<Vec<IpAddr>, String> {
+	Ok(list.iter().map(|a| a.parse().unwrap()).collect())
+}
+
+#[test]
+fn inside_the_networks() {
+	assert!( NsNetworkCheck::compare( &networks(), "ns1.example.com.", addresses(&[ "192.0.2.53", "2001:db8::53" ]) ).is_empty() );
+}
+
+#[test]
+fn outside_the_networks() {
+
+	let findings = NsNetworkCheck::compare( &networks(), "ns1.example.com.", addresses(&[ "192.0.2.53", "198.51.100.53" ]) );
+
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::UnexpectedNsNetwork );
+	assert_eq!( findings[0].message, "nameserver ns1.example.com. address 198.51.100.53 is outside of the allowed networks" );
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.server.as_deref(), Some("ns1.example.com.") );
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.0/24, 2001:db8::/32") );
+	assert_eq!( detail.actual.as_deref(), Some("198.51.100.53") );
+}
+
+#[test]
+fn does_not_resolve() {
+
+	let findings = NsNetworkCheck::compare( &networks(), "ns1.example.com.", Err("ns1.example.com. does not exist".to_string()) );
+
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::NoResolve );
+	assert_eq!( findings[0].message, "nameserver ns1.example.com. did not resolve, ns1.example.com. does not exist" );
+}
@@ -0,0 +1,101 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the lookups of monitors that expect NXDOMAIN. The servers are stood in for by an
+ * Ask that answers from hand built responses
+ */
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use dns_audit::check::NxdomainCheck;
+use dns_audit::monitor::ErrorCode;
+use dns_audit::query::{QueryType, Sender};
+use dns_audit::resolver::{lookup_host_with, name_exists_with};
+
+/**
+ * an authoratative response for old.example.com. with the rcode, a CNAME to
+ * gone.example.com. when alias is set and then an A record when address is
+ */
+fn response( rcode : u8, alias : bool, address : bool ) -> Sender {
+
+	let count = alias as u8 + address as u8;
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, rcode, 0, 1, 0, count, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x03old\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+
+	if alias {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 7 ] );
+		buff.extend_from_slice( b"\x04gone\xc0\x10" );
+	}
+
+	if address {
+		let owner = if alias { 0x2d } else { 0x0c };
+		buff.extend_from_slice( &[ 0xc0, owner, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1 ] );
+	}
+
+	Sender::decode( &buff ).unwrap()
+}
+
+fn servers( _ : &str ) -> Vec<IpAddr> {
+	vec![ "198.51.100.1".parse().unwrap() ]
+}
+
+/**
+ * the findings for old.example.com. when every server answers with the response
+ */
+fn check( rcode : u8, alias : bool, address : bool ) -> Vec<dns_audit::monitor::Finding> {
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, _ : Duration| -> Result<Sender, String> { Ok( response( rcode, alias, address ) ) };
+	NxdomainCheck::compare( "old.example.com.", name_exists_with( "old.example.com.", &servers, Duration::from_secs(1), &ask ) )
+}
+
+#[test]
+fn name_resolves() {
+
+	let findings = check( 0, false, true );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::UnexpectedlyExists );
+	assert_eq!( findings[0].message, "old.example.com. exists but should be NXDOMAIN" );
+	assert_eq!( findings[0].detail.as_ref().unwrap().expected.as_deref(), Some("NXDOMAIN") );
+
+	// through an alias, and a name that exists without any A records
+	assert_eq!( check( 0, true, true )[0].code, ErrorCode::UnexpectedlyExists );
+	assert_eq!( check( 0, false, false )[0].code, ErrorCode::UnexpectedlyExists );
+}
+
+#[test]
+fn name_is_nxdomain() {
+
+	assert!( check( 3, false, false ).is_empty() );
+
+	// an alias to a name that doesn't exist is NXDOMAIN for the end of the chain
+	assert!( check( 3, true, false ).is_empty() );
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, _ : Duration| -> Result<Sender, String> { Ok( response( 3, true, false ) ) };
+	let e = lookup_host_with( "old.example.com.", &servers, Duration::from_secs(1), 8, &ask ).err().unwrap();
+	assert_eq!( e, "gone.example.com. does not exist" );
+
+	let ask = |_ : &IpAddr, _ : &str, _ : QueryType, _ : Duration| -> Result<Sender, String> { Err("no response".to_string()) };
+	let findings = NxdomainCheck::compare( "old.example.com.", name_exists_with( "old.example.com.", &servers, Duration::from_secs(1), &ask ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::NoResolve );
+}
@@ -0,0 +1,182 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the --pcap capture of queries to a server on the loopback on a thread of the test
+ */
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpListener, UdpSocket};
+use std::time::Duration;
+
+use dns_audit::pcap;
+use dns_audit::query::{QueryType, Sender};
+
+/**
+ * a server answering every question with an empty response, the port it listens on is
+ * returned
+ */
+fn serve() -> u16 {
+
+	let udp = UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+	let port = udp.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			let mut response = buff[..size].to_vec();
+			response[2] |= 0x84;
+			udp.send_to( &response, from ).unwrap();
+		}
+	});
+
+	port
+}
+
+/**
+ * a server answering every question over TCP with an empty response, the port it listens on
+ * is returned
+ */
+fn serve_tcp() -> u16 {
+
+	let listener = TcpListener::bind( "127.0.0.1:0" ).unwrap();
+	let port = listener.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		while let Ok( (mut stream, _) ) = listener.accept() {
+			let mut len = [ 0u8; 2 ];
+			stream.read_exact( &mut len ).unwrap();
+			let mut response = vec![ 0u8; u16::from_be_bytes(len) as usize ];
+			stream.read_exact( &mut response ).unwrap();
+			response[2] |= 0x84;
+			stream.write_all( &len ).unwrap();
+			stream.write_all( &response ).unwrap();
+		}
+	});
+
+	port
+}
+
+/**
+ * the internet checksum of a segment and the pseudo header of its IPv4 addresses, a segment
+ * with a correct checksum sums to 0
+ */
+fn segment_checksum( ip : &[u8], segment : &[u8] ) -> u16 {
+
+	let mut data = ip[12..20].to_vec();
+	data.extend_from_slice( &[ 0, ip[9] ] );
+	data.extend_from_slice( &(segment.len() as u16).to_be_bytes() );
+	data.extend_from_slice( segment );
+	if data.len() % 2 == 1 {
+		data.push( 0 );
+	}
+
+	let mut sum : u32 = data.chunks(2).map(|c| u16::from_be_bytes([ c[0], c[1] ]) as u32).sum();
+	while sum > 0xffff {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+	!(sum as u16)
+}
+
+/**
+ * the IP protocol, the source and destination ports and the payload of each IPv4 packet
+ * after the global header, the payload of a TCP segment has the message's two byte length
+ */
+fn packets( capture : &[u8] ) -> Vec<(u8, u16, u16, Vec<u8>)> {
+
+	let mut rval = Vec::new();
+	let mut offset = 24;
+
+	while offset < capture.len() {
+		let length = u32::from_le_bytes( capture[offset + 8..offset + 12].try_into().unwrap() ) as usize;
+		let frame = &capture[offset + 16..offset + 16 + length];
+
+		// Ethernet, a 20 byte IPv4 header then UDP or TCP
+		assert_eq!( &frame[12..14], &[ 0x08, 0x00 ] );
+		let ip = &frame[14..34];
+		let segment = &frame[34..];
+		let header = match ip[9] {
+			17 => { 8 },
+			6 => {
+				assert_eq!( segment_checksum( ip, segment ), 0 );
+				(segment[12] >> 4) as usize * 4
+			},
+			m => { panic!("unexpected protocol {}", m); }
+		};
+		rval.push( (ip[9], u16::from_be_bytes([ segment[0], segment[1] ]), u16::from_be_bytes([ segment[2], segment[3] ]), segment[header..].to_vec()) );
+
+		offset += 16 + length;
+	}
+
+	rval
+}
+
+#[test]
+fn request_and_response() {
+
+	let file_name = std::env::temp_dir().join(format!("dns_audit_pcap_{}.pcap", std::process::id()));
+	pcap::open( &file_name.to_string_lossy() ).unwrap();
+	assert!( pcap::enabled() );
+
+	let server : IpAddr = "127.0.0.1".parse().unwrap();
+	let mut sender = Sender::new( &server );
+	sender.port = serve();
+	sender.timeout = Duration::from_millis(500);
+
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+	sender.query( "example.com.", QueryType::T_MX ).unwrap();
+
+	let mut tcp = Sender::new( &server );
+	tcp.port = serve_tcp();
+	tcp.timeout = Duration::from_millis(500);
+	tcp.use_tcp = true;
+	tcp.query( "example.com.", QueryType::T_AAAA ).unwrap();
+
+	let capture = std::fs::read( &file_name ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	// the little endian magic, version 2.4, the snap length and Ethernet
+	assert_eq!( &capture[..8], &[ 0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0 ] );
+	assert_eq!( &capture[16..24], &[ 0xff, 0xff, 0, 0, 1, 0, 0, 0 ] );
+
+	// a request to the server and its response back for each query, the last over TCP
+	let mut packets = packets( &capture );
+	assert_eq!( packets.len(), 6 );
+	assert_eq!( packets.iter().map(|p| p.0).collect::<Vec<u8>>(), vec![ 17, 17, 17, 17, 6, 6 ] );
+	for packet in &mut packets[4..] {
+		let message = packet.3.split_off(2);
+		assert_eq!( u16::from_be_bytes([ packet.3[0], packet.3[1] ]) as usize, message.len() );
+		packet.3 = message;
+	}
+	for (pair, port) in packets.chunks(2).zip([ sender.port, sender.port, tcp.port ]) {
+		let (request, response) = (&pair[0], &pair[1]);
+		assert_eq!( request.2, port );
+		assert_eq!( response.1, port );
+		assert_eq!( request.1, response.2 );
+		assert_eq!( &request.3[..2], &response.3[..2] );
+		assert_eq!( request.3[2] & 0x80, 0 );
+		assert_eq!( response.3[2] & 0x80, 0x80 );
+	}
+	// the question types after example.com.
+	assert_eq!( u16::from_be_bytes([ packets[0].3[25], packets[0].3[26] ]), 1 );
+	assert_eq!( u16::from_be_bytes([ packets[2].3[25], packets[2].3[26] ]), 15 );
+	assert_eq!( u16::from_be_bytes([ packets[4].3[25], packets[4].3[26] ]), 28 );
+}
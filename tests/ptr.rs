@@ -0,0 +1,91 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for comparing the reverse DNS of an address with what the monitor expects, the forward
+ * lookups are answered from a table
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::check::PtrCheck;
+use dns_audit::monitor::{ErrorCode, ReverseMonitor};
+
+fn reverse( ptr : Option<&str>, fcrdns : bool ) -> ReverseMonitor {
+	ReverseMonitor {
+		ip: "192.0.2.10".parse().unwrap(),
+		ptr: ptr.map(|p| p.to_string()),
+		fcrdns
+	}
+}
+
+/**
+ * mail.example.com. resolves back to the address, web.example.com. to another one
+ */
+fn forward( name : &str ) -> Vec<IpAddr> {
+	match name {
+		"mail.example.com." => { vec![ "2001:db8::10".parse().unwrap(), "192.0.2.10".parse().unwrap() ] },
+		"web.example.com." => { vec![ "192.0.2.20".parse().unwrap() ] },
+		_ => { Vec::new() }
+	}
+}
+
+fn codes( rev : &ReverseMonitor, names : &[&str] ) -> Vec<ErrorCode> {
+	let names = names.iter().map(|n| n.to_string()).collect();
+	PtrCheck::compare( rev, Ok(names), &forward ).iter().map(|f| f.code).collect()
+}
+
+#[test]
+fn fcrdns() {
+
+	// the PTR name resolves back to the address, in any case
+	assert!( codes( &reverse( Some("MAIL.example.com."), true ), &[ "mail.example.com." ] ).is_empty() );
+
+	// one of the names resolving back is enough
+	assert!( codes( &reverse( None, true ), &[ "web.example.com.", "mail.example.com." ] ).is_empty() );
+
+	// it resolves to another address
+	let findings = PtrCheck::compare( &reverse( None, true ), Ok(vec![ "web.example.com.".to_string() ]), &forward );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::FcrdnsMismatch );
+	assert_eq!( findings[0].message, "web.example.com. does not resolve back to 192.0.2.10" );
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.10") );
+	assert_eq!( detail.actual.as_deref(), Some("192.0.2.20") );
+
+	// or to nothing, without fcrdns that isn't looked at
+	assert_eq!( codes( &reverse( None, true ), &[ "gone.example.com." ] ), vec![ ErrorCode::FcrdnsMismatch ] );
+	assert!( codes( &reverse( None, false ), &[ "gone.example.com." ] ).is_empty() );
+}
+
+#[test]
+fn ptr_mismatch() {
+
+	assert_eq!( codes( &reverse( Some("mail.example.com."), false ), &[ "web.example.com." ] ), vec![ ErrorCode::PtrMismatch ] );
+	assert_eq!( codes( &reverse( Some("mail.example.com."), true ), &[ "web.example.com." ] ), vec![ ErrorCode::PtrMismatch, ErrorCode::FcrdnsMismatch ] );
+
+	// no PTR at all, or the lookup failed
+	assert_eq!( codes( &reverse( Some("mail.example.com."), true ), &[] ), vec![ ErrorCode::NoResolve ] );
+	let findings = PtrCheck::compare( &reverse( None, true ), Err("10.2.0.192.in-addr.arpa. does not exist".to_string()), &forward );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::NoResolve );
+	assert!( findings[0].detail.is_none() );
+}
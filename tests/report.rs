@@ -0,0 +1,230 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the AuditReport summary of a run
+ */
+
+use std::time::Duration;
+
+use dns_audit::monitor::{ErrorCode, Finding, MonitorResult};
+use dns_audit::report::AuditReport;
+use dns_audit::stats::ServerStats;
+
+fn result( domain_name : &str, codes : &[ErrorCode] ) -> MonitorResult {
+	let mut rval = MonitorResult::new( domain_name );
+	for code in codes {
+		rval.findings.push( Finding::new( *code, "failed" ) );
+	}
+	rval.success = codes.is_empty();
+	rval
+}
+
+#[test]
+fn counts_and_groups() {
+
+	let results = vec![
+		result( "a.example", &[] ),
+		result( "b.example", &[ ErrorCode::NoResolve, ErrorCode::ResolveIpNotMatch ] ),
+		result( "c.example", &[ ErrorCode::ResolveIpNotMatch ] ),
+		result( "d.example", &[] ),
+	];
+
+	let report = AuditReport::new( &results, Duration::from_secs(3) );
+
+	assert_eq!( report.tested, 4 );
+	assert_eq!( report.passed, 2 );
+	assert_eq!( report.failed, 2 );
+	assert_eq!( report.duration, Duration::from_secs(3) );
+
+	assert_eq!( report.findings.keys().copied().collect::<Vec<ErrorCode>>(), vec![ ErrorCode::NoResolve, ErrorCode::ResolveIpNotMatch ] );
+	let domains = |code : ErrorCode| -> Vec<String> { report.findings[&code].iter().map(|f| f.domain_name.clone()).collect() };
+	assert_eq!( domains(ErrorCode::NoResolve), vec![ "b.example" ] );
+	assert_eq!( domains(ErrorCode::ResolveIpNotMatch), vec![ "b.example", "c.example" ] );
+}
+
+#[test]
+fn slowest_servers_first() {
+
+	let server = |responses : u64, total_ms : u64| -> ServerStats {
+		ServerStats { responses, total: Duration::from_millis(total_ms), slowest: Duration::from_millis(total_ms) }
+	};
+
+	let mut report = AuditReport::default();
+	let mut servers = Vec::new();
+	for i in 1..=7u8 {
+		servers.push( (std::net::IpAddr::from([ 192, 0, 2, i ]), server( 2, 10 * i as u64 )) );
+	}
+	report.set_servers( servers );
+
+	let order : Vec<String> = report.slowest_servers.iter().map(|s| s.server.to_string()).collect();
+	assert_eq!( order, vec![ "192.0.2.7", "192.0.2.6", "192.0.2.5", "192.0.2.4", "192.0.2.3" ] );
+	assert_eq!( report.slowest_servers[0].stats.average(), Duration::from_millis(35) );
+}
+
+#[test]
+fn csv_rows() {
+
+	use std::sync::{Arc, Mutex};
+	use dns_audit::report::{OutputFormat, ResultWriter};
+
+	let mut passed = result( "a.example", &[] );
+	passed.nameservers = Some(vec![ "ns1.example.".to_string(), "ns2.example.".to_string() ]);
+	passed.ips = Some(vec![ "192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap() ]);
+
+	let mut failed = MonitorResult::new( "b.example" );
+	failed.success = false;
+	failed.findings.push( Finding::new( ErrorCode::NoResolve, "no answer from 192.0.2.53, timed out" ) );
+	failed.findings.push( Finding::new( ErrorCode::ResolveIpNotMatch, "expected \"192.0.2.2\"" ) );
+
+	// the rows are written in the order the results were added, under the header
+	let mut writer = ResultWriter::new( OutputFormat::Csv, false, true );
+	let mut out = Vec::new();
+	writer.start( &mut out ).unwrap();
+	for res in [ passed, failed ] {
+		writer.add( &mut out, &Arc::new(Mutex::new( res )) ).unwrap();
+	}
+	writer.finish( &mut out, &AuditReport::default() ).unwrap();
+
+	assert_eq!( String::from_utf8(out).unwrap(), "domain_name,success,flags,reasons,nameservers,ips
+a.example,true,,,ns1.example.;ns2.example.,192.0.2.1;2001:db8::1
+b.example,false,NoResolve;ResolveIpNotMatch,\"no answer from 192.0.2.53, timed out; expected \"\"192.0.2.2\"\"\",,
+" );
+}
+
+#[test]
+fn text_report() {
+
+	use std::sync::{Arc, Mutex};
+	use dns_audit::report::{OutputFormat, ResultWriter};
+
+	let results = || {
+		let mut failed = MonitorResult::new( "b.example" );
+		failed.success = false;
+		failed.findings.push( Finding::new( ErrorCode::NoResolve, "no answer from 192.0.2.53" ) );
+		failed.findings.push( Finding::new( ErrorCode::ResolveIpNotMatch, "192.0.2.1 was not expected" ) );
+		[ result( "a.example", &[] ), failed, result( "c.example", &[ ErrorCode::NoResolve ] ) ]
+	};
+	let report = AuditReport::new( &results(), Duration::from_secs(1) );
+
+	let text = |stream : bool, all : bool| {
+		let mut writer = ResultWriter::new( OutputFormat::Text, stream, all );
+		let mut out = Vec::new();
+		writer.start( &mut out ).unwrap();
+		for res in results() {
+			writer.add( &mut out, &Arc::new(Mutex::new( res )) ).unwrap();
+		}
+		writer.finish( &mut out, &report ).unwrap();
+		String::from_utf8(out).unwrap()
+	};
+
+	// the results in the order they were added, then the counts of the report
+	let all = "a.example OK
+b.example FAIL
+\tno answer from 192.0.2.53
+\t192.0.2.1 was not expected
+c.example FAIL
+\tfailed
+1 passed, 2 failed
+";
+	assert_eq!( text( false, true ), all );
+	assert_eq!( text( true, true ), all );
+
+	// the summary still counts the domain that passed when only the failures are written
+	assert_eq!( text( false, false ), &all["a.example OK\n".len()..] );
+}
+
+#[test]
+fn streamed_results_are_not_kept() {
+
+	use std::sync::{Arc, Mutex};
+	use dns_audit::report::{OutputFormat, ResultWriter};
+
+	let results = [
+		Arc::new(Mutex::new( result( "a.example", &[] ) )),
+		Arc::new(Mutex::new( result( "b.example", &[ ErrorCode::NoResolve ] ) )),
+	];
+
+	// each result is written as it's added and nothing is left for finish
+	let mut writer = ResultWriter::new( OutputFormat::Csv, true, true );
+	let mut out = Vec::new();
+	writer.start( &mut out ).unwrap();
+	for res in &results {
+		writer.add( &mut out, res ).unwrap();
+		assert!( writer.results.is_empty() );
+	}
+	assert_eq!( String::from_utf8(out.clone()).unwrap().lines().count(), 3 );
+	writer.finish( &mut out, &AuditReport::default() ).unwrap();
+	assert_eq!( String::from_utf8(out).unwrap().lines().count(), 3 );
+	assert_eq!( Arc::strong_count( &results[0] ), 1 );
+
+	// without stream they're kept until finish writes them, only the failure without all
+	let mut writer = ResultWriter::new( OutputFormat::Csv, false, false );
+	let mut out = Vec::new();
+	writer.start( &mut out ).unwrap();
+	for res in &results {
+		writer.add( &mut out, res ).unwrap();
+	}
+	assert!( out.is_empty() );
+	assert_eq!( writer.results.len(), 1 );
+	writer.finish( &mut out, &AuditReport::default() ).unwrap();
+	assert!( writer.results.is_empty() );
+	assert_eq!( String::from_utf8(out).unwrap(), "domain_name,success,flags,reasons,nameservers,ips
+b.example,false,NoResolve,failed,,
+" );
+}
+
+#[test]
+fn colored_text() {
+
+	use std::sync::{Arc, Mutex};
+	use dns_audit::config::ColorMode;
+	use dns_audit::report::{OutputFormat, ResultWriter};
+
+	let results = [
+		Arc::new(Mutex::new( result( "a.example", &[] ) )),
+		Arc::new(Mutex::new( result( "b.example", &[ ErrorCode::NoResolve ] ) )),
+	];
+	let report = AuditReport::new( &[ result( "a.example", &[] ), result( "b.example", &[ ErrorCode::NoResolve ] ) ], Duration::from_secs(1) );
+
+	let text = |mode : ColorMode| {
+		let mut writer = ResultWriter::new( OutputFormat::Text, false, true );
+		// the output is a terminal so auto would color too
+		writer.color = mode.enabled( true );
+		let mut out = Vec::new();
+		for res in &results {
+			writer.add( &mut out, res ).unwrap();
+		}
+		writer.finish( &mut out, &report ).unwrap();
+		String::from_utf8(out).unwrap()
+	};
+
+	assert_eq!( text( ColorMode::Never ), "a.example OK\nb.example FAIL\n\tfailed\n1 passed, 1 failed\n" );
+	assert!( !text( ColorMode::Never ).contains('\x1b') );
+	assert_eq!( text( ColorMode::Always ), "a.example \x1b[32mOK\x1b[0m\nb.example \x1b[31mFAIL\x1b[0m\n\tfailed\n1 passed, \x1b[31m1 failed\x1b[0m\n" );
+
+	// the summary line's failed count is red with a failure and green without
+	use dns_audit::report::text_summary;
+	assert_eq!( text_summary( 1, 1, false ), "1 passed, 1 failed" );
+	assert_eq!( text_summary( 1, 1, true ), "1 passed, \x1b[31m1 failed\x1b[0m" );
+	assert_eq!( text_summary( 2, 0, true ), "2 passed, \x1b[32m0 failed\x1b[0m" );
+}
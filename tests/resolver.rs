@@ -0,0 +1,121 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for comparing what a recursive resolver answers with against the authoratative
+ * answer, the authority and the resolver are servers on the loopback on a thread of the test
+ */
+
+use std::net::{IpAddr, UdpSocket};
+use std::time::Duration;
+
+use dns_audit::check::ResolverCheck;
+use dns_audit::monitor::ErrorCode;
+use dns_audit::query::{QueryType, Sender};
+use dns_audit::resolver::query_addresses;
+
+/**
+ * a server on the loopback answering A questions with the addresses and AAAA ones with no
+ * records, the port it listens on is returned
+ */
+fn serve( addresses : Vec<[u8; 4]> ) -> u16 {
+
+	let udp = UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+	let port = udp.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			let request = &buff[..size];
+
+			// the end of the question name, then its type
+			let mut end = 12;
+			while request[end] != 0 {
+				end += request[end] as usize + 1;
+			}
+			let qtype = u16::from_be_bytes([ request[end + 1], request[end + 2] ]);
+			let answers : &[[u8; 4]] = if qtype == 1 { &addresses } else { &[] };
+
+			let mut response = request[..2].to_vec();
+			response.extend_from_slice( &[ 0x85, 0x80, 0, 1, 0, answers.len() as u8, 0, 0, 0, 0 ] );
+			response.extend_from_slice( &request[12..end + 5] );
+			for address in answers {
+				response.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4 ] );
+				response.extend_from_slice( address );
+			}
+			udp.send_to( &response, from ).unwrap();
+		}
+	});
+
+	port
+}
+
+/**
+ * the A and AAAA addresses the server on the port answers with, None when it didn't answer
+ */
+fn addresses( port : u16 ) -> Option<Vec<IpAddr>> {
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_millis(500);
+	sender.recursive = true;
+
+	let mut answered = false;
+	let mut rval = Vec::new();
+	for qtype in [ QueryType::T_A, QueryType::T_AAAA ] {
+		if let Some(found) = query_addresses( "example.com.", &mut sender, qtype ) {
+			answered = true;
+			rval.extend( found.addresses );
+		}
+	}
+
+	if answered { Some(rval) } else { None }
+}
+
+#[test]
+fn resolver_divergence() {
+
+	let authority = addresses( serve( vec![ [ 192, 0, 2, 1 ], [ 192, 0, 2, 2 ] ] ) ).unwrap();
+	assert_eq!( authority.len(), 2 );
+
+	let resolver : IpAddr = "127.0.0.1".parse().unwrap();
+
+	// the same addresses in another order
+	let agrees = addresses( serve( vec![ [ 192, 0, 2, 2 ], [ 192, 0, 2, 1 ] ] ) );
+	assert!( ResolverCheck::compare( &resolver, &authority, agrees.as_deref() ).is_empty() );
+
+	// one of them rewritten
+	let rewritten = addresses( serve( vec![ [ 192, 0, 2, 1 ], [ 198, 51, 100, 7 ] ] ) );
+	let findings = ResolverCheck::compare( &resolver, &authority, rewritten.as_deref() );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::ResolverDivergence );
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.1, 192.0.2.2") );
+	assert_eq!( detail.actual.as_deref(), Some("192.0.2.1, 198.51.100.7") );
+
+	// a port with nothing listening on it
+	let closed = UdpSocket::bind( "127.0.0.1:0" ).unwrap().local_addr().unwrap().port();
+	let silent = addresses( closed );
+	assert!( silent.is_none() );
+	let findings = ResolverCheck::compare( &resolver, &authority, silent.as_deref() );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].code, ErrorCode::NoResolve );
+}
@@ -0,0 +1,64 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for writing each result to its own file with --result-dir
+ */
+
+use dns_audit::monitor::{ErrorCode, Finding, MonitorResult};
+
+#[test]
+fn one_file_per_domain() {
+
+	let dir = std::env::temp_dir().join(format!("dns_audit_result_dir_{}", std::process::id()));
+	std::fs::create_dir_all( &dir ).unwrap();
+	let dir_name = dir.to_string_lossy().to_string();
+
+	let mut failed = MonitorResult::new( "Example.org." );
+	failed.success = false;
+	failed.findings.push( Finding::new( ErrorCode::NoResolve, "domain did not resolve" ) );
+
+	for result in [ MonitorResult::new( "example.com." ), failed, MonitorResult::new( "../etc/passwd" ), MonitorResult::new( "." ) ] {
+		result.write_to_dir( &dir_name ).unwrap();
+	}
+
+	// the names can't leave the directory, and no temporary file is left behind
+	let mut files : Vec<String> = std::fs::read_dir( &dir ).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+	files.sort();
+	assert_eq!( files, vec![ "%2E.%2Fetc%2Fpasswd.json", "%2E.json", "example.com.json", "example.org.json" ] );
+
+	let read = |file : &str| -> MonitorResult {
+		MonitorResult::from_json( &std::fs::read_to_string( dir.join(file) ).unwrap() ).unwrap()
+	};
+	let org = read( "example.org.json" );
+	assert_eq!( org.domain_name, "Example.org." );
+	assert!( !org.success );
+	assert_eq!( org.findings[0].code, ErrorCode::NoResolve );
+
+	// the next pass replaces the file
+	let mut passed = MonitorResult::new( "example.org." );
+	passed.success = true;
+	passed.write_to_dir( &dir_name ).unwrap();
+	assert!( read( "example.org.json" ).success );
+	assert_eq!( std::fs::read_dir( &dir ).unwrap().count(), 4 );
+
+	std::fs::remove_dir_all( &dir ).unwrap();
+}
@@ -0,0 +1,179 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for loading the --root-zone, a zone file for some other zone has to be refused
+ */
+
+use dns_audit::root::Root;
+
+fn load( name : &str, contents : &str ) -> Result<Root, String> {
+	let file_name = std::env::temp_dir().join(format!("dns_audit_root_{}_{}.zone", std::process::id(), name));
+	std::fs::write( &file_name, contents ).unwrap();
+	let rval = Root::create( &file_name.to_string_lossy().to_string(), &".".to_string() );
+	std::fs::remove_file( &file_name ).unwrap();
+	rval
+}
+
+#[test]
+fn root_zone() {
+	let root = load( "root", "$ORIGIN .
+$TTL 86400
+.	IN	SOA	a.root-servers.net. nstld.verisign-grs.com. 2023010100 1800 900 604800 86400
+.	IN	NS	a.root-servers.net.
+a.root-servers.net.	IN	A	198.41.0.4
+com.	IN	NS	a.gtld-servers.net.
+a.gtld-servers.net.	IN	A	192.5.6.30
+" ).unwrap();
+
+	assert!( root.root_addr.contains_key(".") );
+	assert!( root.root_addr.contains_key("com.") );
+}
+
+#[test]
+fn root_hints() {
+	// named.root has no SOA
+	let root = load( "hints", ".	3600000	NS	A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.	3600000	A	198.41.0.4
+A.ROOT-SERVERS.NET.	3600000	AAAA	2001:503:ba3e::2:30
+" ).unwrap();
+
+	assert_eq!( root.root_addr["."].read().unwrap().servers.len(), 2 );
+}
+
+#[test]
+fn forward_zone() {
+	let e = load( "forward", "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+ns1	IN	A	192.0.2.1
+www	IN	A	192.0.2.2
+" ).err().unwrap();
+
+	assert!( e.contains("is the zone file for example.com. not the root zone ."), "{}", e );
+}
+
+#[test]
+fn no_root_nameservers() {
+	let e = load( "tlds", "com.	172800	IN	NS	a.gtld-servers.net.
+a.gtld-servers.net.	172800	IN	A	192.5.6.30
+" ).err().unwrap();
+
+	assert!( e.contains("has no NS records for ."), "{}", e );
+}
+
+#[test]
+fn no_glue() {
+	let e = load( "glueless", ".	518400	IN	NS	a.root-servers.net.
+" ).err().unwrap();
+
+	assert!( e.contains("has no addresses for the nameservers of ., a.root-servers.net."), "{}", e );
+}
+
+#[test]
+fn fall_back() {
+	let dir = std::env::temp_dir();
+	let bad = dir.join(format!("dns_audit_root_{}_fallback_bad.zone", std::process::id()));
+	let good = dir.join(format!("dns_audit_root_{}_fallback_good.zone", std::process::id()));
+	std::fs::write( &bad, ".	518400	IN	NS	a.root-servers.net.
+a.root-servers.net.	518400	IN	A	198.41.0.4 extra
+" ).unwrap();
+	std::fs::write( &good, ".	518400	IN	NS	b.root-servers.net.
+b.root-servers.net.	518400	IN	A	170.247.170.2
+" ).unwrap();
+
+	let sources = |names : &[&std::path::PathBuf]| -> Vec<String> { names.iter().map(|n| n.to_string_lossy().to_string()).collect() };
+	let missing = dir.join(format!("dns_audit_root_{}_fallback_missing.zone", std::process::id()));
+
+	let root = Root::create_from_sources( &sources(&[ &missing, &bad, &good ]), &".".to_string() );
+	let all_bad = Root::create_from_sources( &sources(&[ &missing, &bad ]), &".".to_string() );
+	std::fs::remove_file( &bad ).unwrap();
+	std::fs::remove_file( &good ).unwrap();
+
+	let root = root.unwrap();
+	let servers = &root.root_addr["."].read().unwrap().servers;
+	assert_eq!( servers.len(), 1 );
+	assert_eq!( servers[0].read().unwrap().ip.to_string(), "170.247.170.2" );
+
+	let e = all_bad.err().unwrap();
+	assert!( e.starts_with("none of the root sources could be loaded"), "{}", e );
+	assert_eq!( e.matches("; ").count(), 1, "{}", e );
+
+}
+
+#[test]
+fn builtin_hints() {
+	// without a source the hints built in are used
+	let root = Root::create_from_sources( &[], &".".to_string() ).unwrap();
+	let servers = &root.root_addr["."].read().unwrap().servers;
+
+	let mut names : Vec<String> = servers.iter().map(|s| s.read().unwrap().server_name.to_ascii_lowercase()).collect();
+	names.dedup();
+	assert_eq!( names.len(), 13 );
+	assert_eq!( names[0], "a.root-servers.net." );
+	assert_eq!( names[12], "m.root-servers.net." );
+
+	// an address for each family of each server
+	assert_eq!( servers.len(), 26 );
+	assert!( servers.iter().any(|s| s.read().unwrap().ip.to_string() == "198.41.0.4") );
+
+	assert_eq!( Root::create_from_sources( &[ "builtin".to_string() ], &".".to_string() ).unwrap().root_addr.len(), 1 );
+}
+
+#[test]
+fn root_addr_contents() {
+	// every zone with NS records gets its nameservers, in record order with each address of
+	// a server, names without an address are left out
+	let root = load( "contents", "$ORIGIN .
+$TTL 86400
+.	IN	NS	a.root-servers.net.
+.	IN	NS	b.root-servers.net.
+.	IN	NS	unglued.example.
+a.root-servers.net.	IN	A	198.41.0.4
+a.root-servers.net.	IN	AAAA	2001:503:ba3e::2:30
+b.root-servers.net.	IN	A	170.247.170.2
+com.	IN	NS	a.gtld-servers.net.
+a.gtld-servers.net.	IN	A	192.5.6.30
+org.	IN	NS	unglued.example.
+" ).unwrap();
+
+	let mut zones : Vec<&String> = root.root_addr.keys().collect();
+	zones.sort();
+	assert_eq!( zones, [ ".", "com." ] );
+
+	let servers = |zone : &str| -> Vec<String> {
+		let ns = root.root_addr[zone].read().unwrap();
+		assert_eq!( ns.zone_name, zone );
+		ns.servers.iter().map(|s| {
+			let s = s.read().unwrap();
+			format!("{} {}", s.server_name, s.ip)
+		}).collect()
+	};
+
+	assert_eq!( servers("."), [
+		"a.root-servers.net. 198.41.0.4",
+		"a.root-servers.net. 2001:503:ba3e::2:30",
+		"b.root-servers.net. 170.247.170.2"
+	] );
+	assert_eq!( servers("com."), [ "a.gtld-servers.net. 192.5.6.30" ] );
+}
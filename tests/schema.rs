@@ -0,0 +1,152 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the schema_version of the JSON documents that are written and read back
+ */
+
+
+use dns_audit::baseline::Baseline;
+use dns_audit::config::SCHEMA_VERSION;
+use dns_audit::monitor::{ErrorCode, Finding, FindingDetail, MonitorResult};
+use dns_audit::root::Root;
+
+fn temp_file( name : &str, contents : &str ) -> String {
+	let file_name = std::env::temp_dir().join(format!("dns_audit_schema_{}_{}.json", std::process::id(), name));
+	std::fs::write( &file_name, contents ).unwrap();
+	file_name.to_string_lossy().to_string()
+}
+
+fn empty_root() -> Root {
+	Root::default()
+}
+
+#[test]
+fn result_current_version() {
+	let json = serde_json::to_string( &MonitorResult::new("example.com") ).unwrap();
+	assert!( json.starts_with(&format!("{{\"schema_version\":{},", SCHEMA_VERSION)) );
+
+	let result = MonitorResult::from_json( &json ).unwrap();
+	assert_eq!( result.schema_version, SCHEMA_VERSION );
+	assert_eq!( result.domain_name, "example.com" );
+}
+
+#[test]
+fn result_future_version() {
+	let mut document = serde_json::to_value( MonitorResult::new("example.com") ).unwrap();
+	document["schema_version"] = serde_json::json!( SCHEMA_VERSION + 1 );
+
+	let e = MonitorResult::from_json( &document.to_string() ).err().unwrap();
+	assert!( e.contains("newer version"), "{}", e );
+}
+
+#[test]
+fn result_invalid_version() {
+	let mut document = serde_json::to_value( MonitorResult::new("example.com") ).unwrap();
+	document["schema_version"] = serde_json::json!( "one" );
+
+	assert!( MonitorResult::from_json( &document.to_string() ).is_err() );
+}
+
+#[test]
+fn result_findings() {
+	let mut result = MonitorResult::new("example.com");
+	result.success = false;
+	result.findings.push( Finding::with_detail( ErrorCode::ResolveIpNotMatch, "did not return the correct ips", FindingDetail {
+		server: Some("192.0.2.53".to_string()),
+		expected: Some("192.0.2.1".to_string()),
+		actual: Some("192.0.2.2".to_string())
+	}));
+	result.findings.push( Finding::with_detail( ErrorCode::SerialMismatch, "192.0.2.54 serves serial 1, expected 2", FindingDetail {
+		server: Some("192.0.2.54".to_string()),
+		..Default::default()
+	}));
+	result.findings.push( Finding::new( ErrorCode::NoAuthoratative, "no authoratative nameservers" ) );
+
+	// each finding has its code and message, the detail only has the parts that are set
+	let document = serde_json::to_value( &result ).unwrap();
+	assert_eq!( document["findings"], serde_json::json!([
+		{ "code": "ResolveIpNotMatch", "message": "did not return the correct ips", "detail": { "server": "192.0.2.53", "expected": "192.0.2.1", "actual": "192.0.2.2" } },
+		{ "code": "SerialMismatch", "message": "192.0.2.54 serves serial 1, expected 2", "detail": { "server": "192.0.2.54" } },
+		{ "code": "NoAuthoratative", "message": "no authoratative nameservers" }
+	]) );
+	assert!( document.get("reason").is_none() && document.get("flags").is_none() );
+
+	let reread = MonitorResult::from_json( &document.to_string() ).unwrap();
+	let codes : Vec<ErrorCode> = reread.findings.iter().map(|f| f.code).collect();
+	assert_eq!( codes, vec![ ErrorCode::ResolveIpNotMatch, ErrorCode::SerialMismatch, ErrorCode::NoAuthoratative ] );
+	let detail = reread.findings[0].detail.as_ref().unwrap();
+	assert_eq!( (detail.server.as_deref(), detail.expected.as_deref(), detail.actual.as_deref()), (Some("192.0.2.53"), Some("192.0.2.1"), Some("192.0.2.2")) );
+	assert!( reread.findings[1].detail.as_ref().unwrap().expected.is_none() );
+	assert!( reread.findings[2].detail.is_none() );
+}
+
+#[test]
+fn cache_current_version() {
+	let json = empty_root().to_json();
+	assert_eq!( json, format!("{{\"schema_version\":{},\"zones\":{{}}}}", SCHEMA_VERSION) );
+
+	let mut root = empty_root();
+	root.cache_from_js( &json ).unwrap();
+	assert!( root.root_addr.is_empty() );
+}
+
+#[test]
+fn cache_future_version() {
+	let json = format!("{{\"schema_version\":{},\"zones\":{{}}}}", SCHEMA_VERSION + 1);
+	let e = empty_root().cache_from_js( &json ).err().unwrap();
+	assert!( e.contains("cache file"), "{}", e );
+}
+
+#[test]
+fn cache_legacy() {
+	// a cache from before the schema_version is the map of zones by itself
+	let mut root = empty_root();
+	root.cache_from_js( &"{}".to_string() ).unwrap();
+	assert!( root.root_addr.is_empty() );
+}
+
+#[test]
+fn baseline_current_version() {
+	let legacy = temp_file( "baseline_in", "[{\"domain_name\":\"Example.com\",\"nameservers\":[\"ns1.example.com\"],\"ips\":null}]" );
+	let baseline = Baseline::from_file( &legacy ).unwrap();
+	assert!( baseline.entries.contains_key("example.com") );
+
+	// written back out it gets the current version, and reads again
+	let current = temp_file( "baseline_out", "" );
+	baseline.to_file( &current ).unwrap();
+	let json = std::fs::read_to_string( &current ).unwrap();
+	assert!( json.starts_with(&format!("{{\"schema_version\":{},\"entries\":[", SCHEMA_VERSION)) );
+
+	let reread = Baseline::from_file( &current ).unwrap();
+	assert_eq!( reread.entries["example.com"].nameservers, Some(vec!["ns1.example.com".to_string()]) );
+
+	std::fs::remove_file( &legacy ).unwrap();
+	std::fs::remove_file( &current ).unwrap();
+}
+
+#[test]
+fn baseline_future_version() {
+	let file_name = temp_file( "baseline_future", &format!("{{\"schema_version\":{},\"entries\":[]}}", SCHEMA_VERSION + 1) );
+	assert!( Baseline::from_file( &file_name ).is_err() );
+	std::fs::remove_file( &file_name ).unwrap();
+}
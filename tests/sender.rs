@@ -0,0 +1,300 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for how a Sender talks to a server, the servers are addresses on the loopback with
+ * nothing listening on them or a server on a thread of the test
+ */
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use dns_audit::query::{self, QueryIds, QueryType, Sender};
+
+#[test]
+fn binds_the_server_family() {
+
+	let v4 : IpAddr = "127.0.0.1".parse().unwrap();
+	let v6 : IpAddr = "::1".parse().unwrap();
+
+	assert_eq!( query::bind_addr(&v4), "0.0.0.0:0".parse::<SocketAddr>().unwrap() );
+	assert_eq!( query::bind_addr(&v6), "[::]:0".parse::<SocketAddr>().unwrap() );
+
+	// a socket bound for an IPv6 server can reach it, one bound to 0.0.0.0 can't
+	let socket = std::net::UdpSocket::bind( query::bind_addr(&v6) ).unwrap();
+	assert!( socket.connect( SocketAddr::new(v6, 53) ).is_ok() );
+	assert!( socket.local_addr().unwrap().is_ipv6() );
+	let socket = std::net::UdpSocket::bind( query::bind_addr(&v4) ).unwrap();
+	assert!( socket.connect( SocketAddr::new(v6, 53) ).is_err() );
+}
+
+#[test]
+fn queries_an_ipv6_server() {
+
+	*dns_audit::config::FORCE_IPV6.write().unwrap() = true;
+
+	// the request is sent, nobody answers it
+	let mut sender = Sender::new( &"::1".parse().unwrap() );
+	sender.timeout = Duration::from_millis(200);
+	let e = sender.query( "example.com.", QueryType::T_A ).unwrap_err();
+	assert!( !e.starts_with("bind failed") && !e.starts_with("connect failed") && !e.starts_with("send failed"), "{}", e );
+}
+
+/**
+ * the response to a request for an A record, truncated with no records or the whole answer
+ * of one A record
+ */
+fn answer( request : &[u8], truncated : bool ) -> Vec<u8> {
+
+	let mut buff = request[..2].to_vec();
+	if truncated {
+		buff.extend_from_slice( &[ 0x86, 0x00, 0, 1, 0, 0, 0, 0, 0, 0 ] );
+	} else {
+		buff.extend_from_slice( &[ 0x84, 0x00, 0, 1, 0, 1, 0, 0, 0, 0 ] );
+	}
+	buff.extend_from_slice( &request[12..] );
+	if !truncated {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1 ] );
+	}
+	buff
+}
+
+/**
+ * a server on the loopback that answers UDP requests with udp and TCP ones with tcp, the
+ * port it listens on for both and the count of UDP requests it got are returned. An empty
+ * UDP answer is not sent, the request gets no response
+ */
+fn serve( udp_answer : fn(&[u8]) -> Vec<u8>, tcp_answer : fn(&[u8]) -> Vec<u8> ) -> (u16, Arc<AtomicUsize>) {
+
+	// any free UDP port, tried again when the same TCP port is taken
+	let (udp, tcp) = loop {
+		let udp = UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+		if let Ok(tcp) = TcpListener::bind( udp.local_addr().unwrap() ) {
+			break (udp, tcp);
+		}
+	};
+	let port = udp.local_addr().unwrap().port();
+
+	let udp_requests = Arc::new( AtomicUsize::new(0) );
+
+	let count = udp_requests.clone();
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			count.fetch_add( 1, Ordering::SeqCst );
+			let response = udp_answer( &buff[..size] );
+			if !response.is_empty() {
+				udp.send_to( &response, from ).unwrap();
+			}
+		}
+	});
+
+	std::thread::spawn( move || {
+		for mut stream in tcp.incoming().flatten() {
+			let mut len = [ 0u8; 2 ];
+			stream.read_exact( &mut len ).unwrap();
+			let mut request = vec![ 0u8; u16::from_be_bytes(len) as usize ];
+			stream.read_exact( &mut request ).unwrap();
+
+			let response = tcp_answer( &request );
+			stream.write_all( &(response.len() as u16).to_be_bytes() ).unwrap();
+			stream.write_all( &response ).unwrap();
+		}
+	});
+
+	(port, udp_requests)
+}
+
+/**
+ * a server whose UDP responses are truncated and whose TCP ones have the answer
+ */
+fn truncating_server() -> (u16, Arc<AtomicUsize>) {
+	serve( |request| answer( request, true ), |request| answer( request, false ) )
+}
+
+#[test]
+fn truncated_falls_back_to_tcp() {
+
+	let (port, udp_requests) = truncating_server();
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 1 );
+	assert!( !sender.recv_header.tc );
+	assert_eq!( sender.answer.len(), 1 );
+	assert_eq!( sender.answer[0].rdata.as_ref().unwrap().to_string(), "192.0.2.1" );
+}
+
+#[test]
+fn forced_tcp() {
+
+	let (port, udp_requests) = truncating_server();
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+	sender.use_tcp = true;
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 0 );
+	assert_eq!( sender.answer.len(), 1 );
+}
+
+#[test]
+fn rejects_mismatched_id() {
+
+	// the answer is for another request, its id is one more than the one asked with
+	let wrong_id = |request : &[u8]| {
+		let mut response = answer( request, false );
+		let id = u16::from_be_bytes([ response[0], response[1] ]).wrapping_add(1);
+		response[..2].copy_from_slice( &id.to_be_bytes() );
+		response
+	};
+	let (port, _) = serve( wrong_id, wrong_id );
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+	sender.query_ids = QueryIds::Fixed(0x1234);
+
+	let e = sender.query( "example.com.", QueryType::T_A ).unwrap_err();
+	assert_eq!( sender.request_id, 0x1234 );
+	assert!( e.contains("id mismatch") && e.contains("sent 4660 got 4661"), "{}", e );
+	assert!( sender.answer.is_empty() );
+
+	// over TCP too
+	sender.use_tcp = true;
+	assert!( sender.query( "example.com.", QueryType::T_A ).unwrap_err().contains("id mismatch") );
+}
+
+#[test]
+fn retries_mismatched_question() {
+
+	// the first response is for examplx.com., the ones after it have the question asked
+	static ANSWERED : AtomicUsize = AtomicUsize::new(0);
+	let wrong_first = |request : &[u8]| {
+		let mut response = answer( request, false );
+		if ANSWERED.fetch_add( 1, Ordering::SeqCst ) == 0 {
+			response[19] = b'x';
+		}
+		response
+	};
+	let (port, udp_requests) = serve( wrong_first, wrong_first );
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+	sender.retries = 1;
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 2 );
+	assert_eq!( sender.answer.len(), 1 );
+
+	// without a retry the mismatch is the error
+	ANSWERED.store( 0, Ordering::SeqCst );
+	sender.retries = 0;
+	let e = sender.query( "example.com.", QueryType::T_A ).unwrap_err();
+	assert!( e.contains("question mismatch") && e.contains("examplx.com"), "{}", e );
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 3 );
+}
+
+#[test]
+fn queries_the_port() {
+
+	let mut sender = Sender::new( &"192.0.2.53".parse().unwrap() );
+	assert_eq!( sender.server_addr(), "192.0.2.53:53".parse::<SocketAddr>().unwrap() );
+
+	sender.port = 5353;
+	assert_eq!( sender.server_addr(), "192.0.2.53:5353".parse::<SocketAddr>().unwrap() );
+
+	// a server on a port of its own answers
+	let (port, udp_requests) = serve( |request| answer( request, false ), |request| answer( request, false ) );
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+	assert_eq!( (udp_requests.load(Ordering::SeqCst), sender.answer.len()), (1, 1) );
+}
+
+#[test]
+fn retries_a_failed_query() {
+
+	// the first request gets no response, the ones after it are answered
+	static ASKED : AtomicUsize = AtomicUsize::new(0);
+	let flaky = |request : &[u8]| {
+		match ASKED.fetch_add( 1, Ordering::SeqCst ) {
+			0 => { Vec::new() },
+			_ => { answer( request, false ) }
+		}
+	};
+	let (port, udp_requests) = serve( flaky, flaky );
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_millis(300);
+	sender.retries = 2;
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 2 );
+	assert!( !sender.timed_out );
+	assert_eq!( sender.answer.len(), 1 );
+
+	// without retries the timeout is the answer
+	let (port, udp_requests) = serve( |_| Vec::new(), |request| answer( request, false ) );
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_millis(300);
+	assert!( sender.query( "example.com.", QueryType::T_A ).is_err() );
+	assert!( sender.timed_out );
+	assert_eq!( udp_requests.load(Ordering::SeqCst), 1 );
+}
+
+#[test]
+fn wrong_class_is_not_kept() {
+
+	// the first answer is in the CHAOS class, the one after it in IN
+	static ASKED : AtomicUsize = AtomicUsize::new(0);
+	let chaos_first = |request : &[u8]| {
+		let mut response = answer( request, false );
+		if ASKED.fetch_add( 1, Ordering::SeqCst ) == 0 {
+			let class = response.len() - 12;
+			response[class..class + 2].copy_from_slice( &[ 0, 3 ] );
+		}
+		response
+	};
+	let (port, _) = serve( chaos_first, chaos_first );
+
+	let mut sender = Sender::new( &"127.0.0.1".parse().unwrap() );
+	sender.port = port;
+	sender.timeout = Duration::from_secs(2);
+
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+	assert_eq!( (sender.answer.len(), sender.class_mismatch.len()), (0, 1) );
+
+	sender.query( "example.com.", QueryType::T_A ).unwrap();
+	assert_eq!( (sender.answer.len(), sender.class_mismatch.len()), (1, 0) );
+}
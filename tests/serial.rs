@@ -0,0 +1,118 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * Tests for comparing the SOA serials the authoratative nameservers of a domain answer with,
+ * the answers are hand built responses
+ */
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use dns_audit::check::SerialCheck;
+use dns_audit::monitor::{ErrorCode, Monitor};
+use dns_audit::query::Sender;
+use dns_audit::zone::rr::RDATAsoa;
+
+/**
+ * the serial of the SOA record in a response for example.com. with the serial
+ */
+fn answered_serial( serial : u32 ) -> u32 {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, 1, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 6, 0, 1 ] );
+
+	let mut rdata : Vec<u8> = b"\x03ns1\xc0\x0c\x0ahostmaster\xc0\x0c".to_vec();
+	for value in [ serial, 7200, 3600, 1209600, 3600 ] {
+		rdata.extend_from_slice( &value.to_be_bytes() );
+	}
+	buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 6, 0, 1, 0, 0, 0x0e, 0x10 ] );
+	buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+	buff.extend_from_slice( &rdata );
+
+	let sender = Sender::decode( &buff ).unwrap();
+	sender.answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<RDATAsoa>().unwrap().serial
+}
+
+fn serials( answers : &[(&str, u32)] ) -> BTreeMap<IpAddr, u32> {
+	answers.iter().map(|(server, serial)| (server.parse().unwrap(), answered_serial(*serial))).collect()
+}
+
+#[test]
+fn matching_serials() {
+
+	let same = serials( &[ ("192.0.2.53", 2023100101), ("198.51.100.53", 2023100101) ] );
+
+	assert!( SerialCheck::compare( None, &same ).is_empty() );
+	assert!( SerialCheck::compare( Some(2023100101), &same ).is_empty() );
+	assert!( SerialCheck::compare( None, &BTreeMap::new() ).is_empty() );
+}
+
+#[test]
+fn stuck_secondary() {
+
+	let stuck = serials( &[ ("192.0.2.53", 2023100102), ("198.51.100.53", 2023100101) ] );
+
+	// the secondary behind the newest serial is the one reported
+	let findings = SerialCheck::compare( None, &stuck );
+	assert_eq!( findings.len(), 1 );
+	assert!( findings[0].code == ErrorCode::SerialMismatch );
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.server.as_deref(), Some("198.51.100.53") );
+	assert_eq!( detail.expected.as_deref(), Some("2023100102") );
+	assert_eq!( detail.actual.as_deref(), Some("2023100101") );
+
+	// a serial that wrapped around is newer
+	let wrapped = serials( &[ ("192.0.2.53", 5), ("198.51.100.53", u32::MAX - 5) ] );
+	let findings = SerialCheck::compare( None, &wrapped );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].detail.as_ref().unwrap().server.as_deref(), Some("198.51.100.53") );
+}
+
+#[test]
+fn expected_serial() {
+
+	let monitor = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "expected_serial": 2023100103 }]"# ).unwrap().pop().unwrap();
+	let stuck = serials( &[ ("192.0.2.53", 2023100103), ("198.51.100.53", 2023100101) ] );
+
+	let findings = SerialCheck::compare( monitor.expected_serial, &stuck );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].message, "198.51.100.53 serves serial 2023100101, expected 2023100103" );
+
+	// both are wrong when neither has the expected serial
+	let behind = serials( &[ ("192.0.2.53", 2023100102), ("198.51.100.53", 2023100102) ] );
+	assert_eq!( SerialCheck::compare( monitor.expected_serial, &behind ).len(), 2 );
+}
+
+#[test]
+fn serial_check_runs() {
+
+	// not a default since it asks every nameserver address, unless there is an expected_serial
+	let default = Monitor::list_from_json( r#"[{ "domain_name": "example.com" }]"# ).unwrap().pop().unwrap();
+	assert!( !default.check_names().contains( &"serial".to_string() ) );
+
+	let expected = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "expected_serial": 1 }]"# ).unwrap().pop().unwrap();
+	assert!( expected.check_names().contains( &"serial".to_string() ) );
+	assert!( expected.check_names().contains( &"ns".to_string() ) );
+
+	let listed = Monitor::list_from_json( r#"[{ "domain_name": "example.com", "checks": [ "serial" ] }]"# ).unwrap().pop().unwrap();
+	assert_eq!( listed.check_names(), vec![ "serial" ] );
+}
@@ -0,0 +1,108 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for the per type counters of a run, the queries go to a server on the loopback on a
+ * thread of the test that answers A questions and leaves the rest unanswered
+ */
+
+use std::net::{IpAddr, UdpSocket};
+use std::time::Duration;
+
+use dns_audit::query::{QueryType, Sender};
+use dns_audit::stats;
+
+/**
+ * a server answering A questions with 192.0.2.1 and ignoring any other, the port it listens
+ * on is returned
+ */
+fn serve() -> u16 {
+
+	let udp = UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+	let port = udp.local_addr().unwrap().port();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			let request = &buff[..size];
+
+			let mut end = 12;
+			while request[end] != 0 {
+				end += request[end] as usize + 1;
+			}
+			if u16::from_be_bytes([ request[end + 1], request[end + 2] ]) != 1 {
+				continue;
+			}
+
+			let mut response = request[..2].to_vec();
+			response.extend_from_slice( &[ 0x84, 0x00, 0, 1, 0, 1, 0, 0, 0, 0 ] );
+			response.extend_from_slice( &request[12..end + 5] );
+			response.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1 ] );
+			udp.send_to( &response, from ).unwrap();
+		}
+	});
+
+	port
+}
+
+#[test]
+fn counts_by_type() {
+
+	stats::reset();
+
+	let server : IpAddr = "127.0.0.1".parse().unwrap();
+	let mut sender = Sender::new( &server );
+	sender.port = serve();
+	sender.timeout = Duration::from_millis(200);
+
+	for _ in 0..3 {
+		sender.query( "example.com.", QueryType::T_A ).unwrap();
+	}
+	// a retry is part of the same query, it's counted once
+	sender.retries = 1;
+	assert!( sender.query( "example.com.", QueryType::T_MX ).is_err() );
+
+	// refused rather than timed out
+	let mut closed = Sender::new( &server );
+	closed.port = UdpSocket::bind( "127.0.0.1:0" ).unwrap().local_addr().unwrap().port();
+	assert!( closed.query( "example.com.", QueryType::T_MX ).is_err() );
+
+	let counts : Vec<(String, u64, u64, u64)> = stats::snapshot().iter()
+		.map(|(qtype, s)| (qtype.to_string(), s.sent, s.failed, s.timed_out))
+		.collect();
+	assert_eq!( counts, vec![
+		("T_A".to_string(), 3, 0, 0),
+		("T_MX".to_string(), 2, 2, 1)
+	] );
+
+	let mut out = Vec::new();
+	stats::write_summary( &mut out ).unwrap();
+	assert_eq!( String::from_utf8(out).unwrap(), "\tA: 3 queries, 0 failed, 0 timed out\n\tMX: 2 queries, 2 failed, 1 timed out\n" );
+
+	// only the answers are timed
+	let servers = stats::server_snapshot();
+	assert_eq!( servers.len(), 1 );
+	assert_eq!( servers[0].0, server );
+	assert_eq!( servers[0].1.responses, 3 );
+
+	stats::reset();
+	assert!( stats::snapshot().is_empty() && stats::server_snapshot().is_empty() );
+}
@@ -0,0 +1,92 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for TSIG signing and verification (RFC-8945) against a vector worked out apart from
+ * the code, an HMAC-SHA256 over the request and the TSIG variables
+ */
+
+use dns_audit::query::decode_hex;
+use dns_audit::tsig::TsigKey;
+
+/** when the vectors were signed */
+const TIME_SIGNED : u64 = 1700000000;
+
+fn key() -> TsigKey {
+	TsigKey {
+		name: "test-key.".to_string(),
+		algorithm: "hmac-sha256".to_string(),
+		// the bytes 0 to 31
+		secret: "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=".to_string()
+	}
+}
+
+/** an A query for example.com. with id 0x1234 */
+const REQUEST : &str = "123400000001000000000000076578616d706c6503636f6d0000010001";
+
+const REQUEST_MAC : &str = "fbe05c49d88d54549dab5f63b6bdad4d406cf2a304262d27a3ed57f53f93074d";
+
+/** REQUEST with the TSIG record added and the arcount raised to 1 */
+const SIGNED_REQUEST : &str = "123400000001000000000001076578616d706c6503636f6d000001000108746573742d6b65790000fa00ff00000000003d0b686d61632d7368613235360000006553f100012c0020fbe05c49d88d54549dab5f63b6bdad4d406cf2a304262d27a3ed57f53f93074d123400000000";
+
+/** the answer 192.0.2.1 signed by the server with the MAC of the request */
+const SIGNED_RESPONSE : &str = "123484000001000100000001076578616d706c6503636f6d0000010001c00c0001000100000e100004c000020108746573742d6b65790000fa00ff00000000003d0b686d61632d7368613235360000006553f100012c0020d9c51a8257826d4c438bfbe391d4aa46653d1c55f5d9eac812c4932926d0d2a5123400000000";
+
+#[test]
+fn signs_the_known_vector() {
+
+	let mut request = decode_hex( REQUEST ).unwrap();
+	let mac = key().sign( &mut request, TIME_SIGNED ).unwrap();
+
+	assert_eq!( mac, decode_hex( REQUEST_MAC ).unwrap() );
+	assert_eq!( request, decode_hex( SIGNED_REQUEST ).unwrap() );
+}
+
+#[test]
+fn verifies_the_response() {
+
+	let mac = decode_hex( REQUEST_MAC ).unwrap();
+	let response = decode_hex( SIGNED_RESPONSE ).unwrap();
+
+	key().verify( &mac, &response, TIME_SIGNED ).unwrap();
+	key().verify( &mac, &response, TIME_SIGNED + 300 ).unwrap();
+
+	// the answer is changed from 192.0.2.1 to 192.0.2.2
+	let mut tampered = response.clone();
+	let at = tampered.windows(4).position(|w| w == [ 192, 0, 2, 1 ]).unwrap();
+	tampered[at + 3] = 2;
+	let e = key().verify( &mac, &tampered, TIME_SIGNED ).unwrap_err();
+	assert!( e.contains("did not verify"), "{}", e );
+
+	// signed for another request
+	let mut other_mac = mac.clone();
+	other_mac[0] ^= 1;
+	assert!( key().verify( &other_mac, &response, TIME_SIGNED ).unwrap_err().contains("did not verify") );
+
+	// outside of the fudge
+	assert!( key().verify( &mac, &response, TIME_SIGNED + 301 ).unwrap_err().contains("outside of the fudge") );
+
+	// another key
+	let mut wrong = key();
+	wrong.secret = "AQECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=".to_string();
+	assert!( wrong.verify( &mac, &response, TIME_SIGNED ).is_err() );
+}
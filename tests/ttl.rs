@@ -0,0 +1,93 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+/**
+ * Tests for comparing the TTLs of a domain's records against the monitor's expected_ttl, the
+ * records are from hand built responses
+ */
+
+use std::net::IpAddr;
+
+use dns_audit::check::TtlCheck;
+use dns_audit::monitor::{ErrorCode, Monitor};
+use dns_audit::query::Sender;
+use dns_audit::zone::record::ZoneRecord;
+
+/**
+ * the answer records of a response for the A records of example.com. with the TTLs, and an
+ * NS record for it with ns_ttl
+ */
+fn records( ttls : &[u32], ns_ttl : u32 ) -> Vec<ZoneRecord> {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, ttls.len() as u8 + 1, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+
+	for (x, ttl) in ttls.iter().enumerate() {
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1 ] );
+		buff.extend_from_slice( &ttl.to_be_bytes() );
+		buff.extend_from_slice( &[ 0, 4, 192, 0, 2, x as u8 + 1 ] );
+	}
+
+	buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 2, 0, 1 ] );
+	buff.extend_from_slice( &ns_ttl.to_be_bytes() );
+	buff.extend_from_slice( b"\x00\x06\x03ns1\xc0\x0c" );
+
+	Sender::decode( &buff ).unwrap().answer
+}
+
+fn monitor() -> Monitor {
+	Monitor::list_from_json( r#"[{ "domain_name": "example.com", "expected_ttl": 300, "ttl_tolerance": 30 }]"# ).unwrap().pop().unwrap()
+}
+
+#[test]
+fn inside_tolerance() {
+
+	let monitor = monitor();
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+	let expected = monitor.expected_ttl.unwrap();
+	let tolerance = monitor.ttl_tolerance.unwrap();
+
+	for ttls in [ [ 300, 300 ], [ 270, 330 ] ] {
+		assert!( TtlCheck::compare( &monitor.domain_name, expected, tolerance, &server, &records( &ttls, 300 ) ).is_empty(), "{:?}", ttls );
+	}
+}
+
+#[test]
+fn outside_tolerance() {
+
+	let monitor = monitor();
+	let server : IpAddr = "192.0.2.53".parse().unwrap();
+	let expected = monitor.expected_ttl.unwrap();
+	let tolerance = monitor.ttl_tolerance.unwrap();
+
+	// one A record is left at the TTL from before the migration was planned
+	let findings = TtlCheck::compare( &monitor.domain_name, expected, tolerance, &server, &records( &[ 300, 3600 ], 86400 ) );
+	assert_eq!( findings.len(), 2 );
+	assert!( findings.iter().all(|f| f.code == ErrorCode::TtlMismatch) );
+
+	assert_eq!( findings[0].message, "example.com A records from 192.0.2.53 have TTL 3600, expected 300 within 30" );
+	assert_eq!( findings[1].detail.as_ref().unwrap().actual.as_deref(), Some("86400") );
+
+	// just past the edge of the band
+	let findings = TtlCheck::compare( &monitor.domain_name, expected, tolerance, &server, &records( &[ 269, 331 ], 300 ) );
+	assert_eq!( findings.len(), 1 );
+	assert_eq!( findings[0].detail.as_ref().unwrap().actual.as_deref(), Some("269, 331") );
+}
@@ -0,0 +1,819 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for reading records off the wire. The responses are built by hand and decoded the way
+ * --decode does, without a server
+ */
+
+use base64::Engine;
+
+use dns_audit::check;
+use dns_audit::monitor::{MonitorResult, ResponseFlags};
+use dns_audit::query::{NSClass, Question, QueryFlags, QueryIds, QueryType, Sender};
+use dns_audit::zone::record::ZoneRecord;
+use dns_audit::zone::rr::{self, RecordRDATA};
+use dns_audit::zone::tokenizer::ZoneLines;
+
+/**
+ * a response to a query for example.com. with the given records in the answer section, each
+ * is the type number and the rdata with the owner compressed to the question name
+ */
+fn response( answers : &[(u16, &[u8])] ) -> Vec<u8> {
+
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x81, 0x80, 0, 1, 0, answers.len() as u8, 0, 0, 0, 0 ];
+	buff.extend_from_slice( b"\x07example\x03com\x00" );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+
+	for (record_type, rdata) in answers {
+		buff.extend_from_slice( &[ 0xc0, 0x0c ] );
+		buff.extend_from_slice( &record_type.to_be_bytes() );
+		buff.extend_from_slice( &[ 0, 1, 0, 0, 0x01, 0x2c ] );
+		buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+		buff.extend_from_slice( rdata );
+	}
+
+	buff
+}
+
+fn decode( buff : &[u8] ) -> Vec<ZoneRecord> {
+	match Sender::decode( buff ) {
+		Ok(m) => { m.answer },
+		Err(e) => { panic!("failed to decode the response {}", e); }
+	}
+}
+
+/**
+ * a record read from a zone file line, relative names are under example.com.
+ */
+fn zone_line( text : &str ) -> ZoneRecord {
+	let lines = ZoneLines::from_reader( &mut text.as_bytes() ).unwrap();
+	let record = match ZoneRecord::create( &lines.lines[0] ) {
+		Ok(m) => { m },
+		Err(e) => { panic!("failed to parse '{}' {}", text, e); }
+	};
+	let mut record = record.as_any_ref().downcast_ref::<ZoneRecord>().unwrap().clone();
+	record.origin( &"example.com.".to_string() );
+	record
+}
+
+#[test]
+fn unknown_type_is_rfc3597() {
+
+	let answer = decode( &response( &[ (65280, &[ 0x0a, 0x00, 0x00, 0x01 ]), (65281, &[]) ] ) );
+	assert_eq!( answer.len(), 2 );
+
+	assert_eq!( answer[0].type_name(), "TYPE65280" );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "\\# 4 0A000001" );
+
+	assert_eq!( answer[1].type_name(), "TYPE65281" );
+	assert_eq!( answer[1].rdata.as_ref().unwrap().to_string(), "\\# 0" );
+}
+
+#[test]
+fn recursion_flags_are_kept() {
+
+	// an authoratative answer with RA set and RD clear
+	let mut buff = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	buff[2] = 0x84;
+	buff[3] = 0x80;
+
+	let sender = Sender::decode( &buff ).unwrap();
+	let mut result = MonitorResult::new( "example.com" );
+	check::record_flags( &mut result, &sender );
+
+	assert_eq!( result.response_flags, vec![ ResponseFlags { server: *sender.server(), rd: false, ra: true } ] );
+
+	// only the first response from a server is kept
+	buff[3] = 0x00;
+	check::record_flags( &mut result, &Sender::decode( &buff ).unwrap() );
+	assert_eq!( result.response_flags.len(), 1 );
+	assert!( result.response_flags[0].ra );
+}
+
+#[test]
+fn wrong_class_is_a_finding() {
+
+	// the second A record is in the CHAOS class
+	let mut buff = response( &[ (1, &[ 192, 0, 2, 1 ]), (1, &[ 192, 0, 2, 2 ]) ] );
+	let second = buff.len() - 16;
+	buff[second + 4..second + 6].copy_from_slice( &[ 0, 3 ] );
+
+	let mut sender = Sender::new( &"192.0.2.53".parse().unwrap() );
+	sender.read_response( &buff ).unwrap();
+	assert_eq!( sender.answer.len(), 1 );
+	assert_eq!( sender.class_mismatch.len(), 1 );
+
+	let mut result = MonitorResult::new( "example.com" );
+	let finding = check::check_class( &result, &[], &sender ).unwrap();
+	assert_eq!( finding.code, dns_audit::monitor::ErrorCode::ClassMismatch );
+	assert_eq!( finding.message, "192.0.2.53 returned 1 records in the wrong class" );
+	let detail = finding.detail.as_ref().unwrap();
+	assert_eq!( (detail.server.as_deref(), detail.expected.as_deref(), detail.actual.as_deref()), (Some("192.0.2.53"), Some("1"), Some("3")) );
+
+	// it is only reported once, whether it is in the result or found by the running check
+	assert!( check::check_class( &result, std::slice::from_ref(&finding), &sender ).is_none() );
+	result.findings.push( finding );
+	assert!( check::check_class( &result, &[], &sender ).is_none() );
+
+	// decoding keeps every class
+	assert_eq!( Sender::decode( &buff ).unwrap().answer.len(), 2 );
+}
+
+#[test]
+fn dname_target_is_synthesized() {
+
+	fn name( text : &str ) -> Vec<u8> {
+		dns_audit::query::write_qname( text )
+	}
+
+	fn record( buff : &mut Vec<u8>, owner : &str, record_type : u16, rdata : &[u8] ) {
+		buff.extend( name(owner) );
+		buff.extend_from_slice( &record_type.to_be_bytes() );
+		buff.extend_from_slice( &[ 0, 1, 0, 0, 0x0e, 0x10 ] );
+		buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+		buff.extend_from_slice( rdata );
+	}
+
+	// the server sent the DNAME and the address of the name it stands for, without the
+	// synthesized CNAME
+	let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, 2, 0, 0, 0, 0 ];
+	buff.extend( name("www.a.old.example.") );
+	buff.extend_from_slice( &[ 0, 1, 0, 1 ] );
+	record( &mut buff, "old.example.", 39, &name("new.example.") );
+	record( &mut buff, "www.a.new.example.", 1, &[ 192, 0, 2, 80 ] );
+
+	let sender = Sender::decode( &buff ).unwrap();
+	assert_eq!( sender.follow_aliases( "www.a.old.example." ), "www.a.new.example." );
+	assert_eq!( sender.follow_aliases( "WWW.A.Old.Example" ), "WWW.A.new.example." );
+
+	// the DNAME owner itself is not redirected, only the names below it
+	assert_eq!( sender.follow_aliases( "old.example." ), "old.example." );
+	assert_eq!( sender.follow_aliases( "notold.example." ), "notold.example." );
+}
+
+#[test]
+fn signed_wildcard_is_synthesized() {
+
+	// an A record for www.example.com. and its RRSIG with the given labels field
+	let signed = |labels : u8| -> Sender {
+		let mut buff : Vec<u8> = vec![ 0x12, 0x34, 0x84, 0x00, 0, 1, 0, 2, 0, 0, 0, 0 ];
+		buff.extend_from_slice( b"\x03www\x07example\x03com\x00\x00\x01\x00\x01" );
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1 ] );
+
+		let mut rdata : Vec<u8> = vec![ 0, 1, 13, labels, 0, 0, 0x0e, 0x10 ];
+		rdata.extend_from_slice( &0x6553f100u32.to_be_bytes() );
+		rdata.extend_from_slice( &0x652c6400u32.to_be_bytes() );
+		rdata.extend_from_slice( &[ 0x9f, 0xd3 ] );
+		rdata.extend_from_slice( b"\x07example\x03com\x00" );
+		rdata.extend_from_slice( &[ 0xab; 64 ] );
+		buff.extend_from_slice( &[ 0xc0, 0x0c, 0, 46, 0, 1, 0, 0, 0x0e, 0x10 ] );
+		buff.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+		buff.extend( rdata );
+
+		Sender::decode( &buff ).unwrap()
+	};
+
+	// *.example.com. has two labels, the name it was expanded to has three
+	assert!( signed(2).wildcard_synthesized( "www.example.com." ) );
+	assert!( !signed(3).wildcard_synthesized( "www.example.com." ) );
+
+	// an unsigned answer can't be told apart from a record of its own
+	let unsigned = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	assert!( !Sender::decode( &unsigned ).unwrap().wildcard_synthesized( "example.com." ) );
+}
+
+#[test]
+fn class_any_question_encoding() {
+
+	let request = Sender::encode_request( &[ Question::new( "example.com.", QueryType::T_SOA, NSClass::C_ANY ) ], QueryFlags::default(), 0x1234 ).unwrap();
+
+	let mut expected : Vec<u8> = vec![ 0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0 ];
+	expected.extend_from_slice( b"\x07example\x03com\x00" );
+	expected.extend_from_slice( &[ 0, 6, 0, 0xff ] );
+
+	assert_eq!( request, expected );
+}
+
+#[test]
+fn root_question_encoding() {
+
+	// the root is the single zero length label, not an empty label followed by another
+	for name in [ ".", "" ] {
+		let request = Sender::encode_request( &[ Question::new( name, QueryType::T_NS, NSClass::C_IN ) ], QueryFlags::default(), 0x1234 ).unwrap();
+		assert_eq!( &request[12..], &[ 0x00, 0x00, 0x02, 0x00, 0x01 ], "{:?}", name );
+	}
+
+	assert_eq!( dns_audit::query::write_qname( "." ), vec![ 0 ] );
+}
+
+#[test]
+fn multiple_questions_encoding() {
+
+	let questions = vec![
+		Question::new( "example.com.", QueryType::T_A, NSClass::C_IN ),
+		Question::new( "example.com.", QueryType::T_AAAA, NSClass::C_IN )
+	];
+	let request = Sender::encode_request( &questions, QueryFlags { recursive: true, dnssec_ok: true, ..Default::default() }, 1 ).unwrap();
+
+	// RD set, two questions and the OPT record for the DO bit
+	assert_eq!( &request[..12], &[ 0, 1, 0x01, 0, 0, 2, 0, 0, 0, 0, 0, 1 ] );
+	assert_eq!( &request[12..29], b"\x07example\x03com\x00\x00\x01\x00\x01" );
+	// the second name is a pointer to the first
+	assert_eq!( &request[29..35], b"\xc0\x0c\x00\x1c\x00\x01" );
+	assert_eq!( request.len(), 35 + 11 );
+}
+
+#[test]
+fn compressed_suffix_encoding() {
+
+	let questions = vec![
+		Question::new( "www.example.com.", QueryType::T_A, NSClass::C_IN ),
+		Question::new( "mail.example.com.", QueryType::T_MX, NSClass::C_IN )
+	];
+	let request = Sender::encode_request( &questions, QueryFlags::default(), 1 ).unwrap();
+
+	assert_eq!( &request[4..6], &[ 0, 2 ] );
+	assert_eq!( &request[12..33], b"\x03www\x07example\x03com\x00\x00\x01\x00\x01" );
+	// example.com. starts after the 12 byte header and the 4 bytes of the www label
+	assert_eq!( &request[33..44], b"\x04mail\xc0\x10\x00\x0f\x00\x01" );
+	assert_eq!( request.len(), 44 );
+
+	// a name that only shares the root with the others is written out in full
+	let questions = vec![
+		Question::new( "www.example.com.", QueryType::T_A, NSClass::C_IN ),
+		Question::new( "example.org.", QueryType::T_A, NSClass::C_IN )
+	];
+	let request = Sender::encode_request( &questions, QueryFlags::default(), 1 ).unwrap();
+	assert_eq!( &request[33..], b"\x07example\x03org\x00\x00\x01\x00\x01" );
+}
+
+#[test]
+fn padded_to_block_size() {
+
+	let question = [ Question::new( "example.com.", QueryType::T_A, NSClass::C_IN ) ];
+
+	for (block, dnssec_ok) in [ (128, false), (128, true), (468, false), (29, false) ] {
+
+		let request = Sender::encode_request( &question, QueryFlags { padding: Some(block), dnssec_ok, ..Default::default() }, 1 ).unwrap();
+		assert_eq!( request.len() % block, 0, "block {}", block );
+
+		// the OPT record follows the 29 byte header and question, its one option is the padding
+		assert_eq!( &request[10..12], &[ 0, 1 ] );
+		assert_eq!( &request[30..32], &[ 0, 41 ] );
+		assert_eq!( request[36] & 0x80 != 0, dnssec_ok );
+		assert_eq!( u16::from_be_bytes([ request[38], request[39] ]) as usize, request.len() - 40 );
+		assert_eq!( &request[40..42], &[ 0, 12 ] );
+		assert_eq!( u16::from_be_bytes([ request[42], request[43] ]) as usize, request.len() - 44 );
+		assert!( request[44..].iter().all(|b| *b == 0) );
+	}
+}
+
+#[test]
+fn client_subnet_encoding() {
+
+	let question = [ Question::new( "example.com.", QueryType::T_A, NSClass::C_IN ) ];
+	let subnet : ipnet::IpNet = "198.51.100.77/24".parse().unwrap();
+
+	let request = Sender::encode_request( &question, QueryFlags { client_subnet: Some(subnet), ..Default::default() }, 1 ).unwrap();
+
+	// IPv4, a /24 with a scope of 0 then the 3 bytes the prefix covers, the host bits are dropped
+	assert_eq!( &request[10..12], &[ 0, 1 ] );
+	assert_eq!( &request[30..32], &[ 0, 41 ] );
+	assert_eq!( &request[38..], &[ 0, 11, 0, 8, 0, 7, 0, 1, 24, 0, 198, 51, 100 ] );
+
+	assert_eq!( Sender::client_subnet_option( &"2001:db8:1234::/45".parse().unwrap() ), vec![ 0, 2, 45, 0, 0x20, 0x01, 0x0d, 0xb8, 0x12, 0x30 ] );
+
+	// with padding the subnet comes first
+	let request = Sender::encode_request( &question, QueryFlags { client_subnet: Some(subnet), padding: Some(128), ..Default::default() }, 1 ).unwrap();
+	assert_eq!( request.len(), 128 );
+	assert_eq!( &request[40..51], &[ 0, 8, 0, 7, 0, 1, 24, 0, 198, 51, 100 ] );
+	assert_eq!( &request[51..53], &[ 0, 12 ] );
+}
+
+#[test]
+fn edns_udp_size_encoding() {
+
+	let question = [ Question::new( "example.com.", QueryType::T_A, NSClass::C_IN ) ];
+
+	// arcount 1 and an OPT record owned by the root with 4096 in the class, no flags or options
+	let request = Sender::encode_request( &question, QueryFlags { edns_udp_size: Some(4096), ..Default::default() }, 1 ).unwrap();
+	assert_eq!( &request[10..12], &[ 0, 1 ] );
+	assert_eq!( &request[29..], &[ 0, 0, 41, 0x10, 0x00, 0, 0, 0, 0, 0, 0 ] );
+
+	// with the DO bit the size given is kept, without a size the default is advertised
+	let request = Sender::encode_request( &question, QueryFlags { edns_udp_size: Some(4096), dnssec_ok: true, ..Default::default() }, 1 ).unwrap();
+	assert_eq!( &request[29..], &[ 0, 0, 41, 0x10, 0x00, 0, 0, 0x80, 0, 0, 0 ] );
+	let request = Sender::encode_request( &question, QueryFlags { dnssec_ok: true, ..Default::default() }, 1 ).unwrap();
+	assert_eq!( &request[32..34], &dns_audit::query::EDNS_UDP_SIZE.to_be_bytes() );
+
+	// no OPT record at all
+	let request = Sender::encode_request( &question, QueryFlags::default(), 1 ).unwrap();
+	assert_eq!( (&request[10..12], request.len()), (&[ 0u8, 0 ][..], 29) );
+}
+
+#[test]
+fn opt_in_response() {
+
+	// 1232 in the class, extended rcode 1, version 0, the DO bit and a cookie option
+	let mut buff = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	buff[11] = 1;
+	buff.extend_from_slice( &[ 0, 0, 41, 0x04, 0xd0, 1, 0, 0x80, 0, 0, 12, 0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8 ] );
+
+	let sender = Sender::decode( &buff ).unwrap();
+	assert_eq!( (sender.answer.len(), sender.additional.len()), (1, 1) );
+
+	let opt = sender.edns().unwrap();
+	assert_eq!( (opt.udp_size, opt.extended_rcode, opt.version, opt.dnssec_ok), (1232, 1, 0, true) );
+	assert_eq!( opt.option(10), Some( &[ 1, 2, 3, 4, 5, 6, 7, 8 ][..] ) );
+	assert_eq!( opt.to_string(), "udp 1232 version 0 rcode 1 do option10=0102030405060708" );
+
+	// an option longer than the record is skipped like other RDATA that can't be read
+	let mut buff = response( &[] );
+	buff[11] = 1;
+	buff.extend_from_slice( &[ 0, 0, 41, 0x04, 0xd0, 0, 0, 0, 0, 0, 4, 0, 10, 0, 8 ] );
+	assert!( Sender::decode( &buff ).unwrap().edns().is_none() );
+
+	// an OPT record can't be in a zone
+	let lines = ZoneLines::from_reader( &mut ". 0 IN OPT 4096".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn client_subnet_scope() {
+
+	// an answer with an OPT record that has the subnet asked for and a scope of /20
+	let mut buff = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	buff[11] = 1;
+	buff.extend_from_slice( &[ 0, 0, 41, 0x04, 0xd0, 0, 0, 0, 0, 0, 11, 0, 8, 0, 7, 0, 1, 24, 20, 198, 51, 100 ] );
+
+	let sender = Sender::decode( &buff ).unwrap();
+	assert_eq!( sender.answer.len(), 1 );
+	assert_eq!( sender.client_subnet_scope(), Some(20) );
+
+	// no option when the server doesn't support it
+	let sender = Sender::decode( &response( &[ (1, &[ 192, 0, 2, 1 ]) ] ) ).unwrap();
+	assert_eq!( sender.client_subnet_scope(), None );
+}
+
+#[test]
+fn seeded_query_ids() {
+
+	let question = [ Question::new( "example.com.", QueryType::T_A, NSClass::C_IN ) ];
+	let server : std::net::IpAddr = "192.0.2.53".parse().unwrap();
+
+	let requests = |ids : QueryIds| -> Vec<Vec<u8>> {
+		let mut sender = Sender::new( &server );
+		sender.query_ids = ids;
+		(0..3).map(|_| sender.next_request( &question, QueryFlags::default() ).unwrap()).collect()
+	};
+
+	// the same seed sends the same bytes, only the id differs from one query to the next
+	let first = requests( QueryIds::Seeded(42) );
+	assert_eq!( first, requests( QueryIds::Seeded(42) ) );
+	let ids : Vec<u16> = first.iter().map(|r| u16::from_be_bytes([ r[0], r[1] ])).collect();
+	assert_eq!( ids, vec![ 0x6e95, 0xf103, 0x9f52 ] );
+	assert_eq!( first[0], Sender::encode_request( &question, QueryFlags::default(), 0x6e95 ).unwrap() );
+	assert_eq!( &first[0][2..], &first[2][2..] );
+
+	assert_ne!( first, requests( QueryIds::Seeded(43) ) );
+
+	for request in requests( QueryIds::Fixed(0x1234) ) {
+		assert_eq!( request, Sender::encode_request( &question, QueryFlags::default(), 0x1234 ).unwrap() );
+	}
+}
+
+#[test]
+fn resent_request() {
+
+	let question = [ Question::new( "example.com.", QueryType::T_MX, NSClass::C_IN ) ];
+	let flags = QueryFlags {
+		recursive: true,
+		dnssec_ok: true,
+		padding: Some(128),
+		client_subnet: Some( "192.0.2.0/24".parse().unwrap() ),
+		edns_udp_size: Some(1232)
+	};
+
+	let mut sender = Sender::new( &"192.0.2.53".parse().unwrap() );
+	sender.query_ids = QueryIds::Seeded(7);
+	let first = sender.next_request( &question, flags.clone() ).unwrap();
+	let resent = sender.next_request( &question, flags.clone() ).unwrap();
+
+	// the re-sent request comes from the cache, only the id differs
+	assert_ne!( first[0..2], resent[0..2] );
+	assert_eq!( first[2..], resent[2..] );
+	let id = u16::from_be_bytes([ resent[0], resent[1] ]);
+	assert_eq!( resent, Sender::encode_request( &question, flags.clone(), id ).unwrap() );
+
+	// another question isn't answered from the cache
+	let other = [ Question::new( "example.net.", QueryType::T_MX, NSClass::C_IN ) ];
+	let request = sender.next_request( &other, flags.clone() ).unwrap();
+	let id = u16::from_be_bytes([ request[0], request[1] ]);
+	assert_eq!( request, Sender::encode_request( &other, flags.clone(), id ).unwrap() );
+
+	// nor are other flags
+	let request = sender.next_request( &other, QueryFlags::default() ).unwrap();
+	let id = u16::from_be_bytes([ request[0], request[1] ]);
+	assert_eq!( request, Sender::encode_request( &other, QueryFlags::default(), id ).unwrap() );
+}
+
+#[test]
+fn srv_round_trip() {
+
+	let from_zone = zone_line( "example.com.	300	IN	SRV	10 60 5060 sip1" );
+	let srv = from_zone.rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAsrv>().unwrap();
+	assert_eq!( (srv.priority, srv.weight, srv.port), (10, 60, 5060) );
+	assert_eq!( srv.target.fqdn, "sip1.example.com." );
+
+	// the target is compressed to the question name
+	let answer = decode( &response( &[ (33, b"\x00\x0a\x00\x3c\x13\xc4\x04sip1\xc0\x0c") ] ) );
+	assert_eq!( answer[0].type_name(), "SRV" );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "10\t60\t5060\tsip1.example.com." );
+	assert!( answer[0] == from_zone );
+
+	// the port is missing
+	let lines = ZoneLines::from_reader( &mut "example.com.	300	IN	SRV	10 60 sip1".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn caa_round_trip() {
+
+	for (line, flags, tag, value) in [
+		( "example.com.	300	IN	CAA	0 issue \"letsencrypt.org\"", 0, "issue", "letsencrypt.org" ),
+		( "example.com.	300	IN	CAA	0 issuewild \";\"", 0, "issuewild", ";" ),
+		( "example.com.	300	IN	CAA	128 iodef \"mailto:security@example.com\"", 128, "iodef", "mailto:security@example.com" )
+	] {
+		let from_zone = zone_line( line );
+		let caa = from_zone.rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAcaa>().unwrap();
+		assert_eq!( (caa.flags, caa.tag.as_str(), caa.value.as_str()), (flags, tag, value) );
+		assert_eq!( caa.critical(), flags == 128 );
+
+		let mut rdata = vec![ flags, tag.len() as u8 ];
+		rdata.extend_from_slice( tag.as_bytes() );
+		rdata.extend_from_slice( value.as_bytes() );
+
+		let answer = decode( &response( &[ (257, &rdata) ] ) );
+		assert_eq!( answer[0].type_name(), "CAA" );
+		assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), format!("{}\t{}\t\"{}\"", flags, tag, value) );
+		assert!( answer[0] == from_zone );
+	}
+
+	// a tag length past the end of the record is an error and the record is left out
+	let mut offset = 0;
+	assert!( rr::RDATAcaa::default().from_wire( 7, b"\x00\x09issue", &mut offset ).is_err() );
+	assert!( decode( &response( &[ (257, b"\x00\x09issue") ] ) ).is_empty() );
+
+	let lines = ZoneLines::from_reader( &mut "example.com.	300	IN	CAA	0 is-sue \"x\"".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn ptr_target_is_a_name() {
+
+	// the target is compressed to the question name, it is read back as an absolute name
+	let answer = decode( &response( &[ (12, b"\x04mail\xc0\x0c") ] ) );
+	assert_eq!( answer[0].type_name(), "PTR" );
+
+	let ptr = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATANameRR>().unwrap();
+	assert_eq!( ptr.name.fqdn, "mail.example.com." );
+	assert!( ZoneRecord::record_to_address( &answer[0] ).is_none() );
+
+	assert!( answer[0] == zone_line( "example.com.	300	IN	PTR	mail" ) );
+}
+
+#[test]
+fn naptr_round_trip() {
+
+	// an empty regexp with a replacement, then a regexp with the root as the replacement
+	let answer = decode( &response( &[
+		(35, b"\x00\x64\x00\x0a\x01S\x07SIP+D2U\x00\x04_sip\x04_udp\xc0\x0c"),
+		(35, b"\x00\x0a\x00\x64\x01u\x07E2U+sip\x1b!^.*$!sip:info@example.com!\x00")
+	] ) );
+	assert_eq!( answer.len(), 2 );
+
+	let naptr = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAnaptr>().unwrap();
+	assert_eq!( (naptr.order, naptr.preference), (100, 10) );
+	assert_eq!( (naptr.flags.as_str(), naptr.services.as_str(), naptr.regexp.as_str()), ("S", "SIP+D2U", "") );
+	assert_eq!( naptr.replacement.fqdn, "_sip._udp.example.com." );
+	assert_eq!( naptr.to_string(), "100 10 \"S\" \"SIP+D2U\" \"\" _sip._udp.example.com." );
+	assert!( answer[0] == zone_line( "example.com.	300	IN	NAPTR	100 10 \"S\" \"SIP+D2U\" \"\" _sip._udp" ) );
+
+	assert_eq!( answer[1].rdata.as_ref().unwrap().to_string(), "10 100 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" ." );
+	assert!( answer[1] == zone_line( "example.com.	300	IN	NAPTR	10 100 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" ." ) );
+
+	// the services string runs past the end of the record
+	let mut offset = 0;
+	assert!( rr::RDATAnaptr::default().from_wire( 8, b"\x00\x0a\x00\x64\x01u\x07E2U", &mut offset ).is_err() );
+}
+
+#[test]
+fn tlsa_round_trip() {
+
+	// a SHA-256 of the public key
+	let digest = "0D6FCE3302FD2F09A1E9C9A1E4F9E4C5B8EDE04FFA0A2A3F4CB2E0E8A3C58F21";
+	let mut rdata : Vec<u8> = vec![ 3, 1, 1 ];
+	rdata.extend( dns_audit::query::decode_hex(digest).unwrap() );
+
+	let answer = decode( &response( &[ (52, &rdata) ] ) );
+	let tlsa = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAtlsa>().unwrap();
+	assert_eq!( (tlsa.usage, tlsa.selector, tlsa.matching_type, tlsa.cert_data.len()), (3, 1, 1, 32) );
+	assert_eq!( tlsa.to_string(), format!("3\t1\t1\t{}", digest) );
+	assert!( answer[0] == zone_line( &format!("example.com.	300	IN	TLSA	3 1 1 {} {}", &digest[..32], &digest[32..].to_ascii_lowercase()) ) );
+
+	// a whole certificate, longer than a single byte length
+	let cert : Vec<u8> = [ 0x30, 0x82, 0x01, 0x28 ].into_iter().chain( (0..296).map(|i| i as u8) ).collect();
+	let mut rdata : Vec<u8> = vec![ 1, 0, 0 ];
+	rdata.extend_from_slice( &cert );
+
+	let answer = decode( &response( &[ (52, &rdata) ] ) );
+	let tlsa = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAtlsa>().unwrap();
+	assert_eq!( tlsa.cert_data, cert );
+	let hex : String = cert.iter().map(|b| format!("{:02x}", b)).collect();
+	assert!( answer[0] == zone_line( &format!("example.com.	300	IN	TLSA	1 0 0 {}", hex) ) );
+
+	let lines = ZoneLines::from_reader( &mut "example.com.	300	IN	TLSA	3 1 1 not-hex".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn sshfp_round_trip() {
+
+	let fingerprint = "123456789ABCDEF67890123456789ABCDEF67890123456789ABCDEF678901234";
+	let mut rdata : Vec<u8> = vec![ 4, 2 ];
+	rdata.extend( dns_audit::query::decode_hex(fingerprint).unwrap() );
+
+	let answer = decode( &response( &[ (44, &rdata) ] ) );
+	let sshfp = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAsshfp>().unwrap();
+	assert_eq!( (sshfp.algorithm, sshfp.fp_type, sshfp.fingerprint.len()), (4, 2, 32) );
+	assert_eq!( sshfp.to_string(), format!("4\t2\t{}", fingerprint) );
+
+	// wrapped in ( ) the fingerprint is several tokens over more than one line
+	let wrapped = zone_line( "example.com.	300	IN	SSHFP	4 2 (
+		123456789abcdef67890123456789abc
+		def67890123456789abcdef67890123 4 )" );
+	assert!( answer[0] == wrapped );
+	assert_eq!( wrapped.rdata.as_ref().unwrap().to_string(), sshfp.to_string() );
+
+	// an odd number of digits
+	let lines = ZoneLines::from_reader( &mut "example.com.	300	IN	SSHFP	4 2 ( 123 )".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn rrsig_round_trip() {
+
+	// an A signature by example.com. with the signer compressed to the question name
+	let signature : Vec<u8> = (0..64).collect();
+	let mut rdata : Vec<u8> = vec![ 0, 1, 13, 2, 0, 0, 0x0e, 0x10, 0x6a, 0xe6, 0x81, 0x00, 0x6a, 0xbd, 0xa2, 0x80, 0x9f, 0xd3, 0xc0, 0x0c ];
+	rdata.extend_from_slice( &signature );
+
+	let answer = decode( &response( &[ (46, &rdata) ] ) );
+	assert_eq!( answer[0].type_name(), "RRSIG" );
+
+	let rrsig = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATArrsig>().unwrap();
+	assert_eq!( rrsig.type_covered, 1 );
+	assert_eq!( (rrsig.algorithm, rrsig.labels, rrsig.original_ttl, rrsig.key_tag), (13, 2, 3600, 40915) );
+	assert_eq!( rr::format_dnssec_time( rrsig.sig_expiration ), "20261101000000" );
+	assert_eq!( rr::format_dnssec_time( rrsig.sig_inception ), "20261001000000" );
+	assert_eq!( rrsig.signer_name.fqdn, "example.com." );
+	assert_eq!( rrsig.signature, signature );
+
+	let encoded = base64::engine::general_purpose::STANDARD.encode( &signature );
+	assert_eq!( rrsig.to_string(), format!("A 13 2 3600 20261101000000 20261001000000 40915 example.com. {} ", encoded) );
+
+	// the zone file form with the signature split over lines reads the same
+	let from_zone = zone_line( &format!("example.com.	3600	IN	RRSIG	A 13 2 3600 ( 20261101000000 20261001000000 40915 example.com.
+		{}
+		{} )", &encoded[..44], &encoded[44..]) );
+	assert!( answer[0] == from_zone );
+
+	// a record too short for the fixed fields and the signer
+	let buff = response( &[ (46, &rdata) ] );
+	let mut offset = buff.len() - rdata.len();
+	assert!( rr::RDATArrsig::default().from_wire( 10, &buff, &mut offset ).is_err() );
+}
+
+#[test]
+fn svcb_round_trip() {
+
+	// HTTPS with alpn h2,h3, port 8443, one address of each family and a key without a name
+	let mut rdata : Vec<u8> = vec![ 0, 1, 0, 0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3', 0, 3, 0, 2, 0x20, 0xfb, 0, 4, 0, 4, 192, 0, 2, 1 ];
+	rdata.extend_from_slice( &[ 0, 6, 0, 16 ] );
+	rdata.extend_from_slice( &"2001:db8::1".parse::<std::net::Ipv6Addr>().unwrap().octets() );
+	rdata.extend_from_slice( &[ 0xff, 0x35, 0, 4, b'a', b' ', b'"', 1 ] );
+
+	let answer = decode( &response( &[ (65, &rdata) ] ) );
+	assert_eq!( answer[0].type_name(), "HTTPS" );
+
+	let https = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAsvcb>().unwrap();
+	assert_eq!( (https.priority, https.target.fqdn.as_str(), https.params.len()), (1, ".", 5) );
+	assert_eq!( https.param(3), Some( &[ 0x20, 0xfb ][..] ) );
+	assert_eq!( https.to_string(), "1 . alpn=h2,h3 port=8443 ipv4hint=192.0.2.1 ipv6hint=2001:db8::1 key65333=a\\032\\034\\001" );
+
+	// the presentation format gives the same RDATA, whatever the order of the keys
+	let line = zone_line( "example.com.	300	IN	HTTPS	1 . key65333=a\\032\\034\\001 port=8443 ipv6hint=2001:db8::1 alpn=h2,h3 ipv4hint=192.0.2.1" );
+	assert!( answer[0] == line );
+	assert_eq!( line.rdata.as_ref().unwrap().to_string(), https.to_string() );
+
+	// SVCB in alias mode has no params
+	let answer = decode( &response( &[ (64, &[ 0, 0, 3, b's', b'v', b'c', 0xc0, 0x0c ]) ] ) );
+	assert_eq!( answer[0].type_name(), "SVCB" );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "0 svc.example.com." );
+
+	// a value past the end of the record, and keys out of order
+	let mut svcb = rr::RDATAsvcb::default();
+	let truncated = [ 0, 1, 0, 0, 3, 0, 4, 0x20, 0xfb ];
+	assert!( svcb.from_wire( truncated.len() as u16, &truncated, &mut 0 ).is_err() );
+	let unordered = [ 0, 1, 0, 0, 3, 0, 2, 0x20, 0xfb, 0, 1, 0, 3, 2, b'h', b'2' ];
+	assert!( svcb.from_wire( unordered.len() as u16, &unordered, &mut 0 ).is_err() );
+
+	// a key given twice
+	let lines = ZoneLines::from_reader( &mut "example.com.	300	IN	SVCB	1 . port=53 port=853".as_bytes() ).unwrap();
+	assert!( ZoneRecord::create( &lines.lines[0] ).is_err() );
+}
+
+#[test]
+fn uri_round_trip() {
+
+	// the target has a quote, a backslash, a tab and a character that isn't ascii
+	let target = "https://example.com/a \"b\"\\c\td\u{e9}";
+	let mut rdata : Vec<u8> = vec![ 0, 10, 0, 1 ];
+	rdata.extend( target.chars().map(|c| c as u8) );
+
+	let answer = decode( &response( &[ (256, &rdata) ] ) );
+	assert_eq!( answer[0].type_name(), "URI" );
+
+	let uri = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAuri>().unwrap();
+	assert_eq!( (uri.priority, uri.weight, uri.target.as_str()), (10, 1, target) );
+	assert_eq!( uri.to_string(), "10\t1\t\"https://example.com/a \\\"b\\\"\\\\c\\009d\\233\"" );
+
+	// the escaped target reads back as the same RDATA
+	let line = zone_line( &format!("example.com.	300	IN	URI	{}", uri) );
+	assert!( answer[0] == line );
+	assert_eq!( line.rdata.as_ref().unwrap().to_string(), uri.to_string() );
+
+	// the target has to be quoted and can't be empty
+	for text in [ "10 1 https://example.com/", "10 1 \"\"" ] {
+		let lines = ZoneLines::from_reader( &mut format!("example.com.	300	IN	URI	{}", text).as_bytes() ).unwrap();
+		assert!( ZoneRecord::create( &lines.lines[0] ).is_err(), "{}", text );
+	}
+
+	// a record too short for a target
+	assert!( decode( &response( &[ (256, &[ 0, 10, 0, 1 ]) ] ) ).is_empty() );
+}
+
+#[test]
+fn compression_pointer_loops() {
+
+	// a pointer to itself, and a pointer back to the start of the name it ends
+	assert_eq!( dns_audit::query::read_qname( &[ 0xc0, 0x00 ], &mut 0 ).unwrap(), "" );
+	assert_eq!( dns_audit::query::read_qname( &[ 1, b'a', 0xc0, 0x00 ], &mut 0 ).unwrap(), "a.a" );
+
+	// a pointer past the end of the message and one cut off by the end of the message
+	assert_eq!( dns_audit::query::read_qname( &[ 1, b'a', 0xc0, 0xff ], &mut 0 ).unwrap(), "a" );
+	assert_eq!( dns_audit::query::read_qname( &[ 1, b'a', 0xc0 ], &mut 0 ).unwrap(), "a" );
+
+	// pointers back to earlier names are followed
+	let answer = decode( &response( &[ (5, &[ 3, b'w', b'w', b'w', 0xc0, 0x0c ]) ] ) );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "www.example.com." );
+
+	// a CNAME to itself and one that loops back into its own labels, the rdata are at 41 and 55
+	// and the names are cut short
+	let answer = decode( &response( &[ (5, &[ 0xc0, 41 ]), (5, &[ 3, b'w', b'w', b'w', 0xc0, 55 ]) ] ) );
+	assert_eq!( answer.len(), 2 );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "." );
+	assert_eq!( answer[1].rdata.as_ref().unwrap().to_string(), "www.www." );
+}
+
+#[test]
+fn truncated_answers() {
+
+	let full = response( &[ (1, &[ 192, 0, 2, 1 ]), (1, &[ 192, 0, 2, 2 ]) ] );
+	assert_eq!( decode( &full ).len(), 2 );
+
+	// cut anywhere the response is a format error rather than a panic
+	for len in 0..full.len() {
+		match Sender::decode( &full[..len] ) {
+			Ok(_) => { panic!("a response cut to {} of {} bytes was decoded", len, full.len()); },
+			Err(e) => { assert!( e.starts_with("FORMERR"), "{}", e ); }
+		}
+	}
+
+	// an rdlength past the end of the message
+	let mut long = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	let rdlength_at = long.len() - 6;
+	long[rdlength_at + 1] = 5;
+	match Sender::decode( &long ) {
+		Ok(_) => { panic!("an rdlength past the end was decoded"); },
+		Err(e) => { assert!( e.contains("past the end"), "{}", e ); }
+	}
+
+	// a record whose RDATA can't be read is skipped, the one after it is still read
+	let answer = decode( &response( &[ (257, &[ 0 ]), (1, &[ 192, 0, 2, 1 ]) ] ) );
+	assert_eq!( answer.len(), 1 );
+	assert_eq!( answer[0].rdata.as_ref().unwrap().to_string(), "192.0.2.1" );
+}
+
+#[test]
+fn inflated_counts() {
+
+	// a header claiming the most records it can with nothing after it
+	let answers : [u8; 12] = [ 0x12, 0x34, 0x84, 0x00, 0, 0, 0xff, 0xff, 0, 0, 0, 0 ];
+	let every_section : [u8; 12] = [ 0x12, 0x34, 0x84, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff ];
+
+	for buff in [ &answers, &every_section ] {
+		match Sender::decode( buff ) {
+			Ok(_) => { panic!("a header with no records after it was decoded"); },
+			Err(e) => { assert!( e.starts_with("FORMERR"), "{}", e ); }
+		}
+	}
+
+	// one record there and 65534 missing
+	let mut short = response( &[ (1, &[ 192, 0, 2, 1 ]) ] );
+	short[6..8].copy_from_slice( &[ 0xff, 0xff ] );
+	assert!( Sender::decode( &short ).is_err() );
+}
+
+#[test]
+fn soa_names() {
+
+	// ns1.example.com. then hostmaster with the rest of its name compressed to the question
+	let mut rdata : Vec<u8> = b"\x03ns1\xc0\x0c\x0ahostmaster\xc0\x0c".to_vec();
+	rdata.extend_from_slice( &[ 0x78, 0x96, 0x0e, 0xc5, 0, 0, 0x1c, 0x20, 0, 0, 0x0e, 0x10, 0, 0x12, 0x75, 0, 0, 0, 0x01, 0x2c ] );
+
+	let answer = decode( &response( &[ (6, &rdata) ] ) );
+	let soa = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAsoa>().unwrap();
+	assert_eq!( soa.mname.fqdn, "ns1.example.com." );
+	assert_eq!( soa.rname.fqdn, "hostmaster.example.com." );
+	assert_eq!( (soa.serial, soa.refresh, soa.retry, soa.expire, soa.min), (2023100101, 7200, 3600, 1209600, 300) );
+
+	// the same as the record read from a zone file
+	assert!( answer[0] == zone_line( "example.com.	300	IN	SOA	ns1 hostmaster 2023100101 7200 3600 1209600 300" ) );
+}
+
+#[test]
+fn txt_segments() {
+
+	// a DKIM key split over two character-strings
+	let answer = decode( &response( &[ (16, b"\x10v=DKIM1; k=rsa; \x08p=MIGfMA") ] ) );
+	let txt = answer[0].rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAtxt>().unwrap();
+	assert_eq!( txt.segments, vec![ "v=DKIM1; k=rsa; ", "p=MIGfMA" ] );
+	assert_eq!( txt.text(), "v=DKIM1; k=rsa; p=MIGfMA" );
+	assert_eq!( txt.to_string(), "\"v=DKIM1; k=rsa; \" \"p=MIGfMA\"" );
+	assert!( answer[0] == zone_line( "example.com.	300	IN	TXT	\"v=DKIM1; k=rsa; \" \"p=MIGfMA\"" ) );
+
+	// a length past the end of the record
+	assert!( decode( &response( &[ (16, b"\x03abc\x05de") ] ) ).is_empty() );
+
+	// a string longer than a character-string is split on 255 characters and written one
+	// segment to a line
+	let long = "a".repeat(300);
+	let line = zone_line( &format!("example.com.	300	IN	TXT	\"{}\"", long) );
+	let txt = line.rdata.as_ref().unwrap().as_any().downcast_ref::<rr::RDATAtxt>().unwrap();
+	assert_eq!( txt.segments.iter().map(|s| s.len()).collect::<Vec<usize>>(), vec![ 255, 45 ] );
+	assert_eq!( txt.to_string(), format!("(\n\t\"{}\"\n\t\"{}\"\n)", "a".repeat(255), "a".repeat(45)) );
+	assert!( line == zone_line( &format!("example.com.	300	IN	TXT	{}", txt) ) );
+}
+
+#[test]
+fn name_length_limits() {
+
+	let encode = |host : &str| Sender::encode_request( &[ Question::new( host, QueryType::T_A, NSClass::C_IN ) ], QueryFlags::default(), 1 );
+
+	// a label of 63 octets is the longest there can be
+	let label = "a".repeat(63);
+	let request = encode( &format!("{}.example.com.", label) ).unwrap();
+	assert_eq!( request[12], 63 );
+	assert_eq!( &request[13..76], label.as_bytes() );
+
+	let e = encode( &format!("{}.example.com.", "a".repeat(64)) ).unwrap_err();
+	assert!( e.contains("64 octets"), "{}", e );
+
+	// 255 octets on the wire with the length bytes and the root label, with or without the trailing dot
+	let longest = format!("{}.{}.{}.{}", label, label, label, "b".repeat(61));
+	assert_eq!( encode( &longest ).unwrap().len(), 12 + 255 + 4 );
+	assert_eq!( encode( &format!("{}.", longest) ).unwrap().len(), 12 + 255 + 4 );
+	assert!( encode( &format!("{}b", longest) ).is_err() );
+	assert!( encode( &"a.".repeat(200) ).is_err() );
+}
@@ -0,0 +1,472 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+
+/**
+ * Tests for the zone API, the zones are written to temporary files
+ */
+
+use dns_audit::zone::Zone;
+use dns_audit::zone::record::ZoneRecord;
+
+fn write_zone( name : &str, contents : &str ) -> String {
+	let file_name = std::env::temp_dir().join(format!("dns_audit_zone_{}_{}.zone", std::process::id(), name));
+	std::fs::write( &file_name, contents ).unwrap();
+	file_name.to_string_lossy().to_string()
+}
+
+fn owners( zone : &Zone ) -> Vec<String> {
+	zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.map(|r| format!("{} {}", r.name.fqdn, r.type_name()))
+		.collect()
+}
+
+const MALFORMED : &str = "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+ns1	IN	A	192.0.2.1
+www	IN	A	192.0.2.300
+mail	IN	A	192.0.2.25
+";
+
+#[test]
+fn bad_record_fails_the_zone() {
+	let file_name = write_zone( "strict", MALFORMED );
+	assert!( Zone::create( &file_name, &"example.com.".to_string() ).is_err() );
+	std::fs::remove_file( &file_name ).unwrap();
+}
+
+#[test]
+fn bad_record_is_skipped() {
+
+	let file_name = write_zone( "lenient", MALFORMED );
+	let zone = Zone::create_with( &file_name, &"example.com.".to_string(), true ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	assert_eq!( owners(&zone), vec![
+		"example.com. SOA",
+		"example.com. NS",
+		"ns1.example.com. A",
+		"mail.example.com. A"
+	]);
+
+	assert_eq!( zone.skipped.len(), 1 );
+	assert_eq!( zone.skipped[0].line, 6 );
+	assert_eq!( zone.skipped_lines(), "6" );
+}
+
+#[test]
+fn subtree() {
+
+	let file_name = write_zone( "subtree", "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+ns1	IN	A	192.0.2.1
+_dmarc	IN	TXT	\"v=DMARC1; p=reject\"
+foo	IN	A	192.0.2.2
+www.foo	IN	A	192.0.2.3
+a.b.FOO	IN	AAAA	2001:db8::1
+barfoo	IN	A	192.0.2.4
+foo.bar	IN	A	192.0.2.5
+sub	IN	NS	ns.sub
+ns.sub	IN	A	192.0.2.6
+" );
+	let zone = Zone::create( &file_name, &"example.com.".to_string() ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let names = |name : &str| -> Vec<String> {
+		zone.records_under(name).iter().map(|r| format!("{} {}", r.name.fqdn, r.type_name())).collect()
+	};
+
+	assert_eq!( names("foo.example.com."), vec![
+		"foo.example.com. A",
+		"www.foo.example.com. A",
+		"a.b.FOO.example.com. AAAA"
+	]);
+
+	// the delegation with its glue, the case and trailing dot of the name don't matter
+	assert_eq!( names("SUB.example.com"), vec![ "sub.example.com. NS", "ns.sub.example.com. A" ] );
+	assert_eq!( names("_dmarc.example.com."), vec![ "_dmarc.example.com. TXT" ] );
+	assert_eq!( names("example.com.").len(), 11 );
+	assert!( names("oo.example.com.").is_empty() );
+	assert!( names("example.net.").is_empty() );
+}
+
+#[test]
+fn parse_warnings() {
+
+	let file_name = write_zone( "warnings", "$ORIGIN example.com.
+@	3600	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	3600	NS	ns1
+ns1	3600	A	192.0.2.1
+foo	3600	IN	FOOBAR	some data
+bar	3600	IN	TYPE65280	\\# 2 abcd
+baz	3600	A	192.0.2.2
+" );
+	let (zone, warnings) = Zone::create_with_warnings( &file_name, &"example.com.".to_string(), false ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let warnings : Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+	assert_eq!( warnings, vec![
+		"line 2: warning: example.com. has no class and none was given before it, IN is assumed for it and the records after it without one",
+		"line 5: warning: FOOBAR is not a known record type, the data of foo.example.com. is kept as it was written"
+	]);
+
+	// the warnings don't drop anything, the $ORIGIN directive is kept as a record too
+	assert_eq!( zone.records.len(), 7 );
+}
+
+#[test]
+fn ttl_warnings() {
+
+	let file_name = write_zone( "ttl_warnings", "$ORIGIN example.com.
+$TTL 1h
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+$TTL 3600
+@	IN	NS	ns1
+" );
+	let (_zone, warnings) = Zone::create_with_warnings( &file_name, &"example.com.".to_string(), false ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let warnings : Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+	assert_eq!( warnings, vec![
+		"line 2: warning: $TTL 1h is not a number, it is ignored",
+		"line 3: warning: example.com. has no TTL and there is no $TTL before it, the TTL is 0"
+	]);
+}
+
+#[test]
+fn include_files() {
+
+	let pid = std::process::id();
+
+	// the hosts are included with the zone's origin, the sub zone with one of its own
+	let hosts = write_zone( "include_hosts", "www	IN	A	192.0.2.80
+mail	IN	A	192.0.2.25
+" );
+	let sub = write_zone( "include_sub", "$ORIGIN deeper
+host	IN	A	192.0.2.10
+" );
+	let file_name = write_zone( "include", &format!("$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+$INCLUDE dns_audit_zone_{pid}_include_hosts.zone
+$INCLUDE dns_audit_zone_{pid}_include_sub.zone sub
+ftp	IN	A	192.0.2.21
+" ) );
+
+	let zone = Zone::create( &file_name, &"example.com.".to_string() ).unwrap();
+	for f in [ &file_name, &hosts, &sub ] {
+		std::fs::remove_file( f ).unwrap();
+	}
+
+	// the $ORIGIN in the sub zone file doesn't carry over to ftp
+	assert_eq!( owners(&zone), vec![
+		"example.com. SOA",
+		"www.example.com. A",
+		"mail.example.com. A",
+		"host.deeper.sub.example.com. A",
+		"ftp.example.com. A"
+	]);
+
+	let ttls : Vec<i32> = zone.records.iter().filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>()).map(|r| r.ttl).collect();
+	assert!( ttls.iter().all(|ttl| *ttl == 3600) );
+}
+
+#[test]
+fn include_cycle() {
+
+	let pid = std::process::id();
+
+	let file_name = write_zone( "include_self", &format!("$ORIGIN example.com.
+@	3600	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+$INCLUDE dns_audit_zone_{pid}_include_self.zone
+" ) );
+	let e = Zone::create( &file_name, &"example.com.".to_string() ).err().unwrap();
+
+	let missing = write_zone( "include_missing", "$INCLUDE no_such_file.zone\n" );
+	let no_file = write_zone( "include_no_file", "$INCLUDE\n" );
+	assert!( Zone::create( &missing, &"example.com.".to_string() ).is_err() );
+	assert!( Zone::create( &no_file, &"example.com.".to_string() ).is_err() );
+
+	for f in [ &file_name, &missing, &no_file ] {
+		std::fs::remove_file( f ).unwrap();
+	}
+
+	assert!( e.contains("$INCLUDE cycle") && e.contains("include_self.zone -> "), "{}", e );
+}
+
+#[test]
+fn generate_records() {
+
+	let file_name = write_zone( "generate", "$ORIGIN 2.0.192.in-addr.arpa.
+$TTL 3600
+@	IN	SOA	ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 3600
+$GENERATE 1-3 $ PTR host-${0,3,d}.example.com.
+$GENERATE 10-14/2 dhcp${-9} IN A 192.0.2.$
+" );
+	let zone = Zone::create( &file_name, &"2.0.192.in-addr.arpa.".to_string() ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let records : Vec<String> = zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.skip(1)
+		.map(|r| format!("{} {} {} {}", r.name.fqdn, r.ttl, r.type_name(), r.rdata.as_ref().unwrap().to_string().trim()))
+		.collect();
+
+	assert_eq!( records, vec![
+		"1.2.0.192.in-addr.arpa. 3600 PTR host-001.example.com.",
+		"2.2.0.192.in-addr.arpa. 3600 PTR host-002.example.com.",
+		"3.2.0.192.in-addr.arpa. 3600 PTR host-003.example.com.",
+		"dhcp1.2.0.192.in-addr.arpa. 3600 A 192.0.2.10",
+		"dhcp3.2.0.192.in-addr.arpa. 3600 A 192.0.2.12",
+		"dhcp5.2.0.192.in-addr.arpa. 3600 A 192.0.2.14"
+	]);
+
+	// the generated records are on the line of the $GENERATE
+	let lines : Vec<u32> = zone.records.iter().filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>()).map(|r| r.source_line).collect();
+	assert_eq!( lines, vec![ 3, 4, 4, 4, 5, 5, 5 ] );
+}
+
+#[test]
+fn generate_modifiers() {
+
+	use dns_audit::zone::generate_substitute;
+
+	assert_eq!( generate_substitute( "host$", 7 ).unwrap(), "host7" );
+	assert_eq!( generate_substitute( "${0,3,d}", 7 ).unwrap(), "007" );
+	assert_eq!( generate_substitute( "${10}-${0,4,x}-${0,0,X}-${0,3,o}", 250 ).unwrap(), "260-00fa-FA-372" );
+	assert_eq!( generate_substitute( "cost\\$$", 5 ).unwrap(), "cost$5" );
+
+	assert!( generate_substitute( "${0,3", 1 ).is_err() );
+	assert!( generate_substitute( "${-2}", 1 ).is_err() );
+	assert!( generate_substitute( "${0,3,q}", 1 ).is_err() );
+
+	for range in [ "3-1", "1-3/0", "a-b", "5" ] {
+		let file_name = write_zone( "generate_bad", &format!("$ORIGIN example.com.\n$GENERATE {} host$ A 192.0.2.$\n", range) );
+		assert!( Zone::create( &file_name, &"example.com.".to_string() ).is_err(), "{}", range );
+		std::fs::remove_file( &file_name ).unwrap();
+	}
+}
+
+#[test]
+fn generate_limit() {
+
+	// 65535 records is the most one $GENERATE can expand to
+	for range in [ "0-65535", "0-4294967295", "0-131070/2" ] {
+		let file_name = write_zone( "generate_large", &format!("$ORIGIN example.com.\n$GENERATE {} host$ A 192.0.2.1\n", range) );
+		let e = Zone::create( &file_name, &"example.com.".to_string() ).err().unwrap();
+		std::fs::remove_file( &file_name ).unwrap();
+		assert!( e.contains( &format!("$GENERATE at line 2 range '{}' is more than 65535 records", range) ), "{}", e );
+	}
+}
+
+#[test]
+fn relative_targets() {
+
+	let file_name = write_zone( "relative", "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	MX	10 mail
+@	IN	MX	20 @
+$ORIGIN lab
+@	IN	NS	ns1
+www	IN	CNAME	@
+_sip._tcp	IN	SRV	0 5 5060 sip
+$ORIGIN .
+$TTL 300
+test	IN	NS	ns1.test
+esc	IN	CNAME	host\\.
+" );
+	let zone = Zone::create( &file_name, &"example.com.".to_string() ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let records : Vec<String> = zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.map(|r| format!("{} {} {}", r.name.fqdn, r.type_name(), r.rdata.as_ref().unwrap().to_string().trim()))
+		.collect();
+
+	assert_eq!( records, vec![
+		"example.com. SOA ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 3600",
+		"example.com. NS ns1.example.com.",
+		"example.com. MX 10\tmail.example.com.",
+		"example.com. MX 20\texample.com.",
+		"lab.example.com. NS ns1.lab.example.com.",
+		"www.lab.example.com. CNAME lab.example.com.",
+		"_sip._tcp.lab.example.com. SRV 0\t5\t5060\tsip.lab.example.com.",
+		"test. NS ns1.test.",
+		"esc. CNAME host\\.."
+	]);
+}
+
+#[test]
+fn tokenize() {
+	use dns_audit::zone::tokenizer::ZoneLines;
+
+	let zone = "$TTL 3600\n\
+		@ IN SOA ns1 hostmaster ( 1 ; serial\n\
+		\t7200 900 ) ; refresh and retry\n\
+		\n\
+		txt IN TXT \"a \\\"b\\\"\" \"\"\n";
+
+	let lines = ZoneLines::from_reader( &mut zone.as_bytes() ).unwrap();
+
+	// the blank line is dropped, the parens join lines 2 and 3 and the comments are stripped
+	assert_eq!( lines.lines.len(), 3 );
+
+	let expected = [
+		"1\tTypeDirective\t\"$TTL\"",
+		"1\tTypeWhite\t\" \"",
+		"1\tTypeNumber\t\"3600\"",
+		"",
+		"2\tTypeToken\t\"@\"",
+		"2\tTypeWhite\t\" \"",
+		"2\tTypeToken\t\"IN\"",
+		"2\tTypeWhite\t\" \"",
+		"2\tTypeToken\t\"SOA\"",
+		"2\tTypeWhite\t\" \"",
+		"2\tTypeToken\t\"ns1\"",
+		"2\tTypeWhite\t\" \"",
+		"2\tTypeToken\t\"hostmaster\"",
+		"2\tTypeWhite\t\"  \"",
+		"2\tTypeNumber\t\"1\"",
+		"2\tTypeWhite\t\" \"",
+		"3\tTypeWhite\t\"\\n\\t\"",
+		"3\tTypeNumber\t\"7200\"",
+		"3\tTypeWhite\t\" \"",
+		"3\tTypeNumber\t\"900\"",
+		"3\tTypeWhite\t\"  \"",
+		"",
+		"5\tTypeToken\t\"txt\"",
+		"5\tTypeWhite\t\" \"",
+		"5\tTypeToken\t\"IN\"",
+		"5\tTypeWhite\t\" \"",
+		"5\tTypeToken\t\"TXT\"",
+		"5\tTypeWhite\t\" \"",
+		"5\tTypeString\t\"a \\\"b\\\"\"",
+		"5\tTypeWhite\t\" \"",
+		"5\tTypeString\t\"\"",
+	];
+
+	assert_eq!( lines.to_string(), expected.join("\n") + "\n" );
+}
+
+#[test]
+fn gzip_zone() {
+	use std::io::Write;
+
+	let contents = "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+ns1	IN	A	192.0.2.1
+www	IN	CNAME	@
+@	IN	TXT	\"v=spf1 -all\"
+";
+
+	let mut encoder = flate2::write::GzEncoder::new( Vec::new(), flate2::Compression::default() );
+	encoder.write_all( contents.as_bytes() ).unwrap();
+	let gzipped = encoder.finish().unwrap();
+
+	let plain_file = write_zone( "plain", contents );
+	// one by its extension, the other by the gzip magic number
+	let gz_file = std::env::temp_dir().join(format!("dns_audit_zone_{}_gzip.zone.gz", std::process::id())).to_string_lossy().to_string();
+	std::fs::write( &gz_file, &gzipped ).unwrap();
+	let sniffed_file = write_zone( "sniffed", "" );
+	std::fs::write( &sniffed_file, &gzipped ).unwrap();
+
+	let records = |file_name : &String| -> Vec<String> {
+		let zone = Zone::create( file_name, &"example.com.".to_string() ).unwrap();
+		std::fs::remove_file( file_name ).unwrap();
+		zone.records.iter().map(|r| r.to_string()).collect()
+	};
+
+	let plain = records( &plain_file );
+	assert!( plain.iter().any(|r| r.contains("v=spf1 -all")) );
+	assert_eq!( records( &gz_file ), plain );
+	assert_eq!( records( &sniffed_file ), plain );
+}
+
+#[test]
+fn error_lines() {
+
+	// the comment, blank line and the record split over two lines still count
+	let file_name = write_zone( "error_lines", "$ORIGIN example.com. ; the apex
+@	IN	SOA	ns1 hostmaster ( 1 7200
+		3600 1209600 3600 )
+
+www	IN	A	192.0.2.300
+mail	IN	A	192.0.2.25
+" );
+
+	let e = Zone::create( &file_name, &"example.com.".to_string() ).err().unwrap();
+	assert!( e.contains("invalid A record at line 5"), "{}", e );
+
+	let zone = Zone::create_with( &file_name, &"example.com.".to_string(), true ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+	assert_eq!( zone.skipped.len(), 1 );
+	assert_eq!( zone.skipped[0].line, 5 );
+
+	let lines : Vec<u32> = zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.map(|r| r.source_line)
+		.collect();
+	assert_eq!( lines, vec![ 2, 6 ] );
+
+	// a directive without a value
+	let file_name = write_zone( "error_directive", "$ORIGIN example.com.\n\n\n\n$TTL\n" );
+	let e = Zone::create( &file_name, &"example.com.".to_string() ).err().unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+	assert!( e.contains("$TTL directive at line 5 has no value"), "{}", e );
+}
+
+#[test]
+fn quoted_svc_params() {
+
+	// a quoted value is read the same as one without quotes
+	let file_name = write_zone( "svcb", "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	HTTPS	1 . alpn=\"h2,h3\" port=\"8443\"
+www	IN	HTTPS	1 . alpn=h2,h3 port=8443
+" );
+	let zone = Zone::create( &file_name, &"example.com.".to_string() ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+
+	let rdata : Vec<String> = zone.records.iter()
+		.filter_map(|r| r.as_any_ref().downcast_ref::<ZoneRecord>())
+		.filter(|r| r.type_name() == "HTTPS")
+		.map(|r| r.rdata.as_ref().unwrap().to_string())
+		.collect();
+
+	assert_eq!( rdata, vec![ "1 . alpn=h2,h3 port=8443", "1 . alpn=h2,h3 port=8443" ] );
+
+	// only SvcParams can have a quoted value right after the =
+	let file_name = write_zone( "svcb_txt", "$ORIGIN example.com.\n@	IN	TXT	key=\"value\"\n" );
+	let e = Zone::create( &file_name, &"example.com.".to_string() ).err().unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+	assert!( e.contains("Expected whitespace"), "{}", e );
+}
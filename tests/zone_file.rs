@@ -0,0 +1,162 @@
+/**
+DNS Audit Tool
+
+(c) 2023 Benjamin P Wilder, All Rights Reserved
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+
+/**
+ * Tests for comparing a zone file with the answers of its nameserver, the nameserver is a
+ * server on the loopback on a thread of the test that answers from a table
+ */
+
+use std::collections::HashMap;
+use std::net::{IpAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dns_audit::check::ZoneFileCheck;
+use dns_audit::monitor::{ErrorCode, MonitorResult};
+use dns_audit::query::Sender;
+
+const ZONE : &str = "$ORIGIN example.com.
+$TTL 3600
+@	IN	SOA	ns1 hostmaster 1 7200 3600 1209600 3600
+@	IN	NS	ns1
+@	IN	MX	10 mail
+ns1	IN	A	192.0.2.1
+mail	IN	A	192.0.2.25
+www	IN	A	192.0.2.80
+";
+
+/**
+ * a name in wire format without compression
+ */
+fn wire_name( name : &str ) -> Vec<u8> {
+	let mut rval = Vec::new();
+	for label in name.trim_end_matches('.').split('.') {
+		rval.push( label.len() as u8 );
+		rval.extend_from_slice( label.as_bytes() );
+	}
+	rval.push( 0 );
+	rval
+}
+
+/**
+ * the answers the server gives for each name and type, the MX target is in another case than
+ * the zone file and www has another address
+ */
+fn answers() -> HashMap<(String, u16), Vec<u8>> {
+
+	let mut soa = wire_name( "ns1.example.com." );
+	soa.extend( wire_name( "hostmaster.example.com." ) );
+	for value in [ 1u32, 7200, 3600, 1209600, 3600 ] {
+		soa.extend_from_slice( &value.to_be_bytes() );
+	}
+
+	let mut mx = vec![ 0, 10 ];
+	mx.extend( wire_name( "MAIL.Example.COM." ) );
+
+	HashMap::from([
+		(("example.com.".to_string(), 6), soa),
+		(("example.com.".to_string(), 2), wire_name( "ns1.example.com." )),
+		(("example.com.".to_string(), 15), mx),
+		(("ns1.example.com.".to_string(), 1), vec![ 192, 0, 2, 1 ]),
+		(("mail.example.com.".to_string(), 1), vec![ 192, 0, 2, 25 ]),
+		(("www.example.com.".to_string(), 1), vec![ 192, 0, 2, 81 ]),
+	])
+}
+
+/**
+ * a server answering each question with its record from answers(), the port it listens on
+ * is returned
+ */
+fn serve() -> u16 {
+
+	let udp = UdpSocket::bind( "127.0.0.1:0" ).unwrap();
+	let port = udp.local_addr().unwrap().port();
+	let answers = answers();
+
+	std::thread::spawn( move || {
+		let mut buff = [ 0u8; 512 ];
+		while let Ok( (size, from) ) = udp.recv_from( &mut buff ) {
+			let request = &buff[..size];
+
+			let mut name = String::new();
+			let mut end = 12;
+			while request[end] != 0 {
+				let len = request[end] as usize;
+				name.push_str( &String::from_utf8_lossy( &request[end + 1..end + 1 + len] ).to_lowercase() );
+				name.push( '.' );
+				end += len + 1;
+			}
+			let qtype = u16::from_be_bytes([ request[end + 1], request[end + 2] ]);
+
+			let rdata = answers.get( &(name, qtype) );
+
+			let mut response = request[..2].to_vec();
+			response.extend_from_slice( &[ 0x84, 0x00, 0, 1, 0, rdata.is_some() as u8, 0, 0, 0, 0 ] );
+			response.extend_from_slice( &request[12..end + 5] );
+			if let Some(rdata) = rdata {
+				response.extend_from_slice( &[ 0xc0, 0x0c ] );
+				response.extend_from_slice( &qtype.to_be_bytes() );
+				response.extend_from_slice( &[ 0, 1, 0, 0, 0x0e, 0x10 ] );
+				response.extend_from_slice( &(rdata.len() as u16).to_be_bytes() );
+				response.extend_from_slice( rdata );
+			}
+			udp.send_to( &response, from ).unwrap();
+		}
+	});
+
+	port
+}
+
+#[test]
+fn compare_with_server() {
+
+	let file_name = std::env::temp_dir().join(format!("dns_audit_zone_file_{}.zone", std::process::id())).to_string_lossy().to_string();
+	std::fs::write( &file_name, ZONE ).unwrap();
+
+	let zone = ZoneFileCheck::zone_file( &file_name, "example.com." ).unwrap();
+
+	let server : IpAddr = "127.0.0.1".parse().unwrap();
+	let mut query = Sender::new( &server );
+	query.port = serve();
+	query.timeout = Duration::from_millis(500);
+
+	let mut result = MonitorResult::new( "example.com" );
+	let findings = ZoneFileCheck::compare( &zone, "ns1.example.com.", &mut query, &mut result );
+
+	// the MX target is only in another case, the address of www is wrong
+	assert_eq!( findings.len(), 1, "{:?}", findings.iter().map(|f| f.message.clone()).collect::<Vec<String>>() );
+	assert_eq!( findings[0].code, ErrorCode::ZoneMismatch );
+	assert_eq!( findings[0].message, format!("ns1.example.com. (127.0.0.1) serves www.example.com. A differently than {}", file_name) );
+	let detail = findings[0].detail.as_ref().unwrap();
+	assert_eq!( detail.expected.as_deref(), Some("192.0.2.80") );
+	assert_eq!( detail.actual.as_deref(), Some("192.0.2.81") );
+
+	// the next pass has the same zone until the file is modified
+	assert!( Arc::ptr_eq( &zone, &ZoneFileCheck::zone_file( &file_name, "example.com." ).unwrap() ) );
+	std::fs::write( &file_name, ZONE.replace( "192.0.2.80", "192.0.2.81" ) ).unwrap();
+	let modified = zone.modified.unwrap() + Duration::from_secs(1);
+	std::fs::File::options().write(true).open( &file_name ).unwrap().set_modified( modified ).unwrap();
+
+	let zone = ZoneFileCheck::zone_file( &file_name, "example.com." ).unwrap();
+	std::fs::remove_file( &file_name ).unwrap();
+	assert_eq!( zone.modified, Some(modified) );
+	assert!( ZoneFileCheck::compare( &zone, "ns1.example.com.", &mut query, &mut result ).is_empty() );
+}